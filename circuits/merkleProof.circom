@@ -56,3 +56,68 @@ template MerkleProof(depth) {
 
     out <== nodes[depth];
 }
+
+/**
+ * @title MerkleProofWithDepth template
+ * @dev MerkleProof for trees of any depth up to maxDepth, given the actual depth as an input
+ * @notice Siblings use the padding of `LeanIMT::generate_padded_proof`: the first
+ *   actualDepth entries are the real siblings and the rest are zero. Every level is still
+ *   hashed (constraints are fixed), but the output is the node at level actualDepth, so
+ *   the padding never affects the root.
+ * @param maxDepth The largest tree depth the circuit accepts
+ */
+template MerkleProofWithDepth(maxDepth) {
+    // inputs
+    signal input leaf;                  // leaf value to prove inclusion of
+    signal input leafIndex;             // index of leaf in the Merkle tree
+    signal input siblings[maxDepth];    // sibling values, zero past actualDepth
+    signal input actualDepth;           // depth of the tree the proof was generated for
+
+    // outputs
+    signal output out;
+
+    // internal signals
+    signal nodes[maxDepth + 1];   // stores computed node values at each level
+    signal indices[maxDepth];     // stores path indices for each level
+    signal roots[maxDepth + 1];   // nodes[i] when i == actualDepth, else 0
+
+    // components
+    component hashInCorrectOrder[maxDepth];
+    component hashes[maxDepth];
+    component isDepth[maxDepth + 1];
+
+    component indexToPath = Num2Bits(maxDepth);
+    indexToPath.in <== leafIndex;
+    indices <== indexToPath.out;
+
+    // The depth must be in range, otherwise no level is selected and the root would be 0
+    component depthInRange = LessEqThan(8);
+    depthInRange.in <== [actualDepth, maxDepth];
+    depthInRange.out === 1;
+
+    nodes[0] <== leaf;
+
+    var root = 0;
+    for (var i = 0; i < maxDepth; i++) {
+        isDepth[i] = IsEqual();
+        isDepth[i].in <== [actualDepth, i];
+        roots[i] <== isDepth[i].out * nodes[i];
+        root += roots[i];
+
+        var childrenToSort[2][2] = [ [nodes[i], siblings[i]], [siblings[i], nodes[i]] ];
+        hashInCorrectOrder[i] = MultiMux1(2);
+        hashInCorrectOrder[i].c <== childrenToSort;
+        hashInCorrectOrder[i].s <== indices[i];
+
+        hashes[i] = Poseidon255(2);
+        hashes[i].in <== hashInCorrectOrder[i].out;
+
+        nodes[i + 1] <== hashes[i].out;
+    }
+
+    isDepth[maxDepth] = IsEqual();
+    isDepth[maxDepth].in <== [actualDepth, maxDepth];
+    roots[maxDepth] <== isDepth[maxDepth].out * nodes[maxDepth];
+
+    out <== root + roots[maxDepth];
+}
@@ -0,0 +1,59 @@
+pragma circom 2.2.0;
+
+include "poseidon255.circom";
+include "comparators.circom";
+include "mux1.circom";
+
+/**
+ * @title MerkleProofDomainSeparated template
+ * @dev Same inclusion proof as MerkleProof, for trees built with
+ *      `LeanIMT::new_with_domain_separation(env, depth, true)`:
+ *   1. A leaf enters the tree as Poseidon255(2)(LEAF_DOMAIN, leaf)
+ *   2. Every parent is Poseidon255(3)(NODE_DOMAIN, left, right)
+ *   3. Missing leaves are still zero, so zero siblings are passed as-is
+ * The tags must match `LEAF_DOMAIN` and `NODE_DOMAIN` in libs/lean-imt.
+ * @param depth The depth of the Merkle tree
+ */
+template MerkleProofDomainSeparated(depth) {
+    var LEAF_DOMAIN = 1;
+    var NODE_DOMAIN = 2;
+
+    // inputs
+    signal input leaf;                  // leaf value to prove inclusion of
+    signal input leafIndex;             // index of leaf in the Merkle tree
+    signal input siblings[depth];       // sibling node values along the path to the root
+
+    // outputs
+    signal output out;
+
+    // internal signals
+    signal nodes[depth + 1]; // stores computed node values at each level
+    signal indices[depth];   // stores path indices for each level
+
+    // components
+    component hashInCorrectOrder[depth];
+    component hashes[depth];
+
+    component indexToPath = Num2Bits(depth);
+    indexToPath.in <== leafIndex;
+    indices <== indexToPath.out;
+
+    // Tag the leaf before it enters the tree
+    component leafHash = Poseidon255(2);
+    leafHash.in <== [LEAF_DOMAIN, leaf];
+    nodes[0] <== leafHash.out;
+
+    for (var i = 0; i < depth; i++) {
+        var childrenToSort[2][2] = [ [nodes[i], siblings[i]], [siblings[i], nodes[i]] ];
+        hashInCorrectOrder[i] = MultiMux1(2);
+        hashInCorrectOrder[i].c <== childrenToSort;
+        hashInCorrectOrder[i].s <== indices[i];
+
+        hashes[i] = Poseidon255(3);
+        hashes[i].in <== [NODE_DOMAIN, hashInCorrectOrder[i].out[0], hashInCorrectOrder[i].out[1]];
+
+        nodes[i + 1] <== hashes[i].out;
+    }
+
+    out <== nodes[depth];
+}
@@ -0,0 +1,70 @@
+pragma circom 2.2.0;
+
+include "commitment.circom";
+include "merkleProof.circom";
+include "poseidon.circom";
+
+// The checks every withdrawal variant makes on the coin it spends: the commitment is in
+// the state tree, its label is in the association set (a zero root accepts any label)
+// and withdrawnValue fits in the coin's value.
+//
+// The caller exposes nullifierHash and the three inputs below as its first public
+// signals, in the order [nullifierHash, withdrawnValue, stateRoot, associationRoot]
+// that the contract's PublicSignalLayout starts with, and adds its own after them.
+// main.circom keeps these checks inline so its existing trusted setup stays valid.
+template SpendCoin(treeDepth, associationDepth) {
+    signal input withdrawnValue;
+    signal input stateRoot;             // a known state root
+    signal input associationRoot;       // root of the association set Merkle tree
+
+    // the spent coin
+    signal input label;                 // hash(scope, nonce) % SNARK_SCALAR_FIELD
+    signal input value;                 // value of the commitment
+    signal input nullifier;             // nullifier of the commitment
+    signal input secret;                // Secret of the commitment
+
+    // signals for merkle tree inclusion proofs
+    signal input stateSiblings[treeDepth];
+    signal input stateIndex;
+
+    // signals for association set verification
+    signal input labelIndex;
+    signal input labelSiblings[associationDepth];
+
+    signal output nullifierHash;
+    signal output remainingValue;       // value - withdrawnValue, range checked
+
+    // compute the commitment and its nullifier hash
+    component commitmentHasher = CommitmentHasher();
+    commitmentHasher.label <== label;
+    commitmentHasher.value <== value;
+    commitmentHasher.secret <== secret;
+    commitmentHasher.nullifier <== nullifier;
+    nullifierHash <== commitmentHasher.nullifierHash;
+
+    // verify the commitment is in the state tree
+    component stateRootChecker = MerkleProof(treeDepth);
+    stateRootChecker.leaf <== commitmentHasher.commitment;
+    stateRootChecker.leafIndex <== stateIndex;
+    stateRootChecker.siblings <== stateSiblings;
+    stateRoot === stateRootChecker.out;
+
+    // verify the label is in the association set (a zero root accepts any label, as in main.circom)
+    component associationRootChecker = MerkleProof(associationDepth);
+    associationRootChecker.leaf <== label;
+    associationRootChecker.leafIndex <== labelIndex;
+    associationRootChecker.siblings <== labelSiblings;
+    signal diff <== associationRoot - associationRootChecker.out;
+    signal product <== associationRoot * diff;
+    product === 0;
+
+    // the withdrawn value must not exceed the commitment value
+    remainingValue <== value - withdrawnValue;
+    component remainingValueRangeCheck = Num2Bits(128);
+    remainingValueRangeCheck.in <== remainingValue;
+    _ <== remainingValueRangeCheck.out;
+
+    component withdrawnValueRangeCheck = Num2Bits(128);
+    withdrawnValueRangeCheck.in <== withdrawnValue;
+    _ <== withdrawnValueRangeCheck.out;
+}
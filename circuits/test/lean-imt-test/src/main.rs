@@ -1,11 +1,13 @@
-use lean_imt::LeanIMT;
+use lean_imt::{scalar_to_be_bytes, LeanIMT};
 use num_bigint::BigUint;
 use serde::{Deserialize, Serialize};
 use soroban_sdk::{crypto::bls12_381::Fr as BlsScalar, Env};
 
+mod wtns;
+
 /// Converts a BlsScalar to a decimal string representation
 fn bls_scalar_to_decimal(scalar: BlsScalar) -> String {
-    let bytes = scalar.to_bytes();
+    let bytes = scalar_to_be_bytes(&scalar);
     let mut bytes_array = [0u8; 32];
     bytes.copy_into_slice(&mut bytes_array);
     let biguint = BigUint::from_bytes_be(&bytes_array);
@@ -21,145 +23,169 @@ struct MerkleProofResult {
     root: String,
 }
 
-fn main() {
-    let args: Vec<String> = std::env::args().collect();
-
-    if args.len() >= 6 {
-        // Proof mode - compute merkle proof for specific leaf
-        let mut leaves = Vec::new();
-        for i in 1..5 {
-            let leaf_value = args[i].parse().unwrap_or(0);
-            leaves.push(leaf_value);
-        }
+/// Command-line arguments: `[--depth N] <leaf>... <leaf_index> [witness.wtns]`
+#[derive(Debug, PartialEq)]
+struct Args {
+    depth: u32,
+    leaves: Vec<u64>,
+    leaf_index: u32,
+    witness: Option<String>,
+}
 
-        let leaf_index: u32 = args[5].parse().unwrap_or(0);
+/// Depth of `test_merkleProof.circom`, used when `--depth` is not given
+const DEFAULT_DEPTH: u32 = 2;
 
-        println!("🧪 Computing Merkle Proof for Leaf Index {}", leaf_index);
-        println!("================================================");
-        println!("Testing merkle proof generation with lean-imt");
-        println!("");
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut depth = DEFAULT_DEPTH;
+    let mut positional = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--depth" {
+            let value = iter.next().ok_or("--depth needs a value")?;
+            depth = value
+                .parse()
+                .map_err(|_| format!("invalid depth: {}", value))?;
+        } else {
+            positional.push(arg.as_str());
+        }
+    }
 
-        let env = Env::default();
-        env.cost_estimate().budget().reset_unlimited();
-        let proof_result = compute_merkle_proof(&env, &leaves, leaf_index);
-
-        println!("Leaf index: {}", proof_result.leafIndex);
-        println!("Leaf value: {}", leaves[leaf_index as usize]);
-        // Convert siblings to decimal for display
-        let siblings_decimal_display: Vec<String> = proof_result
-            .siblings
-            .iter()
-            .enumerate()
-            .map(|(i, sibling_decimal)| {
-                if i == 0 {
-                    // First sibling is the other leaf at the same level
-                    let sibling_leaf_index = if leaf_index < 2 {
-                        if leaf_index == 0 {
-                            1
-                        } else {
-                            0
-                        }
-                    } else {
-                        if leaf_index == 2 {
-                            3
-                        } else {
-                            2
-                        }
-                    };
-                    leaves[sibling_leaf_index].to_string()
-                } else {
-                    // Second sibling is already a decimal string
-                    sibling_decimal.to_string()
-                }
-            })
-            .collect();
-        println!("Siblings: {:?}", siblings_decimal_display);
-        println!("Merkle root: {}", proof_result.root);
-
-        // Save circuit-compatible input with decimal string representations
-        let leaf_decimal = leaves[leaf_index as usize].to_string();
-
-        // The siblings are already in decimal format, so we can use them directly
-        let siblings_decimal = proof_result.siblings.clone();
-
-        let circuit_input = CircuitInput {
-            leaf: leaf_decimal,
-            leafIndex: proof_result.leafIndex,
-            siblings: siblings_decimal,
-        };
-        let circuit_json = serde_json::to_string_pretty(&circuit_input).unwrap();
-        std::fs::write("circuit_input.json", circuit_json).unwrap();
-        println!("📁 Circuit input saved to: circuit_input.json");
+    // A trailing argument that isn't a number is the witness file
+    let witness = match positional.last() {
+        Some(last) if last.parse::<u64>().is_err() => positional.pop().map(str::to_string),
+        _ => None,
+    };
+    let (index, leaves) = positional
+        .split_last()
+        .ok_or("expected at least one leaf and a leaf index")?;
+    let leaf_index: u32 = index
+        .parse()
+        .map_err(|_| format!("invalid leaf index: {}", index))?;
+    let leaves = leaves
+        .iter()
+        .map(|leaf| leaf.parse().map_err(|_| format!("invalid leaf: {}", leaf)))
+        .collect::<Result<Vec<u64>, String>>()?;
 
-        return;
+    if leaves.is_empty() {
+        return Err("expected at least one leaf".to_string());
+    }
+    if depth < 32 && leaves.len() as u64 > 1u64 << depth {
+        return Err(format!(
+            "{} leaves do not fit in a tree of depth {}",
+            leaves.len(),
+            depth
+        ));
     }
+    if leaf_index as usize >= leaves.len() {
+        return Err(format!(
+            "leaf index {} is out of range for {} leaves",
+            leaf_index,
+            leaves.len()
+        ));
+    }
+
+    Ok(Args {
+        depth,
+        leaves,
+        leaf_index,
+        witness,
+    })
+}
 
-    // Show usage if no valid arguments provided
+fn print_usage() {
     println!("🧪 Lean-IMT Test Suite");
     println!("======================");
     println!("Usage:");
-    println!("   cargo run -- <leaf1> <leaf2> <leaf3> <leaf4> <leaf_index>");
+    println!("   cargo run -- [--depth N] <leaf>... <leaf_index> [witness.wtns]");
+    println!(
+        "\nThe depth defaults to {}; missing leaves are zero.",
+        DEFAULT_DEPTH
+    );
     println!("\nExample:");
     println!("   cargo run -- 0 0 0 0 0");
+    println!("   cargo run -- --depth 3 1 2 3 4 5 4");
 }
 
-fn compute_merkle_proof(env: &Env, leaves: &[u64], leaf_index: u32) -> MerkleProofResult {
-    // Create a new LeanIMT instance
-    let mut tree = LeanIMT::new(env, 2);
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
+        print_usage();
+        return;
+    }
+    let args = match parse_args(&args) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            print_usage();
+            std::process::exit(1);
+        }
+    };
+    let leaf_index = args.leaf_index;
+
+    println!("🧪 Computing Merkle Proof for Leaf Index {}", leaf_index);
+    println!("================================================");
+    println!(
+        "Testing merkle proof generation with lean-imt (depth {})",
+        args.depth
+    );
+    println!();
+
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+    let proof_result = compute_merkle_proof(&env, args.depth, &args.leaves, leaf_index);
+
+    println!("Leaf index: {}", proof_result.leafIndex);
+    println!("Leaf value: {}", proof_result.leaf);
+    println!("Siblings: {:?}", proof_result.siblings);
+    println!("Merkle root: {}", proof_result.root);
+
+    // Save circuit-compatible input with decimal string representations
+    let circuit_input = CircuitInput {
+        leaf: proof_result.leaf.clone(),
+        leafIndex: proof_result.leafIndex,
+        siblings: proof_result.siblings.clone(),
+    };
+    let circuit_json = serde_json::to_string_pretty(&circuit_input).unwrap();
+    std::fs::write("circuit_input.json", circuit_json).unwrap();
+    println!("📁 Circuit input saved to: circuit_input.json");
 
-    for &leaf in leaves {
-        tree.insert_u64(leaf).unwrap();
+    // Optionally compare against the root computed by the circuit
+    if let Some(witness_path) = args.witness {
+        let output = wtns::extract_witness_output(&witness_path).expect("Failed to read witness");
+        let output_decimal = BigUint::from_bytes_le(&output).to_string();
+        if output_decimal == proof_result.root {
+            println!("✅ Circuit root matches: {}", output_decimal);
+        } else {
+            println!("❌ Circuit root mismatch: {}", output_decimal);
+            std::process::exit(1);
+        }
     }
+}
 
-    // Generate the merkle proof for the specified leaf index
-    let proof = tree
+/// Builds a tree of `depth` over `leaves` and returns the proof for `leaf_index`, with
+/// every value as a decimal string as the circuit expects
+fn compute_merkle_proof(
+    env: &Env,
+    depth: u32,
+    leaves: &[u64],
+    leaf_index: u32,
+) -> MerkleProofResult {
+    let mut tree = LeanIMT::new(env, depth);
+    tree.insert_many_u64(leaves).unwrap();
+
+    // `depth` siblings from the leaf up, zero subtree roots where a side is empty
+    let (siblings, _) = tree
         .generate_proof(leaf_index)
         .expect("Failed to generate proof");
-    let (siblings, depth) = proof;
-
-    // Get the leaf value using the new scalar-based method
     let leaf_scalar = tree
         .get_leaf_scalar(leaf_index as usize)
         .expect("Leaf not found");
-    let leaf_value_decimal = bls_scalar_to_decimal(leaf_scalar);
-
-    // Convert siblings to decimal strings - exactly `depth` items (no root included)
-    let mut siblings_decimal = Vec::new();
-    for i in 0..(depth as usize) {
-        if i == 0 {
-            // First sibling is the other leaf at the same level
-            let sibling_leaf_index = if leaf_index < 2 {
-                if leaf_index == 0 {
-                    1
-                } else {
-                    0
-                }
-            } else {
-                if leaf_index == 2 {
-                    3
-                } else {
-                    2
-                }
-            };
-            siblings_decimal.push(leaves[sibling_leaf_index].to_string());
-        } else {
-            let sibling = siblings
-                .get(i as u32)
-                .expect("Missing sibling in proof for required depth");
-            let decimal_value = bls_scalar_to_decimal(sibling);
-            siblings_decimal.push(decimal_value);
-        }
-    }
-
-    // Get the merkle root for display
-    let root_scalar = tree.get_root_scalar();
-    let root_decimal = bls_scalar_to_decimal(root_scalar);
 
     MerkleProofResult {
-        leaf: leaf_value_decimal,
+        leaf: bls_scalar_to_decimal(leaf_scalar),
         leafIndex: leaf_index,
-        siblings: siblings_decimal,
-        root: root_decimal,
+        siblings: siblings.iter().map(bls_scalar_to_decimal).collect(),
+        root: bls_scalar_to_decimal(tree.get_root_scalar()),
     }
 }
 
@@ -170,3 +196,74 @@ struct CircuitInput {
     leafIndex: u32,
     siblings: Vec<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lean_imt::Poseidon255;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args_depth_leaves_and_witness() {
+        assert_eq!(
+            parse_args(&args(&["1", "2", "3", "4", "0"])).unwrap(),
+            Args {
+                depth: 2,
+                leaves: vec![1, 2, 3, 4],
+                leaf_index: 0,
+                witness: None,
+            }
+        );
+        assert_eq!(
+            parse_args(&args(&["--depth", "3", "5", "6", "7", "1", "out.wtns"])).unwrap(),
+            Args {
+                depth: 3,
+                leaves: vec![5, 6, 7],
+                leaf_index: 1,
+                witness: Some("out.wtns".to_string()),
+            }
+        );
+
+        assert!(parse_args(&args(&["1", "2", "3", "4", "5", "0"])).is_err());
+        assert!(parse_args(&args(&["1", "2", "2"])).is_err());
+        assert!(parse_args(&args(&["--depth"])).is_err());
+    }
+
+    fn decimal_to_scalar(env: &Env, decimal: &str) -> BlsScalar {
+        let mut bytes = [0u8; 32];
+        let be = decimal.parse::<BigUint>().unwrap().to_bytes_be();
+        bytes[32 - be.len()..].copy_from_slice(&be);
+        BlsScalar::from_bytes(soroban_sdk::BytesN::from_array(env, &bytes))
+    }
+
+    #[test]
+    fn test_proof_at_any_depth_recomputes_the_root() {
+        let env = Env::default();
+        env.cost_estimate().budget().reset_unlimited();
+        let poseidon = Poseidon255::new(&env);
+        let leaves = [1, 2, 3, 4, 5];
+
+        for depth in [3, 5] {
+            for leaf_index in 0..leaves.len() as u32 {
+                let result = compute_merkle_proof(&env, depth, &leaves, leaf_index);
+                assert_eq!(result.siblings.len(), depth as usize);
+                assert_eq!(result.leaf, leaves[leaf_index as usize].to_string());
+
+                // Hash up the path the way `MerkleProof(depth)` does
+                let mut node = decimal_to_scalar(&env, &result.leaf);
+                for (level, sibling) in result.siblings.iter().enumerate() {
+                    let sibling = decimal_to_scalar(&env, sibling);
+                    node = if (leaf_index >> level) & 1 == 0 {
+                        poseidon.hash_two(&node, &sibling)
+                    } else {
+                        poseidon.hash_two(&sibling, &node)
+                    };
+                }
+                assert_eq!(bls_scalar_to_decimal(node), result.root);
+            }
+        }
+    }
+}
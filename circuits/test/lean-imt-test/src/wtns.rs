@@ -0,0 +1,242 @@
+//! Minimal reader/writer for snarkjs `.wtns` witness files (version 2).
+//!
+//! Layout, all integers little-endian:
+//! - magic `"wtns"`, version `u32`, section count `u32`
+//! - section 1 (header): type `u32 = 1`, size `u64`, then `n8: u32`, prime (`n8` bytes), `n_witness: u32`
+//! - section 2 (data): type `u32 = 2`, size `u64 = n8 * n_witness`, then the field elements
+//!
+//! Field elements are handled in the file's own little-endian encoding; use
+//! `lean_imt::scalar_to_le_bytes` to compare them against tree values.
+//!
+//! The header does not record how many outputs or public inputs a circuit has (that
+//! lives in the `.r1cs`), so outputs are located through circom's wire ordering:
+//! signal 0 is the constant `1`, followed by the main component's outputs.
+
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"wtns";
+const VERSION: u32 = 2;
+const HEADER_SECTION: u32 = 1;
+const DATA_SECTION: u32 = 2;
+const FIELD_SIZE: u32 = 32;
+
+/// Index of the first output signal in circom's witness layout
+pub const FIRST_OUTPUT_SIGNAL: usize = 1;
+
+/// BLS12-381 scalar field modulus, little-endian (the `--prime bls12381` used for the circuits)
+const BLS12_381_PRIME_LE: [u8; 32] = [
+    0x01, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xfe, 0x5b, 0xfe, 0xff, 0x02, 0xa4, 0xbd, 0x53,
+    0x05, 0xd8, 0xa1, 0x09, 0x08, 0xd8, 0x39, 0x33, 0x48, 0x7d, 0x9d, 0x29, 0x53, 0xa7, 0xed, 0x73,
+];
+
+/// Writes `field_elements` (little-endian, as stored in the file) as a version-2 `.wtns` file
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn write_witness(path: impl AsRef<Path>, field_elements: &[[u8; 32]]) -> io::Result<()> {
+    let n_witness = u32::try_from(field_elements.len())
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "too many witness elements"))?;
+
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.extend_from_slice(&2u32.to_le_bytes());
+
+    // Header section: n8, prime, number of witness elements
+    out.extend_from_slice(&HEADER_SECTION.to_le_bytes());
+    out.extend_from_slice(&(4 + FIELD_SIZE as u64 + 4).to_le_bytes());
+    out.extend_from_slice(&FIELD_SIZE.to_le_bytes());
+    out.extend_from_slice(&BLS12_381_PRIME_LE);
+    out.extend_from_slice(&n_witness.to_le_bytes());
+
+    // Data section: the witness elements themselves
+    out.extend_from_slice(&DATA_SECTION.to_le_bytes());
+    out.extend_from_slice(&(FIELD_SIZE as u64 * n_witness as u64).to_le_bytes());
+    for element in field_elements {
+        out.extend_from_slice(element);
+    }
+
+    fs::write(path, out)
+}
+
+/// Reads all witness elements from a `.wtns` file
+pub fn read_witness(path: impl AsRef<Path>) -> io::Result<Vec<[u8; 32]>> {
+    let data = fs::read(path)?;
+    let mut reader = Reader {
+        data: &data,
+        pos: 0,
+    };
+
+    if reader.take(4)? != MAGIC {
+        return Err(invalid("not a wtns file"));
+    }
+    let version = reader.u32()?;
+    if version != VERSION {
+        return Err(invalid("unsupported wtns version"));
+    }
+    let n_sections = reader.u32()?;
+
+    let mut n_witness = None;
+    let mut elements = None;
+    for _ in 0..n_sections {
+        let section_type = reader.u32()?;
+        let section_size =
+            usize::try_from(reader.u64()?).map_err(|_| invalid("section too large"))?;
+        let mut section = Reader {
+            data: reader.take(section_size)?,
+            pos: 0,
+        };
+
+        match section_type {
+            HEADER_SECTION => {
+                if section.u32()? != FIELD_SIZE {
+                    return Err(invalid("unsupported field element size"));
+                }
+                section.take(FIELD_SIZE as usize)?;
+                n_witness = Some(section.u32()? as usize);
+            }
+            DATA_SECTION => {
+                let chunks = section.data.chunks_exact(FIELD_SIZE as usize);
+                if !chunks.remainder().is_empty() {
+                    return Err(invalid("truncated witness data"));
+                }
+                elements = Some(
+                    chunks
+                        .map(|chunk| chunk.try_into().unwrap())
+                        .collect::<Vec<[u8; 32]>>(),
+                );
+            }
+            // Unknown sections are skipped, as snarkjs does
+            _ => {}
+        }
+    }
+
+    let n_witness = n_witness.ok_or_else(|| invalid("missing header section"))?;
+    let elements = elements.ok_or_else(|| invalid("missing witness data section"))?;
+    if elements.len() != n_witness {
+        return Err(invalid("witness count does not match header"));
+    }
+    Ok(elements)
+}
+
+/// Extracts the first circuit output (signal 1) from a `.wtns` file.
+///
+/// For `test_merkleProof.circom` this is `out`, the computed merkle root.
+///
+/// Fallback: if the witness is too short to contain signal 1, the last non-zero element
+/// is returned instead. That heuristic breaks as soon as any later signal is non-zero,
+/// so it is only kept for hand-made witnesses that do not follow circom's layout.
+pub fn extract_witness_output(path: impl AsRef<Path>) -> io::Result<[u8; 32]> {
+    let elements = read_witness(path)?;
+    if let Some(output) = elements.get(FIRST_OUTPUT_SIGNAL) {
+        return Ok(*output);
+    }
+
+    elements
+        .into_iter()
+        .rev()
+        .find(|element| element.iter().any(|&byte| byte != 0))
+        .ok_or_else(|| invalid("witness has no non-zero element"))
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| invalid("unexpected end of wtns file"))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lean_imt::{scalar_to_le_bytes, LeanIMT};
+    use soroban_sdk::Env;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("{}_{}.wtns", name, std::process::id()))
+    }
+
+    fn field(value: u8) -> [u8; 32] {
+        let mut element = [0u8; 32];
+        element[0] = value;
+        element
+    }
+
+    #[test]
+    fn test_write_then_read_roundtrip() {
+        let path = temp_path("wtns_roundtrip");
+        let elements = vec![field(1), field(7), [0u8; 32], field(42)];
+
+        write_witness(&path, &elements).unwrap();
+        let read_back = read_witness(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(read_back, elements);
+    }
+
+    #[test]
+    fn test_extract_output_matches_lean_imt_root() {
+        let env = Env::default();
+        env.cost_estimate().budget().reset_unlimited();
+
+        let mut tree = LeanIMT::new(&env, 2);
+        tree.insert_many_u64(&[1, 2, 3, 4]).unwrap();
+        let root = scalar_to_le_bytes(&tree.get_root_scalar()).to_array();
+
+        // Circom layout: constant 1, then outputs, then inputs and intermediates.
+        // Later signals are non-zero, which used to fool the last-non-zero scan.
+        let path = temp_path("wtns_root");
+        write_witness(
+            &path,
+            &[field(1), root, field(1), field(0), field(2), field(9)],
+        )
+        .unwrap();
+        let output = extract_witness_output(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(output, root);
+    }
+
+    #[test]
+    fn test_extract_output_falls_back_to_last_non_zero() {
+        let path = temp_path("wtns_fallback");
+        write_witness(&path, &[field(5)]).unwrap();
+        let output = extract_witness_output(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(output, field(5));
+    }
+
+    #[test]
+    fn test_rejects_bad_magic() {
+        let path = temp_path("wtns_bad_magic");
+        fs::write(&path, b"nope\x02\x00\x00\x00").unwrap();
+        let result = read_witness(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+}
@@ -127,3 +127,68 @@ fn main() {
     println!("{}", decimal_output1);
     println!("{}", decimal_output2);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `(in1, in2, Poseidon255(1)(in1), Poseidon255(2)(in1, in2))` as decimal strings
+    ///
+    /// The first two rows are circom outputs: `(123456789, 0)` is the witness recorded in
+    /// `circuits/test/README.md`, and `(1, 2)` is the poseidon-bls12381-circom reference
+    /// for `[1]` and `[1, 2]`. The other rows pin this implementation's current output
+    /// (zero inputs, the sample in `test_poseidon_input.json`, and the largest field
+    /// element) so a change to the constants or their parsing fails the test.
+    const VECTORS: [(&str, &str, &str, &str); 5] = [
+        (
+            "123456789",
+            "0",
+            "49771379518533783451081444171936304251693849153677701053778138403868110038125",
+            "2595333311380081774082696984545715941782212075692277571540746075566179600420",
+        ),
+        (
+            "1",
+            "2",
+            "33312903538086167554741214005086116725441315171650202128840830167854170336490",
+            "28821147804331559602169231704816259064962739503761913593647409715501647586810",
+        ),
+        (
+            "0",
+            "0",
+            "2811068068091031911201269074038037779542827974520177560187358960284013358662",
+            "51576823595707970152643159819788304363803754756066229172775779360774743019614",
+        ),
+        (
+            "51576823595707970152643159819788304363803754756066229172775779360774743019614",
+            "51576823595707970152643159819788304363803754756066229172775779360774743019614",
+            "23949694927245629364409973542171811196735943625447189117637854868491068555458",
+            "33646187916922823865935622258451714952164674255482660942215703235411158105736",
+        ),
+        (
+            "52435875175126190479447740508185965837690552500527637822603658699938581184512",
+            "3",
+            "7813170824547960260738571974704552535576767123587963859613076085894492214587",
+            "42645250214888395287766873645631195277124678371147124803813720407669371266475",
+        ),
+    ];
+
+    fn scalar(env: &Env, decimal: &str) -> BlsScalar {
+        biguint_to_bls_scalar(env, &BigUint::parse_bytes(decimal.as_bytes(), 10).unwrap())
+    }
+
+    #[test]
+    fn test_poseidon255_vectors() {
+        let env = Env::default();
+        env.cost_estimate().budget().reset_unlimited();
+
+        for (in1, in2, hash1, hash2) in VECTORS {
+            let in1 = scalar(&env, in1);
+            let in2 = scalar(&env, in2);
+            assert_eq!(bls_scalar_to_decimal(poseidon_hash_t2(&env, &in1)), hash1);
+            assert_eq!(
+                bls_scalar_to_decimal(poseidon_hash_t3(&env, &in1, &in2)),
+                hash2
+            );
+        }
+    }
+}
@@ -0,0 +1,59 @@
+pragma circom 2.2.0;
+
+include "spendCoin.circom";
+
+// Withdraws a whole coin with a proof that stops being accepted after a ledger chosen by
+// the prover, instead of staying submittable until its state root leaves the history.
+//
+// Public signals, in the order the contract's EXPIRY_SIGNAL_LAYOUT expects:
+//   [nullifierHash, withdrawnValue, stateRoot, associationRoot, expiryLedger]
+template WithdrawWithExpiry(treeDepth, associationDepth) {
+    // PUBLIC SIGNALS
+    signal input withdrawnValue;
+    signal input stateRoot;             // a known state root
+    signal input associationRoot;       // root of the association set Merkle tree
+    signal input expiryLedger;          // last ledger sequence the proof is valid in
+
+    // PRIVATE SIGNALS
+
+    // signals to compute commitments
+    signal input label;                 // hash(scope, nonce) % SNARK_SCALAR_FIELD
+    signal input value;                 // value of the commitment
+    signal input nullifier;             // nullifier of the commitment
+    signal input secret;                // Secret of the commitment
+
+    // signals for merkle tree inclusion proofs
+    signal input stateSiblings[treeDepth];
+    signal input stateIndex;
+
+    // signals for association set verification
+    signal input labelIndex;
+    signal input labelSiblings[associationDepth];
+
+    // OUTPUT SIGNALS
+    signal output nullifierHash;
+
+    // IMPLEMENTATION
+
+    // spend the coin: state tree, association set and value checks
+    component spend = SpendCoin(treeDepth, associationDepth);
+    spend.withdrawnValue <== withdrawnValue;
+    spend.stateRoot <== stateRoot;
+    spend.associationRoot <== associationRoot;
+    spend.label <== label;
+    spend.value <== value;
+    spend.nullifier <== nullifier;
+    spend.secret <== secret;
+    spend.stateSiblings <== stateSiblings;
+    spend.stateIndex <== stateIndex;
+    spend.labelIndex <== labelIndex;
+    spend.labelSiblings <== labelSiblings;
+    nullifierHash <== spend.nullifierHash;
+    _ <== spend.remainingValue;
+
+    // bind the expiry: a public input with no constraint could be optimized away,
+    // leaving the proof valid for any expiry
+    signal expiryLedgerSquare <== expiryLedger * expiryLedger;
+}
+
+component main {public [withdrawnValue, stateRoot, associationRoot, expiryLedger]} = WithdrawWithExpiry(20, 2);  // state tree depth 20, association tree depth 2
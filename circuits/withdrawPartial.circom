@@ -0,0 +1,74 @@
+pragma circom 2.2.0;
+
+include "spendCoin.circom";
+
+// Spends a coin, withdraws part of its value and commits the rest to a change coin.
+//
+// Public signals, in the order the contract's PARTIAL_SIGNAL_LAYOUT expects:
+//   [nullifierHash, withdrawnValue, stateRoot, associationRoot, changeCommitment, denomination]
+template WithdrawPartial(treeDepth, associationDepth) {
+    // PUBLIC SIGNALS
+    signal input withdrawnValue;
+    signal input stateRoot;             // a known state root
+    signal input associationRoot;       // root of the association set Merkle tree
+    signal input changeCommitment;      // commitment to the remaining value, inserted by the contract
+    signal input denomination;          // the pool denomination; bounds the spent coin's value
+
+    // PRIVATE SIGNALS
+
+    // the spent coin
+    signal input label;                 // hash(scope, nonce) % SNARK_SCALAR_FIELD, shared with the change coin
+    signal input value;                 // value of the commitment
+    signal input nullifier;             // nullifier of the commitment
+    signal input secret;                // Secret of the commitment
+
+    // the change coin (same label, so it stays in the association set)
+    signal input changeNullifier;
+    signal input changeSecret;
+
+    // signals for merkle tree inclusion proofs
+    signal input stateSiblings[treeDepth];
+    signal input stateIndex;
+
+    // signals for association set verification
+    signal input labelIndex;
+    signal input labelSiblings[associationDepth];
+
+    // OUTPUT SIGNALS
+    signal output nullifierHash;
+
+    // IMPLEMENTATION
+
+    // spend the coin: state tree, association set and value checks
+    component spend = SpendCoin(treeDepth, associationDepth);
+    spend.withdrawnValue <== withdrawnValue;
+    spend.stateRoot <== stateRoot;
+    spend.associationRoot <== associationRoot;
+    spend.label <== label;
+    spend.value <== value;
+    spend.nullifier <== nullifier;
+    spend.secret <== secret;
+    spend.stateSiblings <== stateSiblings;
+    spend.stateIndex <== stateIndex;
+    spend.labelIndex <== labelIndex;
+    spend.labelSiblings <== labelSiblings;
+    nullifierHash <== spend.nullifierHash;
+
+    // value = withdrawnValue + changeValue, with both parts non-negative
+    signal changeValue <== spend.remainingValue;
+
+    // a coin never holds more than one deposit: value <= denomination
+    component valueBoundCheck = Num2Bits(128);
+    valueBoundCheck.in <== denomination - value;
+    _ <== valueBoundCheck.out;
+
+    // the change coin commits to exactly the remaining value
+    component changeHasher = CommitmentHasher();
+    changeHasher.label <== label;
+    changeHasher.value <== changeValue;
+    changeHasher.secret <== changeSecret;
+    changeHasher.nullifier <== changeNullifier;
+    changeCommitment === changeHasher.commitment;
+}
+
+component main {public [withdrawnValue, stateRoot, associationRoot, changeCommitment, denomination]} = WithdrawPartial(20, 2);  // state tree depth 20, association tree depth 2
@@ -0,0 +1,59 @@
+pragma circom 2.2.0;
+
+include "spendCoin.circom";
+
+// Withdraws a whole coin to a recipient fixed by the proof, so a relayer or anyone who
+// sees the transaction cannot resubmit the proof with their own address.
+//
+// Public signals, in the order the contract's RECIPIENT_SIGNAL_LAYOUT expects:
+//   [nullifierHash, withdrawnValue, stateRoot, associationRoot, recipient]
+template WithdrawToRecipient(treeDepth, associationDepth) {
+    // PUBLIC SIGNALS
+    signal input withdrawnValue;
+    signal input stateRoot;             // a known state root
+    signal input associationRoot;       // root of the association set Merkle tree
+    signal input recipient;             // the contract's recipient_hash(to)
+
+    // PRIVATE SIGNALS
+
+    // signals to compute commitments
+    signal input label;                 // hash(scope, nonce) % SNARK_SCALAR_FIELD
+    signal input value;                 // value of the commitment
+    signal input nullifier;             // nullifier of the commitment
+    signal input secret;                // Secret of the commitment
+
+    // signals for merkle tree inclusion proofs
+    signal input stateSiblings[treeDepth];
+    signal input stateIndex;
+
+    // signals for association set verification
+    signal input labelIndex;
+    signal input labelSiblings[associationDepth];
+
+    // OUTPUT SIGNALS
+    signal output nullifierHash;
+
+    // IMPLEMENTATION
+
+    // spend the coin: state tree, association set and value checks
+    component spend = SpendCoin(treeDepth, associationDepth);
+    spend.withdrawnValue <== withdrawnValue;
+    spend.stateRoot <== stateRoot;
+    spend.associationRoot <== associationRoot;
+    spend.label <== label;
+    spend.value <== value;
+    spend.nullifier <== nullifier;
+    spend.secret <== secret;
+    spend.stateSiblings <== stateSiblings;
+    spend.stateIndex <== stateIndex;
+    spend.labelIndex <== labelIndex;
+    spend.labelSiblings <== labelSiblings;
+    nullifierHash <== spend.nullifierHash;
+    _ <== spend.remainingValue;
+
+    // bind the recipient: a public input with no constraint could be optimized away,
+    // leaving the proof valid for any recipient
+    signal recipientSquare <== recipient * recipient;
+}
+
+component main {public [withdrawnValue, stateRoot, associationRoot, recipient]} = WithdrawToRecipient(20, 2);  // state tree depth 20, association tree depth 2
@@ -3,67 +3,29 @@ use base64::{self, engine::general_purpose};
 use clap::Parser;
 use num_bigint::BigUint;
 use num_traits::Num;
-use serde::Deserialize;
 use std::fs;
 
-// imports related to constructing VK, Proof and Public Signals
-use ark_bls12_381::{Fq, Fq2};
-use ark_serialize::CanonicalSerialize;
-use core::str::FromStr;
-use soroban_sdk::crypto::bls12_381::Fr;
-use soroban_sdk::crypto::bls12_381::{G1Affine, G2Affine, G1_SERIALIZED_SIZE, G2_SERIALIZED_SIZE};
-use soroban_sdk::U256;
-use soroban_sdk::{Bytes, Env, Vec};
-use zk::{Proof, PublicSignals, VerificationKey};
+use soroban_sdk::{Bytes, Env};
+use zk::{
+    Groth16Error, Groth16Verifier, Proof, ProofJson, PublicSignals, VerificationKey,
+    VerificationKeyJson,
+};
 
 #[derive(Parser)]
 struct Args {
+    /// `vk`, `proof`, `public`, or `verify`
     filetype: String,
+    /// Artifact to convert; for `verify`, the verification key
     filename: String,
-}
-
-#[derive(Deserialize)]
-struct VerificationKeyJson {
-    vk_alpha_1: [String; 3],
-    vk_beta_2: [[String; 2]; 3],
-    vk_gamma_2: [[String; 2]; 3],
-    vk_delta_2: [[String; 2]; 3],
-    #[serde(rename = "IC")]
-    ic: std::vec::Vec<[String; 3]>,
-    #[serde(rename = "nPublic")]
-    n_public: u32,
-}
-
-#[derive(Deserialize)]
-struct ProofJson {
-    pi_a: [String; 3],
-    pi_b: [[String; 2]; 3],
-    pi_c: [String; 3],
-    #[serde(rename = "protocol")]
-    _protocol: String,
-    #[serde(rename = "curve")]
-    _curve: String,
+    /// Proof file (`verify` only)
+    proof: Option<String>,
+    /// Public signals file (`verify` only)
+    public: Option<String>,
 }
 
 // Remove the old PublicOutputJson struct and replace with type alias
 type PublicOutputJson = std::vec::Vec<String>;
 
-fn g1_from_coords(env: &Env, x: &str, y: &str) -> G1Affine {
-    let ark_g1 = ark_bls12_381::G1Affine::new(Fq::from_str(x).unwrap(), Fq::from_str(y).unwrap());
-    let mut buf = [0u8; G1_SERIALIZED_SIZE];
-    ark_g1.serialize_uncompressed(&mut buf[..]).unwrap();
-    G1Affine::from_array(env, &buf)
-}
-
-fn g2_from_coords(env: &Env, x1: &str, x2: &str, y1: &str, y2: &str) -> G2Affine {
-    let x = Fq2::new(Fq::from_str(x1).unwrap(), Fq::from_str(x2).unwrap());
-    let y = Fq2::new(Fq::from_str(y1).unwrap(), Fq::from_str(y2).unwrap());
-    let ark_g2 = ark_bls12_381::G2Affine::new(x, y);
-    let mut buf = [0u8; G2_SERIALIZED_SIZE];
-    ark_g2.serialize_uncompressed(&mut buf[..]).unwrap();
-    G2Affine::from_array(env, &buf)
-}
-
 fn validate_vk(vk: &VerificationKeyJson) {
     let expected_ic_size = (vk.n_public + 1) as usize;
     if vk.ic.len() != expected_ic_size {
@@ -111,64 +73,21 @@ fn print_vk(json_str: &String) {
 
 fn vk_to_bytes(json_str: &String) -> Bytes {
     let env = Env::default();
+    vk_from_json(&env, json_str).to_bytes(&env)
+}
 
-    let vk_json: VerificationKeyJson = serde_json::from_str(json_str).expect("Invalid JSON");
-
-    // Validate the verification key structure
-    validate_vk(&vk_json);
-
-    let alphax = vk_json.vk_alpha_1[0].clone();
-    let alphay = vk_json.vk_alpha_1[1].clone();
-    let betax1 = vk_json.vk_beta_2[0][0].clone();
-    let betax2 = vk_json.vk_beta_2[0][1].clone();
-    let betay1 = vk_json.vk_beta_2[1][0].clone();
-    let betay2 = vk_json.vk_beta_2[1][1].clone();
-    let gammax1 = vk_json.vk_gamma_2[0][0].clone();
-    let gammax2 = vk_json.vk_gamma_2[0][1].clone();
-    let gammay1 = vk_json.vk_gamma_2[1][0].clone();
-    let gammay2 = vk_json.vk_gamma_2[1][1].clone();
-    let deltax1 = vk_json.vk_delta_2[0][0].clone();
-    let deltax2 = vk_json.vk_delta_2[0][1].clone();
-    let deltay1 = vk_json.vk_delta_2[1][0].clone();
-    let deltay2 = vk_json.vk_delta_2[1][1].clone();
-
-    // Build IC array dynamically based on nPublic
-    let mut ic_array = Vec::new(&env);
-    for i in 0..=vk_json.n_public {
-        let icx = vk_json.ic[i as usize][0].clone();
-        let icy = vk_json.ic[i as usize][1].clone();
-        ic_array.push_back(g1_from_coords(&env, &icx, &icy));
-    }
-
-    let vk = VerificationKey {
-        alpha: g1_from_coords(&env, &alphax, &alphay),
-        beta: g2_from_coords(&env, &betax1, &betax2, &betay1, &betay2),
-        gamma: g2_from_coords(&env, &gammax1, &gammax2, &gammay1, &gammay2),
-        delta: g2_from_coords(&env, &deltax1, &deltax2, &deltay1, &deltay2),
-        ic: ic_array,
-    };
-
-    return vk.to_bytes(&env);
+fn vk_from_json(env: &Env, json_str: &str) -> VerificationKey {
+    VerificationKey::from_json(env, json_str)
+        .unwrap_or_else(|e| panic!("Invalid verification key: {}", e))
 }
 
 fn proof_to_bytes(json_str: &String) -> Bytes {
     let env = Env::default();
-    let proof_json: ProofJson = serde_json::from_str(json_str).expect("Invalid JSON");
-    let pi_ax = proof_json.pi_a[0].clone();
-    let pi_ay = proof_json.pi_a[1].clone();
-    let pi_bx1 = proof_json.pi_b[0][0].clone();
-    let pi_bx2 = proof_json.pi_b[0][1].clone();
-    let pi_by1 = proof_json.pi_b[1][0].clone();
-    let pi_by2 = proof_json.pi_b[1][1].clone();
-    let pi_cx = proof_json.pi_c[0].clone();
-    let pi_cy = proof_json.pi_c[1].clone();
-
-    let proof = Proof {
-        a: g1_from_coords(&env, &pi_ax, &pi_ay),
-        b: g2_from_coords(&env, &pi_bx1, &pi_bx2, &pi_by1, &pi_by2),
-        c: g1_from_coords(&env, &pi_cx, &pi_cy),
-    };
-    proof.to_bytes(&env)
+    proof_from_json(&env, json_str).to_bytes(&env)
+}
+
+fn proof_from_json(env: &Env, json_str: &str) -> Proof {
+    Proof::from_json(env, json_str).unwrap_or_else(|e| panic!("Invalid proof: {}", e))
 }
 
 fn print_proof(json_str: &String) {
@@ -229,30 +148,61 @@ fn print_public_output(json_str: &String) {
 
 fn public_output_to_bytes(json_str: &String) -> Bytes {
     let env = Env::default();
-    let public_output: PublicOutputJson = serde_json::from_str(json_str).expect("Invalid JSON");
-    let mut pub_signals = Vec::new(&env);
-    for signal in public_output.iter() {
-        let value = num_bigint::BigUint::from_str_radix(signal, 10).unwrap();
-        let mut bytes = value.to_bytes_be();
-        // Pad to 32 bytes
-        if bytes.len() < 32 {
-            let mut padded = std::vec![0u8; 32 - bytes.len()];
-            padded.extend_from_slice(&bytes);
-            bytes = padded;
+    public_output_from_json(&env, json_str).to_bytes(&env)
+}
+
+fn public_output_from_json(env: &Env, json_str: &str) -> PublicSignals {
+    PublicSignals::from_json(env, json_str)
+        .unwrap_or_else(|e| panic!("Invalid public signals: {}", e))
+}
+
+/// Runs the same Groth16 verification as the contract against snarkjs JSON artifacts
+///
+/// A signal count that doesn't match the verification key counts as invalid, with the
+/// two counts printed to stderr.
+fn verify(vk_json: &str, proof_json: &str, public_json: &str) -> bool {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let vk = vk_from_json(&env, vk_json);
+    let proof = proof_from_json(&env, proof_json);
+    let public_signals = public_output_from_json(&env, public_json);
+
+    let expected = vk.ic.len().saturating_sub(1);
+    match Groth16Verifier::verify_proof(&env, vk, proof, &public_signals.pub_signals) {
+        Ok(valid) => valid,
+        Err(Groth16Error::SignalCountMismatch) => {
+            eprintln!(
+                "{} public signals, but the verification key expects {}",
+                public_signals.pub_signals.len(),
+                expected
+            );
+            false
         }
-        let arr: [u8; 32] = bytes.try_into().expect("slice with incorrect length");
-        let u256 = U256::from_be_bytes(&env, &Bytes::from_array(&env, &arr));
-        let fr = Fr::from_u256(u256);
-        pub_signals.push_back(fr);
+        Err(_) => false,
     }
-    let public_signals = PublicSignals { pub_signals };
-    public_signals.to_bytes(&env)
 }
 
 fn main() {
     let args = Args::parse();
     let json_str = fs::read_to_string(&args.filename).expect("Failed to read file");
 
+    if args.filetype == "verify" {
+        let (Some(proof_file), Some(public_file)) = (&args.proof, &args.public) else {
+            eprintln!("Usage: stellar-circom2soroban verify <vk.json> <proof.json> <public.json>");
+            std::process::exit(2);
+        };
+        let proof_json = fs::read_to_string(proof_file).expect("Failed to read proof file");
+        let public_json = fs::read_to_string(public_file).expect("Failed to read public file");
+
+        if verify(&json_str, &proof_json, &public_json) {
+            println!("VALID");
+        } else {
+            println!("INVALID");
+            std::process::exit(1);
+        }
+    }
+
     if args.filetype == "vk" {
         print_vk(&json_str);
         let vk_bytes = vk_to_bytes(&json_str);
@@ -283,3 +233,85 @@ fn main() {
         println!("Public signals Hex encoding:\n{}", public_hex);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Artifacts of the withdrawal circuit proof used by the contract tests
+    const VK_JSON: &str = r#"{
+        "protocol": "groth16",
+        "curve": "bls12381",
+        "nPublic": 4,
+        "vk_alpha_1": [
+            "2625583050305146829700663917277485398332586266229739236073977691599912239208704058548731458555934906273399977862822",
+            "1155364156944807367912876641032696519500054551629402873339575774959620483194368919563799050765095981406853619398751",
+            "1"
+        ],
+        "vk_beta_2": [
+            ["1659696755509039809248937927616726274238080235224171061036366585278216098417245587200210264410333778948851576160490", "1338363397031837211155983756179787835339490797745307535810204658838394402900152502268197396587061400659003281046656"],
+            ["1974652615426136516341494326987376616840373177388374023461177997087381634383568759591087499459321812809521924259354", "3301884318087924474550898163462840036865878131635519297186391370517333773367262804074867347346141727012544462046142"],
+            ["1", "0"]
+        ],
+        "vk_gamma_2": [
+            ["352701069587466618187139116011060144890029952792775240219908644239793785735715026873347600343865175952761926303160", "3059144344244213709971259814753781636986470325476647558659373206291635324768958432433509563104347017837885763365758"],
+            ["1985150602287291935568054521177171638300868978215655730859378665066344726373823718423869104263333984641494340347905", "927553665492332455747201965776037880757740193453592970025027978793976877002675564980949289727957565575433344219582"],
+            ["1", "0"]
+        ],
+        "vk_delta_2": [
+            ["2750191744467054372912942146482544263484467550244832445881626112777617723646810063952263428512022936903253267127350", "2413234737575312815700598631122026291319065432043412800839944397857332202830802685415923770088689063622756702939375"],
+            ["1076967202486993406108941342102174843689250913208763125383730107292668137282535239225119066564005251774661400843821", "784091089348445241891924627629031628871298938526420228496183038286414003726447208549611976928427786617444752683904"],
+            ["1", "0"]
+        ],
+        "IC": [
+            ["1931769351244036379618100283994844046485312882458040431401676712058257124546097756332532237907637132315648906217636", "2219462221684288788247757134332962645470083865115055927456187574960992952094314940257753501443104606354496083113203", "1"],
+            ["2726325242623221693388802248110816107554759305800882344642286106642968529507795071709947858512355148550879270019178", "2690452834591447292232392438454117662004701691035040250634864436657178120453111433393322306334324558619029220405511", "1"],
+            ["2276753520377413052133204619264853734926027674320220733263964937413806530791610300908525130874383991218501161443629", "2216565042994647061456742959690979278824752277479734731836503122505090074006677407948960110633236603228440758211011", "1"],
+            ["2054702829658916052030239062784122350883101497414801284378548048954817335805733517964277882891682327579038641542963", "1861299377849520465661244108949779781960526739720579329803172490216038156998919390163110860296739149427635782605232", "1"],
+            ["2856004998221708121377069305149495649378668245327503671752831152976814973551962498318427356938380464598719642329610", "3445052445376607662168014620609501339582857414982758608624858423598446194176241135586201569345644453045853894315946", "1"]
+        ]
+    }"#;
+
+    const PROOF_JSON: &str = r#"{
+        "pi_a": [
+            "212441980386531387965179969427761278516713032280181090947921812756826140060407715059887577334725859148245723641819",
+            "1043777624107376029707768486456740992720703652869770475160608327115557016215114376228813623265706726948422658129414",
+            "1"
+        ],
+        "pi_b": [
+            ["3418594862087761466119736619773903243566736312162156790303713071542564953050210637510854068742634808271915139642232", "2964213444574507673113570038406470453416167035798707123655994180726336395671895988634516761061394366777882421458970"],
+            ["3223650775040459204413178680640740880187469067260410489432422220219327812050544783645893434878446219624098341623090", "2459594096752687436760263121473341283140767398180854423130454432176129735618526991834231434439933241851791233993087"],
+            ["1", "0"]
+        ],
+        "pi_c": [
+            "443107262259769407693822320108000100156551631812684950355991461785927550068576958619659870180972229362608557133163",
+            "2104019945288105000027262551270879368920664217362082679701219406216434095932547212599338209378889102523865669374434",
+            "1"
+        ],
+        "protocol": "groth16",
+        "curve": "bls12381"
+    }"#;
+
+    // [nullifierHash, withdrawnValue, stateRoot, associationRoot]
+    const PUBLIC_JSON: &str = r#"[
+        "34247368358276748935470155212237028565861472679392865206076749426239931261485",
+        "1000000000",
+        "33610852770702921318594127247451689166620844611969545962852654412220930601660",
+        "42220846460510012783259546685177279211166460034342051256640265180289181334178"
+    ]"#;
+
+    #[test]
+    fn test_verify_accepts_valid_proof() {
+        assert!(verify(VK_JSON, PROOF_JSON, PUBLIC_JSON));
+    }
+
+    #[test]
+    fn test_verify_rejects_altered_signals() {
+        let altered = PUBLIC_JSON.replace("\"1000000000\"", "\"2000000000\"");
+        assert!(!verify(VK_JSON, PROOF_JSON, &altered));
+
+        // Too few signals for the verification key
+        let truncated = r#"["1000000000"]"#;
+        assert!(!verify(VK_JSON, PROOF_JSON, truncated));
+    }
+}
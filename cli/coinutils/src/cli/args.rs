@@ -1,4 +1,5 @@
-use clap::{Parser, Subcommand};
+use crate::config::{COIN_VALUE, TREE_DEPTH};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 #[derive(Parser)]
 #[command(name = "coinutils")]
@@ -18,17 +19,32 @@ pub enum Commands {
         /// Output file path
         #[arg(short, long, default_value = "coin.json")]
         output: String,
+        /// How `scope` is turned into the field element hashed into the label
+        #[arg(long, value_enum, default_value_t = ScopeFormat::Name)]
+        scope_format: ScopeFormat,
+        /// Coin value in the token's base units; must equal the pool's denomination
+        #[arg(long, default_value_t = COIN_VALUE as u128)]
+        value: u128,
+        /// Print the generated coin as a single JSON object instead of a summary
+        #[arg(long)]
+        json: bool,
     },
     /// Withdraw a coin
-    Withdraw {
-        /// Coin file path
-        coin_file: String,
+    Withdraw(WithdrawArgs),
+    /// Build the state merkle tree once and save it for later withdrawals
+    Merkleize {
         /// State file path
-        state_file: String,
-        /// Association set file path (optional)
-        association_file: Option<String>,
+        #[arg(required_unless_present = "ndjson")]
+        state_file: Option<String>,
+        /// Read commitments from a file with one decimal commitment per line instead of
+        /// a state file; blank lines and `#` comments are skipped
+        #[arg(long, value_name = "FILE", conflicts_with = "state_file")]
+        ndjson: Option<String>,
+        /// Tree depth for `--ndjson` input (a state file carries its own)
+        #[arg(long, requires = "ndjson", default_value_t = TREE_DEPTH)]
+        depth: u32,
         /// Output file path
-        #[arg(short, long, default_value = "withdrawal.json")]
+        #[arg(short, long, default_value = "tree.json")]
         output: String,
     },
     /// Update association set
@@ -37,7 +53,61 @@ pub enum Commands {
         association_file: String,
         /// Label to add
         label: String,
+        /// Association tree depth (holds up to 2^depth labels); defaults to the file's
+        /// depth, or 2 for a new file
+        #[arg(long)]
+        depth: Option<u32>,
     },
+    /// Bundle the contract's deployment arguments into one JSON file
+    PrepareDeploy {
+        /// snarkjs verification key of the withdrawal circuit
+        #[arg(long)]
+        vk: String,
+        /// Depth of the state tree the circuit was built for
+        #[arg(long, default_value_t = TREE_DEPTH)]
+        depth: u32,
+        /// Pool scope
+        #[arg(long)]
+        scope: String,
+        /// How `scope` is turned into the contract's 32-byte scope argument
+        #[arg(long, value_enum, default_value_t = ScopeFormat::Name)]
+        scope_format: ScopeFormat,
+        /// Output file path
+        #[arg(long, default_value = "deploy.json")]
+        out: String,
+    },
+}
+
+/// Arguments of the `withdraw` command
+#[derive(Args)]
+pub struct WithdrawArgs {
+    /// Coin file path
+    pub coin_file: String,
+    /// State file path
+    pub state_file: String,
+    /// Association set file path (optional)
+    pub association_file: Option<String>,
+    /// Output file path
+    #[arg(short, long, default_value = "withdrawal.json")]
+    pub output: String,
+    /// Pre-built state tree from `merkleize` (skips rebuilding from the state file)
+    #[arg(long)]
+    pub tree: Option<String>,
+    /// Amount to withdraw in the token's base units; defaults to the coin's value
+    #[arg(long)]
+    pub value: Option<u128>,
+    /// Print the snark input as a single JSON object instead of a summary
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// Encoding of the `generate` scope argument
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ScopeFormat {
+    /// A pool name; its first 31 bytes are right-aligned into a field element
+    Name,
+    /// A decimal field element, e.g. the contract's scope as a number
+    Decimal,
 }
 
 impl Cli {
@@ -49,26 +119,39 @@ impl Cli {
     /// Print usage information
     pub fn print_usage() {
         println!("Usage:");
-        println!("  coinutils generate [scope] [output_file]  - Generate a new coin");
-        println!("  coinutils withdraw <coin_file> <state_file> [association_set_file] [output_file]  - Withdraw a coin");
-        println!("  coinutils updateAssociation <association_set_file> <label>  - Add label to association set");
+        println!("  coinutils generate [scope] [output_file] [--scope-format name|decimal] [--value N] [--json]  - Generate a new coin");
+        println!("  coinutils withdraw <coin_file> <state_file> [association_set_file] [output_file] [--value N] [--json]  - Withdraw a coin");
+        println!("  coinutils updateAssociation <association_set_file> <label> [--depth N]  - Add label to association set");
+        println!("  coinutils merkleize <state_file> [-o tree.json]  - Save the state merkle tree");
+        println!("  coinutils merkleize --ndjson <commitments_file> [--depth N] [-o tree.json]  - Same, from one commitment per line");
+        println!("  coinutils prepare-deploy --vk <verification_key.json> --scope <scope> [--depth N] [--out deploy.json]  - Bundle deployment arguments");
         println!();
         println!("Examples:");
         println!("  coinutils generate my_pool_scope coin.json");
         println!("  coinutils withdraw coin.json state.json association.json withdrawal.json");
         println!("  coinutils updateAssociation association.json \"1234567890...\"");
+        println!("  coinutils merkleize state.json -o tree.json");
+        println!("  coinutils merkleize --ndjson commitments.txt -o tree.json");
+        println!("  coinutils withdraw coin.json state.json association.json --tree tree.json");
+        println!("  coinutils generate my_pool_scope coin.json --json | jq -r '.coin.label'");
+        println!("  coinutils generate 2147132627123717500780 coin.json --scope-format decimal");
+        println!("  coinutils generate my_pool_scope coin.json --value 1000000  # 1-unit pool of a 6-decimal token");
+        println!("  coinutils prepare-deploy --vk verification_key.json --scope my_pool_scope --out deploy.json");
         println!();
         println!("State file format:");
         println!("  {{");
         println!("    \"commitments\": [\"commitment1\", \"commitment2\", ...],");
-        println!("    \"scope\": \"pool_scope\"");
+        println!("    \"scope\": \"pool_scope\",");
+        println!("    \"depth\": 20");
         println!("  }}");
         println!();
         println!("Association set file format:");
         println!("  {{");
         println!("    \"labels\": [\"label1\", \"label2\", \"label3\", \"label4\"],");
         println!("    \"scope\": \"pool_scope\",");
-        println!("    \"root\": \"merkle_tree_root\"");
+        println!("    \"root\": \"merkle_tree_root\",");
+        println!("    \"depth\": 2,");
+        println!("    \"frontier\": [\"node\", null]");
         println!("  }}");
     }
 }
@@ -1,12 +1,20 @@
 use crate::{
-    crypto::coin::generate_coin,
-    error::Result,
+    cli::{ScopeFormat, WithdrawArgs},
+    crypto::{
+        coin::{generate_coin_for_scope, scope_from_name},
+        conversions::{bls_scalar_to_decimal_string, decimal_string_to_bls_scalar},
+        verification_key::verification_key_bytes,
+    },
+    error::{CoinUtilsError, Result},
     io::{FileManager, SerializationManager},
     merkle::association::AssociationManager,
     merkle::withdrawal::WithdrawalManager,
+    types::DeployArtifact,
 };
 use log::{debug, info};
-use soroban_sdk::Env;
+use soroban_sdk::{crypto::bls12_381::Fr as BlsScalar, Env};
+use std::fs::File;
+use std::io::BufReader;
 
 /// Command handler for processing CLI commands
 pub struct CommandHandler {
@@ -27,14 +35,26 @@ impl CommandHandler {
     }
 
     /// Handle the generate command
-    pub fn handle_generate(&self, scope: String, output: String) -> Result<()> {
+    ///
+    /// `scope_format` says whether `scope` is a pool name or a decimal field element,
+    /// and `value` is the coin's value in base units. With `json` set, the saved coin file's contents are printed to stdout as one
+    /// JSON object in place of the summary.
+    pub fn handle_generate(
+        &self,
+        scope: String,
+        output: String,
+        scope_format: ScopeFormat,
+        value: u128,
+        json: bool,
+    ) -> Result<()> {
         info!("Generating coin with scope: {}", scope);
         debug!("Output file: {}", output);
 
         let env = Env::default();
         env.cost_estimate().budget().reset_unlimited();
 
-        let generated_coin = generate_coin(&env, scope.as_bytes());
+        let scope_fr = scope_field(&env, &scope, scope_format)?;
+        let generated_coin = generate_coin_for_scope(&env, &scope_fr, value);
         debug!(
             "Generated coin commitment: {}",
             generated_coin.commitment_hex
@@ -45,6 +65,11 @@ impl CommandHandler {
             .write_coin_file(&generated_coin, &output)?;
         info!("Coin saved to: {}", output);
 
+        if json {
+            println!("{}", serde_json::to_string(&generated_coin)?);
+            return Ok(());
+        }
+
         println!("Generated coin:");
         println!("  Value: {}", generated_coin.coin.value);
         println!("  Nullifier: {}", generated_coin.coin.nullifier);
@@ -57,17 +82,26 @@ impl CommandHandler {
     }
 
     /// Handle the withdraw command
-    pub fn handle_withdraw(
-        &self,
-        coin_file: String,
-        state_file: String,
-        association_file: Option<String>,
-        output: String,
-    ) -> Result<()> {
+    ///
+    /// `value` is the amount to withdraw, the coin's whole value when `None`. With
+    /// `json` set, the snark input is printed to stdout as one JSON object in place of
+    /// the summary.
+    pub fn handle_withdraw(&self, args: WithdrawArgs) -> Result<()> {
+        let WithdrawArgs {
+            coin_file,
+            state_file,
+            association_file,
+            output,
+            tree: tree_file,
+            value,
+            json,
+        } = args;
+
         info!("Processing withdrawal for coin: {}", coin_file);
         debug!("State file: {}", state_file);
         debug!("Association file: {:?}", association_file);
         debug!("Output file: {}", output);
+        debug!("Tree file: {:?}", tree_file);
 
         let env = Env::default();
         env.cost_estimate().budget().reset_unlimited();
@@ -75,8 +109,14 @@ impl CommandHandler {
         // Read existing coin
         let existing_coin = self.file_manager.read_coin_file(&coin_file)?;
 
-        // Read state file
-        let state_data = self.file_manager.read_state_file(&state_file)?;
+        // Load the pre-built state tree if given, otherwise build it from the state file
+        let tree = if let Some(tree_file) = tree_file {
+            self.file_manager.read_tree_file(&env, &tree_file)?
+        } else {
+            let state_data = self.file_manager.read_state_file(&state_file)?;
+            self.withdrawal_manager
+                .build_state_tree(&env, &state_data)?
+        };
 
         // Read association set file if provided
         let association_set_data = if let Some(assoc_file) = association_file {
@@ -86,11 +126,12 @@ impl CommandHandler {
         };
 
         // Generate withdrawal
-        let snark_input = self.withdrawal_manager.withdraw_coin(
+        let snark_input = self.withdrawal_manager.withdraw_coin_with_tree(
             &env,
             &existing_coin.coin,
-            &state_data,
+            &tree,
             association_set_data.as_ref(),
+            value,
         )?;
 
         // Save withdrawal data
@@ -100,6 +141,11 @@ impl CommandHandler {
         std::fs::write(&output, withdrawal_json)?;
         info!("Withdrawal data saved to: {}", output);
 
+        if json {
+            println!("{}", serde_json::to_string(&snark_input)?);
+            return Ok(());
+        }
+
         println!("Withdrawal created:");
         println!("  Withdrawn value: {}", snark_input.withdrawn_value);
         println!("  State root: {}", snark_input.state_root);
@@ -110,8 +156,68 @@ impl CommandHandler {
         Ok(())
     }
 
+    /// Handle the merkleize command
+    ///
+    /// Builds the tree from `state_file`, or from `ndjson` (one commitment per line, at
+    /// `depth`) when given; clap ensures exactly one of the two is present.
+    pub fn handle_merkleize(
+        &self,
+        state_file: Option<String>,
+        ndjson: Option<String>,
+        depth: u32,
+        output: String,
+    ) -> Result<()> {
+        debug!("Output file: {}", output);
+
+        let env = Env::default();
+        env.cost_estimate().budget().reset_unlimited();
+
+        let tree = match (ndjson, state_file) {
+            (Some(path), _) => {
+                info!("Building state tree from commitments in: {}", path);
+                let file = File::open(&path).map_err(|source| CoinUtilsError::FileRead {
+                    path: path.clone(),
+                    source,
+                })?;
+                self.withdrawal_manager.build_state_tree_from_lines(
+                    &env,
+                    BufReader::new(file),
+                    depth,
+                )?
+            }
+            (None, Some(state_file)) => {
+                info!("Building state tree from: {}", state_file);
+                let state_data = self.file_manager.read_state_file(&state_file)?;
+                self.withdrawal_manager
+                    .build_state_tree(&env, &state_data)?
+            }
+            (None, None) => {
+                return Err(CoinUtilsError::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "merkleize needs a state file or --ndjson",
+                )))
+            }
+        };
+
+        self.file_manager.write_tree_file(&tree, &output)?;
+        info!("State tree saved to: {}", output);
+
+        let root = lean_imt::bytes_to_bls_scalar(&tree.get_root());
+        println!("State tree built:");
+        println!("  Commitments: {}", tree.get_leaf_count());
+        println!("  Root: {}", bls_scalar_to_decimal_string(&root));
+        println!("  Saved to: {}", output);
+
+        Ok(())
+    }
+
     /// Handle the updateAssociation command
-    pub fn handle_update_association(&self, association_file: String, label: String) -> Result<()> {
+    pub fn handle_update_association(
+        &self,
+        association_file: String,
+        label: String,
+        depth: Option<u32>,
+    ) -> Result<()> {
         info!("Updating association set: {}", association_file);
         debug!("Adding label: {}", label);
 
@@ -119,12 +225,62 @@ impl CommandHandler {
         env.cost_estimate().budget().reset_unlimited();
 
         self.association_manager
-            .update_association_set(&env, &association_file, &label)?;
+            .update_association_set(&env, &association_file, &label, depth)?;
         info!("Association set updated successfully");
 
         println!("Association set updated successfully");
         Ok(())
     }
+
+    /// Handle the prepare-deploy command
+    ///
+    /// Writes the verification key bytes, tree depth and scope the contract is deployed
+    /// with to `out`, each in the encoding its constructor argument takes.
+    pub fn handle_prepare_deploy(
+        &self,
+        vk: String,
+        depth: u32,
+        scope: String,
+        scope_format: ScopeFormat,
+        out: String,
+    ) -> Result<()> {
+        info!("Preparing deployment artifact from: {}", vk);
+
+        let env = Env::default();
+        env.cost_estimate().budget().reset_unlimited();
+
+        let vk_json = self.file_manager.read_verification_key_file(&vk)?;
+        let vk_bytes = verification_key_bytes(&env, &vk_json)?;
+        let scope_bytes = lean_imt::scalar_to_be_bytes(&scope_field(&env, &scope, scope_format)?);
+        let artifact = DeployArtifact {
+            vk: hex::encode(&vk_bytes),
+            depth,
+            scope: hex::encode(scope_bytes.to_array()),
+        };
+
+        self.file_manager.write_deploy_file(&artifact, &out)?;
+        info!("Deployment artifact saved to: {}", out);
+
+        println!("Deployment artifact:");
+        println!(
+            "  VK: {} bytes, {} public signals",
+            vk_bytes.len(),
+            vk_json.n_public
+        );
+        println!("  Depth: {}", artifact.depth);
+        println!("  Scope: {}", artifact.scope);
+        println!("  Saved to: {}", out);
+
+        Ok(())
+    }
+}
+
+/// The scope field element for `scope`, read as a pool name or a decimal field element
+fn scope_field(env: &Env, scope: &str, scope_format: ScopeFormat) -> Result<BlsScalar> {
+    match scope_format {
+        ScopeFormat::Name => Ok(scope_from_name(env, scope.as_bytes())),
+        ScopeFormat::Decimal => decimal_string_to_bls_scalar(env, scope),
+    }
 }
 
 impl Default for CommandHandler {
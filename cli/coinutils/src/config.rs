@@ -1,5 +1,4 @@
 /// Configuration constants for the coinutils application
-pub const COIN_VALUE: i128 = 1000000000; // 1 XLM in stroops
+pub const COIN_VALUE: i128 = 1000000000; // 100 XLM in stroops, the default coin value
 pub const TREE_DEPTH: u32 = 20;
 pub const ASSOCIATION_TREE_DEPTH: u32 = 2;
-pub const MAX_ASSOCIATION_LABELS: usize = 4;
@@ -1,22 +1,40 @@
 use crate::{
     config::COIN_VALUE,
-    crypto::{poseidon_hash, random_fr},
+    crypto::random_fr,
     types::{CoinData, GeneratedCoin},
 };
+use lean_imt::commitment;
 use rand::{thread_rng, Rng};
 use soroban_sdk::{crypto::bls12_381::Fr as BlsScalar, Bytes, Env, U256};
 
-/// Generate a label for a coin based on scope and nonce
-pub fn generate_label(env: &Env, scope: &[u8], nonce: &[u8; 32]) -> BlsScalar {
-    // Convert scope and nonce to field elements for Poseidon hashing
+/// Encode a scope as the 32-byte big-endian field element hashed into labels
+///
+/// This is the value to pass as the contract's `scope` constructor argument.
+pub fn scope_to_bytes(scope: &[u8]) -> [u8; 32] {
     // Use only lower 31 bytes to ensure values are within BLS12-381 scalar field modulus
-    let scope_fr = BlsScalar::from_u256({
-        let mut bytes = [0u8; 32];
-        let len = scope.len().min(31);
-        // Place scope bytes in lower positions (big-endian U256, so pad at start)
-        bytes[32 - len..].copy_from_slice(&scope[..len]);
-        U256::from_be_bytes(env, &Bytes::from_slice(env, &bytes))
-    });
+    let mut bytes = [0u8; 32];
+    let len = scope.len().min(31);
+    // Place scope bytes in lower positions (big-endian U256, so pad at start)
+    bytes[32 - len..].copy_from_slice(&scope[..len]);
+    bytes
+}
+
+/// The scope field element for a pool name, as encoded by `scope_to_bytes`
+pub fn scope_from_name(env: &Env, name: &[u8]) -> BlsScalar {
+    BlsScalar::from_u256(U256::from_be_bytes(
+        env,
+        &Bytes::from_slice(env, &scope_to_bytes(name)),
+    ))
+}
+
+/// Generate a label for a coin based on scope and nonce
+///
+/// The canonical label is `Poseidon(scope, nonce mod 2^248)`: the nonce's top byte is
+/// cleared so it is always below the field modulus. This is the preimage the contract's
+/// `deposit_structured` hashes (with a nonce whose top byte is already zero) and the
+/// value the circuit takes as its `label` input and proves membership of in the
+/// association tree.
+pub fn generate_label(env: &Env, scope: &BlsScalar, nonce: &[u8; 32]) -> BlsScalar {
     let nonce_fr = BlsScalar::from_u256({
         // Zero MSB and take the last 31 bytes of nonce to stay within field modulus
         let mut bytes = [0u8; 32];
@@ -24,11 +42,13 @@ pub fn generate_label(env: &Env, scope: &[u8], nonce: &[u8; 32]) -> BlsScalar {
         U256::from_be_bytes(env, &Bytes::from_slice(env, &bytes))
     });
 
-    // Hash using Poseidon
-    poseidon_hash(env, &[scope_fr, nonce_fr])
+    commitment::label(env, scope, &nonce_fr)
 }
 
 /// Generate a commitment for a coin
+///
+/// `Poseidon(value, label, Poseidon(nullifier, secret))`, computed by
+/// `lean_imt::commitment` exactly as the contract's `compute_commitment` does.
 pub fn generate_commitment(
     env: &Env,
     value: BlsScalar,
@@ -36,15 +56,22 @@ pub fn generate_commitment(
     nullifier: BlsScalar,
     secret: BlsScalar,
 ) -> BlsScalar {
-    let precommitment = poseidon_hash(env, &[nullifier, secret]);
-    poseidon_hash(env, &[value, label, precommitment])
+    let precommitment = commitment::precommitment(env, &nullifier, &secret);
+    commitment::commitment(env, &value, &label, &precommitment)
 }
 
-/// Generate a complete coin with all necessary components
+/// Generate a complete coin for the pool named `scope`
+///
+/// The coin is worth `COIN_VALUE`, the default pool denomination.
 pub fn generate_coin(env: &Env, scope: &[u8]) -> GeneratedCoin {
+    generate_coin_for_scope(env, &scope_from_name(env, scope), COIN_VALUE as u128)
+}
+
+/// Generate a complete coin worth `value` base units with all necessary components
+pub fn generate_coin_for_scope(env: &Env, scope: &BlsScalar, value: u128) -> GeneratedCoin {
     use crate::crypto::conversions::bls_scalar_to_decimal_string;
 
-    let value = BlsScalar::from_u256(U256::from_u32(env, COIN_VALUE as u32));
+    let value = BlsScalar::from_u256(U256::from_u128(env, value));
     let nullifier = random_fr(env);
     let secret = random_fr(env);
     let nonce = thread_rng().gen::<[u8; 32]>();
@@ -73,24 +100,64 @@ pub fn generate_coin(env: &Env, scope: &[u8]) -> GeneratedCoin {
 
     GeneratedCoin {
         coin: coin_data,
-        commitment_hex: format!("0x{}", hex::encode(commitment.to_bytes().to_array())),
+        commitment_hex: format!(
+            "0x{}",
+            hex::encode(lean_imt::scalar_to_be_bytes(&commitment).to_array())
+        ),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::crypto::conversions::{bls_scalar_to_decimal_string, decimal_string_to_bls_scalar};
 
     #[test]
     fn test_generate_label() {
         let env = Env::default();
-        let scope = b"test_scope";
+        let scope = scope_from_name(&env, b"test_scope");
         let nonce = [1u8; 32];
-        let result = generate_label(&env, scope, &nonce);
+        let result = generate_label(&env, &scope, &nonce);
         // Just verify it doesn't panic and returns a valid scalar
         assert!(result.to_bytes().to_array().iter().any(|&x| x != 0));
     }
 
+    #[test]
+    fn test_generate_label_vector() {
+        let env = Env::default();
+
+        // "test_pool" right-aligned is the decimal scope 2147132627123717500780
+        let scope = decimal_string_to_bls_scalar(&env, "2147132627123717500780").unwrap();
+        assert_eq!(scope, scope_from_name(&env, b"test_pool"));
+
+        // Poseidon255(scope, 0x0001..01): the top nonce byte is cleared before hashing
+        let label = generate_label(&env, &scope, &[1u8; 32]);
+        assert_eq!(
+            label,
+            generate_label(&env, &scope, &{
+                let mut nonce = [1u8; 32];
+                nonce[0] = 0;
+                nonce
+            })
+        );
+        assert_eq!(
+            bls_scalar_to_decimal_string(&label),
+            "8269934881682985768155057313208800243154745739562235480041584466493604909753"
+        );
+    }
+
+    #[test]
+    fn test_scope_to_bytes_right_aligns_and_truncates() {
+        let bytes = scope_to_bytes(b"pool");
+        assert_eq!(&bytes[28..], b"pool");
+        assert!(bytes[..28].iter().all(|&x| x == 0));
+
+        // Scopes longer than 31 bytes keep the first 31, leaving the top byte zero
+        let bytes = scope_to_bytes(&[0xffu8; 40]);
+        assert_eq!(bytes[0], 0);
+        assert!(bytes[1..].iter().all(|&x| x == 0xff));
+    }
+
     #[test]
     fn test_generate_commitment() {
         let env = Env::default();
@@ -117,5 +184,18 @@ mod tests {
         assert!(!result.coin.label.is_empty());
         assert!(!result.coin.commitment.is_empty());
         assert!(result.commitment_hex.starts_with("0x"));
+        assert_eq!(result.coin.value, COIN_VALUE.to_string());
+    }
+
+    #[test]
+    fn test_generate_coin_for_scope_uses_value() {
+        let env = Env::default();
+        let scope = scope_from_name(&env, b"test_scope");
+        let result = generate_coin_for_scope(&env, &scope, 1_000_000);
+        assert_eq!(result.coin.value, "1000000");
+
+        // Values past u32 are kept whole
+        let result = generate_coin_for_scope(&env, &scope, 50_000_000_000);
+        assert_eq!(result.coin.value, "50000000000");
     }
 }
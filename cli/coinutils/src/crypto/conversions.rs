@@ -1,14 +1,18 @@
 use crate::error::{CoinUtilsError, Result};
+use lean_imt::FIELD_MODULUS;
 use num_bigint::BigUint;
 use soroban_sdk::{crypto::bls12_381::Fr as BlsScalar, BytesN, Env, U256};
 
 /// Convert a decimal string to a BlsScalar
+///
+/// Values at or above the field modulus are rejected with `ValueExceedsFieldModulus`
+/// rather than reduced, since a reduced scalar would not match the circuit's input
 pub fn decimal_string_to_bls_scalar(env: &Env, decimal_str: &str) -> Result<BlsScalar> {
     // For now, let's use a simpler approach that works with the existing system
     // We'll convert the decimal to a u128 first, then to BlsScalar
     if let Ok(value) = decimal_str.parse::<u128>() {
         // Convert u128 to BlsScalar
-        return Ok(BlsScalar::from_u256(U256::from_u32(env, value as u32)));
+        return Ok(BlsScalar::from_u256(U256::from_u128(env, value)));
     }
 
     // For very large numbers, we need to handle them differently
@@ -49,6 +53,13 @@ pub fn decimal_string_to_bls_scalar(env: &Env, decimal_str: &str) -> Result<BlsS
     // Reverse the hex string since we built it backwards
     let hex_str: String = hex_digits.chars().rev().collect();
 
+    let modulus = BigUint::from_bytes_be(&FIELD_MODULUS);
+    if BigUint::parse_bytes(hex_str.as_bytes(), 16).is_some_and(|value| value >= modulus) {
+        return Err(CoinUtilsError::ValueExceedsFieldModulus(
+            decimal_str.to_string(),
+        ));
+    }
+
     // Pad to 64 hex characters (32 bytes)
     let padded_hex = format!("{:0>64}", hex_str);
 
@@ -62,12 +73,15 @@ pub fn decimal_string_to_bls_scalar(env: &Env, decimal_str: &str) -> Result<BlsS
     let mut byte_array = [0u8; 32];
     byte_array.copy_from_slice(&bytes);
 
-    Ok(BlsScalar::from_bytes(BytesN::from_array(env, &byte_array)))
+    Ok(lean_imt::scalar_from_be_bytes(&BytesN::from_array(
+        env,
+        &byte_array,
+    )))
 }
 
 /// Convert BlsScalar to decimal string
 pub fn bls_scalar_to_decimal_string(scalar: &BlsScalar) -> String {
-    let array = scalar.to_bytes().to_array();
+    let array = lean_imt::scalar_to_be_bytes(scalar).to_array();
     bytes_to_decimal_string(&array)
 }
 
@@ -98,6 +112,24 @@ mod tests {
         assert_eq!(result, "123456789");
     }
 
+    #[test]
+    fn test_value_at_or_above_modulus_is_rejected() {
+        let env = Env::default();
+        let modulus = BigUint::from_bytes_be(&FIELD_MODULUS);
+
+        let below = (&modulus - 1u32).to_str_radix(10);
+        let scalar = decimal_string_to_bls_scalar(&env, &below).unwrap();
+        assert_eq!(bls_scalar_to_decimal_string(&scalar), below);
+
+        for value in [modulus.clone(), &modulus + 1u32, &modulus << 8] {
+            let result = decimal_string_to_bls_scalar(&env, &value.to_str_radix(10));
+            assert!(matches!(
+                result,
+                Err(CoinUtilsError::ValueExceedsFieldModulus(_))
+            ));
+        }
+    }
+
     #[test]
     fn test_invalid_decimal_character() {
         let env = Env::default();
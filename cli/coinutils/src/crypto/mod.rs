@@ -1,7 +1,9 @@
 pub mod coin;
 pub mod conversions;
 pub mod poseidon;
+pub mod verification_key;
 
 pub use coin::*;
 pub use conversions::*;
 pub use poseidon::*;
+pub use verification_key::*;
@@ -0,0 +1,112 @@
+use crate::{
+    error::{CoinUtilsError, Result},
+    types::VerificationKeyJson,
+};
+use soroban_sdk::Env;
+use zk::VerificationKey;
+
+/// Converts a snarkjs verification key into the verifier's `VerificationKey`
+///
+/// Uses the zk crate's parser, so `IC` must hold `nPublic + 1` points and every point
+/// must be on the curve.
+pub fn verification_key_from_json(
+    env: &Env,
+    vk_json: &VerificationKeyJson,
+) -> Result<VerificationKey> {
+    VerificationKey::from_snarkjs(env, vk_json)
+        .map_err(|e| CoinUtilsError::InvalidVerificationKey(e.to_string()))
+}
+
+/// The contract-native bytes (`VerificationKey::to_bytes`) of a snarkjs verification key
+pub fn verification_key_bytes(env: &Env, vk_json: &VerificationKeyJson) -> Result<Vec<u8>> {
+    let vk = verification_key_from_json(env, vk_json)?;
+    Ok(vk.to_bytes(env).iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use soroban_sdk::Bytes;
+
+    // Verification key of the withdrawal circuit (as in circom2soroban's tests)
+    const VK_JSON: &str = r#"{
+        "protocol": "groth16",
+        "curve": "bls12381",
+        "nPublic": 4,
+        "vk_alpha_1": [
+            "2625583050305146829700663917277485398332586266229739236073977691599912239208704058548731458555934906273399977862822",
+            "1155364156944807367912876641032696519500054551629402873339575774959620483194368919563799050765095981406853619398751",
+            "1"
+        ],
+        "vk_beta_2": [
+            ["1659696755509039809248937927616726274238080235224171061036366585278216098417245587200210264410333778948851576160490", "1338363397031837211155983756179787835339490797745307535810204658838394402900152502268197396587061400659003281046656"],
+            ["1974652615426136516341494326987376616840373177388374023461177997087381634383568759591087499459321812809521924259354", "3301884318087924474550898163462840036865878131635519297186391370517333773367262804074867347346141727012544462046142"],
+            ["1", "0"]
+        ],
+        "vk_gamma_2": [
+            ["352701069587466618187139116011060144890029952792775240219908644239793785735715026873347600343865175952761926303160", "3059144344244213709971259814753781636986470325476647558659373206291635324768958432433509563104347017837885763365758"],
+            ["1985150602287291935568054521177171638300868978215655730859378665066344726373823718423869104263333984641494340347905", "927553665492332455747201965776037880757740193453592970025027978793976877002675564980949289727957565575433344219582"],
+            ["1", "0"]
+        ],
+        "vk_delta_2": [
+            ["2750191744467054372912942146482544263484467550244832445881626112777617723646810063952263428512022936903253267127350", "2413234737575312815700598631122026291319065432043412800839944397857332202830802685415923770088689063622756702939375"],
+            ["1076967202486993406108941342102174843689250913208763125383730107292668137282535239225119066564005251774661400843821", "784091089348445241891924627629031628871298938526420228496183038286414003726447208549611976928427786617444752683904"],
+            ["1", "0"]
+        ],
+        "IC": [
+            ["1931769351244036379618100283994844046485312882458040431401676712058257124546097756332532237907637132315648906217636", "2219462221684288788247757134332962645470083865115055927456187574960992952094314940257753501443104606354496083113203", "1"],
+            ["2726325242623221693388802248110816107554759305800882344642286106642968529507795071709947858512355148550879270019178", "2690452834591447292232392438454117662004701691035040250634864436657178120453111433393322306334324558619029220405511", "1"],
+            ["2276753520377413052133204619264853734926027674320220733263964937413806530791610300908525130874383991218501161443629", "2216565042994647061456742959690979278824752277479734731836503122505090074006677407948960110633236603228440758211011", "1"],
+            ["2054702829658916052030239062784122350883101497414801284378548048954817335805733517964277882891682327579038641542963", "1861299377849520465661244108949779781960526739720579329803172490216038156998919390163110860296739149427635782605232", "1"],
+            ["2856004998221708121377069305149495649378668245327503671752831152976814973551962498318427356938380464598719642329610", "3445052445376607662168014620609501339582857414982758608624858423598446194176241135586201569345644453045853894315946", "1"]
+        ]
+    }"#;
+
+    fn parse(json: &str) -> VerificationKeyJson {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_verification_key_bytes_round_trip() {
+        let env = Env::default();
+        let bytes = verification_key_bytes(&env, &parse(VK_JSON)).unwrap();
+
+        let vk = VerificationKey::from_bytes(&env, &Bytes::from_slice(&env, &bytes)).unwrap();
+        assert_eq!(vk.ic.len(), 5);
+        assert_eq!(vk.to_bytes(&env).iter().collect::<Vec<u8>>(), bytes);
+    }
+
+    #[test]
+    fn test_malformed_keys_are_rejected() {
+        let env = Env::default();
+
+        let mut vk_json = parse(VK_JSON);
+        vk_json.n_public = 3;
+        let err = verification_key_bytes(&env, &vk_json).err().unwrap();
+        assert!(
+            err.to_string().contains("IC array has 5 elements"),
+            "{}",
+            err
+        );
+
+        let mut vk_json = parse(VK_JSON);
+        vk_json.vk_alpha_1[1] = "1".to_string();
+        let err = verification_key_bytes(&env, &vk_json).err().unwrap();
+        assert!(
+            err.to_string()
+                .contains("vk_alpha_1 is not a valid curve point"),
+            "{}",
+            err
+        );
+
+        let mut vk_json = parse(VK_JSON);
+        vk_json.vk_delta_2[0][0] = "not a number".to_string();
+        let err = verification_key_bytes(&env, &vk_json).err().unwrap();
+        assert!(
+            err.to_string()
+                .contains("vk_delta_2 is not a base field element"),
+            "{}",
+            err
+        );
+    }
+}
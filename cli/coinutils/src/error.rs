@@ -8,6 +8,21 @@ pub enum CoinUtilsError {
     #[error("JSON serialization error: {0}")]
     Json(#[from] serde_json::Error),
 
+    #[error("Could not read {path}: {source}")]
+    FileRead {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("Invalid {kind} file {path}: at `{field}`: {message}")]
+    InvalidFile {
+        kind: &'static str,
+        path: String,
+        /// serde path to the offending value, `.` for the top level
+        field: String,
+        message: String,
+    },
+
     #[error("Hex conversion error: {0}")]
     Hex(#[from] hex::FromHexError),
 
@@ -17,11 +32,19 @@ pub enum CoinUtilsError {
     #[error("Commitment not found in state")]
     CommitmentNotFound,
 
+    #[error("Coin file is corrupt: commitment mismatch")]
+    CoinCommitmentMismatch,
+
+    #[error("Withdrawn value {withdrawn} exceeds the coin's value {value}")]
+    WithdrawnValueExceedsCoin { withdrawn: u128, value: String },
+
     #[error("Label not found in association set")]
     LabelNotFound,
 
-    #[error("Association set is full")]
-    AssociationSetFull,
+    #[error(
+        "Association set is full at depth {depth}; rebuild it with --depth {needed_depth} or more"
+    )]
+    AssociationSetFull { depth: u32, needed_depth: u32 },
 
     #[error("Merkle proof generation failed")]
     ProofGenerationFailed,
@@ -29,9 +52,15 @@ pub enum CoinUtilsError {
     #[error("Invalid byte length: expected 32, got {0}")]
     InvalidByteLength(usize),
 
+    #[error("Value exceeds the BLS12-381 scalar field modulus: {0}")]
+    ValueExceedsFieldModulus(String),
+
     #[error("Invalid decimal character: {0}")]
     InvalidDecimalCharacter(char),
 
+    #[error("Invalid verification key: {0}")]
+    InvalidVerificationKey(String),
+
     #[error("LeanIMT error: {0}")]
     LeanIMT(String),
 }
@@ -42,4 +71,10 @@ impl From<&str> for CoinUtilsError {
     }
 }
 
+impl From<lean_imt::LeanIMTError> for CoinUtilsError {
+    fn from(err: lean_imt::LeanIMTError) -> Self {
+        CoinUtilsError::LeanIMT(err.to_string())
+    }
+}
+
 pub type Result<T> = std::result::Result<T, CoinUtilsError>;
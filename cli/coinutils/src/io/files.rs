@@ -1,11 +1,35 @@
 use crate::{
+    config::ASSOCIATION_TREE_DEPTH,
     error::{CoinUtilsError, Result},
-    types::{AssociationSetFile, GeneratedCoin, StateFile},
+    types::{
+        AssociationSetFile, DeployArtifact, GeneratedCoin, StateFile, TreeHash, VerificationKeyJson,
+    },
 };
+use lean_imt::LeanIMT;
+use serde::de::DeserializeOwned;
+use soroban_sdk::Env;
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
 
+/// Reads and parses a JSON input file
+///
+/// Errors name the file and, for parse failures, the serde path of the missing or
+/// malformed value (e.g. `coin.nullifier`), so the CLI can print them as they are.
+fn read_json_file<T: DeserializeOwned>(path: &str, kind: &'static str) -> Result<T> {
+    let content = std::fs::read_to_string(path).map_err(|source| CoinUtilsError::FileRead {
+        path: path.to_string(),
+        source,
+    })?;
+    let deserializer = &mut serde_json::Deserializer::from_str(&content);
+    serde_path_to_error::deserialize(deserializer).map_err(|e| CoinUtilsError::InvalidFile {
+        kind,
+        path: path.to_string(),
+        field: e.path().to_string(),
+        message: e.into_inner().to_string(),
+    })
+}
+
 /// File manager for handling file I/O operations
 pub struct FileManager;
 
@@ -16,8 +40,7 @@ impl FileManager {
 
     /// Read a coin file from disk
     pub fn read_coin_file(&self, path: &str) -> Result<GeneratedCoin> {
-        let content = std::fs::read_to_string(path).map_err(|e| CoinUtilsError::Io(e))?;
-        serde_json::from_str(&content).map_err(|e| CoinUtilsError::Json(e))
+        read_json_file(path, "coin")
     }
 
     /// Write a coin file to disk
@@ -31,8 +54,7 @@ impl FileManager {
 
     /// Read a state file from disk
     pub fn read_state_file(&self, path: &str) -> Result<StateFile> {
-        let content = std::fs::read_to_string(path).map_err(|e| CoinUtilsError::Io(e))?;
-        serde_json::from_str(&content).map_err(|e| CoinUtilsError::Json(e))
+        read_json_file(path, "state")
     }
 
     /// Write a state file to disk
@@ -46,8 +68,7 @@ impl FileManager {
 
     /// Read an association set file from disk
     pub fn read_association_file(&self, path: &str) -> Result<AssociationSetFile> {
-        let content = std::fs::read_to_string(path).map_err(|e| CoinUtilsError::Io(e))?;
-        serde_json::from_str(&content).map_err(|e| CoinUtilsError::Json(e))
+        read_json_file(path, "association set")
     }
 
     /// Write an association set file to disk
@@ -64,6 +85,34 @@ impl FileManager {
         Ok(())
     }
 
+    /// Read a serialized state tree (written by `write_tree_file`) from disk
+    pub fn read_tree_file(&self, env: &Env, path: &str) -> Result<LeanIMT> {
+        let content = std::fs::read_to_string(path).map_err(CoinUtilsError::Io)?;
+        LeanIMT::from_json(env, &content).map_err(CoinUtilsError::LeanIMT)
+    }
+
+    /// Write a state tree to disk as JSON
+    pub fn write_tree_file(&self, tree: &LeanIMT, path: &str) -> Result<()> {
+        let mut file = File::create(path).map_err(CoinUtilsError::Io)?;
+        file.write_all(tree.to_json().as_bytes())
+            .map_err(CoinUtilsError::Io)?;
+        Ok(())
+    }
+
+    /// Read a snarkjs verification key file from disk
+    pub fn read_verification_key_file(&self, path: &str) -> Result<VerificationKeyJson> {
+        read_json_file(path, "verification key")
+    }
+
+    /// Write a deployment artifact to disk
+    pub fn write_deploy_file(&self, artifact: &DeployArtifact, path: &str) -> Result<()> {
+        let json = serde_json::to_string_pretty(artifact).map_err(CoinUtilsError::Json)?;
+        let mut file = File::create(path).map_err(CoinUtilsError::Io)?;
+        file.write_all(json.as_bytes())
+            .map_err(CoinUtilsError::Io)?;
+        Ok(())
+    }
+
     /// Check if a file exists
     pub fn file_exists(&self, path: &str) -> bool {
         Path::new(path).exists()
@@ -78,6 +127,9 @@ impl FileManager {
                 labels: Vec::new(),
                 scope: "default_scope".to_string(),
                 root: None,
+                depth: ASSOCIATION_TREE_DEPTH,
+                frontier: Vec::new(),
+                hash: TreeHash::default(),
             };
             self.write_association_file(&association, path)?;
             Ok(association)
@@ -115,4 +167,38 @@ mod tests {
         assert_eq!(coin.coin.value, read_coin.coin.value);
         assert_eq!(coin.commitment_hex, read_coin.commitment_hex);
     }
+
+    #[test]
+    fn test_parse_errors_name_the_file_and_field() {
+        let file_manager = FileManager::new();
+        let temp_file = NamedTempFile::new().unwrap();
+        let path = temp_file.path().to_str().unwrap();
+
+        // Missing field: reported at its parent
+        std::fs::write(
+            path,
+            r#"{"coin": {"value": "1", "nullifier": "2", "label": "4", "commitment": "5"},
+                "commitment_hex": "0x5"}"#,
+        )
+        .unwrap();
+        let err = file_manager.read_coin_file(path).err().unwrap().to_string();
+        assert!(err.starts_with(&format!("Invalid coin file {}: at `coin`", path)));
+        assert!(err.contains("missing field `secret`"), "{}", err);
+
+        // Wrong type: reported at the value itself
+        std::fs::write(path, r#"{"commitments": [1], "scope": "s"}"#).unwrap();
+        let err = file_manager
+            .read_state_file(path)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("at `commitments[0]`"), "{}", err);
+
+        let err = file_manager
+            .read_association_file("/nonexistent/association.json")
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.starts_with("Could not read /nonexistent/association.json"));
+    }
 }
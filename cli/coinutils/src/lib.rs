@@ -8,7 +8,7 @@ pub mod types;
 
 pub use cli::{Cli, CommandHandler, Commands};
 pub use config::*;
-pub use crypto::{coin::*, conversions::*, poseidon::*};
+pub use crypto::{coin::*, conversions::*, poseidon::*, verification_key::*};
 pub use error::*;
 pub use io::*;
 pub use merkle::*;
@@ -11,17 +11,32 @@ fn main() {
     let command_handler = CommandHandler::new();
 
     let result = match cli.command {
-        Commands::Generate { scope, output } => command_handler.handle_generate(scope, output),
-        Commands::Withdraw {
-            coin_file,
+        Commands::Generate {
+            scope,
+            output,
+            scope_format,
+            value,
+            json,
+        } => command_handler.handle_generate(scope, output, scope_format, value, json),
+        Commands::Withdraw(args) => command_handler.handle_withdraw(args),
+        Commands::Merkleize {
             state_file,
-            association_file,
+            ndjson,
+            depth,
             output,
-        } => command_handler.handle_withdraw(coin_file, state_file, association_file, output),
+        } => command_handler.handle_merkleize(state_file, ndjson, depth, output),
         Commands::UpdateAssociation {
             association_file,
             label,
-        } => command_handler.handle_update_association(association_file, label),
+            depth,
+        } => command_handler.handle_update_association(association_file, label, depth),
+        Commands::PrepareDeploy {
+            vk,
+            depth,
+            scope,
+            scope_format,
+            out,
+        } => command_handler.handle_prepare_deploy(vk, depth, scope, scope_format, out),
     };
 
     if let Err(e) = result {
@@ -1,12 +1,12 @@
 use crate::{
-    config::{ASSOCIATION_TREE_DEPTH, MAX_ASSOCIATION_LABELS},
+    config::ASSOCIATION_TREE_DEPTH,
     crypto::conversions::*,
     error::{CoinUtilsError, Result},
     io::FileManager,
-    types::AssociationSetFile,
+    types::{AssociationSetFile, TreeHash},
 };
 use lean_imt::LeanIMT;
-use soroban_sdk::Env;
+use soroban_sdk::{BytesN, Env, Vec as SorobanVec};
 
 /// Manager for handling association set operations
 pub struct AssociationManager {
@@ -21,7 +21,17 @@ impl AssociationManager {
     }
 
     /// Update association set by adding a new label
-    pub fn update_association_set(&self, env: &Env, filename: &str, label: &str) -> Result<()> {
+    ///
+    /// `depth` sets the association tree depth for a new file, or rebuilds an existing
+    /// one at that depth; `None` keeps the file's depth (`ASSOCIATION_TREE_DEPTH` for a
+    /// new file). The set holds at most `2^depth` labels.
+    pub fn update_association_set(
+        &self,
+        env: &Env,
+        filename: &str,
+        label: &str,
+        depth: Option<u32>,
+    ) -> Result<()> {
         // Try to read existing association set file
         let mut association_set = if self.file_manager.file_exists(filename) {
             self.file_manager.read_association_file(filename)?
@@ -31,54 +41,57 @@ impl AssociationManager {
                 labels: Vec::new(),
                 scope: "default_scope".to_string(),
                 root: None,
+                depth: depth.unwrap_or(ASSOCIATION_TREE_DEPTH),
+                frontier: Vec::new(),
+                hash: TreeHash::default(),
             }
         };
 
-        // Check if label already exists
-        if !association_set.labels.contains(&label.to_string()) {
-            // Check if we're at the limit for depth 2 (4 labels max)
-            if association_set.labels.len() >= MAX_ASSOCIATION_LABELS {
-                return Err(CoinUtilsError::AssociationSetFull);
+        // A new depth changes every node, so the saved root and frontier no longer apply
+        if let Some(depth) = depth {
+            if depth != association_set.depth {
+                association_set.depth = depth;
+                association_set.root = None;
+                association_set.frontier.clear();
             }
+        }
 
-            association_set.labels.push(label.to_string());
+        let mut association_tree = load_association_tree(env, &association_set)?;
 
-            // Compute the Merkle tree root for the association set
-            if !association_set.labels.is_empty() {
-                // Build association set merkle tree (depth 2)
-                let mut association_tree = LeanIMT::new(env, ASSOCIATION_TREE_DEPTH);
-
-                for label_str in &association_set.labels {
-                    let label_fr = decimal_string_to_bls_scalar(env, label_str).map_err(|e| {
-                        CoinUtilsError::InvalidDecimal(format!("Invalid association label: {}", e))
-                    })?;
-
-                    // Convert BlsScalar to bytes and insert into association tree
-                    let label_bytes = lean_imt::bls_scalar_to_bytes(label_fr);
-                    association_tree.insert(label_bytes)?;
-                }
-
-                // Get the root and convert to decimal string
-                let association_root_scalar =
-                    lean_imt::bytes_to_bls_scalar(&association_tree.get_root());
-                association_set.root = Some(bls_scalar_to_decimal_string(&association_root_scalar));
+        // Check if label already exists
+        let is_new = !association_set.labels.contains(&label.to_string());
+        if is_new {
+            if association_tree.is_full() {
+                return Err(set_full(&association_set, association_set.labels.len() + 1));
             }
 
-            // Save updated association set
+            let label_fr = decimal_string_to_bls_scalar(env, label).map_err(|e| {
+                CoinUtilsError::InvalidDecimal(format!("Invalid association label: {}", e))
+            })?;
+            association_tree.insert(lean_imt::bls_scalar_to_bytes(label_fr))?;
+            association_set.labels.push(label.to_string());
+        }
+
+        // Save the root and frontier so the next update only hashes the insertion path
+        let changed = association_set.root.is_none() || is_new;
+        store_association_tree(&mut association_set, &association_tree);
+        if changed {
             self.file_manager
                 .write_association_file(&association_set, filename)?;
+        }
 
+        if is_new {
             println!(
                 "Added label '{}' to association set. Total labels: {}",
                 label,
                 association_set.labels.len()
             );
-            if let Some(ref root) = association_set.root {
-                println!("Association set root: {}", root);
-            }
         } else {
             println!("Label '{}' already exists in association set", label);
         }
+        if let Some(ref root) = association_set.root {
+            println!("Association set root: {}", root);
+        }
 
         Ok(())
     }
@@ -89,3 +102,187 @@ impl Default for AssociationManager {
         Self::new()
     }
 }
+
+/// Loads the association tree described by an association set file
+///
+/// When the file carries a root and a frontier (as written by `updateAssociation`), they
+/// are trusted as-is and nothing is re-hashed on load. Files without them, such as
+/// hand-written ones, are rebuilt by inserting every label, and so are trees with a
+/// `hash` other than Poseidon, since a restored tree always hashes with Poseidon.
+pub fn load_association_tree(env: &Env, association_set: &AssociationSetFile) -> Result<LeanIMT> {
+    let depth = association_set.depth;
+    if label_depth(association_set.labels.len()) > depth {
+        return Err(set_full(association_set, association_set.labels.len()));
+    }
+
+    let mut leaves = SorobanVec::new(env);
+    for (index, label_str) in association_set.labels.iter().enumerate() {
+        let label_fr = decimal_string_to_bls_scalar(env, label_str).map_err(|e| {
+            CoinUtilsError::InvalidDecimal(format!(
+                "Invalid association label at index {}: {}",
+                index, e
+            ))
+        })?;
+        leaves.push_back(lean_imt::bls_scalar_to_bytes(label_fr));
+    }
+
+    match &association_set.root {
+        Some(root)
+            if association_set.hash == TreeHash::Poseidon
+                && association_set.frontier.len() == depth as usize =>
+        {
+            let root = decimal_to_bytes(env, root)?;
+            let mut frontier = SorobanVec::new(env);
+            for node in &association_set.frontier {
+                let node = match node {
+                    Some(node) => Some(decimal_to_bytes(env, node)?),
+                    None => None,
+                };
+                frontier.push_back(node);
+            }
+            Ok(LeanIMT::from_storage_with_frontier(
+                env, leaves, depth, root, frontier,
+            ))
+        }
+        _ => {
+            let mut tree = LeanIMT::new_with_hash_function(env, depth, association_set.hash.into());
+            for leaf in leaves.iter() {
+                tree.insert(leaf)?;
+            }
+            Ok(tree)
+        }
+    }
+}
+
+/// Writes the tree's root and frontier back into the association set file
+fn store_association_tree(association_set: &mut AssociationSetFile, tree: &LeanIMT) {
+    association_set.root = Some(bls_scalar_to_decimal_string(&tree.get_root_scalar()));
+    association_set.frontier = tree
+        .get_frontier()
+        .iter()
+        .map(|node| node.map(|node| bytes_to_decimal_string(&node.to_array())))
+        .collect();
+}
+
+/// Depth an association tree needs for `labels` labels
+fn label_depth(labels: usize) -> u32 {
+    lean_imt::depth_for_capacity(u32::try_from(labels).unwrap_or(u32::MAX))
+}
+
+/// The error for a set that can't hold `labels` labels at its depth
+fn set_full(association_set: &AssociationSetFile, labels: usize) -> CoinUtilsError {
+    CoinUtilsError::AssociationSetFull {
+        depth: association_set.depth,
+        needed_depth: label_depth(labels),
+    }
+}
+
+fn decimal_to_bytes(env: &Env, decimal: &str) -> Result<BytesN<32>> {
+    Ok(lean_imt::bls_scalar_to_bytes(decimal_string_to_bls_scalar(
+        env, decimal,
+    )?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lean_imt::HashFunction;
+    use tempfile::TempDir;
+
+    fn label(env: &Env, value: u64) -> String {
+        bls_scalar_to_decimal_string(&lean_imt::u64_to_bls_scalar(env, value))
+    }
+
+    #[test]
+    fn test_incremental_update_matches_full_rebuild() {
+        let env = Env::default();
+        env.cost_estimate().budget().reset_unlimited();
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("association.json");
+        let path = path.to_str().unwrap();
+
+        // Depth 3 holds more than the old hard-coded limit of 4 labels
+        let manager = AssociationManager::new();
+        for value in 1..=6 {
+            manager
+                .update_association_set(&env, path, &label(&env, value), Some(3))
+                .unwrap();
+        }
+
+        let saved = FileManager::new().read_association_file(path).unwrap();
+        assert_eq!(saved.depth, 3);
+        assert_eq!(saved.labels.len(), 6);
+        assert_eq!(saved.frontier.len(), 3);
+
+        let mut rebuilt = LeanIMT::new(&env, 3);
+        for value in 1..=6 {
+            rebuilt.insert_u64(value).unwrap();
+        }
+        assert_eq!(
+            saved.root,
+            Some(bls_scalar_to_decimal_string(&rebuilt.get_root_scalar()))
+        );
+    }
+
+    #[test]
+    fn test_update_rejects_label_beyond_capacity() {
+        let env = Env::default();
+        env.cost_estimate().budget().reset_unlimited();
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("association.json");
+        let path = path.to_str().unwrap();
+
+        let manager = AssociationManager::new();
+        for value in 1..=2 {
+            manager
+                .update_association_set(&env, path, &label(&env, value), Some(1))
+                .unwrap();
+        }
+
+        let result = manager.update_association_set(&env, path, &label(&env, 3), None);
+        assert!(matches!(
+            result,
+            Err(CoinUtilsError::AssociationSetFull {
+                depth: 1,
+                needed_depth: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_association_tree_uses_the_file_hash() {
+        let env = Env::default();
+        env.cost_estimate().budget().reset_unlimited();
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("association.json");
+        let path = path.to_str().unwrap();
+
+        std::fs::write(
+            path,
+            r#"{"labels": [], "scope": "s", "root": null, "depth": 2, "hash": "poseidon2"}"#,
+        )
+        .unwrap();
+        let manager = AssociationManager::new();
+        for value in 1..=3 {
+            manager
+                .update_association_set(&env, path, &label(&env, value), None)
+                .unwrap();
+        }
+
+        let mut expected = LeanIMT::new_with_hash_function(&env, 2, HashFunction::Poseidon2);
+        let mut poseidon = LeanIMT::new(&env, 2);
+        for value in 1..=3 {
+            expected.insert_u64(value).unwrap();
+            poseidon.insert_u64(value).unwrap();
+        }
+
+        // The saved root and the reloaded tree both use Poseidon2
+        let saved = FileManager::new().read_association_file(path).unwrap();
+        assert_eq!(saved.hash, TreeHash::Poseidon2);
+        let root = bls_scalar_to_decimal_string(&expected.get_root_scalar());
+        assert_eq!(saved.root, Some(root));
+        let loaded = load_association_tree(&env, &saved).unwrap();
+        assert_eq!(loaded.get_root(), expected.get_root());
+        assert_ne!(loaded.get_root(), poseidon.get_root());
+    }
+}
@@ -2,10 +2,13 @@ use crate::{
     config::TREE_DEPTH,
     crypto::{coin::generate_commitment, conversions::*},
     error::{CoinUtilsError, Result},
+    merkle::association::load_association_tree,
     types::{AssociationSetFile, CoinData, SnarkInput, StateFile},
 };
 use lean_imt::LeanIMT;
-use soroban_sdk::{crypto::bls12_381::Fr as BlsScalar, Env};
+use log::warn;
+use soroban_sdk::{crypto::bls12_381::Fr as BlsScalar, Env, U256};
+use std::io::BufRead;
 
 /// Manager for handling coin withdrawal operations
 pub struct WithdrawalManager;
@@ -15,63 +18,196 @@ impl WithdrawalManager {
         Self
     }
 
+    /// Build the state merkle tree from the commitments in a state file
+    ///
+    /// The tree uses the file's `depth`, which has to match the contract's tree;
+    /// otherwise the root and siblings won't match on-chain and the proof fails.
+    pub fn build_state_tree(&self, env: &Env, state_file: &StateFile) -> Result<LeanIMT> {
+        if state_file.depth != TREE_DEPTH {
+            warn!(
+                "State file depth {} differs from the contract's tree depth {}; proofs will not verify on-chain",
+                state_file.depth, TREE_DEPTH
+            );
+        }
+        let capacity = 1u64.checked_shl(state_file.depth).unwrap_or(u64::MAX);
+        if state_file.commitments.len() as u64 > capacity {
+            warn!(
+                "State file has {} commitments but a depth-{} tree holds at most {}",
+                state_file.commitments.len(),
+                state_file.depth,
+                capacity
+            );
+        }
+
+        let mut tree = LeanIMT::new(env, state_file.depth);
+
+        for (index, commitment_str) in state_file.commitments.iter().enumerate() {
+            let commitment_fr = decimal_string_to_bls_scalar(env, commitment_str).map_err(|e| {
+                CoinUtilsError::InvalidDecimal(format!(
+                    "Invalid commitment at index {}: {}",
+                    index, e
+                ))
+            })?;
+
+            tree.insert_scalar(commitment_fr)?;
+        }
+
+        Ok(tree)
+    }
+
+    /// Build the state merkle tree from one decimal commitment per line
+    ///
+    /// Lines are read and inserted one at a time instead of parsing a whole JSON state
+    /// file first; the tree still keeps every leaf, which proof generation needs. Blank
+    /// lines and `#` comments (whole-line or trailing) are skipped, and a commitment may
+    /// be JSON-quoted, so newline-delimited JSON strings work as well.
+    pub fn build_state_tree_from_lines<R: BufRead>(
+        &self,
+        env: &Env,
+        reader: R,
+        depth: u32,
+    ) -> Result<LeanIMT> {
+        if depth != TREE_DEPTH {
+            warn!(
+                "Tree depth {} differs from the contract's tree depth {}; proofs will not verify on-chain",
+                depth, TREE_DEPTH
+            );
+        }
+
+        let mut tree = LeanIMT::new(env, depth);
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let value = line.split('#').next().unwrap_or("").trim();
+            if value.is_empty() {
+                continue;
+            }
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value);
+
+            let line_number = index + 1;
+            let commitment_fr = decimal_string_to_bls_scalar(env, value).map_err(|e| {
+                CoinUtilsError::InvalidDecimal(format!(
+                    "Invalid commitment on line {}: {}",
+                    line_number, e
+                ))
+            })?;
+            tree.insert_scalar(commitment_fr)
+                .map_err(|e| CoinUtilsError::LeanIMT(format!("line {}: {}", line_number, e)))?;
+        }
+
+        Ok(tree)
+    }
+
     /// Withdraw a coin and generate SNARK input
+    ///
+    /// `withdrawn_value` is the amount taken out, in base units; `None` withdraws the
+    /// coin's whole value.
     pub fn withdraw_coin(
         &self,
         env: &Env,
         coin: &CoinData,
         state_file: &StateFile,
         association_set_file: Option<&AssociationSetFile>,
+        withdrawn_value: Option<u128>,
     ) -> Result<SnarkInput> {
-        // Parse decimal string values to BlsScalar
-        let value = decimal_string_to_bls_scalar(env, &coin.value)?;
-        let nullifier = decimal_string_to_bls_scalar(env, &coin.nullifier)?;
-        let secret = decimal_string_to_bls_scalar(env, &coin.secret)?;
-        let label = decimal_string_to_bls_scalar(env, &coin.label)?;
+        // A corrupt coin can never be found, so report it before building the tree
+        let commitment = self.coin_commitment(env, coin)?;
+        let tree = self.build_state_tree(env, state_file)?;
+        self.withdraw_checked_coin(
+            env,
+            coin,
+            commitment,
+            &tree,
+            association_set_file,
+            withdrawn_value,
+        )
+    }
 
-        // Reconstruct the commitment to verify it matches
+    /// Recomputes a coin's commitment from its fields and checks it against the
+    /// commitment stored in the coin file
+    ///
+    /// Returns `CoinCommitmentMismatch` when they differ: the coin file has been edited
+    /// or damaged, which no state file can fix.
+    pub fn coin_commitment(&self, env: &Env, coin: &CoinData) -> Result<BlsScalar> {
         let commitment = generate_commitment(
             env,
-            value.clone(),
-            label.clone(),
-            nullifier.clone(),
-            secret.clone(),
+            decimal_string_to_bls_scalar(env, &coin.value)?,
+            decimal_string_to_bls_scalar(env, &coin.label)?,
+            decimal_string_to_bls_scalar(env, &coin.nullifier)?,
+            decimal_string_to_bls_scalar(env, &coin.secret)?,
         );
 
-        // Build merkle tree from state file using lean-imt
-        let mut tree = LeanIMT::new(env, TREE_DEPTH);
-        let mut commitment_index = None;
-
-        for (index, commitment_str) in state_file.commitments.iter().enumerate() {
-            let commitment_fr = decimal_string_to_bls_scalar(env, commitment_str).map_err(|e| {
-                CoinUtilsError::InvalidDecimal(format!(
-                    "Invalid commitment at index {}: {}",
-                    index, e
-                ))
-            })?;
+        let stored = decimal_string_to_bls_scalar(env, &coin.commitment)?;
+        if commitment != stored {
+            return Err(CoinUtilsError::CoinCommitmentMismatch);
+        }
+        Ok(commitment)
+    }
 
-            // Convert BlsScalar to bytes and insert into lean-imt
-            let commitment_bytes = lean_imt::bls_scalar_to_bytes(commitment_fr.clone());
-            tree.insert(commitment_bytes)?;
+    /// Withdraw a coin against an already constructed state tree (e.g. loaded from
+    /// a `merkleize` output) and generate SNARK input
+    pub fn withdraw_coin_with_tree(
+        &self,
+        env: &Env,
+        coin: &CoinData,
+        tree: &LeanIMT,
+        association_set_file: Option<&AssociationSetFile>,
+        withdrawn_value: Option<u128>,
+    ) -> Result<SnarkInput> {
+        let commitment = self.coin_commitment(env, coin)?;
+        self.withdraw_checked_coin(
+            env,
+            coin,
+            commitment,
+            tree,
+            association_set_file,
+            withdrawn_value,
+        )
+    }
 
-            // Check if this is the commitment we're withdrawing
-            if commitment_fr == commitment {
-                commitment_index = Some(index);
+    /// `withdraw_coin_with_tree` for a coin whose `commitment` `coin_commitment` has
+    /// already checked
+    fn withdraw_checked_coin(
+        &self,
+        env: &Env,
+        coin: &CoinData,
+        commitment: BlsScalar,
+        tree: &LeanIMT,
+        association_set_file: Option<&AssociationSetFile>,
+        withdrawn_value: Option<u128>,
+    ) -> Result<SnarkInput> {
+        // Parse decimal string values to BlsScalar
+        let value = decimal_string_to_bls_scalar(env, &coin.value)?;
+        let withdrawn_value = match withdrawn_value {
+            // The circuit can't prove more than the coin holds
+            Some(withdrawn) if U256::from_u128(env, withdrawn) > value.to_u256() => {
+                return Err(CoinUtilsError::WithdrawnValueExceedsCoin {
+                    withdrawn,
+                    value: coin.value.clone(),
+                });
             }
-        }
-
-        // Verify the commitment exists in the state
-        let commitment_index =
-            commitment_index.ok_or_else(|| CoinUtilsError::CommitmentNotFound)?;
+            Some(withdrawn) => withdrawn.to_string(),
+            None => bls_scalar_to_decimal_string(&value),
+        };
+        let nullifier = decimal_string_to_bls_scalar(env, &coin.nullifier)?;
+        let secret = decimal_string_to_bls_scalar(env, &coin.secret)?;
+        let label = decimal_string_to_bls_scalar(env, &coin.label)?;
 
-        // Generate merkle proof using lean-imt
-        let proof = tree
-            .generate_proof(commitment_index as u32)
-            .ok_or_else(|| CoinUtilsError::ProofGenerationFailed)?;
-        let (siblings_scalars, _depth) = proof;
+        // Find the commitment we're withdrawing among the tree leaves
+        let commitment_bytes = lean_imt::bls_scalar_to_bytes(commitment);
+        let commitment_index = tree
+            .leaves_iter()
+            .position(|leaf| leaf == commitment_bytes)
+            .ok_or_else(|| CoinUtilsError::CommitmentNotFound)?;
 
-        // Convert siblings from BlsScalar to strings
-        let siblings: Vec<BlsScalar> = siblings_scalars.iter().map(|s| s.clone()).collect();
+        // Generate merkle proof using lean-imt, converting siblings to strings as they are computed
+        let state_siblings = tree
+            .proof_iter(commitment_index as u32)
+            .ok_or_else(|| CoinUtilsError::ProofGenerationFailed)?
+            .map(|(sibling, _)| bls_scalar_to_decimal_string(&sibling))
+            .collect();
 
         // Get the root from lean-imt
         let root_scalar = lean_imt::bytes_to_bls_scalar(&tree.get_root());
@@ -96,17 +232,14 @@ impl WithdrawalManager {
         let state_root_decimal = bls_scalar_to_decimal_string(&root_scalar);
 
         Ok(SnarkInput {
-            withdrawn_value: crate::config::COIN_VALUE.to_string(),
+            withdrawn_value,
             label: label_decimal,
             value: value_decimal,
             nullifier: nullifier_decimal,
             secret: secret_decimal,
             state_root: state_root_decimal,
             state_index: commitment_index.to_string(),
-            state_siblings: siblings
-                .into_iter()
-                .map(|s| bls_scalar_to_decimal_string(&s))
-                .collect(),
+            state_siblings,
             association_root,
             label_index,
             label_siblings,
@@ -120,52 +253,29 @@ impl WithdrawalManager {
         association_set: &AssociationSetFile,
         label: &BlsScalar,
     ) -> Result<(String, String, Vec<String>)> {
-        use crate::config::ASSOCIATION_TREE_DEPTH;
-
-        // Build association set merkle tree (depth 2)
-        let mut association_tree = LeanIMT::new(env, ASSOCIATION_TREE_DEPTH);
-        let mut label_index = None;
-
-        for (index, label_str) in association_set.labels.iter().enumerate() {
-            let label_fr = decimal_string_to_bls_scalar(env, label_str).map_err(|e| {
-                CoinUtilsError::InvalidDecimal(format!(
-                    "Invalid association label at index {}: {}",
-                    index, e
-                ))
-            })?;
-
-            // Convert BlsScalar to bytes and insert into association tree
-            let label_bytes = lean_imt::bls_scalar_to_bytes(label_fr.clone());
-            association_tree.insert(label_bytes)?;
-
-            // Check if this is the label we're using
-            if label_fr == *label {
-                label_index = Some(index);
-            }
-        }
+        // Reuse the root and frontier saved by `updateAssociation` when present
+        let association_tree = load_association_tree(env, association_set)?;
 
         // Verify the label exists in the association set
-        let label_index = label_index.ok_or_else(|| CoinUtilsError::LabelNotFound)?;
+        let label_bytes = lean_imt::bls_scalar_to_bytes(label.clone());
+        let label_index = association_tree
+            .leaves_iter()
+            .position(|leaf| leaf == label_bytes)
+            .ok_or_else(|| CoinUtilsError::LabelNotFound)?;
 
         // Generate association set merkle proof
-        let association_proof = association_tree
-            .generate_proof(label_index as u32)
-            .ok_or_else(|| CoinUtilsError::ProofGenerationFailed)?;
-        let (association_siblings_scalars, _depth) = association_proof;
+        let association_siblings = association_tree
+            .proof_iter(label_index as u32)
+            .ok_or_else(|| CoinUtilsError::ProofGenerationFailed)?
+            .map(|(sibling, _)| bls_scalar_to_decimal_string(&sibling))
+            .collect();
 
         let association_root_scalar = lean_imt::bytes_to_bls_scalar(&association_tree.get_root());
-        let association_siblings: Vec<BlsScalar> = association_siblings_scalars
-            .iter()
-            .map(|s| s.clone())
-            .collect();
 
         Ok((
             bls_scalar_to_decimal_string(&association_root_scalar),
             label_index.to_string(),
-            association_siblings
-                .into_iter()
-                .map(|s| bls_scalar_to_decimal_string(&s))
-                .collect(),
+            association_siblings,
         ))
     }
 }
@@ -175,3 +285,144 @@ impl Default for WithdrawalManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::coin::{generate_coin, generate_coin_for_scope, scope_from_name};
+
+    #[test]
+    fn test_build_state_tree_uses_file_depth() {
+        let env = Env::default();
+        env.cost_estimate().budget().reset_unlimited();
+
+        // Older state files have no depth and get the contract's
+        let state: StateFile = serde_json::from_str(
+            r#"{"commitments": ["1", "2"], "scope": "s", "association_set": null}"#,
+        )
+        .unwrap();
+        assert_eq!(state.depth, TREE_DEPTH);
+
+        let state = StateFile { depth: 1, ..state };
+        let tree = WithdrawalManager::new()
+            .build_state_tree(&env, &state)
+            .unwrap();
+        assert_eq!(tree.get_depth(), 1);
+        assert_eq!(tree.get_leaf_count(), 2);
+
+        // Three commitments do not fit a depth-1 tree
+        let state = StateFile {
+            commitments: vec!["1".to_string(), "2".to_string(), "3".to_string()],
+            ..state
+        };
+        assert!(WithdrawalManager::new()
+            .build_state_tree(&env, &state)
+            .is_err());
+    }
+
+    #[test]
+    fn test_build_state_tree_from_lines_matches_state_file() {
+        let env = Env::default();
+        env.cost_estimate().budget().reset_unlimited();
+        let manager = WithdrawalManager::new();
+
+        let input = "# exported commitments\n1\n\n  \"2\"  \n3 # from ledger 42\n";
+        let tree = manager
+            .build_state_tree_from_lines(&env, input.as_bytes(), 2)
+            .unwrap();
+        let state = StateFile {
+            commitments: vec!["1".to_string(), "2".to_string(), "3".to_string()],
+            scope: "s".to_string(),
+            association_set: None,
+            depth: 2,
+        };
+        let expected = manager.build_state_tree(&env, &state).unwrap();
+        assert_eq!(tree.get_leaf_count(), 3);
+        assert_eq!(tree.get_root(), expected.get_root());
+
+        // Errors name the offending line
+        let err = manager
+            .build_state_tree_from_lines(&env, "1\n# comment\nabc\n".as_bytes(), 2)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("line 3"), "{}", err);
+        let err = manager
+            .build_state_tree_from_lines(&env, "1\n2\n3\n".as_bytes(), 1)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("line 3"), "{}", err);
+    }
+
+    #[test]
+    fn test_withdraw_reports_corrupt_coin_before_missing_commitment() {
+        let env = Env::default();
+        env.cost_estimate().budget().reset_unlimited();
+        let manager = WithdrawalManager::new();
+        let coin = generate_coin(&env, b"test_scope").coin;
+        let state = StateFile {
+            commitments: vec![coin.commitment.clone()],
+            scope: "test_scope".to_string(),
+            association_set: None,
+            depth: 2,
+        };
+
+        // An intact coin that is not in the state is just missing
+        let other_state = StateFile {
+            commitments: vec!["1".to_string()],
+            scope: "test_scope".to_string(),
+            association_set: None,
+            depth: 2,
+        };
+        assert!(matches!(
+            manager.withdraw_coin(&env, &coin, &other_state, None, None),
+            Err(CoinUtilsError::CommitmentNotFound)
+        ));
+
+        // A tampered field no longer hashes to the stored commitment
+        let tampered = CoinData {
+            value: "2".to_string(),
+            nullifier: coin.nullifier.clone(),
+            secret: coin.secret.clone(),
+            label: coin.label.clone(),
+            commitment: coin.commitment.clone(),
+        };
+        assert!(matches!(
+            manager.withdraw_coin(&env, &tampered, &state, None, None),
+            Err(CoinUtilsError::CoinCommitmentMismatch)
+        ));
+        assert!(manager
+            .withdraw_coin(&env, &coin, &state, None, None)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_withdrawn_value_defaults_to_coin_value() {
+        let env = Env::default();
+        env.cost_estimate().budget().reset_unlimited();
+        let manager = WithdrawalManager::new();
+        let scope = scope_from_name(&env, b"test_scope");
+        let coin = generate_coin_for_scope(&env, &scope, 1_000_000).coin;
+        let state = StateFile {
+            commitments: vec![coin.commitment.clone()],
+            scope: "test_scope".to_string(),
+            association_set: None,
+            depth: 2,
+        };
+
+        let input = manager
+            .withdraw_coin(&env, &coin, &state, None, None)
+            .unwrap();
+        assert_eq!(input.withdrawn_value, "1000000");
+        assert_eq!(input.value, "1000000");
+
+        let input = manager
+            .withdraw_coin(&env, &coin, &state, None, Some(400_000))
+            .unwrap();
+        assert_eq!(input.withdrawn_value, "400000");
+
+        assert!(matches!(
+            manager.withdraw_coin(&env, &coin, &state, None, Some(1_000_001)),
+            Err(CoinUtilsError::WithdrawnValueExceedsCoin { .. })
+        ));
+    }
+}
@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+pub use zk::VerificationKeyJson;
+
+/// Constructor arguments for a contract deployment, written by `prepare-deploy`
+#[derive(Serialize, Deserialize)]
+pub struct DeployArtifact {
+    /// Verification key in the contract's `VerificationKey::to_bytes` layout, hex-encoded
+    pub vk: String,
+    /// Depth of the state tree the circuit was built for
+    pub depth: u32,
+    /// The contract's `scope` argument: 32 big-endian bytes, hex-encoded
+    pub scope: String,
+}
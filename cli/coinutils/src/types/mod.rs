@@ -1,7 +1,9 @@
 pub mod coin;
+pub mod deploy;
 pub mod snark;
 pub mod state;
 
 pub use coin::*;
+pub use deploy::*;
 pub use snark::*;
 pub use state::*;
@@ -1,3 +1,5 @@
+use crate::config::{ASSOCIATION_TREE_DEPTH, TREE_DEPTH};
+use lean_imt::HashFunction;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize)]
@@ -5,6 +7,13 @@ pub struct StateFile {
     pub commitments: Vec<String>,
     pub scope: String,
     pub association_set: Option<Vec<String>>, // Optional association set labels
+    /// Depth of the contract's state tree; files without it use `TREE_DEPTH`
+    #[serde(default = "default_tree_depth")]
+    pub depth: u32,
+}
+
+fn default_tree_depth() -> u32 {
+    TREE_DEPTH
 }
 
 #[derive(Serialize, Deserialize)]
@@ -12,4 +21,39 @@ pub struct AssociationSetFile {
     pub labels: Vec<String>,
     pub scope: String,
     pub root: Option<String>, // Merkle tree root of the association set
+    /// Depth of the association tree; files without it use `ASSOCIATION_TREE_DEPTH`
+    #[serde(default = "default_association_depth")]
+    pub depth: u32,
+    /// Frontier of the association tree (`LeanIMT::get_frontier`) as decimal strings,
+    /// kept so the next label can be added without rebuilding the tree
+    #[serde(default)]
+    pub frontier: Vec<Option<String>>,
+    /// Hash of the association tree's internal nodes; files without it use Poseidon
+    #[serde(default)]
+    pub hash: TreeHash,
+}
+
+/// Hash function of a tree described by a coinutils file, written `"poseidon"` or `"poseidon2"`
+///
+/// Only `Poseidon` matches `merkleProof.circom`; pick `Poseidon2` for an ASP circuit
+/// that hashes its association tree with Poseidon2.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TreeHash {
+    #[default]
+    Poseidon,
+    Poseidon2,
+}
+
+impl From<TreeHash> for HashFunction {
+    fn from(hash: TreeHash) -> Self {
+        match hash {
+            TreeHash::Poseidon => HashFunction::Poseidon,
+            TreeHash::Poseidon2 => HashFunction::Poseidon2,
+        }
+    }
+}
+
+fn default_association_depth() -> u32 {
+    ASSOCIATION_TREE_DEPTH
 }
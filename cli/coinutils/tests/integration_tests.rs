@@ -1,12 +1,37 @@
 use coinutils::{
-    cli::CommandHandler,
-    crypto::coin::generate_coin,
+    cli::{CommandHandler, ScopeFormat, WithdrawArgs},
+    config::COIN_VALUE,
+    crypto::{
+        coin::{
+            generate_coin, generate_commitment, generate_label, scope_from_name, scope_to_bytes,
+        },
+        conversions::{bytes_to_decimal_string, decimal_string_to_bls_scalar},
+    },
     error::Result,
     io::FileManager,
-    types::{AssociationSetFile, StateFile},
+    merkle::{AssociationManager, WithdrawalManager},
+    types::{AssociationSetFile, GeneratedCoin, StateFile, TreeHash},
+};
+use privacy_pools::{
+    fr_to_bytes32, Error, PoolOptions, PrivacyPoolsContract, PrivacyPoolsContractClient,
+    PUBLIC_SIGNAL_LAYOUT,
+};
+use soroban_sdk::{
+    testutils::Address as _, token::StellarAssetClient, Address, Bytes, BytesN, Env,
+    Vec as SorobanVec,
 };
-use soroban_sdk::Env;
 use tempfile::TempDir;
+use zk::{Proof, PublicSignals, SERIALIZATION_VERSION};
+
+/// Constructor options of a fresh pool: default root history, no import, no decimals check
+fn pool_options() -> PoolOptions {
+    PoolOptions {
+        root_history_size: 0,
+        initial_commitments: None,
+        initial_nullifiers: None,
+        token_decimals: None,
+    }
+}
 
 #[tokio::test]
 async fn test_full_coin_lifecycle() -> Result<()> {
@@ -29,6 +54,7 @@ async fn test_full_coin_lifecycle() -> Result<()> {
         commitments: vec![generated_coin.coin.commitment.clone()],
         scope: "test_scope".to_string(),
         association_set: None,
+        depth: 20,
     };
 
     let state_file_path = temp_dir.path().join("state.json");
@@ -39,6 +65,9 @@ async fn test_full_coin_lifecycle() -> Result<()> {
         labels: vec![generated_coin.coin.label.clone()],
         scope: "test_scope".to_string(),
         root: None,
+        depth: 2,
+        frontier: Vec::new(),
+        hash: TreeHash::default(),
     };
 
     let association_file_path = temp_dir.path().join("association.json");
@@ -47,12 +76,15 @@ async fn test_full_coin_lifecycle() -> Result<()> {
 
     // Step 4: Withdraw the coin
     let withdrawal_file = temp_dir.path().join("withdrawal.json");
-    command_handler.handle_withdraw(
-        coin_file.to_str().unwrap().to_string(),
-        state_file_path.to_str().unwrap().to_string(),
-        Some(association_file_path.to_str().unwrap().to_string()),
-        withdrawal_file.to_str().unwrap().to_string(),
-    )?;
+    command_handler.handle_withdraw(WithdrawArgs {
+        coin_file: coin_file.to_str().unwrap().to_string(),
+        state_file: state_file_path.to_str().unwrap().to_string(),
+        association_file: Some(association_file_path.to_str().unwrap().to_string()),
+        output: withdrawal_file.to_str().unwrap().to_string(),
+        tree: None,
+        value: None,
+        json: false,
+    })?;
 
     // Verify the withdrawal file was created
     assert!(withdrawal_file.exists());
@@ -60,6 +92,95 @@ async fn test_full_coin_lifecycle() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_merkleize_then_withdraw_with_tree() -> Result<()> {
+    let temp_dir = TempDir::new().unwrap();
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let file_manager = FileManager::new();
+    let command_handler = CommandHandler::new();
+
+    let generated_coin = generate_coin(&env, b"test_scope");
+    let other_coin = generate_coin(&env, b"test_scope");
+    let coin_file = temp_dir.path().join("coin.json");
+    file_manager.write_coin_file(&generated_coin, coin_file.to_str().unwrap())?;
+
+    let state_file = StateFile {
+        commitments: vec![
+            other_coin.coin.commitment.clone(),
+            generated_coin.coin.commitment.clone(),
+        ],
+        scope: "test_scope".to_string(),
+        association_set: None,
+        depth: 20,
+    };
+    let state_file_path = temp_dir.path().join("state.json");
+    file_manager.write_state_file(&state_file, state_file_path.to_str().unwrap())?;
+
+    // Build the tree once and persist it
+    let tree_file = temp_dir.path().join("tree.json");
+    command_handler.handle_merkleize(
+        Some(state_file_path.to_str().unwrap().to_string()),
+        None,
+        20,
+        tree_file.to_str().unwrap().to_string(),
+    )?;
+    let tree = file_manager.read_tree_file(&env, tree_file.to_str().unwrap())?;
+    assert_eq!(tree.get_leaf_count(), 2);
+
+    // The same commitments exported one per line give the same tree
+    let ndjson_file = temp_dir.path().join("commitments.ndjson");
+    std::fs::write(
+        &ndjson_file,
+        format!(
+            "# commitments in deposit order\n{}\n\n{}\n",
+            other_coin.coin.commitment, generated_coin.coin.commitment
+        ),
+    )
+    .unwrap();
+    let ndjson_tree_file = temp_dir.path().join("tree_ndjson.json");
+    command_handler.handle_merkleize(
+        None,
+        Some(ndjson_file.to_str().unwrap().to_string()),
+        20,
+        ndjson_tree_file.to_str().unwrap().to_string(),
+    )?;
+    assert_eq!(
+        std::fs::read_to_string(&tree_file).unwrap(),
+        std::fs::read_to_string(&ndjson_tree_file).unwrap()
+    );
+
+    // Withdrawing against the saved tree matches rebuilding it from the state file
+    let rebuilt_output = temp_dir.path().join("withdrawal_rebuilt.json");
+    command_handler.handle_withdraw(WithdrawArgs {
+        coin_file: coin_file.to_str().unwrap().to_string(),
+        state_file: state_file_path.to_str().unwrap().to_string(),
+        association_file: None,
+        output: rebuilt_output.to_str().unwrap().to_string(),
+        tree: None,
+        value: None,
+        json: false,
+    })?;
+    let loaded_output = temp_dir.path().join("withdrawal_loaded.json");
+    command_handler.handle_withdraw(WithdrawArgs {
+        coin_file: coin_file.to_str().unwrap().to_string(),
+        state_file: state_file_path.to_str().unwrap().to_string(),
+        association_file: None,
+        output: loaded_output.to_str().unwrap().to_string(),
+        tree: Some(tree_file.to_str().unwrap().to_string()),
+        value: None,
+        json: false,
+    })?;
+
+    assert_eq!(
+        std::fs::read_to_string(rebuilt_output).unwrap(),
+        std::fs::read_to_string(loaded_output).unwrap()
+    );
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_association_set_management() -> Result<()> {
     let temp_dir = TempDir::new().unwrap();
@@ -73,12 +194,14 @@ async fn test_association_set_management() -> Result<()> {
     command_handler.handle_update_association(
         association_file.to_str().unwrap().to_string(),
         "123456789".to_string(),
+        None,
     )?;
 
     // Add second label
     command_handler.handle_update_association(
         association_file.to_str().unwrap().to_string(),
         "987654321".to_string(),
+        None,
     )?;
 
     // Verify the association file was created and has both labels
@@ -106,6 +229,9 @@ async fn test_coin_generation() -> Result<()> {
     command_handler.handle_generate(
         "test_scope".to_string(),
         output_file.to_str().unwrap().to_string(),
+        ScopeFormat::Name,
+        COIN_VALUE as u128,
+        false,
     )?;
 
     // Verify the coin file was created
@@ -124,3 +250,264 @@ async fn test_coin_generation() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_generate_json_prints_the_saved_coin() -> Result<()> {
+    let temp_dir = TempDir::new().unwrap();
+    let output_file = temp_dir.path().join("coin.json");
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_stellar-coinutils"))
+        .args(["generate", "test_scope", "--json", "-o"])
+        .arg(&output_file)
+        .output()?;
+    assert!(output.status.success());
+
+    // stdout is exactly one JSON object, the same one written to the coin file
+    let printed: GeneratedCoin = serde_json::from_slice(&output.stdout)?;
+    let saved = FileManager::new().read_coin_file(output_file.to_str().unwrap())?;
+    assert_eq!(printed.coin.commitment, saved.coin.commitment);
+    assert_eq!(printed.commitment_hex, saved.commitment_hex);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_contract_commitment_matches_coinutils() -> Result<()> {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let contract_id = env.register(
+        PrivacyPoolsContract,
+        (
+            Bytes::new(&env),
+            Address::generate(&env),
+            Address::generate(&env),
+            BytesN::from_array(&env, &scope_to_bytes(b"test_scope")),
+            1_000_000_000i128,
+            pool_options(),
+        ),
+    );
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+
+    for _ in 0..3 {
+        let coin = generate_coin(&env, b"test_scope").coin;
+        let field = |decimal: &str| -> Result<BytesN<32>> {
+            Ok(lean_imt::scalar_to_be_bytes(&decimal_string_to_bls_scalar(
+                &env, decimal,
+            )?))
+        };
+        let commitment = client.compute_commitment(
+            &coin.value.parse::<i128>().unwrap(),
+            &field(&coin.label)?,
+            &field(&coin.nullifier)?,
+            &field(&coin.secret)?,
+        );
+        assert_eq!(commitment, field(&coin.commitment)?);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_structured_deposit_matches_coinutils_commitment() -> Result<()> {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+    env.mock_all_auths();
+
+    let token = env.register_stellar_asset_contract_v2(Address::generate(&env));
+    let contract_id = env.register(
+        PrivacyPoolsContract,
+        (
+            Bytes::new(&env),
+            token.address(),
+            Address::generate(&env),
+            BytesN::from_array(&env, &scope_to_bytes(b"test_scope")),
+            1_000_000_000i128,
+            pool_options(),
+        ),
+    );
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let depositor = Address::generate(&env);
+    let denomination = client.get_denomination();
+    StellarAssetClient::new(&env, &token.address()).mint(&depositor, &denomination);
+
+    // The contract hashes the label and commitment itself; coinutils must get the same leaf
+    let mut nonce = [7u8; 32];
+    nonce[0] = 0;
+    let value = decimal_string_to_bls_scalar(&env, &denomination.to_string())?;
+    let nullifier = decimal_string_to_bls_scalar(&env, "12345")?;
+    let secret = decimal_string_to_bls_scalar(&env, "67890")?;
+    let label = generate_label(&env, &scope_from_name(&env, b"test_scope"), &nonce);
+    let expected = generate_commitment(&env, value, label, nullifier.clone(), secret.clone());
+
+    let precommitment = lean_imt::commitment::precommitment(&env, &nullifier, &secret);
+    client.deposit_structured(
+        &depositor,
+        &denomination,
+        &BytesN::from_array(&env, &nonce),
+        &lean_imt::scalar_to_be_bytes(&precommitment),
+    );
+    assert_eq!(
+        client.get_commitments().get(0).unwrap(),
+        lean_imt::scalar_to_be_bytes(&expected)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_withdrawal_signals_match_contract_state() -> Result<()> {
+    let temp_dir = TempDir::new().unwrap();
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+    env.mock_all_auths();
+
+    let token = env.register_stellar_asset_contract_v2(Address::generate(&env));
+    let admin = Address::generate(&env);
+    let scope = BytesN::from_array(&env, &scope_to_bytes(b"test_scope"));
+    let contract_id = env.register(
+        PrivacyPoolsContract,
+        (
+            Bytes::new(&env),
+            token.address(),
+            admin.clone(),
+            scope.clone(),
+            1_000_000_000i128,
+            pool_options(),
+        ),
+    );
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let field = |decimal: &str| -> Result<BytesN<32>> {
+        Ok(lean_imt::scalar_to_be_bytes(&decimal_string_to_bls_scalar(
+            &env, decimal,
+        )?))
+    };
+
+    // Deposit three coinutils coins
+    let depositor = Address::generate(&env);
+    let denomination = client.get_denomination();
+    StellarAssetClient::new(&env, &token.address()).mint(&depositor, &(4 * denomination));
+    let coins: Vec<_> = (0..3)
+        .map(|_| generate_coin(&env, b"test_scope").coin)
+        .collect();
+    for coin in &coins {
+        client.deposit(&depositor, &field(&coin.commitment)?);
+    }
+
+    // Export the on-chain commitments and merkleize them like an operator would
+    let state_file = StateFile {
+        commitments: client
+            .get_commitments()
+            .iter()
+            .map(|commitment| bytes_to_decimal_string(&commitment.to_array()))
+            .collect(),
+        scope: "test_scope".to_string(),
+        association_set: None,
+        depth: client.get_merkle_depth(),
+    };
+    let state_path = temp_dir.path().join("state.json");
+    let tree_path = temp_dir.path().join("tree.json");
+    let file_manager = FileManager::new();
+    file_manager.write_state_file(&state_file, state_path.to_str().unwrap())?;
+    CommandHandler::new().handle_merkleize(
+        Some(state_path.to_str().unwrap().to_string()),
+        None,
+        state_file.depth,
+        tree_path.to_str().unwrap().to_string(),
+    )?;
+    let tree = file_manager.read_tree_file(&env, tree_path.to_str().unwrap())?;
+    assert_eq!(tree.get_root(), client.get_merkle_root());
+
+    // Publish an association set holding every label
+    let association_path = temp_dir.path().join("association.json");
+    let association_path = association_path.to_str().unwrap();
+    for coin in &coins {
+        AssociationManager::new().update_association_set(
+            &env,
+            association_path,
+            &coin.label,
+            None,
+        )?;
+    }
+    let association = file_manager.read_association_file(association_path)?;
+    client.set_association_root(&admin, &field(association.root.as_ref().unwrap())?);
+
+    // The public signals a proof for the middle coin would carry
+    let coin = &coins[1];
+    let snark_input = WithdrawalManager::new().withdraw_coin_with_tree(
+        &env,
+        coin,
+        &tree,
+        Some(&association),
+        None,
+    )?;
+    let nullifier_hash = lean_imt::commitment::nullifier_hash(
+        &env,
+        &decimal_string_to_bls_scalar(&env, &coin.nullifier)?,
+    );
+    let layout = PUBLIC_SIGNAL_LAYOUT;
+    let mut signals = [
+        nullifier_hash.clone(),
+        nullifier_hash.clone(),
+        nullifier_hash.clone(),
+        nullifier_hash,
+    ];
+    signals[layout.withdrawn_value as usize] =
+        decimal_string_to_bls_scalar(&env, &snark_input.withdrawn_value)?;
+    signals[layout.state_root as usize] =
+        decimal_string_to_bls_scalar(&env, &snark_input.state_root)?;
+    signals[layout.association_root as usize] =
+        decimal_string_to_bls_scalar(&env, &snark_input.association_root)?;
+    assert_eq!(signals.len() as u32, layout.count);
+
+    // Each signal decodes to the value the contract checks it against
+    let pub_signals = PublicSignals {
+        pub_signals: SorobanVec::from_array(&env, signals),
+    }
+    .to_bytes(&env);
+    let extracted = PublicSignals::from_bytes(&env, &pub_signals)
+        .unwrap()
+        .pub_signals;
+    let signal = |index: u32| fr_to_bytes32(&env, &extracted.get(index).unwrap());
+    assert_eq!(signal(layout.state_root), client.get_merkle_root());
+    assert!(client.is_known_root(&signal(layout.state_root)));
+    assert_eq!(
+        signal(layout.association_root),
+        client.get_association_root()
+    );
+    assert_eq!(
+        signal(layout.withdrawn_value),
+        field(&denomination.to_string())?
+    );
+
+    // A pool that has already spent the coin rejects the signals as a double spend,
+    // so the contract derives the nullifier hash coinutils does. No prover runs here:
+    // the nullifier is checked before the proof, which is left blank.
+    let spent = env.register(
+        PrivacyPoolsContract,
+        (
+            Bytes::new(&env),
+            token.address(),
+            admin.clone(),
+            scope,
+            1_000_000_000i128,
+            PoolOptions {
+                initial_commitments: Some(client.get_commitments()),
+                initial_nullifiers: Some(SorobanVec::from_array(
+                    &env,
+                    [signal(layout.nullifier_hash)],
+                )),
+                ..pool_options()
+            },
+        ),
+    );
+    let spent = PrivacyPoolsContractClient::new(&env, &spent);
+    spent.deposit(&depositor, &BytesN::from_array(&env, &[9u8; 32]));
+    spent.set_association_root(&admin, &client.get_association_root());
+    let mut proof = [0u8; Proof::SERIALIZED_SIZE];
+    proof[0] = SERIALIZATION_VERSION;
+    let result = spent.try_can_withdraw(&Bytes::from_slice(&env, &proof), &pub_signals);
+    assert_eq!(result, Err(Ok(Error::NullifierUsed)));
+
+    Ok(())
+}
@@ -2,12 +2,17 @@
 
 extern crate alloc;
 
+use soroban_poseidon::poseidon_hash;
 use soroban_sdk::{
-    contract, contractimpl, log, symbol_short, token, vec, Address, Bytes, BytesN, Env, String,
-    Symbol, Vec,
+    contract, contractevent, contractimpl, contracttype, crypto::bls12_381::Fr as BlsScalar, log,
+    symbol_short, token, vec, xdr::ToXdr, Address, Bytes, BytesN, Env, IntoVal, Map, String,
+    Symbol, TryFromVal, Val, Vec, U256,
 };
 
-use lean_imt::{LeanIMT, TREE_DEPTH_KEY, TREE_LEAVES_KEY, TREE_ROOT_KEY};
+use lean_imt::{
+    commitment, LeanIMT, FIELD_MODULUS, TREE_DEPTH_KEY, TREE_FRONTIER_KEY, TREE_LEAVES_KEY,
+    TREE_ROOT_KEY,
+};
 use zk::{Groth16Verifier, Proof, PublicSignals, VerificationKey};
 
 #[cfg(test)]
@@ -26,6 +31,58 @@ pub enum Error {
     OnlyAdmin = 4,
     TreeAtCapacity = 5,
     AssociationRootMismatch = 6,
+    InvalidDenomination = 7,
+    InvalidFieldElement = 8,
+    ReentrantCall = 9,
+    PublicSignalCount = 10,
+    DuplicateCommitment = 11,
+    InvalidWithdrawAmount = 12,
+    ChangeCommitmentMismatch = 13,
+    PartialWithdrawalsDisabled = 14,
+    RecipientMismatch = 15,
+    RecipientWithdrawalsDisabled = 16,
+    Paused = 17,
+    PoolExists = 18,
+    UnknownPool = 19,
+    CommitmentMismatch = 20,
+    BalanceOverflow = 21,
+    ProofExpired = 22,
+    ExpiringWithdrawalsDisabled = 23,
+    TokenDecimalsMismatch = 24,
+    SharedToken = 25,
+}
+
+impl Error {
+    /// Status message `withdraw` returns for this error
+    pub fn message(&self) -> &'static str {
+        match self {
+            Error::NullifierUsed => ERROR_NULLIFIER_USED,
+            Error::InsufficientBalance => ERROR_INSUFFICIENT_BALANCE,
+            Error::CoinOwnershipProofFailed => ERROR_COIN_OWNERSHIP_PROOF,
+            Error::OnlyAdmin => ERROR_ONLY_ADMIN,
+            Error::TreeAtCapacity => "Tree is at capacity",
+            Error::AssociationRootMismatch => ERROR_ASSOCIATION_ROOT_MISMATCH,
+            Error::InvalidDenomination => "Value does not match the pool denomination",
+            Error::InvalidFieldElement => "Value is not below the field modulus",
+            Error::ReentrantCall => ERROR_REENTRANT_CALL,
+            Error::PublicSignalCount => ERROR_PUBLIC_SIGNAL_COUNT,
+            Error::DuplicateCommitment => "Commitment already deposited",
+            Error::InvalidWithdrawAmount => "Withdraw amount does not match the proof",
+            Error::ChangeCommitmentMismatch => "Change commitment does not match the proof",
+            Error::PartialWithdrawalsDisabled => "Partial withdrawals are not enabled",
+            Error::RecipientMismatch => "Recipient does not match the proof",
+            Error::RecipientWithdrawalsDisabled => "Recipient-bound withdrawals are not enabled",
+            Error::Paused => "Deposits are paused",
+            Error::PoolExists => "Pool already exists",
+            Error::UnknownPool => "Pool does not exist",
+            Error::CommitmentMismatch => "Commitment does not match its parts",
+            Error::BalanceOverflow => "Pool balance would overflow",
+            Error::ProofExpired => "Proof has expired",
+            Error::ExpiringWithdrawalsDisabled => "Expiring withdrawals are not enabled",
+            Error::TokenDecimalsMismatch => "Token decimals do not match",
+            Error::SharedToken => "Token is shared with another pool",
+        }
+    }
 }
 
 // Error messages for Vec<String> returns (legacy compatibility)
@@ -35,81 +92,597 @@ pub const ERROR_COIN_OWNERSHIP_PROOF: &str = "Couldn't verify coin ownership pro
 pub const ERROR_WITHDRAW_SUCCESS: &str = "Withdrawal successful";
 pub const ERROR_ONLY_ADMIN: &str = "Only the admin can set association root";
 pub const SUCCESS_ASSOCIATION_ROOT_SET: &str = "Association root set successfully";
+pub const ERROR_PUBLIC_SIGNAL_COUNT: &str = "Unexpected number of public signals";
+pub const ERROR_REENTRANT_CALL: &str = "Reentrant call rejected";
+pub const ERROR_ASSOCIATION_ROOT_MISMATCH: &str = "Association set root mismatch";
+
+/// Position of each named public signal in a withdrawal circuit's public inputs
+///
+/// The order is fixed by the circuit's outputs followed by its public inputs. Every
+/// withdrawal circuit starts with the spent coin's four signals; `extra` names the
+/// positions of the signals only that circuit has (`()` for `main.circom`). A circuit
+/// change that adds, removes or reorders signals must update its layout (and the
+/// verification key) together, otherwise every proof is rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PublicSignalLayout<E = ()> {
+    pub nullifier_hash: u32,
+    pub withdrawn_value: u32,
+    pub state_root: u32,
+    pub association_root: u32,
+    /// Positions of the circuit's own signals, after the spent coin's
+    pub extra: E,
+    /// Total number of public signals the circuit exposes
+    pub count: u32,
+}
+
+impl<E> PublicSignalLayout<E> {
+    /// Checks that a public signal vector has exactly the expected number of entries
+    pub fn matches(&self, signal_count: u32) -> bool {
+        signal_count == self.count
+    }
+}
+
+/// Layout of `main.circom`: [nullifierHash, withdrawnValue, stateRoot, associationRoot]
+pub const PUBLIC_SIGNAL_LAYOUT: PublicSignalLayout = PublicSignalLayout {
+    nullifier_hash: 0,
+    withdrawn_value: 1,
+    state_root: 2,
+    association_root: 3,
+    extra: (),
+    count: 4,
+};
+
+/// Signals `withdrawPartial.circom` adds: the change commitment and the denomination
+/// bound on the spent value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PartialSignals {
+    pub change_commitment: u32,
+    pub denomination: u32,
+}
+
+/// Position of each named public signal in `withdrawPartial.circom`
+pub type PartialSignalLayout = PublicSignalLayout<PartialSignals>;
+
+/// Signal `withdrawRecipient.circom` adds: the binding to `recipient_hash(to)`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecipientSignals {
+    pub recipient: u32,
+}
+
+/// Position of each named public signal in `withdrawRecipient.circom`
+pub type RecipientSignalLayout = PublicSignalLayout<RecipientSignals>;
+
+/// Layout of `withdrawRecipient.circom`:
+/// [nullifierHash, withdrawnValue, stateRoot, associationRoot, recipient]
+pub const RECIPIENT_SIGNAL_LAYOUT: RecipientSignalLayout = PublicSignalLayout {
+    nullifier_hash: 0,
+    withdrawn_value: 1,
+    state_root: 2,
+    association_root: 3,
+    extra: RecipientSignals { recipient: 4 },
+    count: 5,
+};
+
+/// Signal `withdrawExpiry.circom` adds: the last ledger the proof may be submitted in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExpirySignals {
+    pub expiry_ledger: u32,
+}
+
+/// Position of each named public signal in `withdrawExpiry.circom`
+pub type ExpirySignalLayout = PublicSignalLayout<ExpirySignals>;
+
+/// Layout of `withdrawExpiry.circom`:
+/// [nullifierHash, withdrawnValue, stateRoot, associationRoot, expiryLedger]
+pub const EXPIRY_SIGNAL_LAYOUT: ExpirySignalLayout = PublicSignalLayout {
+    nullifier_hash: 0,
+    withdrawn_value: 1,
+    state_root: 2,
+    association_root: 3,
+    extra: ExpirySignals { expiry_ledger: 4 },
+    count: 5,
+};
+
+/// Layout of `withdrawPartial.circom`:
+/// [nullifierHash, withdrawnValue, stateRoot, associationRoot, changeCommitment, denomination]
+pub const PARTIAL_SIGNAL_LAYOUT: PartialSignalLayout = PublicSignalLayout {
+    nullifier_hash: 0,
+    withdrawn_value: 1,
+    state_root: 2,
+    association_root: 3,
+    extra: PartialSignals {
+        change_commitment: 4,
+        denomination: 5,
+    },
+    count: 6,
+};
 
 const TREE_DEPTH: u32 = 20;
 
-// Storage keys
+/// Largest number of commitments returned by a single `get_commitments_page` call
+pub const MAX_COMMITMENTS_PAGE: u32 = 100;
+
+/// Number of recent merkle roots accepted by `withdraw` when the constructor is given 0
+pub const DEFAULT_ROOT_HISTORY_SIZE: u32 = 32;
+
+/// Largest root history the constructor accepts
+pub const MAX_ROOT_HISTORY_SIZE: u32 = 256;
+
+/// Ledgers per day at a 5 second close time
+const DAY_IN_LEDGERS: u32 = 17280;
+
+/// TTL that `deposit` and `withdraw` extend the contract instance to
+pub const INSTANCE_TTL_EXTEND_TO: u32 = 30 * DAY_IN_LEDGERS;
+
+/// Remaining TTL below which `deposit` and `withdraw` extend the instance, so most
+/// calls skip the extension fee
+pub const INSTANCE_TTL_THRESHOLD: u32 = INSTANCE_TTL_EXTEND_TO - DAY_IN_LEDGERS;
+
+// Storage keys. These live in instance storage, which shares one TTL with the
+// contract instance and code: the tree, root history, nullifiers and configuration are
+// archived (and restored) together, never one without the others. Records that grow
+// with every withdrawal are kept in persistent storage under `DataKey` instead.
 const NULL_KEY: Symbol = symbol_short!("null");
 const VK_KEY: Symbol = symbol_short!("vk");
 const TOKEN_KEY: Symbol = symbol_short!("token");
 const ASSOCIATION_ROOT_KEY: Symbol = symbol_short!("assoc");
 const ADMIN_KEY: Symbol = symbol_short!("admin");
+const BALANCE_KEY: Symbol = symbol_short!("balance");
+const LOCK_KEY: Symbol = symbol_short!("lock");
+const SCOPE_KEY: Symbol = symbol_short!("scope");
+const ROOTS_KEY: Symbol = symbol_short!("roots");
+const ROOTS_SIZE_KEY: Symbol = symbol_short!("roots_sz");
+const VK_VERSION_KEY: Symbol = symbol_short!("vk_ver");
+const PREVIOUS_VK_KEY: Symbol = symbol_short!("prev_vk");
+const PARTIAL_VK_KEY: Symbol = symbol_short!("part_vk");
+const RECIPIENT_VK_KEY: Symbol = symbol_short!("rcpt_vk");
+const EXPIRY_VK_KEY: Symbol = symbol_short!("exp_vk");
+const PAUSED_KEY: Symbol = symbol_short!("paused");
+const DENOMINATION_KEY: Symbol = symbol_short!("denom");
+const TOKEN_BALANCE_KEY: Symbol = symbol_short!("tok_bal");
+
+/// Keys of the per-withdrawal records and of created pools' state in persistent storage
+///
+/// Each spend writes its own entries, so a withdrawal never rewrites the records of
+/// earlier ones. Every entry has its own TTL, extended when it is written.
+#[contracttype]
+#[derive(Clone)]
+enum DataKey {
+    /// State root a spend in `pool` with this nullifier was proven against
+    WithdrawalRoot(u32, BytesN<32>),
+    /// Verification key version that accepted that spend's proof
+    WithdrawalVk(u32, BytesN<32>),
+    /// Value of a pool-level key (tree, roots, nullifiers, token, ...) in a pool created
+    /// with `create_pool`
+    Pool(u32, Symbol),
+    /// Created pools holding this token, so token balance mode can require it unshared
+    TokenPools(Address),
+}
+
+/// Pool-level keys of a created pool, whose TTLs each deposit and withdrawal (and
+/// `extend_pool_ttl`) extends
+const POOL_STATE_KEYS: [Symbol; 13] = [
+    TOKEN_KEY,
+    SCOPE_KEY,
+    DENOMINATION_KEY,
+    TOKEN_BALANCE_KEY,
+    ROOTS_SIZE_KEY,
+    ROOTS_KEY,
+    NULL_KEY,
+    BALANCE_KEY,
+    ASSOCIATION_ROOT_KEY,
+    TREE_LEAVES_KEY,
+    TREE_DEPTH_KEY,
+    TREE_ROOT_KEY,
+    TREE_FRONTIER_KEY,
+];
+
+/// Published for every commitment added to a pool's tree, in insertion order
+///
+/// Inserting the commitments of a pool's events into an empty `LeanIMT` of depth
+/// `TREE_DEPTH` rebuilds its tree. Commitments imported by the constructor are not
+/// announced; an indexer takes those from the old pool.
+#[contractevent]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CommitmentInserted {
+    #[topic]
+    pub pool: u32,
+    pub commitment: BytesN<32>,
+    pub leaf_index: u32,
+    pub root: BytesN<32>,
+}
+
+/// Id of the pool configured by the constructor
+///
+/// The contract can host further pools created with `create_pool`. Each pool has its own
+/// tree, root history, nullifiers, balance, token, scope, denomination, balance mode and
+/// association root; the admin, verification keys and pause switch are shared by all of
+/// them.
+pub const DEFAULT_POOL: u32 = 0;
+
+/// Denomination of a deployment that predates the constructor's `denomination`
+/// argument: 1,000,000,000 stroops (100 XLM)
+const FIXED_AMOUNT: i128 = 1000000000;
+
+/// Checks that big-endian bytes encode a value below the field modulus
+fn is_canonical_field_element(bytes: &BytesN<32>) -> bool {
+    bytes.to_array() < FIELD_MODULUS
+}
+
+/// Converts a field element to the 32-byte big-endian form kept in storage
+///
+/// Every root, nullifier and commitment the contract stores uses this encoding.
+/// `Fr` may not reduce the value it wraps, so a public signal can carry `x + r`
+/// (with `r` the field modulus) where `x` was meant; the verifier treats both the
+/// same, so the value is reduced first. Otherwise one nullifier would have two
+/// encodings and could be spent twice. The result is always left-padded to exactly
+/// 32 bytes.
+pub fn fr_to_bytes32(env: &Env, value: &BlsScalar) -> BytesN<32> {
+    let modulus = U256::from_be_bytes(env, &Bytes::from_array(env, &FIELD_MODULUS));
+    let be = value.to_u256().rem_euclid(&modulus).to_be_bytes();
+
+    // A reduced value never needs more than 32 bytes; keep the low-order ones regardless
+    let len = be.len().min(32);
+    let mut out = [0u8; 32];
+    be.slice(be.len() - len..)
+        .copy_into_slice(&mut out[32 - len as usize..]);
+    BytesN::from_array(env, &out)
+}
+
+/// Compares a field element from a proof against a stored 32-byte value
+///
+/// Both sides are normalized to `fr_to_bytes32` before comparing, so a scalar and its
+/// stored encoding are equal exactly when they represent the same field element.
+/// The comparison is an ordinary byte equality, not constant-time: contract execution
+/// is deterministic and its cost is metered by the host, and all compared values
+/// (roots, nullifier hashes) are public anyway.
+fn fr_matches_bytes32(env: &Env, value: &BlsScalar, stored: &BytesN<32>) -> bool {
+    fr_to_bytes32(env, value) == *stored
+}
+
+/// Whether any value occurs more than once in `values`
+fn has_duplicates(env: &Env, values: &Vec<BytesN<32>>) -> bool {
+    let mut seen = Map::new(env);
+    for value in values.iter() {
+        if seen.contains_key(value.clone()) {
+            return true;
+        }
+        seen.set(value, ());
+    }
+    false
+}
+
+/// `Poseidon(value, label, precommitment)`, the commitment `commitment.circom` checks
+fn commitment_hash(
+    env: &Env,
+    value: i128,
+    label: &BlsScalar,
+    precommitment: &BlsScalar,
+) -> BytesN<32> {
+    let value = BlsScalar::from_u256(U256::from_u128(env, value as u128));
+    let commitment = commitment::commitment(env, &value, label, precommitment);
+    fr_to_bytes32(env, &commitment)
+}
+
+/// The field element a recipient-bound proof commits to for `to`
+///
+/// `Poseidon(sha256(xdr(to)) with its first byte zeroed)`: the address is XDR-encoded as
+/// an `ScVal`, hashed with SHA-256, and the leading byte cleared so the 248-bit value is
+/// always below the field modulus. Returned as a big-endian field element.
+fn recipient_hash(env: &Env, to: &Address) -> BytesN<32> {
+    let mut digest = env.crypto().sha256(&to.clone().to_xdr(env)).to_array();
+    digest[0] = 0;
+    let inputs = vec![
+        env,
+        U256::from_be_bytes(env, &Bytes::from_array(env, &digest)),
+    ];
+    let hash = BlsScalar::from_u256(poseidon_hash::<2, BlsScalar>(env, &inputs));
+    fr_to_bytes32(env, &hash)
+}
+
+/// The pool's parameters, as returned by `get_config`
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PoolConfig {
+    /// Depth of the state merkle tree
+    pub depth: u32,
+    /// Amount moved by every deposit and withdrawal
+    pub denomination: i128,
+    /// Token accepted by the pool
+    pub token: Address,
+    /// Scope hashed into every label
+    pub scope: BytesN<32>,
+    /// SHA-256 of the current verification key bytes
+    pub vk_hash: BytesN<32>,
+    /// Version of the current verification key
+    pub vk_version: u32,
+    /// Number of recent roots `withdraw` accepts
+    pub root_history_size: u32,
+}
+
+/// Constructor settings beyond the pool's identity and denomination
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PoolOptions {
+    /// Number of recent roots `withdraw` accepts; 0 selects `DEFAULT_ROOT_HISTORY_SIZE`
+    pub root_history_size: u32,
+    /// Commitments of an older pool to seed the tree with, in deposit order
+    pub initial_commitments: Option<Vec<BytesN<32>>>,
+    /// Spent nullifiers of that older pool, one per spent coin in `initial_commitments`
+    pub initial_nullifiers: Option<Vec<BytesN<32>>>,
+    /// Decimals `denomination` was computed for, checked against the token's
+    pub token_decimals: Option<u32>,
+}
+
+/// Whether a field element from a proof equals a positive token amount
+fn fr_matches_amount(env: &Env, value: &BlsScalar, amount: i128) -> bool {
+    let amount = BlsScalar::from_u256(U256::from_u128(env, amount as u128));
+    fr_matches_bytes32(env, value, &fr_to_bytes32(env, &amount))
+}
+
+/// Values from a withdrawal that passed every check, needed to apply it
+struct ValidatedWithdrawal {
+    nullifier: BytesN<32>,
+    /// State root the proof was generated against
+    state_root: BytesN<32>,
+    /// Version of the verification key that accepted the proof; `None` for the other
+    /// circuits, whose keys are not versioned
+    vk_version: Option<u32>,
+    pool_balance: i128,
+}
+
+/// A withdrawal circuit as the contract sees it: where its verification key is kept and
+/// where its public signals are
+struct WithdrawalCircuit<E> {
+    /// Instance storage key of the verification key
+    vk_key: Symbol,
+    /// Returned while no key has been set for the circuit
+    disabled: Error,
+    /// Key kept through one rotation, tried when `vk_key` rejects a proof; only
+    /// `main.circom`'s key is versioned and rotated this way
+    previous_vk_key: Option<Symbol>,
+    layout: PublicSignalLayout<E>,
+}
+
+/// `withdraw`: the constructor's key, so never disabled
+const WITHDRAW_CIRCUIT: WithdrawalCircuit<()> = WithdrawalCircuit {
+    vk_key: VK_KEY,
+    disabled: Error::CoinOwnershipProofFailed,
+    previous_vk_key: Some(PREVIOUS_VK_KEY),
+    layout: PUBLIC_SIGNAL_LAYOUT,
+};
+
+const PARTIAL_CIRCUIT: WithdrawalCircuit<PartialSignals> = WithdrawalCircuit {
+    vk_key: PARTIAL_VK_KEY,
+    disabled: Error::PartialWithdrawalsDisabled,
+    previous_vk_key: None,
+    layout: PARTIAL_SIGNAL_LAYOUT,
+};
+
+const RECIPIENT_CIRCUIT: WithdrawalCircuit<RecipientSignals> = WithdrawalCircuit {
+    vk_key: RECIPIENT_VK_KEY,
+    disabled: Error::RecipientWithdrawalsDisabled,
+    previous_vk_key: None,
+    layout: RECIPIENT_SIGNAL_LAYOUT,
+};
 
-const FIXED_AMOUNT: i128 = 1000000000; // 1 XLM in stroops
+const EXPIRY_CIRCUIT: WithdrawalCircuit<ExpirySignals> = WithdrawalCircuit {
+    vk_key: EXPIRY_VK_KEY,
+    disabled: Error::ExpiringWithdrawalsDisabled,
+    previous_vk_key: None,
+    layout: EXPIRY_SIGNAL_LAYOUT,
+};
 
 #[contract]
 pub struct PrivacyPoolsContract;
 
 #[contractimpl]
 impl PrivacyPoolsContract {
-    pub fn __constructor(env: &Env, vk_bytes: Bytes, token_address: Address, admin: Address) {
+    /// Initializes the pool
+    ///
+    /// `options.root_history_size` is how many of the most recent merkle roots `withdraw`
+    /// accepts (0 selects `DEFAULT_ROOT_HISTORY_SIZE`, at most `MAX_ROOT_HISTORY_SIZE`). A
+    /// larger history keeps proofs valid while other deposits land, at the cost of storage.
+    ///
+    /// A pool migrated from an older deployment can be seeded with that pool's
+    /// `initial_commitments` (in deposit order, as returned by `get_commitments`) and its
+    /// spent `initial_nullifiers` (as returned by `get_nullifiers`), so existing coins stay
+    /// withdrawable and spent ones cannot be withdrawn again. Each nullifier must be the
+    /// nullifier hash of exactly one imported commitment, and each spent commitment must
+    /// have its nullifier imported: the contract cannot check the pairing, and the pool
+    /// balance starts at `denomination` per commitment without one. The matching tokens
+    /// must be transferred to the new contract separately. Panics on duplicate entries,
+    /// on entries that are not below the field modulus, on more commitments than the tree
+    /// holds, or on more nullifiers than commitments.
+    ///
+    /// `denomination` is the amount of every deposit and withdrawal in the token's base
+    /// units, e.g. `10_000_000` for 1 XLM (7 decimals) or `1_000_000` for 1 unit of a
+    /// 6-decimal token; it must be positive. Passing the `token_decimals` it was computed
+    /// for makes the constructor check them against the token's `decimals()`, and panic
+    /// on a mismatch.
+    pub fn __constructor(
+        env: &Env,
+        vk_bytes: Bytes,
+        token_address: Address,
+        admin: Address,
+        scope: BytesN<32>,
+        denomination: i128,
+        options: PoolOptions,
+    ) {
+        let PoolOptions {
+            root_history_size,
+            initial_commitments,
+            initial_nullifiers,
+            token_decimals,
+        } = options;
+        // The scope is hashed into every label, so it has to be a valid field element
+        if !is_canonical_field_element(&scope) {
+            panic!("Scope must be below the field modulus");
+        }
+        if denomination <= 0 {
+            panic!("Denomination must be positive");
+        }
+        if let Some(decimals) = token_decimals {
+            if token::Client::new(env, &token_address).decimals() != decimals {
+                panic!("Token decimals do not match");
+            }
+        }
+
+        let root_history_size = match root_history_size {
+            0 => DEFAULT_ROOT_HISTORY_SIZE,
+            size if size > MAX_ROOT_HISTORY_SIZE => {
+                panic!("Root history size exceeds MAX_ROOT_HISTORY_SIZE")
+            }
+            size => size,
+        };
+        env.storage()
+            .instance()
+            .set(&ROOTS_SIZE_KEY, &root_history_size);
+
         // Store the admin
         env.storage().instance().set(&ADMIN_KEY, &admin);
 
         env.storage().instance().set(&VK_KEY, &vk_bytes);
+        env.storage().instance().set(&VK_VERSION_KEY, &1u32);
         env.storage().instance().set(&TOKEN_KEY, &token_address);
+        env.storage().instance().set(&SCOPE_KEY, &scope);
+        env.storage()
+            .instance()
+            .set(&DENOMINATION_KEY, &denomination);
 
-        // Initialize empty merkle tree with fixed depth
-        let tree = LeanIMT::new(env, TREE_DEPTH);
-        let (leaves, depth, root) = tree.to_storage();
-        env.storage().instance().set(&TREE_LEAVES_KEY, &leaves);
-        env.storage().instance().set(&TREE_DEPTH_KEY, &depth);
-        env.storage().instance().set(&TREE_ROOT_KEY, &root);
+        // Initialize the merkle tree with fixed depth, empty unless migrating
+        let commitments = initial_commitments.unwrap_or(vec![env]);
+        let nullifiers = initial_nullifiers.unwrap_or(vec![env]);
+        // Checked first so an oversized import fails before the duplicate scans
+        if lean_imt::depth_for_capacity(commitments.len()) > TREE_DEPTH {
+            panic!("Initial commitments exceed the tree capacity");
+        }
+        if has_duplicates(env, &commitments) {
+            panic!("Duplicate initial commitment");
+        }
+        if !commitments.iter().all(|c| is_canonical_field_element(&c)) {
+            panic!("Initial commitment is not below the field modulus");
+        }
+        if has_duplicates(env, &nullifiers) {
+            panic!("Duplicate initial nullifier");
+        }
+        // `check_spent_coin` only looks up canonical nullifier hashes, so any other
+        // encoding would never match a spend
+        if !nullifiers.iter().all(|n| is_canonical_field_element(&n)) {
+            panic!("Initial nullifier is not below the field modulus");
+        }
+        if nullifiers.len() > commitments.len() {
+            panic!("More initial nullifiers than commitments");
+        }
+        let unspent = (commitments.len() - nullifiers.len()) as i128;
+
+        let tree = LeanIMT::new_from_leaves(env, TREE_DEPTH, commitments)
+            .expect("Initial commitments fit the tree");
+        Self::store_tree(env, DEFAULT_POOL, &tree);
+        env.storage()
+            .instance()
+            .set(&ROOTS_KEY, &vec![env, tree.get_root()]);
+        if !nullifiers.is_empty() {
+            env.storage().instance().set(&NULL_KEY, &nullifiers);
+        }
+        if unspent > 0 {
+            env.storage().instance().set(
+                &BALANCE_KEY,
+                &unspent
+                    .checked_mul(denomination)
+                    .expect("Initial pool balance overflows"),
+            );
+        }
     }
 
     /// Stores a commitment in the merkle tree and updates the tree state
     ///
     /// # Arguments
     /// * `env` - The Soroban environment
+    /// * `pool` - The pool whose tree receives the commitment
     /// * `commitment` - The commitment to store
     ///
     /// # Returns
     /// * A Result containing a tuple of (updated_merkle_root, leaf_index) after insertion
-    fn store_commitment(env: &Env, commitment: BytesN<32>) -> Result<(BytesN<32>, u32), Error> {
+    /// * `Error::DuplicateCommitment` if the commitment is already stored
+    /// * `Error::InvalidFieldElement` if the commitment is not below the field modulus
+    fn store_commitment(
+        env: &Env,
+        pool: u32,
+        commitment: BytesN<32>,
+    ) -> Result<(BytesN<32>, u32), Error> {
+        // The tree reduces leaves mod the field, so only canonical values are accepted;
+        // otherwise `c` and `c + r` would be distinct commitments with the same leaf
+        if !is_canonical_field_element(&commitment) {
+            return Err(Error::InvalidFieldElement);
+        }
+
         // Load current tree state
-        let leaves: Vec<BytesN<32>> = env
-            .storage()
-            .instance()
-            .get(&TREE_LEAVES_KEY)
-            .unwrap_or(vec![&env]);
-        let depth: u32 = env.storage().instance().get(&TREE_DEPTH_KEY).unwrap_or(0);
-        let root: BytesN<32> = env
-            .storage()
-            .instance()
-            .get(&TREE_ROOT_KEY)
-            .unwrap_or(BytesN::from_array(&env, &[0u8; 32]));
+        let leaves = Self::commitments_of(env, pool);
+        let depth = Self::merkle_depth_of(env, pool);
+        let root = Self::merkle_root_of(env, pool);
 
-        // Create tree and insert new commitment
-        let mut tree = LeanIMT::from_storage(env, leaves, depth, root);
-        tree.insert(commitment).map_err(|_| Error::TreeAtCapacity)?;
+        // Create tree and insert new commitment. With the saved frontier the insert
+        // hashes only the new leaf's path; without it (storage written before the
+        // frontier was kept) the left side of the tree is recomputed from the leaves.
+        let frontier: Option<Vec<Option<BytesN<32>>>> =
+            Self::pool_get(env, pool, &TREE_FRONTIER_KEY);
+        let mut tree = match frontier {
+            Some(frontier) => {
+                LeanIMT::from_storage_with_frontier(env, leaves, depth, root, frontier)
+            }
+            None => LeanIMT::from_storage(env, leaves, depth, root),
+        };
 
-        // Get the leaf index (it's the last leaf in the tree)
-        let leaf_index = tree.get_leaf_count() - 1;
+        // Each commitment backs exactly one nullifier, so it may only be stored once
+        if tree.contains(&commitment) {
+            return Err(Error::DuplicateCommitment);
+        }
+        // Insertion can only fail with `LeanIMTError::TreeFull`
+        let (new_root, leaf_index) = tree
+            .insert_returning(commitment.clone())
+            .map_err(|_| Error::TreeAtCapacity)?;
 
         // Store updated tree state
-        let (new_leaves, new_depth, new_root) = tree.to_storage();
-        env.storage().instance().set(&TREE_LEAVES_KEY, &new_leaves);
-        env.storage().instance().set(&TREE_DEPTH_KEY, &new_depth);
-        env.storage().instance().set(&TREE_ROOT_KEY, &new_root);
+        Self::store_tree(env, pool, &tree);
+        Self::record_root(env, pool, new_root.clone());
+
+        CommitmentInserted {
+            pool,
+            commitment,
+            leaf_index,
+            root: new_root.clone(),
+        }
+        .publish(env);
 
         Ok((new_root, leaf_index))
     }
 
+    /// Saves the tree's leaves, depth, root and frontier
+    fn store_tree(env: &Env, pool: u32, tree: &LeanIMT) {
+        let (leaves, depth, root) = tree.to_storage();
+        Self::pool_set(env, pool, &TREE_LEAVES_KEY, &leaves);
+        Self::pool_set(env, pool, &TREE_DEPTH_KEY, &depth);
+        Self::pool_set(env, pool, &TREE_ROOT_KEY, &root);
+        Self::pool_set(env, pool, &TREE_FRONTIER_KEY, &tree.get_frontier());
+    }
+
+    /// Appends a root to the history, evicting the oldest one once it is full
+    fn record_root(env: &Env, pool: u32, root: BytesN<32>) {
+        let mut roots = Self::root_history_of(env, pool);
+        roots.push_back(root);
+        while roots.len() > Self::root_history_size_of(env, pool) {
+            roots.pop_front();
+        }
+        Self::pool_set(env, pool, &ROOTS_KEY, &roots);
+    }
+
     /// Deposits funds into the privacy pool and stores a commitment in the merkle tree.
     ///
-    /// This function allows a user to deposit a fixed amount (1 XLM) of the configured token into the privacy pool
-    /// while providing a cryptographic commitment that will be used for zero-knowledge proof
-    /// verification during withdrawal.
+    /// This function allows a user to deposit a fixed amount (the pool's denomination, see
+    /// `get_denomination`) of the configured token into the privacy pool while providing a
+    /// cryptographic commitment that will be used for zero-knowledge proof verification
+    /// during withdrawal.
     ///
     /// # Arguments
     ///
@@ -121,12 +694,22 @@ impl PrivacyPoolsContract {
     /// # Returns
     ///
     /// * The leaf index where the commitment was stored in the merkle tree
+    /// * `Error::TreeAtCapacity` if the pool already holds `2^TREE_DEPTH` commitments;
+    ///   this is checked before the transfer, so no funds move
+    /// * `Error::DuplicateCommitment` if `commitment` is already in the tree, also
+    ///   checked before the transfer
+    /// * `Error::InvalidFieldElement` if `commitment`, read as a big-endian integer, is
+    ///   not below the BLS12-381 scalar field modulus, so every stored commitment has a
+    ///   single canonical encoding
+    /// * `Error::Paused` if the admin has paused deposits (see `set_deposits_paused`)
+    /// * `Error::BalanceOverflow` if the pool balance can't grow by the denomination
+    ///   without overflowing, checked before the transfer
     ///
     /// # Security
     ///
     /// * Requires authentication from the `from` address
     /// * The commitment is stored in a merkle tree for efficient inclusion proofs
-    /// * Transfers exactly `FIXED_AMOUNT` of the configured token from the depositor to the contract
+    /// * Transfers exactly the denomination of the configured token from the depositor to the contract
     ///
     /// # Storage
     ///
@@ -135,24 +718,181 @@ impl PrivacyPoolsContract {
     pub fn deposit(env: &Env, from: Address, commitment: BytesN<32>) -> Result<u32, Error> {
         from.require_auth();
 
-        // Get the stored token address
-        let token_address: Address = env.storage().instance().get(&TOKEN_KEY).unwrap();
+        Self::deposit_commitment(env, DEFAULT_POOL, &from, commitment)
+    }
+
+    /// Deposits funds and computes the commitment on-chain from its parts.
+    ///
+    /// The label is derived on-chain as `Poseidon(scope, nonce)` from this pool's scope,
+    /// and the commitment as `Poseidon(value, label, precommitment)`, the same hashes as
+    /// coinutils' `generate_label`/`generate_commitment` and `commitment.circom`, where
+    /// `precommitment = Poseidon(nullifier, secret)` stays private to the depositor.
+    /// Because the contract hashes `value` and the scope itself, the stored commitment
+    /// provably commits to the amount that was transferred and cannot be replayed into
+    /// a pool with a different scope.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The Soroban environment
+    /// * `from` - The address of the depositor (must be authenticated)
+    /// * `value` - The coin value; must equal the pool denomination
+    /// * `nonce` - The label nonce as a big-endian field element
+    /// * `precommitment` - `Poseidon(nullifier, secret)` as a big-endian field element
+    ///
+    /// # Returns
+    ///
+    /// * The leaf index where the computed commitment was stored in the merkle tree
+    /// * `Error::InvalidDenomination` if `value` is not the denomination
+    /// * `Error::InvalidFieldElement` if `nonce` or `precommitment` is not below the field modulus
+    /// * `Error::TreeAtCapacity`, `Error::DuplicateCommitment` and `Error::Paused` under
+    ///   the same conditions as `deposit`
+    ///
+    /// All checks run before the transfer, so a rejected deposit moves no funds.
+    pub fn deposit_structured(
+        env: &Env,
+        from: Address,
+        value: i128,
+        nonce: BytesN<32>,
+        precommitment: BytesN<32>,
+    ) -> Result<u32, Error> {
+        from.require_auth();
+
+        let commitment = Self::structured_commitment(env, value, nonce, precommitment)?;
+        Self::deposit_commitment(env, DEFAULT_POOL, &from, commitment)
+    }
+
+    /// Deposits like `deposit_structured`, but only if the on-chain commitment equals
+    /// the `commitment` the depositor computed
+    ///
+    /// This checks the client's coin file against the chain before any funds move: a
+    /// wallet that hashed a different value, label or precommitment than the contract
+    /// learns it here instead of holding a coin it can never withdraw. The nullifier
+    /// and secret stay off-chain, so the precommitment is still supplied pre-hashed.
+    ///
+    /// The hashes run in this contract through the Poseidon host functions rather than
+    /// a call to another contract; `test_structured_deposit_instruction_cost` measures
+    /// the cost against `deposit`, which remains the cheaper opaque path.
+    ///
+    /// # Returns
+    ///
+    /// * The leaf index where the commitment was stored in the merkle tree
+    /// * `Error::CommitmentMismatch` if the computed commitment differs from `commitment`
+    /// * Otherwise the same errors as `deposit_structured`
+    pub fn deposit_structured_checked(
+        env: &Env,
+        from: Address,
+        value: i128,
+        nonce: BytesN<32>,
+        precommitment: BytesN<32>,
+        commitment: BytesN<32>,
+    ) -> Result<u32, Error> {
+        from.require_auth();
+
+        let computed = Self::structured_commitment(env, value, nonce, precommitment)?;
+        if computed != commitment {
+            return Err(Error::CommitmentMismatch);
+        }
+        Self::deposit_commitment(env, DEFAULT_POOL, &from, computed)
+    }
+
+    /// `Poseidon(value, Poseidon(scope, nonce), precommitment)` for the default pool,
+    /// after checking the parts `deposit_structured` accepts
+    fn structured_commitment(
+        env: &Env,
+        value: i128,
+        nonce: BytesN<32>,
+        precommitment: BytesN<32>,
+    ) -> Result<BytesN<32>, Error> {
+        if value != Self::get_denomination(env) {
+            return Err(Error::InvalidDenomination);
+        }
+        if !is_canonical_field_element(&nonce) || !is_canonical_field_element(&precommitment) {
+            return Err(Error::InvalidFieldElement);
+        }
+
+        // Bind the label to this pool
+        let scope = BlsScalar::from_bytes(Self::get_scope(env));
+        let label = commitment::label(env, &scope, &BlsScalar::from_bytes(nonce));
+
+        Ok(commitment_hash(
+            env,
+            value,
+            &label,
+            &BlsScalar::from_bytes(precommitment),
+        ))
+    }
+
+    /// Deposits into pool `pool_id` like `deposit` does into the default pool
+    ///
+    /// Takes the pool's token and stores the commitment in the pool's own tree, so it
+    /// can only be withdrawn from the same pool with `withdraw_from_pool`.
+    ///
+    /// # Returns
+    ///
+    /// * The leaf index in the pool's tree
+    /// * `Error::UnknownPool` if no pool `pool_id` exists
+    /// * Otherwise the same errors as `deposit`
+    pub fn deposit_to_pool(
+        env: &Env,
+        pool_id: u32,
+        from: Address,
+        commitment: BytesN<32>,
+    ) -> Result<u32, Error> {
+        from.require_auth();
+
+        if !Self::has_pool(env, pool_id) {
+            return Err(Error::UnknownPool);
+        }
+        Self::deposit_commitment(env, pool_id, &from, commitment)
+    }
+
+    /// Moves the denomination from `from` into `pool` and stores `commitment`
+    ///
+    /// Shared by `deposit`, `deposit_structured` and `deposit_to_pool`; callers must
+    /// authenticate `from` and check that `pool` exists.
+    fn deposit_commitment(
+        env: &Env,
+        pool: u32,
+        from: &Address,
+        commitment: BytesN<32>,
+    ) -> Result<u32, Error> {
+        if Self::deposits_paused(env) {
+            return Err(Error::Paused);
+        }
+
+        // Reject before moving any funds once the pool is full
+        if Self::is_full_of(env, pool) {
+            return Err(Error::TreeAtCapacity);
+        }
+        let denomination = Self::denomination_of(env, pool);
+        let new_balance = Self::balance_of(env, pool)
+            .checked_add(denomination)
+            .ok_or(Error::BalanceOverflow)?;
+
+        // Store the commitment in the merkle tree; this rejects duplicates, so it runs
+        // before the transfer
+        let (_, leaf_index) = Self::store_commitment(env, pool, commitment)?;
 
         // Create token client and transfer from depositor to contract
-        let token_client = token::Client::new(env, &token_address);
-        token_client.transfer(&from, &env.current_contract_address(), &FIXED_AMOUNT);
+        let token_client = token::Client::new(env, &Self::token_of(env, pool));
+        token_client.transfer(from, &env.current_contract_address(), &denomination);
 
-        // Store the commitment in the merkle tree
-        let (_, leaf_index) = Self::store_commitment(env, commitment)?;
+        // Track funds owed to depositors independently of the token balance
+        if !Self::uses_token_balance_of(env, pool) {
+            Self::pool_set(env, pool, &BALANCE_KEY, &new_balance);
+        }
 
+        Self::bump_instance_ttl(env);
+        Self::bump_pool_ttl(env, pool);
         Ok(leaf_index)
     }
 
     /// Withdraws funds from the privacy pool using a zero-knowledge proof.
     ///
-    /// This function allows a user to withdraw a fixed amount (1 XLM) of the configured token from the privacy pool
-    /// by providing a cryptographic proof that demonstrates ownership of a previously deposited
-    /// commitment without revealing which specific commitment it corresponds to.
+    /// This function allows a user to withdraw a fixed amount (the pool's denomination, see
+    /// `get_denomination`) of the configured token from the privacy pool by providing a
+    /// cryptographic proof that demonstrates ownership of a previously deposited commitment
+    /// without revealing which specific commitment it corresponds to.
     ///
     /// # Arguments
     ///
@@ -168,18 +908,32 @@ impl PrivacyPoolsContract {
     /// * Empty vector `[]` on successful withdrawal (success is logged as a diagnostic event)
     /// * `["Nullifier already used"]` if the nullifier has been used before
     /// * `["Couldn't verify coin ownership proof"]` if the zero-knowledge proof verification fails
-    /// * `["Insufficient balance"]` if the contract doesn't have enough funds
+    /// * `["Insufficient balance"]` if the pool's deposited balance doesn't cover the denomination
+    /// * `["Couldn't verify coin ownership proof"]` also if `proof_bytes` is not a serialized proof
+    /// * `["Unexpected number of public signals"]` if the signals are malformed or don't match
+    ///   `PUBLIC_SIGNAL_LAYOUT`
+    /// * `["Reentrant call rejected"]` if called while another withdrawal's transfer is in flight
+    /// * `["Association set root mismatch"]` if the proof was made for a different association set
+    /// * `["Value is not below the field modulus"]` if the nullifier hash signal is not a
+    ///   canonical field element
+    ///
+    /// The checks are shared with `can_withdraw`, which reports the same failures as `Error`s.
     ///
     /// # Security
     ///
     /// * Requires authentication from the `to` address
     /// * Verifies that the nullifier hasn't been used before (prevents double-spending)
     /// * Validates the zero-knowledge proof using Groth16 verification
-    /// * Transfers exactly `FIXED_AMOUNT` of the configured token from the contract to the recipient
+    /// * Follows checks-effects-interactions: the nullifier and pool balance are updated
+    ///   before the token transfer, which is the only external call after the checks
+    /// * Holds a storage lock during the transfer as defense in depth; the Soroban host
+    ///   also refuses to re-enter a contract that is already on the call stack
+    /// * Transfers exactly the denomination of the configured token from the contract to the recipient
     ///
     /// # Storage
     ///
     /// * Adds the nullifier to the used nullifiers list to prevent reuse
+    /// * Decrements the pool balance by the denomination
     /// * Transfers the asset from the contract to the recipient
     ///
     /// # Privacy
@@ -195,124 +949,717 @@ impl PrivacyPoolsContract {
     ) -> Vec<String> {
         to.require_auth();
 
-        // Require association root to be set before any withdrawal
-        if !Self::has_association_set(env) {
-            panic!("Association root must be set before withdrawal");
+        Self::execute_withdrawal(env, DEFAULT_POOL, &to, &proof_bytes, &pub_signals_bytes)
+    }
+
+    /// Withdraws from pool `pool_id` like `withdraw` does from the default pool
+    ///
+    /// The proof must be against one of the pool's own recent roots and its association
+    /// root, and the nullifier must be unspent in this pool. The pool's token is paid.
+    ///
+    /// # Returns
+    ///
+    /// * `["Pool does not exist"]` if no pool `pool_id` exists
+    /// * Otherwise the same status messages as `withdraw`
+    pub fn withdraw_from_pool(
+        env: &Env,
+        pool_id: u32,
+        to: Address,
+        proof_bytes: Bytes,
+        pub_signals_bytes: Bytes,
+    ) -> Vec<String> {
+        to.require_auth();
+
+        if !Self::has_pool(env, pool_id) {
+            return vec![env, String::from_str(env, Error::UnknownPool.message())];
         }
+        Self::execute_withdrawal(env, pool_id, &to, &proof_bytes, &pub_signals_bytes)
+    }
 
-        // Get the stored token address
-        let token_address: Address = env.storage().instance().get(&TOKEN_KEY).unwrap();
+    /// Withdraws to `to` like `withdraw`, then calls `target.fn_name(args)` in the same
+    /// transaction.
+    ///
+    /// This lets an integration receive the funds in a contract (for example a swap) and
+    /// act on them atomically. Typically `to` is `target` itself, and `args` tells it
+    /// what to do with the denomination it just received.
+    ///
+    /// # Returns
+    ///
+    /// * The same status messages as `withdraw`; when a check fails nothing moves and
+    ///   `target` is not called
+    ///
+    /// # Atomicity
+    ///
+    /// The call runs after the nullifier is recorded and the funds are transferred. If it
+    /// fails, this function panics, so the whole invocation reverts: the nullifier stays
+    /// unspent and the funds stay in the pool.
+    pub fn withdraw_and_call(
+        env: &Env,
+        to: Address,
+        target: Address,
+        fn_name: Symbol,
+        args: Vec<Val>,
+        proof_bytes: Bytes,
+        pub_signals_bytes: Bytes,
+    ) -> Vec<String> {
+        to.require_auth();
 
-        // Check contract balance before updating state
-        let token_client = token::Client::new(env, &token_address);
-        let contract_balance = token_client.balance(&env.current_contract_address());
-        if contract_balance < FIXED_AMOUNT {
-            return vec![env, String::from_str(env, ERROR_INSUFFICIENT_BALANCE)];
+        let result =
+            Self::execute_withdrawal(env, DEFAULT_POOL, &to, &proof_bytes, &pub_signals_bytes);
+        if !result.is_empty() {
+            return result;
         }
 
-        let vk_bytes: Bytes = env.storage().instance().get(&VK_KEY).unwrap();
-        let vk = VerificationKey::from_bytes(env, &vk_bytes).unwrap();
-        let proof = Proof::from_bytes(env, &proof_bytes);
-        let pub_signals = PublicSignals::from_bytes(env, &pub_signals_bytes);
+        env.invoke_contract::<Val>(&target, &fn_name, args);
+        result
+    }
+
+    /// Verifies a withdrawal from `pool` and pays `to`; callers must authenticate `to`
+    ///
+    /// Shared by `withdraw`, `withdraw_and_call` and `withdraw_from_pool`. Returns an
+    /// empty vector on success or the status message of the failed check.
+    fn execute_withdrawal(
+        env: &Env,
+        pool: u32,
+        to: &Address,
+        proof_bytes: &Bytes,
+        pub_signals_bytes: &Bytes,
+    ) -> Vec<String> {
+        let validated =
+            match Self::validate_full_withdrawal(env, pool, proof_bytes, pub_signals_bytes) {
+                Ok(validated) => validated,
+                Err(err) => return vec![env, String::from_str(env, err.message())],
+            };
 
-        // Extract public signals: [nullifierHash, withdrawnValue, stateRoot, associationRoot]
-        let nullifier_hash = &pub_signals.pub_signals.get(0).unwrap();
-        let _withdrawn_value = &pub_signals.pub_signals.get(1).unwrap();
-        let proof_root = &pub_signals.pub_signals.get(2).unwrap();
-        let proof_association_root = &pub_signals.pub_signals.get(3).unwrap();
+        // Effects: record the nullifier and debit the pool only after all checks pass,
+        // and before any external call
+        let denomination = Self::denomination_of(env, pool);
+        Self::record_spend(env, pool, validated, denomination);
 
-        // Verify association set root matches the proof
-        let stored_association_root = Self::get_association_root(env);
-        let proof_association_root_bytes = proof_association_root.to_bytes();
+        // Interactions: transfer the asset from the contract to the recipient
+        Self::pay_out(env, pool, to, denomination);
 
-        if stored_association_root != proof_association_root_bytes {
-            return vec![env, String::from_str(env, "Association set root mismatch")];
-        }
+        Self::bump_instance_ttl(env);
+        Self::bump_pool_ttl(env, pool);
 
-        // Check if nullifier has been used before
-        let mut nullifiers: Vec<BytesN<32>> =
-            env.storage().instance().get(&NULL_KEY).unwrap_or(vec![env]);
+        // Log success message as diagnostic event
+        log!(&env, "{}", ERROR_WITHDRAW_SUCCESS);
+
+        vec![env]
+    }
 
-        let nullifier = nullifier_hash.to_bytes();
+    /// Records a validated spend in `pool`: marks the nullifier used and debits `amount`
+    fn record_spend(env: &Env, pool: u32, validated: ValidatedWithdrawal, amount: i128) {
+        let mut nullifiers = Self::nullifiers_of(env, pool);
+        nullifiers.push_back(validated.nullifier.clone());
+        Self::pool_set(env, pool, &NULL_KEY, &nullifiers);
+        // Validation already checked the balance covers `amount`; in token balance mode
+        // the transfer itself debits it
+        if !Self::uses_token_balance_of(env, pool) {
+            let new_balance = validated
+                .pool_balance
+                .checked_sub(amount)
+                .expect("Pool balance underflow");
+            Self::pool_set(env, pool, &BALANCE_KEY, &new_balance);
+        }
 
-        if nullifiers.contains(&nullifier) {
-            return vec![env, String::from_str(env, ERROR_NULLIFIER_USED)];
+        // Keep an audit trail of the tree state each spend was proven against
+        Self::set_persistent(
+            env,
+            &DataKey::WithdrawalRoot(pool, validated.nullifier.clone()),
+            &validated.state_root,
+        );
+        if let Some(vk_version) = validated.vk_version {
+            Self::set_persistent(
+                env,
+                &DataKey::WithdrawalVk(pool, validated.nullifier),
+                &vk_version,
+            );
         }
+    }
+
+    /// Transfers `amount` of `pool`'s token to `to` under the reentrancy lock
+    fn pay_out(env: &Env, pool: u32, to: &Address, amount: i128) {
+        let token_client = token::Client::new(env, &Self::token_of(env, pool));
+        env.storage().instance().set(&LOCK_KEY, &true);
+        token_client.transfer(&env.current_contract_address(), to, &amount);
+        env.storage().instance().remove(&LOCK_KEY);
+    }
+
+    /// Checks whether `withdraw` would accept a proof, without changing any state
+    ///
+    /// Runs exactly the checks `withdraw` runs (lock, pool balance, public signals,
+    /// association root, nullifier, state root and the Groth16 proof) but requires no
+    /// authorization and writes nothing, so relayers can simulate a withdrawal first.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if a withdrawal with these inputs would currently succeed
+    /// * The `Error` matching the first failed check; `Error::message` gives the status
+    ///   string `withdraw` would return
+    ///
+    /// Panics like `withdraw` if no association root has been set.
+    pub fn can_withdraw(
+        env: &Env,
+        proof_bytes: Bytes,
+        pub_signals_bytes: Bytes,
+    ) -> Result<(), Error> {
+        Self::validate_full_withdrawal(env, DEFAULT_POOL, &proof_bytes, &pub_signals_bytes)
+            .map(|_| ())
+    }
 
-        // Verify state root matches
-        let state_root: BytesN<32> = env
+    /// Read-only checks shared by `withdraw` and `can_withdraw`, against `pool`'s state
+    fn validate_full_withdrawal(
+        env: &Env,
+        pool: u32,
+        proof_bytes: &Bytes,
+        pub_signals_bytes: &Bytes,
+    ) -> Result<ValidatedWithdrawal, Error> {
+        Self::validate_withdrawal(
+            env,
+            pool,
+            &WITHDRAW_CIRCUIT,
+            Self::denomination_of(env, pool),
+            proof_bytes,
+            pub_signals_bytes,
+            |_, _| Ok(()),
+        )
+    }
+
+    /// Read-only checks shared by every withdrawal path, against `pool`'s state
+    ///
+    /// Runs, in order: the reentrancy lock, whether `circuit` has a key, the association
+    /// root, that the pool holds `amount`, the signal count, `extra_check` on the
+    /// circuit's own signals, that the proof withdraws exactly `amount`, the spent coin
+    /// and finally the proof itself. Returns the nullifier to record, the state root the
+    /// proof used and the current pool balance on success.
+    fn validate_withdrawal<E>(
+        env: &Env,
+        pool: u32,
+        circuit: &WithdrawalCircuit<E>,
+        amount: i128,
+        proof_bytes: &Bytes,
+        pub_signals_bytes: &Bytes,
+        extra_check: impl FnOnce(&Vec<BlsScalar>, &E) -> Result<(), Error>,
+    ) -> Result<ValidatedWithdrawal, Error> {
+        if env.storage().instance().has(&LOCK_KEY) {
+            return Err(Error::ReentrantCall);
+        }
+        let vk_bytes: Bytes = env
             .storage()
             .instance()
-            .get(&TREE_ROOT_KEY)
-            .unwrap_or(BytesN::from_array(&env, &[0u8; 32]));
+            .get(&circuit.vk_key)
+            .ok_or(circuit.disabled)?;
 
-        let proof_root_bytes = proof_root.to_bytes();
+        // Require association root to be set before any withdrawal
+        if !Self::has_association_set_of(env, pool) {
+            panic!("Association root must be set before withdrawal");
+        }
 
-        if state_root != proof_root_bytes {
-            return vec![env, String::from_str(env, ERROR_COIN_OWNERSHIP_PROOF)];
+        // Check the pool balance before anything else is parsed
+        let pool_balance = Self::balance_of(env, pool);
+        if pool_balance < amount {
+            return Err(Error::InsufficientBalance);
         }
 
-        // Verify the zero-knowledge proof
-        let res = Groth16Verifier::verify_proof(env, vk, proof, &pub_signals.pub_signals);
-        if res.is_err() || !res.unwrap() {
-            return vec![env, String::from_str(env, ERROR_COIN_OWNERSHIP_PROOF)];
+        let proof =
+            Proof::from_bytes(env, proof_bytes).map_err(|_| Error::CoinOwnershipProofFailed)?;
+        let pub_signals = PublicSignals::from_bytes(env, pub_signals_bytes)
+            .map_err(|_| Error::PublicSignalCount)?
+            .pub_signals;
+
+        // Reject signal vectors that don't match the circuit before indexing into them
+        let layout = &circuit.layout;
+        if !layout.matches(pub_signals.len()) {
+            return Err(Error::PublicSignalCount);
         }
+        extra_check(&pub_signals, &layout.extra)?;
 
-        // Add nullifier to used nullifiers only after all checks pass
-        nullifiers.push_back(nullifier);
-        env.storage().instance().set(&NULL_KEY, &nullifiers);
+        // Every deposit is worth the denomination, and so is every full withdrawal; a
+        // smaller change coin from `withdraw_partial` can only leave through that path
+        let withdrawn_value = pub_signals.get(layout.withdrawn_value).unwrap();
+        if !fr_matches_amount(env, &withdrawn_value, amount) {
+            return Err(Error::InvalidDenomination);
+        }
 
-        // Transfer the asset from the contract to the recipient
-        token_client.transfer(&env.current_contract_address(), &to, &FIXED_AMOUNT);
+        let (nullifier, state_root) =
+            Self::check_spent_coin(env, pool, &pub_signals, pub_signals_bytes, layout)?;
 
-        // Log success message as diagnostic event
-        log!(&env, "{}", ERROR_WITHDRAW_SUCCESS);
+        // Verify the zero-knowledge proof against the current key, then against the
+        // previous one if it was kept for proofs generated before a key rotation
+        let vk_version = if Self::verifies(env, &vk_bytes, &proof, &pub_signals) {
+            circuit
+                .previous_vk_key
+                .as_ref()
+                .map(|_| Self::get_vk_version(env))
+        } else {
+            match circuit
+                .previous_vk_key
+                .as_ref()
+                .and_then(|key| env.storage().instance().get::<_, Bytes>(key))
+            {
+                Some(previous) if Self::verifies(env, &previous, &proof, &pub_signals) => {
+                    Some(Self::get_vk_version(env) - 1)
+                }
+                _ => return Err(Error::CoinOwnershipProofFailed),
+            }
+        };
 
-        vec![env]
+        Ok(ValidatedWithdrawal {
+            nullifier,
+            state_root,
+            vk_version,
+            pool_balance,
+        })
     }
 
-    /// Gets the current merkle root of the commitment tree
-    pub fn get_merkle_root(env: &Env) -> BytesN<32> {
-        env.storage()
-            .instance()
-            .get(&TREE_ROOT_KEY)
-            .unwrap_or(BytesN::from_array(&env, &[0u8; 32]))
+    /// Checks the spent coin's signals against `pool`: association root, unused
+    /// nullifier and a known state root. Returns the nullifier to record and the state
+    /// root.
+    fn check_spent_coin<E>(
+        env: &Env,
+        pool: u32,
+        pub_signals: &Vec<BlsScalar>,
+        pub_signals_bytes: &Bytes,
+        layout: &PublicSignalLayout<E>,
+    ) -> Result<(BytesN<32>, BytesN<32>), Error> {
+        let nullifier_hash = pub_signals.get(layout.nullifier_hash).unwrap();
+        let proof_root = pub_signals.get(layout.state_root).unwrap();
+        let proof_association_root = pub_signals.get(layout.association_root).unwrap();
+
+        // Verify association set root matches the proof
+        if !fr_matches_bytes32(
+            env,
+            &proof_association_root,
+            &Self::association_root_of(env, pool),
+        ) {
+            return Err(Error::AssociationRootMismatch);
+        }
+
+        // `fr_to_bytes32` would reduce `x + r` to `x`, but a nullifier hash is only
+        // accepted in its canonical form, so no second encoding reaches the spent set.
+        // The submitted bytes are checked, since parsing may already have reduced them.
+        let raw_nullifier_hash =
+            PublicSignals::raw_signal(env, pub_signals_bytes, layout.nullifier_hash).unwrap();
+        if !is_canonical_field_element(&raw_nullifier_hash) {
+            return Err(Error::InvalidFieldElement);
+        }
+
+        // Check if nullifier has been used before
+        let nullifier = fr_to_bytes32(env, &nullifier_hash);
+        if Self::nullifiers_of(env, pool).contains(&nullifier) {
+            return Err(Error::NullifierUsed);
+        }
+
+        // The proof may be against any recent root, so deposits made after it was
+        // generated don't invalidate it
+        let state_root = fr_to_bytes32(env, &proof_root);
+        if !Self::root_history_of(env, pool).contains(&state_root) {
+            return Err(Error::CoinOwnershipProofFailed);
+        }
+
+        Ok((nullifier, state_root))
     }
 
-    /// Gets the current depth of the merkle tree
-    pub fn get_merkle_depth(env: &Env) -> u32 {
-        env.storage().instance().get(&TREE_DEPTH_KEY).unwrap_or(0)
+    /// Whether `proof` verifies for `pub_signals` under the serialized key `vk_bytes`
+    fn verifies(env: &Env, vk_bytes: &Bytes, proof: &Proof, pub_signals: &Vec<BlsScalar>) -> bool {
+        let vk = VerificationKey::from_bytes(env, vk_bytes).unwrap();
+        Groth16Verifier::verify_proof(env, vk, proof.clone(), pub_signals).unwrap_or(false)
+    }
+
+    /// Spends a coin, pays `withdraw_amount` to `to` and deposits the rest as a new coin
+    ///
+    /// The proof (for `withdrawPartial.circom`, see `PARTIAL_SIGNAL_LAYOUT`) shows that
+    /// the spent coin is in the tree and holds at most the denomination, and that
+    /// `change_commitment` commits to its value minus `withdraw_amount` under the same
+    /// label. The change coin can later be spent with another partial withdrawal.
+    ///
+    /// # Returns
+    ///
+    /// * An empty vector on success
+    /// * `["Partial withdrawals are not enabled"]` until the admin sets a key with
+    ///   `set_partial_verification_key`
+    /// * `["Withdraw amount does not match the proof"]` if `withdraw_amount` is not
+    ///   positive or differs from the proof's withdrawn value
+    /// * `["Change commitment does not match the proof"]` if `change_commitment` differs
+    ///   from the proof's
+    /// * `["Commitment already deposited"]`, `["Tree is at capacity"]` and
+    ///   `["Value is not below the field modulus"]` if the change coin cannot be inserted
+    /// * Otherwise the same failures as `withdraw`, including
+    ///   `["Value does not match the pool denomination"]` for a proof bounded by another
+    ///   denomination
+    ///
+    /// # Atomicity
+    ///
+    /// Every check runs before any state changes. The change commitment is then
+    /// inserted (updating the root history), the nullifier recorded, the pool debited
+    /// by `withdraw_amount` and the tokens transferred, all in this invocation.
+    pub fn withdraw_partial(
+        env: &Env,
+        to: Address,
+        withdraw_amount: i128,
+        change_commitment: BytesN<32>,
+        proof_bytes: Bytes,
+        pub_signals_bytes: Bytes,
+    ) -> Vec<String> {
+        to.require_auth();
+
+        let validated = match Self::validate_partial_withdrawal(
+            env,
+            withdraw_amount,
+            &change_commitment,
+            &proof_bytes,
+            &pub_signals_bytes,
+        ) {
+            Ok(validated) => validated,
+            Err(err) => return vec![env, String::from_str(env, err.message())],
+        };
+
+        // Effects: the change coin, then the spend, all before the transfer
+        if let Err(err) = Self::store_commitment(env, DEFAULT_POOL, change_commitment) {
+            return vec![env, String::from_str(env, err.message())];
+        }
+        Self::record_spend(env, DEFAULT_POOL, validated, withdraw_amount);
+
+        // Interactions
+        Self::pay_out(env, DEFAULT_POOL, &to, withdraw_amount);
+
+        Self::bump_instance_ttl(env);
+        log!(&env, "{}", ERROR_WITHDRAW_SUCCESS);
+
+        vec![env]
+    }
+
+    /// Withdraws a whole coin to the recipient the proof is bound to
+    ///
+    /// The proof (for `withdrawRecipient.circom`, see `RECIPIENT_SIGNAL_LAYOUT`) carries
+    /// `recipient_hash(to)` as a public signal, so a relayer or an observer of the pending
+    /// transaction cannot resubmit it with another `to`. Otherwise it behaves like
+    /// `withdraw`: the same checks, the same amount and the same nullifier set.
+    ///
+    /// # Returns
+    ///
+    /// * An empty vector on success
+    /// * `["Recipient-bound withdrawals are not enabled"]` until the admin sets a key
+    ///   with `set_recipient_verification_key`
+    /// * `["Recipient does not match the proof"]` if the proof was made for another
+    ///   recipient
+    /// * Otherwise the same failures as `withdraw`
+    pub fn withdraw_to_recipient(
+        env: &Env,
+        to: Address,
+        proof_bytes: Bytes,
+        pub_signals_bytes: Bytes,
+    ) -> Vec<String> {
+        to.require_auth();
+
+        let validated =
+            match Self::validate_recipient_withdrawal(env, &to, &proof_bytes, &pub_signals_bytes) {
+                Ok(validated) => validated,
+                Err(err) => return vec![env, String::from_str(env, err.message())],
+            };
+
+        let denomination = Self::get_denomination(env);
+        Self::record_spend(env, DEFAULT_POOL, validated, denomination);
+        Self::pay_out(env, DEFAULT_POOL, &to, denomination);
+
+        Self::bump_instance_ttl(env);
+        log!(&env, "{}", ERROR_WITHDRAW_SUCCESS);
+
+        vec![env]
+    }
+
+    /// Read-only checks for `withdraw_to_recipient`
+    fn validate_recipient_withdrawal(
+        env: &Env,
+        to: &Address,
+        proof_bytes: &Bytes,
+        pub_signals_bytes: &Bytes,
+    ) -> Result<ValidatedWithdrawal, Error> {
+        Self::validate_withdrawal(
+            env,
+            DEFAULT_POOL,
+            &RECIPIENT_CIRCUIT,
+            Self::get_denomination(env),
+            proof_bytes,
+            pub_signals_bytes,
+            |pub_signals, extra| {
+                let recipient = pub_signals.get(extra.recipient).unwrap();
+                if !fr_matches_bytes32(env, &recipient, &recipient_hash(env, to)) {
+                    return Err(Error::RecipientMismatch);
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Withdraws a whole coin with a proof that expires
+    ///
+    /// The proof (for `withdrawExpiry.circom`, see `EXPIRY_SIGNAL_LAYOUT`) carries the last
+    /// ledger sequence it may be submitted in. Past that ledger it is refused, however long
+    /// its state root stays in the root history, which bounds how long a leaked or
+    /// withheld proof can be used. Otherwise it behaves like `withdraw`: the same checks,
+    /// the same amount and the same nullifier set.
+    ///
+    /// # Returns
+    ///
+    /// * An empty vector on success
+    /// * `["Expiring withdrawals are not enabled"]` until the admin sets a key with
+    ///   `set_expiry_verification_key`
+    /// * `["Proof has expired"]` if the current ledger sequence is greater than the
+    ///   proof's expiry ledger
+    /// * Otherwise the same failures as `withdraw`
+    pub fn withdraw_with_expiry(
+        env: &Env,
+        to: Address,
+        proof_bytes: Bytes,
+        pub_signals_bytes: Bytes,
+    ) -> Vec<String> {
+        to.require_auth();
+
+        let validated =
+            match Self::validate_expiring_withdrawal(env, &proof_bytes, &pub_signals_bytes) {
+                Ok(validated) => validated,
+                Err(err) => return vec![env, String::from_str(env, err.message())],
+            };
+
+        let denomination = Self::get_denomination(env);
+        Self::record_spend(env, DEFAULT_POOL, validated, denomination);
+        Self::pay_out(env, DEFAULT_POOL, &to, denomination);
+
+        Self::bump_instance_ttl(env);
+        log!(&env, "{}", ERROR_WITHDRAW_SUCCESS);
+
+        vec![env]
+    }
+
+    /// Read-only checks for `withdraw_with_expiry`
+    fn validate_expiring_withdrawal(
+        env: &Env,
+        proof_bytes: &Bytes,
+        pub_signals_bytes: &Bytes,
+    ) -> Result<ValidatedWithdrawal, Error> {
+        Self::validate_withdrawal(
+            env,
+            DEFAULT_POOL,
+            &EXPIRY_CIRCUIT,
+            Self::get_denomination(env),
+            proof_bytes,
+            pub_signals_bytes,
+            |pub_signals, extra| {
+                // Compared as field elements, so an expiry beyond `u32::MAX` never runs out
+                let expiry = pub_signals.get(extra.expiry_ledger).unwrap();
+                let expiry = U256::from_be_bytes(env, &fr_to_bytes32(env, &expiry).into());
+                if U256::from_u32(env, env.ledger().sequence()) > expiry {
+                    return Err(Error::ProofExpired);
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// The `recipient` public signal a `withdraw_to_recipient` proof for `to` must carry
+    ///
+    /// Provers can simulate this call instead of reimplementing the encoding: SHA-256 of
+    /// the address's `ScVal` XDR, with the first byte zeroed, hashed with `Poseidon(1)`.
+    pub fn recipient_hash(env: &Env, to: Address) -> BytesN<32> {
+        recipient_hash(env, &to)
+    }
+
+    /// Read-only checks for `withdraw_partial`
+    fn validate_partial_withdrawal(
+        env: &Env,
+        withdraw_amount: i128,
+        change_commitment: &BytesN<32>,
+        proof_bytes: &Bytes,
+        pub_signals_bytes: &Bytes,
+    ) -> Result<ValidatedWithdrawal, Error> {
+        let denomination = Self::get_denomination(env);
+        if withdraw_amount <= 0 || withdraw_amount > denomination {
+            return Err(Error::InvalidWithdrawAmount);
+        }
+
+        Self::validate_withdrawal(
+            env,
+            DEFAULT_POOL,
+            &PARTIAL_CIRCUIT,
+            withdraw_amount,
+            proof_bytes,
+            pub_signals_bytes,
+            |pub_signals, extra| {
+                // The caller's arguments must be the values the proof commits to
+                let signal = |index: u32| pub_signals.get(index).unwrap();
+                let withdrawn_value = signal(PARTIAL_SIGNAL_LAYOUT.withdrawn_value);
+                if !fr_matches_amount(env, &withdrawn_value, withdraw_amount) {
+                    return Err(Error::InvalidWithdrawAmount);
+                }
+                if !fr_matches_bytes32(env, &signal(extra.change_commitment), change_commitment) {
+                    return Err(Error::ChangeCommitmentMismatch);
+                }
+                if !fr_matches_amount(env, &signal(extra.denomination), denomination) {
+                    return Err(Error::InvalidDenomination);
+                }
+
+                // The change coin has to fit in the tree, checked before anything is written
+                if !is_canonical_field_element(change_commitment) {
+                    return Err(Error::InvalidFieldElement);
+                }
+                if Self::get_commitments(env).contains(change_commitment) {
+                    return Err(Error::DuplicateCommitment);
+                }
+                if Self::is_full(env) {
+                    return Err(Error::TreeAtCapacity);
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Gets the current merkle root of the commitment tree
+    pub fn get_merkle_root(env: &Env) -> BytesN<32> {
+        Self::merkle_root_of(env, DEFAULT_POOL)
+    }
+
+    /// Gets the current merkle root and the association roots a withdrawal may use,
+    /// read in one call so both come from the same ledger
+    ///
+    /// The pool accepts a single association root, so the list holds it once one has
+    /// been set and is empty before that (when withdrawals are not yet possible).
+    pub fn get_roots(env: &Env) -> (BytesN<32>, Vec<BytesN<32>>) {
+        let association_roots = if Self::has_association_set(env) {
+            vec![env, Self::get_association_root(env)]
+        } else {
+            vec![env]
+        };
+        (Self::get_merkle_root(env), association_roots)
+    }
+
+    /// Checks whether `root` is one of the last `get_root_history_size` merkle roots
+    pub fn is_known_root(env: &Env, root: BytesN<32>) -> bool {
+        Self::get_root_history(env).contains(&root)
+    }
+
+    /// Gets the retained merkle roots, oldest first; the last entry is the current root
+    pub fn get_root_history(env: &Env) -> Vec<BytesN<32>> {
+        Self::root_history_of(env, DEFAULT_POOL)
+    }
+
+    /// Gets the `count` most recent merkle roots, oldest first
+    ///
+    /// Each inserted commitment (a deposit, or the change of a partial withdrawal) adds
+    /// exactly one root, so a client that mirrored the tree at commitment count `n` asks
+    /// for `get_commitment_count() - n` roots. At most the retained history is
+    /// returned; a client further behind than `get_root_history_size` has to resync from
+    /// `get_commitments_page`.
+    pub fn get_roots_since(env: &Env, count: u32) -> Vec<BytesN<32>> {
+        let roots = Self::get_root_history(env);
+        let start = roots.len().saturating_sub(count);
+        roots.slice(start..)
+    }
+
+    /// Gets the number of merkle roots the pool retains
+    pub fn get_root_history_size(env: &Env) -> u32 {
+        Self::root_history_size_of(env, DEFAULT_POOL)
+    }
+
+    /// Gets the current depth of the merkle tree
+    pub fn get_merkle_depth(env: &Env) -> u32 {
+        Self::merkle_depth_of(env, DEFAULT_POOL)
     }
 
     /// Gets the number of commitments (leaves) in the merkle tree
     pub fn get_commitment_count(env: &Env) -> u32 {
-        let leaves: Vec<BytesN<32>> = env
-            .storage()
-            .instance()
-            .get(&TREE_LEAVES_KEY)
-            .unwrap_or(vec![&env]);
-        leaves.len() as u32
+        Self::commitments_of(env, DEFAULT_POOL).len()
+    }
+
+    /// Gets the number of commitments the merkle tree can hold, `2^depth`
+    ///
+    /// Saturates at `u32::MAX` for a depth of 32 or more.
+    pub fn get_capacity(env: &Env) -> u32 {
+        Self::capacity_of(env, DEFAULT_POOL)
+    }
+
+    /// Whether the merkle tree is full, so the next deposit would fail with
+    /// `Error::TreeAtCapacity`
+    pub fn is_full(env: &Env) -> bool {
+        Self::is_full_of(env, DEFAULT_POOL)
     }
 
     /// Gets all commitments (leaves) in the merkle tree
     pub fn get_commitments(env: &Env) -> Vec<BytesN<32>> {
-        env.storage()
-            .instance()
-            .get(&TREE_LEAVES_KEY)
-            .unwrap_or(vec![env])
+        Self::commitments_of(env, DEFAULT_POOL)
+    }
+
+    /// Gets a range of commitments (leaves) in insertion order
+    ///
+    /// Lets clients rebuild the tree incrementally instead of fetching every commitment
+    /// at once; page through with `get_commitment_count`. `count` is clamped to
+    /// `MAX_COMMITMENTS_PAGE`, and an empty vector is returned when `start` is past the
+    /// last commitment.
+    pub fn get_commitments_page(env: &Env, start: u32, count: u32) -> Vec<BytesN<32>> {
+        let leaves = Self::get_commitments(env);
+        let start = start.min(leaves.len());
+        let count = count.min(MAX_COMMITMENTS_PAGE);
+        let end = start.saturating_add(count).min(leaves.len());
+        leaves.slice(start..end)
+    }
+
+    /// Gets the merkle proof for the commitment at `leaf_index`
+    ///
+    /// # Returns
+    ///
+    /// * `(siblings, directions)` from the leaf level up, where `directions[i]` is `true`
+    ///   when the path node at level `i` is a right child, matching `stateSiblings` and
+    ///   the bits of `stateIndex` in the withdrawal circuit
+    /// * `None` if no commitment exists at `leaf_index`
+    ///
+    /// Rebuilding the tree from storage costs budget that grows with the number of
+    /// commitments; clients of a large pool can use `get_commitments_page` instead.
+    pub fn get_proof(env: &Env, leaf_index: u32) -> Option<(Vec<BytesN<32>>, Vec<bool>)> {
+        let leaves = Self::get_commitments(env);
+        let depth = Self::get_merkle_depth(env);
+        let root = Self::get_merkle_root(env);
+
+        LeanIMT::from_storage(env, leaves, depth, root).get_path(leaf_index)
     }
 
     pub fn get_nullifiers(env: &Env) -> Vec<BytesN<32>> {
-        env.storage().instance().get(&NULL_KEY).unwrap_or(vec![env])
+        Self::nullifiers_of(env, DEFAULT_POOL)
+    }
+
+    /// Gets the state root a spent nullifier's withdrawal proof was verified against
+    ///
+    /// Returns `None` for nullifiers that have not been spent. The root may be older
+    /// than the current one, since `withdraw` accepts any root in the root history.
+    pub fn get_withdrawal_root(env: &Env, nullifier: BytesN<32>) -> Option<BytesN<32>> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::WithdrawalRoot(DEFAULT_POOL, nullifier))
+    }
+
+    /// Gets the version of the verification key each spent nullifier was proven against
+    ///
+    /// Returns `None` for nullifiers that were never spent.
+    pub fn get_withdrawal_vk_version(env: &Env, nullifier: BytesN<32>) -> Option<u32> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::WithdrawalVk(DEFAULT_POOL, nullifier))
     }
 
     /// Gets the balance of the configured token held by the contract
     pub fn get_balance(env: &Env) -> i128 {
-        let token_address: Address = env.storage().instance().get(&TOKEN_KEY).unwrap();
-        let token_client = token::Client::new(env, &token_address);
+        let token_client = token::Client::new(env, &Self::get_token(env));
         token_client.balance(&env.current_contract_address())
     }
 
+    /// Gets the amount deposited into the pool and not yet withdrawn
+    ///
+    /// Unlike `get_balance`, this ignores tokens sent to the contract outside of `deposit`,
+    /// unless `enable_token_balance_mode` replaced the counter with that balance.
+    pub fn get_pool_balance(env: &Env) -> i128 {
+        Self::balance_of(env, DEFAULT_POOL)
+    }
+
     /// Validates that the caller is the admin
     ///
     /// # Arguments
@@ -328,6 +1675,324 @@ impl PrivacyPoolsContract {
         *caller == admin
     }
 
+    /// Keeps the pool's storage alive after a state change
+    ///
+    /// Extends the instance (and with it the contract code and every storage key) to
+    /// `INSTANCE_TTL_EXTEND_TO` once the remaining TTL drops below `INSTANCE_TTL_THRESHOLD`.
+    fn bump_instance_ttl(env: &Env) {
+        env.storage()
+            .instance()
+            .extend_ttl(INSTANCE_TTL_THRESHOLD, INSTANCE_TTL_EXTEND_TO);
+    }
+
+    /// Writes a persistent entry and extends its TTL to match the instance's
+    fn set_persistent<V: IntoVal<Env, Val>>(env: &Env, key: &DataKey, value: &V) {
+        let storage = env.storage().persistent();
+        storage.set(key, value);
+        storage.extend_ttl(key, INSTANCE_TTL_THRESHOLD, INSTANCE_TTL_EXTEND_TO);
+    }
+
+    /// Extends the TTL of the default pool's storage so it is not archived
+    ///
+    /// Deposits and withdrawals already extend it; this lets the admin keep an idle pool
+    /// alive. The instance, its code and the default pool's state share one TTL, which is
+    /// extended together with the persistent records of the pool's past withdrawals.
+    /// `extend_pool_ttl` does the same for a created pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The Soroban environment
+    /// * `caller` - The address of the caller (must be authenticated and be the admin)
+    /// * `ledgers` - TTL to extend to, capped at the network's maximum TTL
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` once the TTL is at least `ledgers` (or the maximum)
+    /// * `Err(Error::OnlyAdmin)` if the caller is not the admin
+    pub fn extend_ttl(env: &Env, caller: Address, ledgers: u32) -> Result<(), Error> {
+        Self::extend_pool_ttl(env, caller, DEFAULT_POOL, ledgers)
+    }
+
+    /// Extends the TTL of pool `pool_id`'s storage, as `extend_ttl` does for the default
+    /// pool
+    ///
+    /// Covers the instance (which a pool cannot be used without), every persistent entry
+    /// of a created pool's state, the record of the pools holding its token and the
+    /// withdrawal records of the pool's spent nullifiers, so the cost grows with the
+    /// number of withdrawals.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` once every TTL is at least `ledgers` (or the maximum)
+    /// * `Err(Error::OnlyAdmin)` if the caller is not the admin
+    /// * `Err(Error::UnknownPool)` if no pool `pool_id` exists
+    pub fn extend_pool_ttl(
+        env: &Env,
+        caller: Address,
+        pool_id: u32,
+        ledgers: u32,
+    ) -> Result<(), Error> {
+        caller.require_auth();
+
+        if !Self::is_admin(env, &caller) {
+            return Err(Error::OnlyAdmin);
+        }
+        if !Self::has_pool(env, pool_id) {
+            return Err(Error::UnknownPool);
+        }
+
+        let extend_to = ledgers.min(env.storage().max_ttl());
+        env.storage().instance().extend_ttl(extend_to, extend_to);
+
+        let storage = env.storage().persistent();
+        let extend = |key: DataKey| {
+            if storage.has(&key) {
+                storage.extend_ttl(&key, extend_to, extend_to);
+            }
+        };
+        if pool_id != DEFAULT_POOL {
+            for key in POOL_STATE_KEYS {
+                extend(DataKey::Pool(pool_id, key));
+            }
+        }
+        extend(DataKey::TokenPools(Self::token_of(env, pool_id)));
+        for nullifier in Self::nullifiers_of(env, pool_id) {
+            extend(DataKey::WithdrawalRoot(pool_id, nullifier.clone()));
+            extend(DataKey::WithdrawalVk(pool_id, nullifier));
+        }
+        Ok(())
+    }
+
+    /// Replaces the Groth16 verification key, e.g. after a circuit upgrade
+    ///
+    /// Each replacement increments the key version (the constructor's key is version 1),
+    /// and `withdraw` records the version that accepted each proof.
+    ///
+    /// Proofs generated for the old circuit do not verify against the new key. With
+    /// `keep_previous` set, the replaced key stays active as a fallback (one key back)
+    /// so those in-flight proofs can still be withdrawn; otherwise they are rejected from
+    /// now on and must be regenerated with the new circuit.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The Soroban environment
+    /// * `caller` - The address of the caller (must be authenticated and be the admin)
+    /// * `vk_bytes` - The new verification key, serialized as for the constructor
+    /// * `keep_previous` - Whether to keep accepting proofs for the replaced key
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(version)` with the new key version
+    /// * `Err(Error::OnlyAdmin)` if the caller is not the admin
+    ///
+    /// # Panics
+    ///
+    /// * If `vk_bytes` is not a serialized verification key
+    pub fn set_verification_key(
+        env: &Env,
+        caller: Address,
+        vk_bytes: Bytes,
+        keep_previous: bool,
+    ) -> Result<u32, Error> {
+        caller.require_auth();
+
+        if !Self::is_admin(env, &caller) {
+            return Err(Error::OnlyAdmin);
+        }
+
+        VerificationKey::from_bytes(env, &vk_bytes).unwrap();
+
+        let previous: Bytes = env.storage().instance().get(&VK_KEY).unwrap();
+        if keep_previous {
+            env.storage().instance().set(&PREVIOUS_VK_KEY, &previous);
+        } else {
+            env.storage().instance().remove(&PREVIOUS_VK_KEY);
+        }
+
+        let version = Self::get_vk_version(env) + 1;
+        env.storage().instance().set(&VK_KEY, &vk_bytes);
+        env.storage().instance().set(&VK_VERSION_KEY, &version);
+        Ok(version)
+    }
+
+    /// Sets the verification key for `withdrawPartial.circom`, enabling `withdraw_partial`
+    ///
+    /// The partial circuit has its own trusted setup, so its key is kept apart from the
+    /// `withdraw` key and is not versioned.
+    ///
+    /// # Returns
+    ///
+    /// * `Err(Error::OnlyAdmin)` if the caller is not the admin
+    ///
+    /// # Panics
+    ///
+    /// * If `vk_bytes` is not a serialized verification key
+    pub fn set_partial_verification_key(
+        env: &Env,
+        caller: Address,
+        vk_bytes: Bytes,
+    ) -> Result<(), Error> {
+        Self::set_circuit_key(env, &caller, &PARTIAL_VK_KEY, &vk_bytes)
+    }
+
+    /// Sets the verification key for `withdrawRecipient.circom`, enabling
+    /// `withdraw_to_recipient`
+    ///
+    /// Like the partial key, it comes from its own trusted setup and is not versioned.
+    ///
+    /// # Returns
+    ///
+    /// * `Err(Error::OnlyAdmin)` if the caller is not the admin
+    ///
+    /// # Panics
+    ///
+    /// * If `vk_bytes` is not a serialized verification key
+    pub fn set_recipient_verification_key(
+        env: &Env,
+        caller: Address,
+        vk_bytes: Bytes,
+    ) -> Result<(), Error> {
+        Self::set_circuit_key(env, &caller, &RECIPIENT_VK_KEY, &vk_bytes)
+    }
+
+    /// Sets the verification key for `withdrawExpiry.circom`, enabling
+    /// `withdraw_with_expiry`
+    ///
+    /// Like the partial key, it comes from its own trusted setup and is not versioned.
+    ///
+    /// # Returns
+    ///
+    /// * `Err(Error::OnlyAdmin)` if the caller is not the admin
+    ///
+    /// # Panics
+    ///
+    /// * If `vk_bytes` is not a serialized verification key
+    pub fn set_expiry_verification_key(
+        env: &Env,
+        caller: Address,
+        vk_bytes: Bytes,
+    ) -> Result<(), Error> {
+        Self::set_circuit_key(env, &caller, &EXPIRY_VK_KEY, &vk_bytes)
+    }
+
+    /// Pauses or resumes deposits
+    ///
+    /// While paused, `deposit` and the `deposit_structured` variants return
+    /// `Error::Paused` before moving any funds. Withdrawals are unaffected, so depositors can always exit.
+    ///
+    /// # Returns
+    ///
+    /// * `Err(Error::OnlyAdmin)` if the caller is not the admin
+    pub fn set_deposits_paused(env: &Env, caller: Address, paused: bool) -> Result<(), Error> {
+        caller.require_auth();
+
+        if !Self::is_admin(env, &caller) {
+            return Err(Error::OnlyAdmin);
+        }
+
+        env.storage().instance().set(&PAUSED_KEY, &paused);
+        Ok(())
+    }
+
+    /// Whether deposits are currently paused (false until the admin pauses them)
+    pub fn deposits_paused(env: &Env) -> bool {
+        env.storage().instance().get(&PAUSED_KEY).unwrap_or(false)
+    }
+
+    /// Drops the deposit counter and checks withdrawals against the token balance
+    ///
+    /// By default every pool counts the denomination in on `deposit` and out on
+    /// `withdraw`, and a withdrawal needs that counter to cover it. After this call the
+    /// counter is gone: `withdraw` checks the contract's balance of the pool's token
+    /// instead, and `get_pool_balance`/`get_pool_deposits` return that balance, so the
+    /// two can no longer diverge.
+    ///
+    /// Tokens sent to the contract outside of `deposit` then count as pool funds. A
+    /// donation lets the balance check pass for a withdrawal the deposits alone would not
+    /// cover; the proof and nullifier checks still apply. The balance is the contract's
+    /// whole balance of the token, so the mode is refused while another pool holds the
+    /// same token, and `create_pool` refuses the token afterwards. The switch applies to
+    /// the default pool only (`enable_pool_token_balance_mode` covers created pools) and
+    /// cannot be undone.
+    ///
+    /// # Returns
+    ///
+    /// * `Err(Error::OnlyAdmin)` if the caller is not the admin
+    /// * `Err(Error::SharedToken)` if a created pool holds the same token
+    pub fn enable_token_balance_mode(env: &Env, caller: Address) -> Result<(), Error> {
+        Self::enable_pool_token_balance_mode(env, caller, DEFAULT_POOL)
+    }
+
+    /// Switches pool `pool_id` to token balance mode, as `enable_token_balance_mode` does
+    /// for the default pool
+    ///
+    /// # Returns
+    ///
+    /// * `Err(Error::OnlyAdmin)` if the caller is not the admin
+    /// * `Err(Error::UnknownPool)` if no pool `pool_id` exists
+    /// * `Err(Error::SharedToken)` if another pool holds the same token
+    pub fn enable_pool_token_balance_mode(
+        env: &Env,
+        caller: Address,
+        pool_id: u32,
+    ) -> Result<(), Error> {
+        caller.require_auth();
+
+        if !Self::is_admin(env, &caller) {
+            return Err(Error::OnlyAdmin);
+        }
+        if !Self::has_pool(env, pool_id) {
+            return Err(Error::UnknownPool);
+        }
+        // Any other pool's deposits would count as this pool's funds
+        if Self::pools_holding(env, &Self::token_of(env, pool_id)).len() > 1 {
+            return Err(Error::SharedToken);
+        }
+
+        Self::pool_set(env, pool_id, &TOKEN_BALANCE_KEY, &true);
+        Self::pool_remove(env, pool_id, &BALANCE_KEY);
+        Ok(())
+    }
+
+    /// Whether the default pool's withdrawals are checked against the token balance
+    /// instead of the deposit counter (false until the admin calls
+    /// `enable_token_balance_mode`)
+    pub fn uses_token_balance(env: &Env) -> bool {
+        Self::uses_token_balance_of(env, DEFAULT_POOL)
+    }
+
+    /// Whether pool `pool_id` is in token balance mode, as `uses_token_balance` reports
+    /// for the default pool
+    pub fn uses_pool_token_balance(env: &Env, pool_id: u32) -> Result<bool, Error> {
+        if !Self::has_pool(env, pool_id) {
+            return Err(Error::UnknownPool);
+        }
+        Ok(Self::uses_token_balance_of(env, pool_id))
+    }
+
+    /// Stores an unversioned verification key for one of the additional circuits
+    fn set_circuit_key(
+        env: &Env,
+        caller: &Address,
+        key: &Symbol,
+        vk_bytes: &Bytes,
+    ) -> Result<(), Error> {
+        caller.require_auth();
+
+        if !Self::is_admin(env, caller) {
+            return Err(Error::OnlyAdmin);
+        }
+
+        VerificationKey::from_bytes(env, vk_bytes).unwrap();
+        env.storage().instance().set(key, vk_bytes);
+        Ok(())
+    }
+
+    /// Gets the version of the current verification key (1 until it is first replaced)
+    pub fn get_vk_version(env: &Env) -> u32 {
+        env.storage().instance().get(&VK_VERSION_KEY).unwrap_or(1)
+    }
+
     /// Sets the association set root for compliance verification
     ///
     /// This function allows the admin to update the association set root,
@@ -374,10 +2039,7 @@ impl PrivacyPoolsContract {
     ///
     /// * The current association set root, or zero bytes if not set
     pub fn get_association_root(env: &Env) -> BytesN<32> {
-        env.storage()
-            .instance()
-            .get(&ASSOCIATION_ROOT_KEY)
-            .unwrap_or(BytesN::from_array(&env, &[0u8; 32]))
+        Self::association_root_of(env, DEFAULT_POOL)
     }
 
     /// Checks if an association set is currently configured
@@ -386,9 +2048,210 @@ impl PrivacyPoolsContract {
     ///
     /// * `true` if an association set root is configured, `false` otherwise
     pub fn has_association_set(env: &Env) -> bool {
-        let association_root = Self::get_association_root(env);
-        let zero_root = BytesN::from_array(&env, &[0u8; 32]);
-        association_root != zero_root
+        Self::has_association_set_of(env, DEFAULT_POOL)
+    }
+
+    /// Gets the address of the token accepted by the pool
+    ///
+    /// # Returns
+    ///
+    /// * The token address configured at deployment
+    pub fn get_token(env: &Env) -> Address {
+        Self::token_of(env, DEFAULT_POOL)
+    }
+
+    /// Gets the scope identifying this pool
+    ///
+    /// # Returns
+    ///
+    /// * The scope set at deployment, as a big-endian field element. Labels of
+    ///   commitments deposited through `deposit_structured` are `Poseidon(scope, nonce)`.
+    pub fn get_scope(env: &Env) -> BytesN<32> {
+        Self::scope_of(env, DEFAULT_POOL)
+    }
+
+    /// Gets the fixed amount moved by every deposit and withdrawal in the default pool
+    ///
+    /// # Returns
+    ///
+    /// * The denomination in the token's base units, as passed to the constructor;
+    ///   `FIXED_AMOUNT` (100 XLM in stroops) for a deployment that predates it
+    pub fn get_denomination(env: &Env) -> i128 {
+        Self::denomination_of(env, DEFAULT_POOL)
+    }
+
+    /// Gets the fixed amount moved by every deposit and withdrawal in pool `pool_id`, as
+    /// passed to `create_pool`
+    pub fn get_pool_denomination(env: &Env, pool_id: u32) -> Result<i128, Error> {
+        if !Self::has_pool(env, pool_id) {
+            return Err(Error::UnknownPool);
+        }
+        Ok(Self::denomination_of(env, pool_id))
+    }
+
+    /// Gets all of the pool's parameters in one call
+    ///
+    /// A client can compare this against the values it expects (in particular
+    /// `vk_hash`, the SHA-256 of the verification key it generates proofs for) to check
+    /// it is talking to the right pool before depositing.
+    pub fn get_config(env: &Env) -> PoolConfig {
+        Self::config_of(env, DEFAULT_POOL)
+    }
+
+    /// Creates pool `pool_id` for `token`, with its own tree, nullifiers, balance and
+    /// `denomination`
+    ///
+    /// The new pool shares the admin, verification keys and tree depth of the default
+    /// pool and keeps the same number of recent roots. It starts out counting deposits,
+    /// whatever mode the default pool is in, and cannot hold the token of a pool in token
+    /// balance mode, whose balance is the contract's whole balance of that token. Coins
+    /// are tied to a pool by its state roots, and `deposit_structured`-style labels by
+    /// `scope`, so give each pool a distinct scope. Withdrawals need an association root
+    /// set with `set_pool_association_root`.
+    ///
+    /// # Arguments
+    ///
+    /// * `denomination` - Amount every deposit and withdrawal moves, in `token`'s base units
+    /// * `token_decimals` - The decimals `denomination` was computed for; if set, `token`
+    ///                      must report the same number
+    ///
+    /// # Returns
+    ///
+    /// * `Err(Error::OnlyAdmin)` if the caller is not the admin
+    /// * `Err(Error::PoolExists)` if `pool_id` is `DEFAULT_POOL` or already created
+    /// * `Err(Error::InvalidFieldElement)` if `scope` is not below the field modulus
+    /// * `Err(Error::InvalidDenomination)` if `denomination` is not positive
+    /// * `Err(Error::TokenDecimalsMismatch)` if `token` has other decimals than
+    ///   `token_decimals`
+    /// * `Err(Error::SharedToken)` if a pool in token balance mode holds `token`
+    pub fn create_pool(
+        env: &Env,
+        caller: Address,
+        pool_id: u32,
+        token: Address,
+        scope: BytesN<32>,
+        denomination: i128,
+        token_decimals: Option<u32>,
+    ) -> Result<(), Error> {
+        caller.require_auth();
+
+        if !Self::is_admin(env, &caller) {
+            return Err(Error::OnlyAdmin);
+        }
+        if Self::has_pool(env, pool_id) {
+            return Err(Error::PoolExists);
+        }
+        if !is_canonical_field_element(&scope) {
+            return Err(Error::InvalidFieldElement);
+        }
+        if denomination <= 0 {
+            return Err(Error::InvalidDenomination);
+        }
+        if let Some(decimals) = token_decimals {
+            if token::Client::new(env, &token).decimals() != decimals {
+                return Err(Error::TokenDecimalsMismatch);
+            }
+        }
+        let holders = Self::pools_holding(env, &token);
+        if holders
+            .iter()
+            .any(|pool| Self::uses_token_balance_of(env, pool))
+        {
+            return Err(Error::SharedToken);
+        }
+
+        let token_pools = DataKey::TokenPools(token.clone());
+        let mut created: Vec<u32> = env
+            .storage()
+            .persistent()
+            .get(&token_pools)
+            .unwrap_or(vec![env]);
+        created.push_back(pool_id);
+        Self::set_persistent(env, &token_pools, &created);
+        Self::pool_set(env, pool_id, &TOKEN_KEY, &token);
+        Self::pool_set(env, pool_id, &SCOPE_KEY, &scope);
+        Self::pool_set(env, pool_id, &DENOMINATION_KEY, &denomination);
+        Self::pool_set(
+            env,
+            pool_id,
+            &ROOTS_SIZE_KEY,
+            &Self::get_root_history_size(env),
+        );
+
+        let tree = LeanIMT::new(env, TREE_DEPTH);
+        Self::store_tree(env, pool_id, &tree);
+        Self::pool_set(env, pool_id, &ROOTS_KEY, &vec![env, tree.get_root()]);
+        Ok(())
+    }
+
+    /// Whether pool `pool_id` exists; `DEFAULT_POOL` always does
+    pub fn has_pool(env: &Env, pool_id: u32) -> bool {
+        if pool_id == DEFAULT_POOL {
+            return env.storage().instance().has(&TOKEN_KEY);
+        }
+        env.storage()
+            .persistent()
+            .has(&DataKey::Pool(pool_id, TOKEN_KEY))
+    }
+
+    /// Sets the association root withdrawals from pool `pool_id` must prove against
+    ///
+    /// `set_association_root` does the same for the default pool.
+    ///
+    /// # Returns
+    ///
+    /// * `Err(Error::OnlyAdmin)` if the caller is not the admin
+    /// * `Err(Error::UnknownPool)` if no pool `pool_id` exists
+    pub fn set_pool_association_root(
+        env: &Env,
+        caller: Address,
+        pool_id: u32,
+        association_root: BytesN<32>,
+    ) -> Result<(), Error> {
+        caller.require_auth();
+
+        if !Self::is_admin(env, &caller) {
+            return Err(Error::OnlyAdmin);
+        }
+        if !Self::has_pool(env, pool_id) {
+            return Err(Error::UnknownPool);
+        }
+
+        Self::pool_set(env, pool_id, &ASSOCIATION_ROOT_KEY, &association_root);
+        Ok(())
+    }
+
+    /// Gets pool `pool_id`'s parameters, as `get_config` does for the default pool
+    pub fn get_pool_config(env: &Env, pool_id: u32) -> Result<PoolConfig, Error> {
+        if !Self::has_pool(env, pool_id) {
+            return Err(Error::UnknownPool);
+        }
+        Ok(Self::config_of(env, pool_id))
+    }
+
+    /// Gets the current merkle root of pool `pool_id`'s tree
+    pub fn get_pool_merkle_root(env: &Env, pool_id: u32) -> Result<BytesN<32>, Error> {
+        if !Self::has_pool(env, pool_id) {
+            return Err(Error::UnknownPool);
+        }
+        Ok(Self::merkle_root_of(env, pool_id))
+    }
+
+    /// Gets all commitments in pool `pool_id`'s tree, in deposit order
+    pub fn get_pool_commitments(env: &Env, pool_id: u32) -> Result<Vec<BytesN<32>>, Error> {
+        if !Self::has_pool(env, pool_id) {
+            return Err(Error::UnknownPool);
+        }
+        Ok(Self::commitments_of(env, pool_id))
+    }
+
+    /// Gets the amount deposited into pool `pool_id` and not yet withdrawn, or the
+    /// contract's balance of its token in token balance mode
+    pub fn get_pool_deposits(env: &Env, pool_id: u32) -> Result<i128, Error> {
+        if !Self::has_pool(env, pool_id) {
+            return Err(Error::UnknownPool);
+        }
+        Ok(Self::balance_of(env, pool_id))
     }
 
     /// Gets the admin address (the contract deployer)
@@ -400,3 +2263,197 @@ impl PrivacyPoolsContract {
         env.storage().instance().get(&ADMIN_KEY).unwrap()
     }
 }
+
+// Per-pool state
+//
+// The default pool keeps its state in instance storage under the plain keys, so state
+// written before pools existed is read unchanged. A created pool keeps each value in its
+// own persistent entry under `DataKey::Pool(pool, key)`, so adding pools does not grow
+// the instance entry every call loads.
+impl PrivacyPoolsContract {
+    fn pool_get<V: TryFromVal<Env, Val>>(env: &Env, pool: u32, key: &Symbol) -> Option<V> {
+        if pool == DEFAULT_POOL {
+            env.storage().instance().get(key)
+        } else {
+            env.storage()
+                .persistent()
+                .get(&DataKey::Pool(pool, key.clone()))
+        }
+    }
+
+    fn pool_set<V: IntoVal<Env, Val>>(env: &Env, pool: u32, key: &Symbol, value: &V) {
+        if pool == DEFAULT_POOL {
+            env.storage().instance().set(key, value);
+        } else {
+            Self::set_persistent(env, &DataKey::Pool(pool, key.clone()), value);
+        }
+    }
+
+    fn pool_remove(env: &Env, pool: u32, key: &Symbol) {
+        if pool == DEFAULT_POOL {
+            env.storage().instance().remove(key);
+        } else {
+            env.storage()
+                .persistent()
+                .remove(&DataKey::Pool(pool, key.clone()));
+        }
+    }
+
+    /// Keeps a created pool's state alive after a deposit or withdrawal
+    ///
+    /// Extends every entry of the pool, including those the call only read, the way
+    /// `bump_instance_ttl` does for the default pool.
+    fn bump_pool_ttl(env: &Env, pool: u32) {
+        if pool == DEFAULT_POOL {
+            return;
+        }
+        let storage = env.storage().persistent();
+        for key in POOL_STATE_KEYS {
+            let key = DataKey::Pool(pool, key);
+            if storage.has(&key) {
+                storage.extend_ttl(&key, INSTANCE_TTL_THRESHOLD, INSTANCE_TTL_EXTEND_TO);
+            }
+        }
+    }
+
+    fn merkle_root_of(env: &Env, pool: u32) -> BytesN<32> {
+        Self::pool_get(env, pool, &TREE_ROOT_KEY).unwrap_or(BytesN::from_array(env, &[0u8; 32]))
+    }
+
+    /// The pool's tree depth, set when the pool is created
+    ///
+    /// There is no fallback: a missing depth means the pool's storage is incomplete, and
+    /// treating it as a depth-0 tree (capacity 1) would misplace every later commitment.
+    fn merkle_depth_of(env: &Env, pool: u32) -> u32 {
+        Self::pool_get(env, pool, &TREE_DEPTH_KEY).expect("Tree depth is not initialized")
+    }
+
+    fn capacity_of(env: &Env, pool: u32) -> u32 {
+        1u32.checked_shl(Self::merkle_depth_of(env, pool))
+            .unwrap_or(u32::MAX)
+    }
+
+    fn is_full_of(env: &Env, pool: u32) -> bool {
+        Self::commitments_of(env, pool).len() >= Self::capacity_of(env, pool)
+    }
+
+    fn commitments_of(env: &Env, pool: u32) -> Vec<BytesN<32>> {
+        Self::pool_get(env, pool, &TREE_LEAVES_KEY).unwrap_or(vec![env])
+    }
+
+    fn root_history_of(env: &Env, pool: u32) -> Vec<BytesN<32>> {
+        Self::pool_get(env, pool, &ROOTS_KEY).unwrap_or(vec![env])
+    }
+
+    fn root_history_size_of(env: &Env, pool: u32) -> u32 {
+        Self::pool_get(env, pool, &ROOTS_SIZE_KEY).unwrap_or(DEFAULT_ROOT_HISTORY_SIZE)
+    }
+
+    fn nullifiers_of(env: &Env, pool: u32) -> Vec<BytesN<32>> {
+        Self::pool_get(env, pool, &NULL_KEY).unwrap_or(vec![env])
+    }
+
+    /// The funds `pool` can pay out: its deposit counter, or in token balance mode the
+    /// contract's balance of the pool's token
+    fn balance_of(env: &Env, pool: u32) -> i128 {
+        if Self::uses_token_balance_of(env, pool) {
+            let token_client = token::Client::new(env, &Self::token_of(env, pool));
+            return token_client.balance(&env.current_contract_address());
+        }
+        Self::pool_get(env, pool, &BALANCE_KEY).unwrap_or(0)
+    }
+
+    /// The pool's denomination; `FIXED_AMOUNT` for a default pool that predates it
+    fn denomination_of(env: &Env, pool: u32) -> i128 {
+        Self::pool_get(env, pool, &DENOMINATION_KEY).unwrap_or(FIXED_AMOUNT)
+    }
+
+    fn uses_token_balance_of(env: &Env, pool: u32) -> bool {
+        Self::pool_get(env, pool, &TOKEN_BALANCE_KEY).unwrap_or(false)
+    }
+
+    fn token_of(env: &Env, pool: u32) -> Address {
+        Self::pool_get(env, pool, &TOKEN_KEY).unwrap()
+    }
+
+    /// The pools holding `token`: the created ones recorded under `DataKey::TokenPools`,
+    /// after the default pool if it holds `token` too
+    fn pools_holding(env: &Env, token: &Address) -> Vec<u32> {
+        let mut pools: Vec<u32> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::TokenPools(token.clone()))
+            .unwrap_or(vec![env]);
+        if Self::token_of(env, DEFAULT_POOL) == *token {
+            pools.push_front(DEFAULT_POOL);
+        }
+        pools
+    }
+
+    fn scope_of(env: &Env, pool: u32) -> BytesN<32> {
+        Self::pool_get(env, pool, &SCOPE_KEY).unwrap()
+    }
+
+    /// The pool's association root, or zero bytes if not set
+    fn association_root_of(env: &Env, pool: u32) -> BytesN<32> {
+        Self::pool_get(env, pool, &ASSOCIATION_ROOT_KEY)
+            .unwrap_or(BytesN::from_array(env, &[0u8; 32]))
+    }
+
+    fn has_association_set_of(env: &Env, pool: u32) -> bool {
+        Self::association_root_of(env, pool) != BytesN::from_array(env, &[0u8; 32])
+    }
+
+    fn config_of(env: &Env, pool: u32) -> PoolConfig {
+        let vk_bytes: Bytes = env.storage().instance().get(&VK_KEY).unwrap();
+        PoolConfig {
+            depth: Self::merkle_depth_of(env, pool),
+            denomination: Self::denomination_of(env, pool),
+            token: Self::token_of(env, pool),
+            scope: Self::scope_of(env, pool),
+            vk_hash: env.crypto().sha256(&vk_bytes).into(),
+            vk_version: Self::get_vk_version(env),
+            root_history_size: Self::root_history_size_of(env, pool),
+        }
+    }
+}
+
+#[cfg(test)]
+#[contractimpl]
+impl PrivacyPoolsContract {
+    /// Whether the stored root is the root of the stored commitments (test builds only)
+    ///
+    /// Rebuilds the default pool's tree from its commitments and compares it with the
+    /// stored root, to confirm a migration or manual storage edit left the two in sync.
+    pub fn verify_tree(env: &Env) -> bool {
+        let leaves = Self::commitments_of(env, DEFAULT_POOL);
+        let depth = Self::merkle_depth_of(env, DEFAULT_POOL);
+        let root = Self::merkle_root_of(env, DEFAULT_POOL);
+
+        LeanIMT::from_storage(env, leaves, depth, root).verify_internal_consistency()
+    }
+}
+
+#[cfg(feature = "test_hash")]
+#[contractimpl]
+impl PrivacyPoolsContract {
+    /// Computes a commitment from all of its parts (test builds only)
+    ///
+    /// Runs the same hashes as coinutils' `generate_commitment`: `Poseidon(value, label,
+    /// Poseidon(nullifier, secret))`, with every part but `value` a big-endian field
+    /// element. Lets tests check that the contract and coinutils agree on the formula.
+    pub fn compute_commitment(
+        env: &Env,
+        value: i128,
+        label: BytesN<32>,
+        nullifier: BytesN<32>,
+        secret: BytesN<32>,
+    ) -> BytesN<32> {
+        let precommitment = commitment::precommitment(
+            env,
+            &BlsScalar::from_bytes(nullifier),
+            &BlsScalar::from_bytes(secret),
+        );
+        commitment_hash(env, value, &BlsScalar::from_bytes(label), &precommitment)
+    }
+}
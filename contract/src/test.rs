@@ -1,14 +1,18 @@
 #![cfg(test)]
+extern crate std;
 use super::*;
 use ark_bls12_381::{Fq, Fq2};
 use ark_serialize::CanonicalSerialize;
 use core::str::FromStr;
-use soroban_sdk::testutils::Address as TestAddress;
+use soroban_sdk::testutils::{Address as TestAddress, Events as _, Ledger as _};
 use soroban_sdk::{
     crypto::bls12_381::{Fr, G1Affine, G2Affine, G1_SERIALIZED_SIZE, G2_SERIALIZED_SIZE},
-    symbol_short, vec, Address, Bytes, BytesN, Env, String, U256,
+    symbol_short, vec, xdr, Address, Bytes, BytesN, Env, IntoVal, Map, String, Symbol, TryFromVal,
+    Val, U256,
 };
 
+mod bench;
+
 // Mock token contract for testing
 #[contract]
 pub struct MockToken;
@@ -46,6 +50,13 @@ impl MockToken {
         env.storage().instance().get(&id).unwrap_or(0)
     }
 
+    pub fn decimals(env: &Env) -> u32 {
+        env.storage()
+            .instance()
+            .get(&symbol_short!("decimal"))
+            .unwrap()
+    }
+
     pub fn transfer(env: &Env, from: Address, to: Address, amount: i128) {
         from.require_auth();
 
@@ -62,6 +73,92 @@ impl MockToken {
     }
 }
 
+// Token that tries to re-enter the pool's `withdraw` from inside `transfer`
+#[contract]
+pub struct ReentrantToken;
+
+#[contractimpl]
+impl ReentrantToken {
+    pub fn arm(env: &Env, pool: Address, proof: Bytes, pub_signals: Bytes) {
+        env.storage().instance().set(&symbol_short!("pool"), &pool);
+        env.storage()
+            .instance()
+            .set(&symbol_short!("proof"), &proof);
+        env.storage()
+            .instance()
+            .set(&symbol_short!("signals"), &pub_signals);
+    }
+
+    pub fn balance(_env: &Env, _id: Address) -> i128 {
+        i128::MAX
+    }
+
+    // Balances aren't tracked, so minting is a no-op
+    pub fn mint(_env: &Env, _to: Address, _amount: i128) {}
+
+    pub fn transfer(env: &Env, _from: Address, to: Address, _amount: i128) {
+        let pool: Option<Address> = env.storage().instance().get(&symbol_short!("pool"));
+        let Some(pool) = pool else {
+            return;
+        };
+        // Only re-enter once
+        env.storage().instance().remove(&symbol_short!("pool"));
+
+        let proof: Bytes = env
+            .storage()
+            .instance()
+            .get(&symbol_short!("proof"))
+            .unwrap();
+        let pub_signals: Bytes = env
+            .storage()
+            .instance()
+            .get(&symbol_short!("signals"))
+            .unwrap();
+        let result = env.try_invoke_contract::<Vec<String>, soroban_sdk::Error>(
+            &pool,
+            &Symbol::new(env, "withdraw"),
+            vec![
+                env,
+                to.into_val(env),
+                proof.into_val(env),
+                pub_signals.into_val(env),
+            ],
+        );
+        match result {
+            Ok(Ok(messages)) => env
+                .storage()
+                .instance()
+                .set(&symbol_short!("messages"), &messages),
+            // The host's re-entry guard, as opposed to anything the pool checks
+            Err(Ok(error))
+                if error
+                    == soroban_sdk::Error::from_type_and_code(
+                        xdr::ScErrorType::Context,
+                        xdr::ScErrorCode::InvalidAction,
+                    ) =>
+            {
+                env.storage()
+                    .instance()
+                    .set(&symbol_short!("refused"), &true)
+            }
+            _ => panic!("unexpected nested withdraw outcome"),
+        }
+    }
+
+    /// Status messages returned by the nested `withdraw`, if it ran
+    pub fn reentry_messages(env: &Env) -> Option<Vec<String>> {
+        env.storage().instance().get(&symbol_short!("messages"))
+    }
+
+    /// Whether the host refused to run the nested `withdraw` at all
+    pub fn reentry_refused(env: &Env) -> bool {
+        env.storage()
+            .instance()
+            .get(&symbol_short!("refused"))
+            .unwrap_or(false)
+    }
+}
+
 fn g1_from_coords(env: &Env, x: &str, y: &str) -> G1Affine {
     let ark_g1 = ark_bls12_381::G1Affine::new(Fq::from_str(x).unwrap(), Fq::from_str(y).unwrap());
     let mut buf = [0u8; G1_SERIALIZED_SIZE];
@@ -282,6 +379,42 @@ fn init_erronous_pub_signals(env: &Env) -> Bytes {
     return pub_signals.to_bytes(env);
 }
 
+// Callback target for `withdraw_and_call`
+#[contract]
+pub struct MockReceiver;
+
+#[contractimpl]
+impl MockReceiver {
+    pub fn on_withdraw(env: &Env, tag: u32) {
+        env.storage().instance().set(&symbol_short!("tag"), &tag);
+    }
+
+    pub fn fail(_env: &Env) {
+        panic!("receiver rejected the withdrawal");
+    }
+
+    pub fn tag(env: &Env) -> Option<u32> {
+        env.storage().instance().get(&symbol_short!("tag"))
+    }
+}
+
+/// Constructor options of a fresh pool: default root history, no import, no decimals check
+fn pool_options() -> PoolOptions {
+    PoolOptions {
+        root_history_size: 0,
+        initial_commitments: None,
+        initial_nullifiers: None,
+        token_decimals: None,
+    }
+}
+
+// Scope "test_pool", right-aligned the way coinutils' generate_label encodes it
+fn init_scope(env: &Env) -> BytesN<32> {
+    let mut scope = [0u8; 32];
+    scope[32 - b"test_pool".len()..].copy_from_slice(b"test_pool");
+    BytesN::from_array(env, &scope)
+}
+
 fn setup_test_environment(env: &Env) -> (Address, Address, Address) {
     // Deploy mock token
     let token_admin = Address::generate(env);
@@ -300,12 +433,56 @@ fn setup_test_environment(env: &Env) -> (Address, Address, Address) {
     let admin = Address::generate(env);
     let privacy_pools_id = env.register(
         PrivacyPoolsContract,
-        (init_vk(env), token_id.clone(), admin.clone()),
+        (
+            init_vk(env),
+            token_id.clone(),
+            admin.clone(),
+            init_scope(env),
+            FIXED_AMOUNT,
+            pool_options(),
+        ),
     );
 
     (token_id, privacy_pools_id, admin)
 }
 
+/// Commitment of the coin spent by the fixture proof
+fn fixture_commitment(env: &Env) -> BytesN<32> {
+    BytesN::from_array(
+        env,
+        &[
+            0x10, 0xcb, 0x63, 0x1d, 0x17, 0x4a, 0x98, 0xb2, 0x44, 0x0b, 0x68, 0xd2, 0xe5, 0x7d,
+            0xa2, 0xae, 0x9a, 0x13, 0xf7, 0xd1, 0xcc, 0xcb, 0x1f, 0x41, 0xa1, 0xdd, 0x3d, 0x69,
+            0xa2, 0x2f, 0xaa, 0xe9,
+        ],
+    )
+}
+
+/// Association set root the fixture proof was generated against
+fn fixture_association_root(env: &Env) -> BytesN<32> {
+    BytesN::from_array(
+        env,
+        &[
+            0x5d, 0x58, 0x26, 0xf9, 0xc9, 0x18, 0x7b, 0xdb, 0x21, 0x3f, 0x01, 0xde, 0xd6, 0xd2,
+            0x30, 0xe9, 0xf1, 0xab, 0x65, 0x3b, 0x5b, 0xee, 0x60, 0x36, 0x50, 0x4e, 0x82, 0xbc,
+            0x07, 0x16, 0xba, 0xa2,
+        ],
+    )
+}
+
+/// Deposits the fixture commitment and publishes the fixture association root
+fn prepare_fixture_withdrawal(
+    env: &Env,
+    client: &PrivacyPoolsContractClient,
+    token_id: &Address,
+    admin: &Address,
+) {
+    let alice = Address::generate(env);
+    MockTokenClient::new(env, token_id).mint(&alice, &1000000000);
+    client.deposit(&alice, &fixture_commitment(env));
+    client.set_association_root(admin, &fixture_association_root(env));
+}
+
 #[test]
 fn test_deposit_and_withdraw_correct_proof() {
     let env = Env::default();
@@ -328,14 +505,7 @@ fn test_deposit_and_withdraw_correct_proof() {
     assert_eq!(token_client.balance(&alice), 1000000000);
 
     // Test deposit
-    let commitment = BytesN::from_array(
-        &env,
-        &[
-            0x10, 0xcb, 0x63, 0x1d, 0x17, 0x4a, 0x98, 0xb2, 0x44, 0x0b, 0x68, 0xd2, 0xe5, 0x7d,
-            0xa2, 0xae, 0x9a, 0x13, 0xf7, 0xd1, 0xcc, 0xcb, 0x1f, 0x41, 0xa1, 0xdd, 0x3d, 0x69,
-            0xa2, 0x2f, 0xaa, 0xe9,
-        ],
-    );
+    let commitment = fixture_commitment(&env);
 
     // Mock authentication for alice
     env.mock_all_auths();
@@ -351,14 +521,7 @@ fn test_deposit_and_withdraw_correct_proof() {
     assert_eq!(token_client.balance(&contract_id), 1000000000); // Contract should have the tokens
 
     // Set association root to match the proof
-    let association_root = BytesN::from_array(
-        &env,
-        &[
-            0x5d, 0x58, 0x26, 0xf9, 0xc9, 0x18, 0x7b, 0xdb, 0x21, 0x3f, 0x01, 0xde, 0xd6, 0xd2,
-            0x30, 0xe9, 0xf1, 0xab, 0x65, 0x3b, 0x5b, 0xee, 0x60, 0x36, 0x50, 0x4e, 0x82, 0xbc,
-            0x07, 0x16, 0xba, 0xa2,
-        ],
-    );
+    let association_root = fixture_association_root(&env);
     env.mock_all_auths();
     let set_result = client.set_association_root(&admin, &association_root);
     assert_eq!(
@@ -369,8 +532,8 @@ fn test_deposit_and_withdraw_correct_proof() {
     // Test withdraw
     let proof = init_proof(&env);
     let pub_signals = init_pub_signals(&env);
-    let pub_signals_struct = PublicSignals::from_bytes(&env, &pub_signals);
-    let nullifier = pub_signals_struct.pub_signals.get(0).unwrap().to_bytes();
+    let pub_signals_struct = PublicSignals::from_bytes(&env, &pub_signals).unwrap();
+    let nullifier = fr_to_bytes32(&env, &pub_signals_struct.pub_signals.get(0).unwrap());
 
     let result = client.withdraw(&bob, &proof, &pub_signals);
     // Success is now logged as a diagnostic event, so we return an empty vec
@@ -386,6 +549,99 @@ fn test_deposit_and_withdraw_correct_proof() {
     assert_eq!(nullifiers.get(0).unwrap(), nullifier);
 }
 
+#[test]
+fn test_withdraw_against_historical_root_records_root() {
+    let env = Env::default();
+    let (token_id, contract_id, admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    env.mock_all_auths();
+    token_client.mint(&alice, &2000000000);
+
+    let commitment = fixture_commitment(&env);
+    client.deposit(&alice, &commitment);
+    let proof_root = client.get_merkle_root();
+
+    // A later deposit moves the tree past the root the proof was generated against
+    client.deposit(&alice, &BytesN::from_array(&env, &[1u8; 32]));
+    assert_ne!(client.get_merkle_root(), proof_root);
+
+    let association_root = fixture_association_root(&env);
+    client.set_association_root(&admin, &association_root);
+
+    let proof = init_proof(&env);
+    let pub_signals = init_pub_signals(&env);
+    let pub_signals_struct = PublicSignals::from_bytes(&env, &pub_signals).unwrap();
+    let nullifier = fr_to_bytes32(&env, &pub_signals_struct.pub_signals.get(0).unwrap());
+    assert_eq!(client.get_withdrawal_root(&nullifier), None);
+
+    assert_eq!(client.withdraw(&bob, &proof, &pub_signals), vec![&env]);
+    assert_eq!(token_client.balance(&bob), 1000000000);
+    assert_eq!(client.get_withdrawal_root(&nullifier), Some(proof_root));
+
+    // The record is a persistent entry of its own, kept alive like the instance
+    let key = DataKey::WithdrawalRoot(DEFAULT_POOL, nullifier.clone());
+    assert_eq!(
+        persistent_ttl(&env, &contract_id, &key),
+        INSTANCE_TTL_EXTEND_TO
+    );
+
+    // The historical root does not make the nullifier reusable
+    assert_eq!(
+        client.withdraw(&bob, &proof, &pub_signals),
+        vec![&env, String::from_str(&env, ERROR_NULLIFIER_USED)]
+    );
+}
+
+#[test]
+fn test_can_withdraw_matches_withdraw() {
+    let env = Env::default();
+    let (token_id, contract_id, admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    env.mock_all_auths();
+    token_client.mint(&alice, &1000000000);
+
+    let proof = init_proof(&env);
+    let pub_signals = init_pub_signals(&env);
+
+    let commitment = fixture_commitment(&env);
+    let association_root = fixture_association_root(&env);
+    client.set_association_root(&admin, &association_root);
+
+    // Empty pool
+    assert_eq!(
+        client.try_can_withdraw(&proof, &pub_signals),
+        Err(Ok(Error::InsufficientBalance))
+    );
+
+    client.deposit(&alice, &commitment);
+
+    // A valid proof passes the dry run without touching state
+    assert_eq!(client.try_can_withdraw(&proof, &pub_signals), Ok(Ok(())));
+    assert_eq!(client.get_nullifiers().len(), 0);
+    assert_eq!(client.get_pool_balance(), 1000000000);
+    assert_eq!(token_client.balance(&contract_id), 1000000000);
+
+    assert_eq!(
+        client.try_can_withdraw(&proof, &init_erronous_pub_signals(&env)),
+        Err(Ok(Error::CoinOwnershipProofFailed))
+    );
+
+    // Once spent, the dry run reports the same failure withdraw would
+    assert_eq!(client.withdraw(&bob, &proof, &pub_signals), vec![&env]);
+    assert_eq!(
+        client.try_can_withdraw(&proof, &pub_signals),
+        Err(Ok(Error::InsufficientBalance))
+    );
+}
+
 #[test]
 fn test_deposit_and_withdraw_wrong_proof() {
     let env = Env::default();
@@ -407,14 +663,7 @@ fn test_deposit_and_withdraw_wrong_proof() {
     assert_eq!(token_client.balance(&alice), 1000000000);
 
     // Test deposit
-    let commitment = BytesN::from_array(
-        &env,
-        &[
-            0x10, 0xcb, 0x63, 0x1d, 0x17, 0x4a, 0x98, 0xb2, 0x44, 0x0b, 0x68, 0xd2, 0xe5, 0x7d,
-            0xa2, 0xae, 0x9a, 0x13, 0xf7, 0xd1, 0xcc, 0xcb, 0x1f, 0x41, 0xa1, 0xdd, 0x3d, 0x69,
-            0xa2, 0x2f, 0xaa, 0xe9,
-        ],
-    );
+    let commitment = fixture_commitment(&env);
 
     // Mock authentication for alice
     env.mock_all_auths();
@@ -426,14 +675,7 @@ fn test_deposit_and_withdraw_wrong_proof() {
     assert_eq!(commitments.get(0).unwrap(), commitment);
 
     // Set association root to match the erroneous pub signals
-    let association_root = BytesN::from_array(
-        &env,
-        &[
-            0x5d, 0x58, 0x26, 0xf9, 0xc9, 0x18, 0x7b, 0xdb, 0x21, 0x3f, 0x01, 0xde, 0xd6, 0xd2,
-            0x30, 0xe9, 0xf1, 0xab, 0x65, 0x3b, 0x5b, 0xee, 0x60, 0x36, 0x50, 0x4e, 0x82, 0xbc,
-            0x07, 0x16, 0xba, 0xa2,
-        ],
-    );
+    let association_root = fixture_association_root(&env);
     env.mock_all_auths();
     client.set_association_root(&admin, &association_root);
 
@@ -462,14 +704,7 @@ fn test_withdraw_insufficient_balance() {
     let client = PrivacyPoolsContractClient::new(&env, &contract_id);
 
     // Set association root to match the proof
-    let association_root = BytesN::from_array(
-        &env,
-        &[
-            0x5d, 0x58, 0x26, 0xf9, 0xc9, 0x18, 0x7b, 0xdb, 0x21, 0x3f, 0x01, 0xde, 0xd6, 0xd2,
-            0x30, 0xe9, 0xf1, 0xab, 0x65, 0x3b, 0x5b, 0xee, 0x60, 0x36, 0x50, 0x4e, 0x82, 0xbc,
-            0x07, 0x16, 0xba, 0xa2,
-        ],
-    );
+    let association_root = fixture_association_root(&env);
     env.mock_all_auths();
     client.set_association_root(&admin, &association_root);
 
@@ -504,26 +739,12 @@ fn test_reuse_nullifier() {
     token_client.mint(&alice, &1000000000);
 
     // Deposit
-    let commitment = BytesN::from_array(
-        &env,
-        &[
-            0x10, 0xcb, 0x63, 0x1d, 0x17, 0x4a, 0x98, 0xb2, 0x44, 0x0b, 0x68, 0xd2, 0xe5, 0x7d,
-            0xa2, 0xae, 0x9a, 0x13, 0xf7, 0xd1, 0xcc, 0xcb, 0x1f, 0x41, 0xa1, 0xdd, 0x3d, 0x69,
-            0xa2, 0x2f, 0xaa, 0xe9,
-        ],
-    );
+    let commitment = fixture_commitment(&env);
     env.mock_all_auths();
     client.deposit(&alice, &commitment);
 
     // Set association root to match the proof
-    let association_root = BytesN::from_array(
-        &env,
-        &[
-            0x5d, 0x58, 0x26, 0xf9, 0xc9, 0x18, 0x7b, 0xdb, 0x21, 0x3f, 0x01, 0xde, 0xd6, 0xd2,
-            0x30, 0xe9, 0xf1, 0xab, 0x65, 0x3b, 0x5b, 0xee, 0x60, 0x36, 0x50, 0x4e, 0x82, 0xbc,
-            0x07, 0x16, 0xba, 0xa2,
-        ],
-    );
+    let association_root = fixture_association_root(&env);
     env.mock_all_auths();
     client.set_association_root(&admin, &association_root);
 
@@ -538,10 +759,11 @@ fn test_reuse_nullifier() {
     let nullifiers = client.get_nullifiers();
     assert_eq!(nullifiers.len(), 1);
 
-    // Attempt to reuse nullifier - should fail even though contract has no balance
-    // The balance check comes first, so we need to add balance to reach the nullifier check
+    // Attempt to reuse nullifier - the balance check comes first, so another
+    // deposit is needed to reach the nullifier check
     env.mock_all_auths();
-    token_client.mint(&contract_id, &1000000000); // Add balance directly to contract
+    token_client.mint(&alice, &1000000000);
+    client.deposit(&alice, &BytesN::from_array(&env, &[1u8; 32]));
 
     // Now try to withdraw again with the same proof
     env.mock_all_auths();
@@ -552,6 +774,180 @@ fn test_reuse_nullifier() {
     );
 }
 
+#[test]
+fn test_donation_only_counts_in_token_balance_mode() {
+    for token_balance_mode in [false, true] {
+        let env = Env::default();
+        let (token_id, contract_id, admin) = setup_test_environment(&env);
+        let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+        let token_client = MockTokenClient::new(&env, &token_id);
+        env.mock_all_auths();
+
+        let alice = Address::generate(&env);
+        if token_balance_mode {
+            let result = client.try_enable_token_balance_mode(&alice);
+            assert_eq!(result, Err(Ok(Error::OnlyAdmin)));
+            client.enable_token_balance_mode(&admin);
+        }
+        assert_eq!(client.uses_token_balance(), token_balance_mode);
+
+        prepare_fixture_withdrawal(&env, &client, &token_id, &admin);
+
+        let bob = Address::generate(&env);
+        let result = client.withdraw(&bob, &init_proof(&env), &init_pub_signals(&env));
+        assert_eq!(result, vec![&env]);
+        assert_eq!(client.get_pool_balance(), 0);
+
+        // A transfer straight to the contract, outside of `deposit`
+        token_client.mint(&contract_id, &1000000000);
+        assert_eq!(client.get_balance(), 1000000000);
+
+        let result = client.withdraw(&bob, &init_proof(&env), &init_pub_signals(&env));
+        if token_balance_mode {
+            // The donation passes the balance check; only the spent nullifier stops it
+            assert_eq!(client.get_pool_balance(), 1000000000);
+            assert_eq!(
+                result,
+                vec![&env, String::from_str(&env, ERROR_NULLIFIER_USED)]
+            );
+        } else {
+            // The counter only knows about the one deposit, which was withdrawn
+            assert_eq!(client.get_pool_balance(), 0);
+            assert_eq!(
+                result,
+                vec![&env, String::from_str(&env, ERROR_INSUFFICIENT_BALANCE)]
+            );
+        }
+        assert_eq!(token_client.balance(&bob), 1000000000);
+    }
+}
+
+/// Fixture signals with the nullifier hash replaced by `nullifier_hash`
+fn pub_signals_with_nullifier(env: &Env, nullifier_hash: U256) -> Bytes {
+    // Spliced in as bytes, since building an `Fr` may reduce it modulo `r`
+    let fixture = init_pub_signals(env);
+    let start = 5 + PUBLIC_SIGNAL_LAYOUT.nullifier_hash * 32;
+    let mut pub_signals = fixture.slice(..start);
+    pub_signals.append(&nullifier_hash.to_be_bytes());
+    pub_signals.append(&fixture.slice(start + 32..));
+    pub_signals
+}
+
+#[test]
+fn test_withdraw_rejects_non_canonical_nullifier_hash() {
+    let env = Env::default();
+    let (token_id, contract_id, admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+
+    let bob = Address::generate(&env);
+    env.mock_all_auths();
+    prepare_fixture_withdrawal(&env, &client, &token_id, &admin);
+    // A second coin keeps the pool funded after the real spend below
+    let alice = Address::generate(&env);
+    token_client.mint(&alice, &1000000000);
+    client.deposit(&alice, &BytesN::from_array(&env, &[1u8; 32]));
+
+    let modulus = U256::from_be_bytes(&env, &Bytes::from_array(&env, &FIELD_MODULUS));
+    let fixture = PublicSignals::from_bytes(&env, &init_pub_signals(&env))
+        .unwrap()
+        .pub_signals;
+    let nullifier_hash = fixture
+        .get(PUBLIC_SIGNAL_LAYOUT.nullifier_hash)
+        .unwrap()
+        .to_u256();
+    let aliased = pub_signals_with_nullifier(&env, nullifier_hash.add(&modulus));
+    let invalid_field_element = vec![
+        &env,
+        String::from_str(&env, "Value is not below the field modulus"),
+    ];
+
+    // The fixture's nullifier shifted by the modulus is refused before the proof is checked
+    let result = client.withdraw(&bob, &init_proof(&env), &aliased);
+    assert_eq!(result, invalid_field_element);
+    assert_eq!(client.get_nullifiers().len(), 0);
+
+    // r - 1 is still canonical; it only fails because the proof doesn't cover it
+    let near_modulus = modulus.sub(&U256::from_u32(&env, 1));
+    let result = client.withdraw(
+        &bob,
+        &init_proof(&env),
+        &pub_signals_with_nullifier(&env, near_modulus),
+    );
+    assert_eq!(
+        result,
+        vec![&env, String::from_str(&env, ERROR_COIN_OWNERSHIP_PROOF)]
+    );
+
+    // After the real spend the alias still can't be used for a second withdrawal
+    let result = client.withdraw(&bob, &init_proof(&env), &init_pub_signals(&env));
+    assert_eq!(result, vec![&env]);
+    let result = client.withdraw(&bob, &init_proof(&env), &aliased);
+    assert_eq!(result, invalid_field_element);
+    assert_eq!(client.get_nullifiers().len(), 1);
+    assert_eq!(token_client.balance(&bob), 1000000000);
+}
+
+#[test]
+fn test_withdraw_and_call() {
+    let env = Env::default();
+    let (token_id, contract_id, admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+    let receiver_id = env.register(MockReceiver, ());
+    let receiver_client = MockReceiverClient::new(&env, &receiver_id);
+
+    env.mock_all_auths();
+    prepare_fixture_withdrawal(&env, &client, &token_id, &admin);
+
+    let proof = init_proof(&env);
+    let pub_signals = init_pub_signals(&env);
+
+    // A failing callback reverts the whole withdrawal
+    let result = client.try_withdraw_and_call(
+        &receiver_id,
+        &receiver_id,
+        &Symbol::new(&env, "fail"),
+        &vec![&env],
+        &proof,
+        &pub_signals,
+    );
+    assert!(result.is_err());
+    assert_eq!(client.get_nullifiers().len(), 0);
+    assert_eq!(client.get_pool_balance(), 1000000000);
+    assert_eq!(token_client.balance(&contract_id), 1000000000);
+    assert_eq!(token_client.balance(&receiver_id), 0);
+
+    // A rejected proof returns the status without calling the target
+    let result = client.withdraw_and_call(
+        &receiver_id,
+        &receiver_id,
+        &Symbol::new(&env, "on_withdraw"),
+        &vec![&env, 7u32.into_val(&env)],
+        &proof,
+        &init_erronous_pub_signals(&env),
+    );
+    assert_eq!(
+        result,
+        vec![&env, String::from_str(&env, ERROR_COIN_OWNERSHIP_PROOF)]
+    );
+    assert_eq!(receiver_client.tag(), None);
+
+    // A valid proof pays the receiver, then calls it
+    let result = client.withdraw_and_call(
+        &receiver_id,
+        &receiver_id,
+        &Symbol::new(&env, "on_withdraw"),
+        &vec![&env, 7u32.into_val(&env)],
+        &proof,
+        &pub_signals,
+    );
+    assert_eq!(result, vec![&env]);
+    assert_eq!(receiver_client.tag(), Some(7));
+    assert_eq!(token_client.balance(&receiver_id), 1000000000);
+    assert_eq!(client.get_nullifiers().len(), 1);
+}
+
 #[test]
 fn test_contract_initialization() {
     let env = Env::default();
@@ -568,6 +964,8 @@ fn test_contract_initialization() {
     // Verify initial state
     assert_eq!(merkle_depth, 20);
     assert_eq!(commitment_count, 0);
+    assert_eq!(client.get_capacity(), 1 << 20);
+    assert!(!client.is_full());
     assert_eq!(commitments.len(), 0);
     assert_eq!(nullifiers.len(), 0);
 
@@ -576,37 +974,148 @@ fn test_contract_initialization() {
 }
 
 #[test]
-#[should_panic(expected = "Association root must be set before withdrawal")]
-fn test_withdraw_without_association_set() {
+fn test_get_config_reports_pool_parameters() {
     let env = Env::default();
-    let (token_id, contract_id, _admin) = setup_test_environment(&env);
-
-    // Create test addresses
-    let alice = Address::generate(&env);
-    let bob = Address::generate(&env);
-
+    env.cost_estimate().budget().reset_unlimited();
+    let (token_id, contract_id, admin) = setup_test_environment(&env);
     let client = PrivacyPoolsContractClient::new(&env, &contract_id);
-    let token_client = MockTokenClient::new(&env, &token_id);
-
-    // Mint tokens to alice
     env.mock_all_auths();
-    token_client.mint(&alice, &1000000000);
 
-    // Test initial balance
-    assert_eq!(client.get_balance(), 0);
-    assert_eq!(token_client.balance(&alice), 1000000000);
+    let vk_hash: BytesN<32> = env.crypto().sha256(&init_vk(&env)).into();
+    assert_eq!(
+        client.get_config(),
+        PoolConfig {
+            depth: 20,
+            denomination: FIXED_AMOUNT,
+            token: token_id,
+            scope: init_scope(&env),
+            vk_hash,
+            vk_version: 1,
+            root_history_size: DEFAULT_ROOT_HISTORY_SIZE,
+        }
+    );
 
-    // Test deposit - use the same commitment as in our proof
-    let commitment = BytesN::from_array(
-        &env,
-        &[
-            0x10, 0xcb, 0x63, 0x1d, 0x17, 0x4a, 0x98, 0xb2, 0x44, 0x0b, 0x68, 0xd2, 0xe5, 0x7d,
-            0xa2, 0xae, 0x9a, 0x13, 0xf7, 0xd1, 0xcc, 0xcb, 0x1f, 0x41, 0xa1, 0xdd, 0x3d, 0x69,
-            0xa2, 0x2f, 0xaa, 0xe9,
-        ],
+    // Rotating the key changes the hash and version
+    client.set_verification_key(&admin, &swapped_ic_vk(&env), &false);
+    let config = client.get_config();
+    assert_eq!(
+        config.vk_hash,
+        BytesN::from(env.crypto().sha256(&swapped_ic_vk(&env)))
     );
+    assert_eq!(config.vk_version, 2);
+}
 
-    // Mock authentication for alice
+#[test]
+fn test_verification_key_from_json_matches_init_vk() {
+    // `init_vk`'s key, as snarkjs exports it
+    const VK_JSON: &str = r#"{
+        "protocol": "groth16",
+        "curve": "bls12381",
+        "nPublic": 4,
+        "vk_alpha_1": [
+            "2625583050305146829700663917277485398332586266229739236073977691599912239208704058548731458555934906273399977862822",
+            "1155364156944807367912876641032696519500054551629402873339575774959620483194368919563799050765095981406853619398751",
+            "1"
+        ],
+        "vk_beta_2": [
+            ["1659696755509039809248937927616726274238080235224171061036366585278216098417245587200210264410333778948851576160490", "1338363397031837211155983756179787835339490797745307535810204658838394402900152502268197396587061400659003281046656"],
+            ["1974652615426136516341494326987376616840373177388374023461177997087381634383568759591087499459321812809521924259354", "3301884318087924474550898163462840036865878131635519297186391370517333773367262804074867347346141727012544462046142"],
+            ["1", "0"]
+        ],
+        "vk_gamma_2": [
+            ["352701069587466618187139116011060144890029952792775240219908644239793785735715026873347600343865175952761926303160", "3059144344244213709971259814753781636986470325476647558659373206291635324768958432433509563104347017837885763365758"],
+            ["1985150602287291935568054521177171638300868978215655730859378665066344726373823718423869104263333984641494340347905", "927553665492332455747201965776037880757740193453592970025027978793976877002675564980949289727957565575433344219582"],
+            ["1", "0"]
+        ],
+        "vk_delta_2": [
+            ["2750191744467054372912942146482544263484467550244832445881626112777617723646810063952263428512022936903253267127350", "2413234737575312815700598631122026291319065432043412800839944397857332202830802685415923770088689063622756702939375"],
+            ["1076967202486993406108941342102174843689250913208763125383730107292668137282535239225119066564005251774661400843821", "784091089348445241891924627629031628871298938526420228496183038286414003726447208549611976928427786617444752683904"],
+            ["1", "0"]
+        ],
+        "IC": [
+            ["1931769351244036379618100283994844046485312882458040431401676712058257124546097756332532237907637132315648906217636", "2219462221684288788247757134332962645470083865115055927456187574960992952094314940257753501443104606354496083113203", "1"],
+            ["2726325242623221693388802248110816107554759305800882344642286106642968529507795071709947858512355148550879270019178", "2690452834591447292232392438454117662004701691035040250634864436657178120453111433393322306334324558619029220405511", "1"],
+            ["2276753520377413052133204619264853734926027674320220733263964937413806530791610300908525130874383991218501161443629", "2216565042994647061456742959690979278824752277479734731836503122505090074006677407948960110633236603228440758211011", "1"],
+            ["2054702829658916052030239062784122350883101497414801284378548048954817335805733517964277882891682327579038641542963", "1861299377849520465661244108949779781960526739720579329803172490216038156998919390163110860296739149427635782605232", "1"],
+            ["2856004998221708121377069305149495649378668245327503671752831152976814973551962498318427356938380464598719642329610", "3445052445376607662168014620609501339582857414982758608624858423598446194176241135586201569345644453045853894315946", "1"]
+        ]
+    }"#;
+
+    let env = Env::default();
+    let vk = VerificationKey::from_json(&env, VK_JSON).unwrap();
+    assert_eq!(vk.ic.len(), 5);
+    assert_eq!(vk.to_bytes(&env), init_vk(&env));
+}
+
+#[test]
+fn test_proof_and_public_signals_from_json_match_fixtures() {
+    // `init_proof` and `init_pub_signals`, as snarkjs exports them
+    const PROOF_JSON: &str = r#"{
+        "pi_a": [
+            "212441980386531387965179969427761278516713032280181090947921812756826140060407715059887577334725859148245723641819",
+            "1043777624107376029707768486456740992720703652869770475160608327115557016215114376228813623265706726948422658129414",
+            "1"
+        ],
+        "pi_b": [
+            ["3418594862087761466119736619773903243566736312162156790303713071542564953050210637510854068742634808271915139642232", "2964213444574507673113570038406470453416167035798707123655994180726336395671895988634516761061394366777882421458970"],
+            ["3223650775040459204413178680640740880187469067260410489432422220219327812050544783645893434878446219624098341623090", "2459594096752687436760263121473341283140767398180854423130454432176129735618526991834231434439933241851791233993087"],
+            ["1", "0"]
+        ],
+        "pi_c": [
+            "443107262259769407693822320108000100156551631812684950355991461785927550068576958619659870180972229362608557133163",
+            "2104019945288105000027262551270879368920664217362082679701219406216434095932547212599338209378889102523865669374434",
+            "1"
+        ],
+        "protocol": "groth16",
+        "curve": "bls12381"
+    }"#;
+    const PUBLIC_JSON: &str = r#"[
+        "34247368358276748935470155212237028565861472679392865206076749426239931261485",
+        "1000000000",
+        "33610852770702921318594127247451689166620844611969545962852654412220930601660",
+        "42220846460510012783259546685177279211166460034342051256640265180289181334178"
+    ]"#;
+
+    let env = Env::default();
+    let proof = Proof::from_json(&env, PROOF_JSON).unwrap();
+    assert_eq!(proof.to_bytes(&env), init_proof(&env));
+    let pub_signals = PublicSignals::from_json(&env, PUBLIC_JSON).unwrap();
+    assert_eq!(pub_signals.to_bytes(&env), init_pub_signals(&env));
+
+    // Signals are not reduced modulo the field
+    let out_of_range =
+        r#"["52435875175126190479447740508185965837690552500527637822603658699938581184513"]"#;
+    assert_eq!(
+        PublicSignals::from_json(&env, out_of_range).err(),
+        Some(zk::SnarkjsError::InvalidScalar("public[0]".into()))
+    );
+}
+
+#[test]
+#[should_panic(expected = "Association root must be set before withdrawal")]
+fn test_withdraw_without_association_set() {
+    let env = Env::default();
+    let (token_id, contract_id, _admin) = setup_test_environment(&env);
+
+    // Create test addresses
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+
+    // Mint tokens to alice
+    env.mock_all_auths();
+    token_client.mint(&alice, &1000000000);
+
+    // Test initial balance
+    assert_eq!(client.get_balance(), 0);
+    assert_eq!(token_client.balance(&alice), 1000000000);
+
+    // Test deposit - use the same commitment as in our proof
+    let commitment = fixture_commitment(&env);
+
+    // Mock authentication for alice
     env.mock_all_auths();
     client.deposit(&alice, &commitment);
 
@@ -657,14 +1166,7 @@ fn test_withdraw_association_root_mismatch() {
     assert_eq!(token_client.balance(&alice), 1000000000);
 
     // Test deposit - use the same commitment as in our proof
-    let commitment = BytesN::from_array(
-        &env,
-        &[
-            0x10, 0xcb, 0x63, 0x1d, 0x17, 0x4a, 0x98, 0xb2, 0x44, 0x0b, 0x68, 0xd2, 0xe5, 0x7d,
-            0xa2, 0xae, 0x9a, 0x13, 0xf7, 0xd1, 0xcc, 0xcb, 0x1f, 0x41, 0xa1, 0xdd, 0x3d, 0x69,
-            0xa2, 0x2f, 0xaa, 0xe9,
-        ],
-    );
+    let commitment = fixture_commitment(&env);
 
     // Mock authentication for alice
     env.mock_all_auths();
@@ -730,14 +1232,7 @@ fn test_set_association_root_non_admin() {
     let non_admin = Address::generate(&env);
 
     // Create a test association root
-    let association_root = BytesN::from_array(
-        &env,
-        &[
-            0x5d, 0x58, 0x26, 0xf9, 0xc9, 0x18, 0x7b, 0xdb, 0x21, 0x3f, 0x01, 0xde, 0xd6, 0xd2,
-            0x30, 0xe9, 0xf1, 0xab, 0x65, 0x3b, 0x5b, 0xee, 0x60, 0x36, 0x50, 0x4e, 0x82, 0xbc,
-            0x07, 0x16, 0xba, 0xa2,
-        ],
-    );
+    let association_root = fixture_association_root(&env);
 
     // Mock authentication for the non-admin user
     env.mock_all_auths();
@@ -782,14 +1277,7 @@ fn test_withdraw_requires_association_root() {
     token_client.mint(&alice, &1000000000);
 
     // Test deposit
-    let commitment = BytesN::from_array(
-        &env,
-        &[
-            0x10, 0xcb, 0x63, 0x1d, 0x17, 0x4a, 0x98, 0xb2, 0x44, 0x0b, 0x68, 0xd2, 0xe5, 0x7d,
-            0xa2, 0xae, 0x9a, 0x13, 0xf7, 0xd1, 0xcc, 0xcb, 0x1f, 0x41, 0xa1, 0xdd, 0x3d, 0x69,
-            0xa2, 0x2f, 0xaa, 0xe9,
-        ],
-    );
+    let commitment = fixture_commitment(&env);
 
     // Mock authentication for alice
     env.mock_all_auths();
@@ -814,3 +1302,2046 @@ fn test_withdraw_requires_association_root() {
     env.mock_all_auths();
     client.withdraw(&bob, &proof, &pub_signals);
 }
+
+#[test]
+fn test_deposit_rejected_when_pool_full() {
+    let env = Env::default();
+    let (token_id, contract_id, _admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+
+    // Shrink the stored tree to depth 1 and fill both leaves
+    env.as_contract(&contract_id, || {
+        let mut tree = LeanIMT::new(&env, 1);
+        tree.insert_u64(1).unwrap();
+        tree.insert_u64(2).unwrap();
+        let (leaves, depth, root) = tree.to_storage();
+        env.storage().instance().set(&TREE_LEAVES_KEY, &leaves);
+        env.storage().instance().set(&TREE_DEPTH_KEY, &depth);
+        env.storage().instance().set(&TREE_ROOT_KEY, &root);
+    });
+    let root_before = client.get_merkle_root();
+    assert_eq!(client.get_capacity(), 2);
+    assert!(client.is_full());
+
+    let alice = Address::generate(&env);
+    env.mock_all_auths();
+    token_client.mint(&alice, &1000000000);
+
+    let commitment = BytesN::from_array(&env, &[7u8; 32]);
+    let result = client.try_deposit(&alice, &commitment);
+    assert_eq!(result, Err(Ok(Error::TreeAtCapacity)));
+
+    // Neither funds nor tree state moved
+    assert_eq!(token_client.balance(&alice), 1000000000);
+    assert_eq!(client.get_balance(), 0);
+    assert_eq!(client.get_commitment_count(), 2);
+    assert_eq!(client.get_merkle_root(), root_before);
+}
+
+#[test]
+fn test_missing_tree_depth_is_not_read_as_zero() {
+    let env = Env::default();
+    let (token_id, contract_id, _admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+    assert_eq!(client.get_merkle_depth(), 20);
+
+    // Storage that lost its depth, as after an incomplete migration
+    env.as_contract(&contract_id, || {
+        env.storage().instance().remove(&TREE_DEPTH_KEY);
+    });
+
+    let alice = Address::generate(&env);
+    env.mock_all_auths();
+    token_client.mint(&alice, &1000000000);
+
+    // A depth-0 tree would have accepted this deposit as its only leaf
+    let commitment = BytesN::from_array(&env, &[7u8; 32]);
+    assert!(client.try_deposit(&alice, &commitment).is_err());
+    assert!(client.try_get_merkle_depth().is_err());
+    assert!(client.try_get_capacity().is_err());
+    assert_eq!(client.get_commitment_count(), 0);
+    assert_eq!(token_client.balance(&alice), 1000000000);
+}
+
+#[test]
+fn test_verify_tree_detects_corrupted_root() {
+    let env = Env::default();
+    let (token_id, contract_id, _admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+    assert!(client.verify_tree());
+
+    env.mock_all_auths();
+    for byte in 1..=3u8 {
+        let depositor = Address::generate(&env);
+        token_client.mint(&depositor, &1000000000);
+        client.deposit(&depositor, &BytesN::from_array(&env, &[byte; 32]));
+    }
+    assert!(client.verify_tree());
+
+    // A root left over from before the last deposit, as after a bad manual edit
+    let root = client.get_merkle_root();
+    let stale = client.get_root_history().get(1).unwrap();
+    assert_ne!(stale, root);
+    env.as_contract(&contract_id, || {
+        env.storage().instance().set(&TREE_ROOT_KEY, &stale);
+    });
+    assert!(!client.verify_tree());
+
+    env.as_contract(&contract_id, || {
+        env.storage().instance().set(&TREE_ROOT_KEY, &root);
+    });
+    assert!(client.verify_tree());
+}
+
+#[test]
+#[should_panic(expected = "Tree depth is not initialized")]
+fn test_missing_tree_depth_panics_with_message() {
+    let env = Env::default();
+    let (_token_id, contract_id, _admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    env.as_contract(&contract_id, || {
+        env.storage().instance().remove(&TREE_DEPTH_KEY);
+    });
+    client.get_merkle_depth();
+}
+
+#[test]
+fn test_deposit_rejects_balance_overflow() {
+    let env = Env::default();
+    let (token_id, contract_id, _admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+
+    // One more deposit would carry the balance past i128::MAX
+    let near_max = i128::MAX - FIXED_AMOUNT + 1;
+    env.as_contract(&contract_id, || {
+        env.storage().instance().set(&BALANCE_KEY, &near_max);
+    });
+
+    let alice = Address::generate(&env);
+    env.mock_all_auths();
+    token_client.mint(&alice, &1000000000);
+
+    let result = client.try_deposit(&alice, &BytesN::from_array(&env, &[7u8; 32]));
+    assert_eq!(result, Err(Ok(Error::BalanceOverflow)));
+    assert_eq!(token_client.balance(&alice), 1000000000);
+    assert_eq!(client.get_pool_balance(), near_max);
+    assert_eq!(client.get_commitment_count(), 0);
+
+    // Exactly reaching i128::MAX still fits
+    env.as_contract(&contract_id, || {
+        env.storage().instance().set(&BALANCE_KEY, &(near_max - 1));
+    });
+    client.deposit(&alice, &BytesN::from_array(&env, &[7u8; 32]));
+    assert_eq!(client.get_pool_balance(), i128::MAX);
+}
+
+#[test]
+fn test_deposit_rejects_duplicate_commitment() {
+    let env = Env::default();
+    let (token_id, contract_id, _admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+
+    let alice = Address::generate(&env);
+    env.mock_all_auths();
+    token_client.mint(&alice, &2000000000);
+
+    let commitment = BytesN::from_array(&env, &[7u8; 32]);
+    assert_eq!(client.deposit(&alice, &commitment), 0);
+    let root_before = client.get_merkle_root();
+
+    let result = client.try_deposit(&alice, &commitment);
+    assert_eq!(result, Err(Ok(Error::DuplicateCommitment)));
+
+    // The second deposit stored nothing and moved no funds
+    assert_eq!(client.get_commitment_count(), 1);
+    assert_eq!(client.get_merkle_root(), root_before);
+    assert_eq!(token_client.balance(&alice), 1000000000);
+    assert_eq!(client.get_pool_balance(), 1000000000);
+}
+
+#[test]
+fn test_deposit_rejects_commitment_outside_field() {
+    let env = Env::default();
+    let (token_id, contract_id, _admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+
+    let alice = Address::generate(&env);
+    env.mock_all_auths();
+    token_client.mint(&alice, &2000000000);
+
+    // r + 1 would reduce to the same leaf as 1
+    let mut above = FIELD_MODULUS;
+    above[31] += 1;
+    let result = client.try_deposit(&alice, &BytesN::from_array(&env, &above));
+    assert_eq!(result, Err(Ok(Error::InvalidFieldElement)));
+    let result = client.try_deposit(&alice, &BytesN::from_array(&env, &FIELD_MODULUS));
+    assert_eq!(result, Err(Ok(Error::InvalidFieldElement)));
+    assert_eq!(client.get_commitment_count(), 0);
+    assert_eq!(token_client.balance(&alice), 2000000000);
+
+    // r - 1 is the largest value accepted
+    let mut max = FIELD_MODULUS;
+    max[31] -= 1;
+    assert_eq!(client.deposit(&alice, &BytesN::from_array(&env, &max)), 0);
+}
+
+#[test]
+fn test_deposit_rejects_commitment_aliased_by_field_modulus() {
+    let env = Env::default();
+    let (token_id, contract_id, _admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+
+    let alice = Address::generate(&env);
+    env.mock_all_auths();
+    token_client.mint(&alice, &2000000000);
+
+    // `commitment + r` is a different 32-byte value that becomes the same tree leaf
+    let commitment = BytesN::from_array(&env, &[7u8; 32]);
+    let modulus = U256::from_be_bytes(&env, &Bytes::from_array(&env, &FIELD_MODULUS));
+    let aliased_bytes = U256::from_be_bytes(&env, &commitment.clone().into())
+        .add(&modulus)
+        .to_be_bytes();
+    let aliased: BytesN<32> = aliased_bytes.try_into().unwrap();
+    assert_ne!(aliased, commitment);
+    assert_eq!(
+        fr_to_bytes32(&env, &lean_imt::bytes_to_bls_scalar(&aliased)),
+        fr_to_bytes32(&env, &lean_imt::bytes_to_bls_scalar(&commitment))
+    );
+
+    assert_eq!(client.deposit(&alice, &commitment), 0);
+    let root_before = client.get_merkle_root();
+
+    // Accepting the alias would give one leaf two deposits and so two spendable notes
+    let result = client.try_deposit(&alice, &aliased);
+    assert_eq!(result, Err(Ok(Error::InvalidFieldElement)));
+    assert_eq!(client.get_commitment_count(), 1);
+    assert_eq!(client.get_merkle_root(), root_before);
+    assert_eq!(token_client.balance(&alice), 1000000000);
+    assert_eq!(client.get_pool_balance(), 1000000000);
+
+    // In the other order the alias is rejected before the commitment could collide
+    let other = BytesN::from_array(&env, &[9u8; 32]);
+    let other_aliased: BytesN<32> = U256::from_be_bytes(&env, &other.clone().into())
+        .add(&modulus)
+        .to_be_bytes()
+        .try_into()
+        .unwrap();
+    let result = client.try_deposit(&alice, &other_aliased);
+    assert_eq!(result, Err(Ok(Error::InvalidFieldElement)));
+    assert_eq!(client.deposit(&alice, &other), 1);
+}
+
+#[test]
+fn test_paused_pool_rejects_deposits() {
+    let env = Env::default();
+    let (token_id, contract_id, admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+
+    let alice = Address::generate(&env);
+    env.mock_all_auths();
+    token_client.mint(&alice, &2000000000);
+
+    assert!(!client.deposits_paused());
+    let result = client.try_set_deposits_paused(&alice, &true);
+    assert_eq!(result, Err(Ok(Error::OnlyAdmin)));
+    client.set_deposits_paused(&admin, &true);
+    assert!(client.deposits_paused());
+
+    let commitment = BytesN::from_array(&env, &[7u8; 32]);
+    let result = client.try_deposit(&alice, &commitment);
+    assert_eq!(result, Err(Ok(Error::Paused)));
+    let result = client.try_deposit_structured(
+        &alice,
+        &FIXED_AMOUNT,
+        &BytesN::from_array(&env, &[1u8; 32]),
+        &BytesN::from_array(&env, &[2u8; 32]),
+    );
+    assert_eq!(result, Err(Ok(Error::Paused)));
+    assert_eq!(client.get_commitment_count(), 0);
+    assert_eq!(token_client.balance(&alice), 2000000000);
+
+    client.set_deposits_paused(&admin, &false);
+    assert_eq!(client.deposit(&alice, &commitment), 0);
+}
+
+#[test]
+fn test_pools_keep_separate_state() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+    let (token_id, contract_id, admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    env.mock_all_auths();
+
+    // A second pool for another token
+    let other_token_id = env.register(MockToken, ());
+    let other_token = MockTokenClient::new(&env, &other_token_id);
+    other_token.initialize(
+        &Address::generate(&env),
+        &7u32,
+        &String::from_str(&env, "Other Token"),
+        &String::from_str(&env, "OTHER"),
+    );
+    let scope = BytesN::from_array(&env, &[3u8; 32]);
+    let result = client.try_create_pool(
+        &Address::generate(&env),
+        &1,
+        &other_token_id,
+        &scope,
+        &FIXED_AMOUNT,
+        &None,
+    );
+    assert_eq!(result, Err(Ok(Error::OnlyAdmin)));
+    let result = client.try_create_pool(
+        &admin,
+        &DEFAULT_POOL,
+        &other_token_id,
+        &scope,
+        &FIXED_AMOUNT,
+        &None,
+    );
+    assert_eq!(result, Err(Ok(Error::PoolExists)));
+    assert!(!client.has_pool(&1));
+    client.create_pool(&admin, &1, &other_token_id, &scope, &FIXED_AMOUNT, &None);
+    assert!(client.has_pool(&1));
+    let result = client.try_create_pool(&admin, &1, &other_token_id, &scope, &FIXED_AMOUNT, &None);
+    assert_eq!(result, Err(Ok(Error::PoolExists)));
+
+    let config = client.get_pool_config(&1);
+    assert_eq!(config.token, other_token_id);
+    assert_eq!(config.scope, scope);
+    assert_eq!(config.depth, client.get_config().depth);
+    assert_eq!(client.get_pool_merkle_root(&1), client.get_merkle_root());
+
+    // The fixture coin, deposited into pool 1 only
+    let alice = Address::generate(&env);
+    other_token.mint(&alice, &1000000000);
+    let commitment = fixture_commitment(&env);
+    let result = client.try_deposit_to_pool(&2, &alice, &commitment);
+    assert_eq!(result, Err(Ok(Error::UnknownPool)));
+    assert_eq!(client.deposit_to_pool(&1, &alice, &commitment), 0);
+    assert_eq!(client.get_pool_commitments(&1), vec![&env, commitment]);
+    assert_eq!(client.get_pool_deposits(&1), 1000000000);
+    assert_eq!(client.get_commitment_count(), 0);
+    assert_eq!(client.get_pool_balance(), 0);
+    assert_ne!(client.get_pool_merkle_root(&1), client.get_merkle_root());
+
+    // Association roots are per pool too
+    let association_root = fixture_association_root(&env);
+    client.set_pool_association_root(&admin, &1, &association_root);
+    assert!(!client.has_association_set());
+    client.set_association_root(&admin, &association_root);
+
+    // The default pool never saw the coin, so its roots reject the proof
+    let bob = Address::generate(&env);
+    let proof = init_proof(&env);
+    let pub_signals = init_pub_signals(&env);
+    let carol = Address::generate(&env);
+    MockTokenClient::new(&env, &token_id).mint(&carol, &1000000000);
+    client.deposit(&carol, &BytesN::from_array(&env, &[7u8; 32]));
+    let result = client.withdraw(&bob, &proof, &pub_signals);
+    assert_eq!(
+        result,
+        vec![&env, String::from_str(&env, ERROR_COIN_OWNERSHIP_PROOF)]
+    );
+
+    let result = client.withdraw_from_pool(&2, &bob, &proof, &pub_signals);
+    assert_eq!(
+        result,
+        vec![&env, String::from_str(&env, Error::UnknownPool.message())]
+    );
+    let result = client.withdraw_from_pool(&1, &bob, &proof, &pub_signals);
+    assert_eq!(result, vec![&env]);
+    assert_eq!(other_token.balance(&bob), 1000000000);
+    assert_eq!(client.get_pool_deposits(&1), 0);
+    assert!(client.get_nullifiers().is_empty());
+
+    // Another coin keeps the pool funded, so the replay reaches the nullifier check
+    other_token.mint(&carol, &1000000000);
+    client.deposit_to_pool(&1, &carol, &BytesN::from_array(&env, &[8u8; 32]));
+    let result = client.withdraw_from_pool(&1, &bob, &proof, &pub_signals);
+    assert_eq!(
+        result,
+        vec![&env, String::from_str(&env, ERROR_NULLIFIER_USED)]
+    );
+}
+
+/// The fixture key with two `ic` points swapped: well-formed, but the fixture proof
+/// no longer verifies against it
+fn swapped_ic_vk(env: &Env) -> Bytes {
+    let mut vk = VerificationKey::from_bytes(env, &init_vk(env)).unwrap();
+    let first = vk.ic.get(1).unwrap();
+    vk.ic.set(1, vk.ic.get(2).unwrap());
+    vk.ic.set(2, first);
+    vk.to_bytes(env)
+}
+
+fn register_migrated_pool(
+    env: &Env,
+    token_id: &Address,
+    admin: &Address,
+    commitments: Vec<BytesN<32>>,
+    nullifiers: Vec<BytesN<32>>,
+) -> Address {
+    env.register(
+        PrivacyPoolsContract,
+        (
+            init_vk(env),
+            token_id.clone(),
+            admin.clone(),
+            init_scope(env),
+            FIXED_AMOUNT,
+            PoolOptions {
+                initial_commitments: Some(commitments),
+                initial_nullifiers: Some(nullifiers),
+                ..pool_options()
+            },
+        ),
+    )
+}
+
+#[test]
+fn test_constructor_imports_commitments_and_nullifiers() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+    let (token_id, contract_id, admin) = setup_test_environment(&env);
+    let old_client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    env.mock_all_auths();
+    prepare_fixture_withdrawal(&env, &old_client, &token_id, &admin);
+    let association_root = old_client.get_association_root();
+
+    let bob = Address::generate(&env);
+    let pub_signals = init_pub_signals(&env);
+    let pub_signals_struct = PublicSignals::from_bytes(&env, &pub_signals).unwrap();
+    let nullifier = fr_to_bytes32(&env, &pub_signals_struct.pub_signals.get(0).unwrap());
+
+    // The migrated tree has the old root, so existing proofs still verify
+    let migrated_id = register_migrated_pool(
+        &env,
+        &token_id,
+        &admin,
+        old_client.get_commitments(),
+        vec![&env],
+    );
+    let migrated = PrivacyPoolsContractClient::new(&env, &migrated_id);
+    assert_eq!(migrated.get_merkle_root(), old_client.get_merkle_root());
+    assert_eq!(migrated.get_commitments(), old_client.get_commitments());
+    assert_eq!(migrated.get_pool_balance(), FIXED_AMOUNT);
+    MockTokenClient::new(&env, &token_id).mint(&migrated_id, &FIXED_AMOUNT);
+    migrated.set_association_root(&admin, &association_root);
+    assert_eq!(
+        migrated.withdraw(&bob, &init_proof(&env), &pub_signals),
+        vec![&env]
+    );
+    assert_eq!(migrated.get_pool_balance(), 0);
+
+    // An imported nullifier is already spent
+    let spent_id = register_migrated_pool(
+        &env,
+        &token_id,
+        &admin,
+        old_client.get_commitments(),
+        vec![&env, nullifier.clone()],
+    );
+    let spent = PrivacyPoolsContractClient::new(&env, &spent_id);
+    assert_eq!(spent.get_nullifiers(), vec![&env, nullifier]);
+    assert_eq!(spent.get_pool_balance(), 0);
+
+    // A later deposit funds the pool, but the imported coin stays spent
+    let carol = Address::generate(&env);
+    MockTokenClient::new(&env, &token_id).mint(&carol, &FIXED_AMOUNT);
+    spent.deposit(&carol, &BytesN::from_array(&env, &[1u8; 32]));
+    spent.set_association_root(&admin, &association_root);
+    assert_eq!(
+        spent.withdraw(&bob, &init_proof(&env), &pub_signals),
+        vec![&env, String::from_str(&env, ERROR_NULLIFIER_USED)]
+    );
+}
+
+#[test]
+#[should_panic(expected = "Duplicate initial commitment")]
+fn test_constructor_rejects_duplicate_initial_commitments() {
+    let env = Env::default();
+    let (token_id, _, admin) = setup_test_environment(&env);
+    let commitment = BytesN::from_array(&env, &[1u8; 32]);
+    register_migrated_pool(
+        &env,
+        &token_id,
+        &admin,
+        vec![&env, commitment.clone(), commitment],
+        vec![&env],
+    );
+}
+
+#[test]
+#[should_panic(expected = "Initial nullifier is not below the field modulus")]
+fn test_constructor_rejects_non_canonical_initial_nullifier() {
+    let env = Env::default();
+    let (token_id, _, admin) = setup_test_environment(&env);
+    register_migrated_pool(
+        &env,
+        &token_id,
+        &admin,
+        vec![&env, BytesN::from_array(&env, &[1u8; 32])],
+        vec![&env, BytesN::from_array(&env, &[0xffu8; 32])],
+    );
+}
+
+#[test]
+fn test_set_verification_key_keeps_previous_key() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+    let (token_id, contract_id, admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    env.mock_all_auths();
+    prepare_fixture_withdrawal(&env, &client, &token_id, &admin);
+
+    assert_eq!(client.get_vk_version(), 1);
+    assert_eq!(
+        client.try_set_verification_key(&Address::generate(&env), &swapped_ic_vk(&env), &true),
+        Err(Ok(Error::OnlyAdmin))
+    );
+    assert_eq!(
+        client.set_verification_key(&admin, &swapped_ic_vk(&env), &true),
+        2
+    );
+    assert_eq!(client.get_vk_version(), 2);
+
+    // A proof for the old circuit is still accepted through the previous key
+    let bob = Address::generate(&env);
+    let pub_signals = init_pub_signals(&env);
+    let pub_signals_struct = PublicSignals::from_bytes(&env, &pub_signals).unwrap();
+    let nullifier = fr_to_bytes32(&env, &pub_signals_struct.pub_signals.get(0).unwrap());
+    assert_eq!(client.get_withdrawal_vk_version(&nullifier), None);
+    assert_eq!(
+        client.withdraw(&bob, &init_proof(&env), &pub_signals),
+        vec![&env]
+    );
+    assert_eq!(client.get_withdrawal_vk_version(&nullifier), Some(1));
+
+    // Recorded per nullifier in persistent storage, like the withdrawal root
+    let key = DataKey::WithdrawalVk(DEFAULT_POOL, nullifier);
+    assert_eq!(
+        persistent_ttl(&env, &contract_id, &key),
+        INSTANCE_TTL_EXTEND_TO
+    );
+}
+
+#[test]
+fn test_set_verification_key_without_previous_rejects_old_proofs() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+    let (token_id, contract_id, admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    env.mock_all_auths();
+    prepare_fixture_withdrawal(&env, &client, &token_id, &admin);
+
+    let bob = Address::generate(&env);
+    let proof = init_proof(&env);
+    let pub_signals = init_pub_signals(&env);
+
+    // Keeping the previous key only reaches one rotation back
+    client.set_verification_key(&admin, &swapped_ic_vk(&env), &true);
+    client.set_verification_key(&admin, &swapped_ic_vk(&env), &false);
+    assert_eq!(client.get_vk_version(), 3);
+    assert_eq!(
+        client.withdraw(&bob, &proof, &pub_signals),
+        vec![&env, String::from_str(&env, ERROR_COIN_OWNERSHIP_PROOF)]
+    );
+
+    // Rotating back to the fixture key accepts the proof under the new version
+    client.set_verification_key(&admin, &init_vk(&env), &false);
+    assert_eq!(client.withdraw(&bob, &proof, &pub_signals), vec![&env]);
+    let pub_signals_struct = PublicSignals::from_bytes(&env, &pub_signals).unwrap();
+    let nullifier = fr_to_bytes32(&env, &pub_signals_struct.pub_signals.get(0).unwrap());
+    assert_eq!(client.get_withdrawal_vk_version(&nullifier), Some(4));
+}
+
+/// CPU instruction ceilings for `test_deposit_and_withdraw_instruction_budget` and
+/// `test_deposit_cost_does_not_grow_with_the_tree`
+///
+/// Measured with the native test build (not Wasm, so VM costs are excluded), plus about
+/// 20% headroom. A change that trips these, like re-hashing the whole tree on load, has
+/// to justify the new cost before the ceiling is raised.
+const MAX_DEPOSIT_INSTRUCTIONS: i64 = 52_000_000;
+const MAX_WITHDRAW_INSTRUCTIONS: i64 = 62_000_000;
+
+#[test]
+fn test_deposit_and_withdraw_instruction_budget() {
+    let env = Env::default();
+    let (token_id, contract_id, admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    env.mock_all_auths();
+
+    // Measure a deposit into a non-empty tree; the proof stays valid via the root history
+    prepare_fixture_withdrawal(&env, &client, &token_id, &admin);
+    let alice = Address::generate(&env);
+    MockTokenClient::new(&env, &token_id).mint(&alice, &2000000000);
+    client.deposit(&alice, &BytesN::from_array(&env, &[1u8; 32]));
+    client.deposit(&alice, &BytesN::from_array(&env, &[2u8; 32]));
+    let deposit_instructions = env.cost_estimate().resources().instructions;
+
+    let bob = Address::generate(&env);
+    client.withdraw(&bob, &init_proof(&env), &init_pub_signals(&env));
+    let withdraw_instructions = env.cost_estimate().resources().instructions;
+    assert_eq!(client.get_nullifiers().len(), 1);
+
+    std::println!(
+        "deposit: {} instructions (max {}), withdraw: {} instructions (max {})",
+        deposit_instructions,
+        MAX_DEPOSIT_INSTRUCTIONS,
+        withdraw_instructions,
+        MAX_WITHDRAW_INSTRUCTIONS
+    );
+    assert!(deposit_instructions <= MAX_DEPOSIT_INSTRUCTIONS);
+    assert!(withdraw_instructions <= MAX_WITHDRAW_INSTRUCTIONS);
+}
+
+/// Closes `n` ledgers: moves the sequence number forward by `n` and the timestamp by
+/// the matching 5 seconds per ledger
+fn advance_ledgers(env: &Env, n: u32) {
+    env.ledger().with_mut(|ledger| {
+        ledger.sequence_number += n;
+        ledger.timestamp += 5 * n as u64;
+    });
+}
+
+fn instance_ttl(env: &Env, contract_id: &Address) -> u32 {
+    use soroban_sdk::testutils::storage::Instance as _;
+    env.as_contract(contract_id, || env.storage().instance().get_ttl())
+}
+
+fn persistent_ttl(env: &Env, contract_id: &Address, key: &DataKey) -> u32 {
+    use soroban_sdk::testutils::storage::Persistent as _;
+    env.as_contract(contract_id, || env.storage().persistent().get_ttl(key))
+}
+
+#[test]
+fn test_deposit_extends_instance_ttl() {
+    let env = Env::default();
+    let (token_id, contract_id, _admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+
+    let alice = Address::generate(&env);
+    env.mock_all_auths();
+    token_client.mint(&alice, &1000000000);
+
+    assert!(instance_ttl(&env, &contract_id) < INSTANCE_TTL_THRESHOLD);
+    client.deposit(&alice, &BytesN::from_array(&env, &[7u8; 32]));
+    assert_eq!(instance_ttl(&env, &contract_id), INSTANCE_TTL_EXTEND_TO);
+}
+
+#[test]
+fn test_deposit_extends_instance_ttl_only_below_threshold() {
+    let env = Env::default();
+    let (token_id, contract_id, _admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+
+    let alice = Address::generate(&env);
+    env.mock_all_auths();
+    token_client.mint(&alice, &3000000000);
+    client.deposit(&alice, &BytesN::from_array(&env, &[7u8; 32]));
+    assert_eq!(instance_ttl(&env, &contract_id), INSTANCE_TTL_EXTEND_TO);
+
+    // Half a day later the TTL is still above the threshold, so a deposit leaves it be
+    advance_ledgers(&env, DAY_IN_LEDGERS / 2);
+    let ttl = INSTANCE_TTL_EXTEND_TO - DAY_IN_LEDGERS / 2;
+    assert_eq!(instance_ttl(&env, &contract_id), ttl);
+    client.deposit(&alice, &BytesN::from_array(&env, &[8u8; 32]));
+    assert_eq!(instance_ttl(&env, &contract_id), ttl);
+
+    // Another half day and one ledger take it below the threshold
+    advance_ledgers(&env, DAY_IN_LEDGERS / 2 + 1);
+    assert!(instance_ttl(&env, &contract_id) < INSTANCE_TTL_THRESHOLD);
+    client.deposit(&alice, &BytesN::from_array(&env, &[9u8; 32]));
+    assert_eq!(instance_ttl(&env, &contract_id), INSTANCE_TTL_EXTEND_TO);
+}
+
+#[test]
+fn test_extend_ttl_admin_only() {
+    let env = Env::default();
+    let (_token_id, contract_id, admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    env.mock_all_auths();
+
+    let non_admin = Address::generate(&env);
+    let ttl_before = instance_ttl(&env, &contract_id);
+    let result = client.try_extend_ttl(&non_admin, &(2 * INSTANCE_TTL_EXTEND_TO));
+    assert_eq!(result, Err(Ok(Error::OnlyAdmin)));
+    assert_eq!(instance_ttl(&env, &contract_id), ttl_before);
+
+    client.extend_ttl(&admin, &(2 * INSTANCE_TTL_EXTEND_TO));
+    assert_eq!(instance_ttl(&env, &contract_id), 2 * INSTANCE_TTL_EXTEND_TO);
+
+    // Requests beyond the network maximum are capped instead of failing
+    client.extend_ttl(&admin, &u32::MAX);
+    let max_ttl = env.as_contract(&contract_id, || env.storage().max_ttl());
+    assert_eq!(instance_ttl(&env, &contract_id), max_ttl);
+}
+
+#[test]
+fn test_extend_pool_ttl_keeps_idle_created_pool_alive() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+    let (_token_id, contract_id, admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    env.mock_all_auths();
+
+    // Pool 1 holds one spent and one unspent coin, then sees no activity
+    let other_token_id = env.register(MockToken, ());
+    let other_token = MockTokenClient::new(&env, &other_token_id);
+    other_token.initialize(
+        &Address::generate(&env),
+        &7u32,
+        &String::from_str(&env, "Other Token"),
+        &String::from_str(&env, "OTHER"),
+    );
+    let scope = BytesN::from_array(&env, &[3u8; 32]);
+    client.create_pool(&admin, &1, &other_token_id, &scope, &FIXED_AMOUNT, &None);
+    let alice = Address::generate(&env);
+    other_token.mint(&alice, &(2 * FIXED_AMOUNT));
+    client.deposit_to_pool(&1, &alice, &fixture_commitment(&env));
+    client.set_pool_association_root(&admin, &1, &fixture_association_root(&env));
+    let pub_signals = init_pub_signals(&env);
+    let result = client.withdraw_from_pool(
+        &1,
+        &Address::generate(&env),
+        &init_proof(&env),
+        &pub_signals,
+    );
+    assert_eq!(result, vec![&env]);
+    client.deposit_to_pool(&1, &alice, &BytesN::from_array(&env, &[7u8; 32]));
+    let commitments = client.get_pool_commitments(&1);
+    let root = client.get_pool_merkle_root(&1);
+
+    let pub_signals = PublicSignals::from_bytes(&env, &pub_signals).unwrap();
+    let nullifier = fr_to_bytes32(&env, &pub_signals.pub_signals.get(0).unwrap());
+    let keys = [
+        DataKey::Pool(1, TREE_LEAVES_KEY),
+        DataKey::Pool(1, NULL_KEY),
+        DataKey::Pool(1, BALANCE_KEY),
+        DataKey::Pool(1, ASSOCIATION_ROOT_KEY),
+        DataKey::TokenPools(other_token_id),
+        DataKey::WithdrawalRoot(1, nullifier.clone()),
+        DataKey::WithdrawalVk(1, nullifier),
+    ];
+    for key in keys.iter() {
+        assert_eq!(
+            persistent_ttl(&env, &contract_id, key),
+            INSTANCE_TTL_EXTEND_TO
+        );
+    }
+
+    let result =
+        client.try_extend_pool_ttl(&Address::generate(&env), &1, &(2 * INSTANCE_TTL_EXTEND_TO));
+    assert_eq!(result, Err(Ok(Error::OnlyAdmin)));
+    let result = client.try_extend_pool_ttl(&admin, &2, &(2 * INSTANCE_TTL_EXTEND_TO));
+    assert_eq!(result, Err(Ok(Error::UnknownPool)));
+    client.extend_pool_ttl(&admin, &1, &(2 * INSTANCE_TTL_EXTEND_TO));
+    assert_eq!(instance_ttl(&env, &contract_id), 2 * INSTANCE_TTL_EXTEND_TO);
+    for key in keys.iter() {
+        assert_eq!(
+            persistent_ttl(&env, &contract_id, key),
+            2 * INSTANCE_TTL_EXTEND_TO
+        );
+    }
+
+    // Past the TTL the entries were written with, none of them has expired
+    advance_ledgers(&env, INSTANCE_TTL_EXTEND_TO + DAY_IN_LEDGERS);
+    assert_eq!(client.get_pool_commitments(&1), commitments);
+    assert_eq!(client.get_pool_merkle_root(&1), root);
+    assert_eq!(client.get_pool_deposits(&1), FIXED_AMOUNT);
+    assert_eq!(client.get_pool_config(&1).scope, scope);
+    for key in keys.iter() {
+        assert_eq!(
+            persistent_ttl(&env, &contract_id, key),
+            INSTANCE_TTL_EXTEND_TO - DAY_IN_LEDGERS
+        );
+    }
+}
+
+#[test]
+fn test_get_proof_and_commitments_page() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+    let (token_id, contract_id, _admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+
+    let alice = Address::generate(&env);
+    env.mock_all_auths();
+    token_client.mint(&alice, &3000000000);
+
+    let mut tree = LeanIMT::new(&env, TREE_DEPTH);
+    for byte in 1u8..=3 {
+        let commitment = BytesN::from_array(&env, &[byte; 32]);
+        client.deposit(&alice, &commitment);
+        tree.insert(commitment).unwrap();
+    }
+
+    // The proof matches a locally rebuilt tree and verifies against the contract root
+    let (siblings, directions) = client.get_proof(&1).unwrap();
+    assert_eq!(
+        (siblings.clone(), directions.clone()),
+        tree.get_path(1).unwrap()
+    );
+    assert_eq!(siblings.len(), TREE_DEPTH);
+    assert!(directions.get(0).unwrap());
+    assert_eq!(tree.get_root(), client.get_merkle_root());
+    assert!(client.get_proof(&3).is_none());
+
+    // Pages slice the stored commitments and stop at the end
+    let page = client.get_commitments_page(&1, &5);
+    assert_eq!(page.len(), 2);
+    assert_eq!(page.get(0).unwrap(), BytesN::from_array(&env, &[2u8; 32]));
+    assert_eq!(client.get_commitments_page(&0, &2).len(), 2);
+    assert_eq!(client.get_commitments_page(&3, &1).len(), 0);
+    assert_eq!(client.get_commitments_page(&10, &1).len(), 0);
+}
+
+#[test]
+fn test_get_commitments_page_clamps_count() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+    let (_token_id, contract_id, _admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+
+    // Store more leaves than fit in one page
+    let total = MAX_COMMITMENTS_PAGE + 5;
+    env.as_contract(&contract_id, || {
+        let mut leaves = Vec::new(&env);
+        for i in 0..total {
+            leaves.push_back(fr_to_bytes32(
+                &env,
+                &lean_imt::u64_to_bls_scalar(&env, i as u64 + 1),
+            ));
+        }
+        env.storage().instance().set(&TREE_LEAVES_KEY, &leaves);
+    });
+
+    let page = client.get_commitments_page(&0, &u32::MAX);
+    assert_eq!(page.len(), MAX_COMMITMENTS_PAGE);
+
+    let last = client.get_commitments_page(&MAX_COMMITMENTS_PAGE, &MAX_COMMITMENTS_PAGE);
+    assert_eq!(last.len(), 5);
+    assert_eq!(
+        last.get(4).unwrap(),
+        fr_to_bytes32(&env, &lean_imt::u64_to_bls_scalar(&env, total as u64))
+    );
+    assert_eq!(client.get_commitments_page(&u32::MAX, &u32::MAX).len(), 0);
+}
+
+#[test]
+fn test_get_token_and_denomination() {
+    let env = Env::default();
+    let (token_id, contract_id, _admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+
+    assert_eq!(client.get_token(), token_id);
+    assert_eq!(client.get_denomination(), FIXED_AMOUNT);
+}
+
+#[test]
+fn test_get_scope() {
+    let env = Env::default();
+    let (_token_id, contract_id, _admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+
+    assert_eq!(client.get_scope(), init_scope(&env));
+}
+
+#[test]
+#[should_panic]
+fn test_constructor_rejects_scope_outside_field() {
+    let env = Env::default();
+    let token_id = env.register(MockToken, ());
+    let admin = Address::generate(&env);
+    env.register(
+        PrivacyPoolsContract,
+        (
+            init_vk(&env),
+            token_id,
+            admin,
+            BytesN::from_array(&env, &[0xffu8; 32]),
+            FIXED_AMOUNT,
+            pool_options(),
+        ),
+    );
+}
+
+/// A pool over a fresh `MockToken` with `token_decimals` decimals
+fn register_with_denomination(
+    env: &Env,
+    token_decimals: u32,
+    denomination: i128,
+    checked_decimals: Option<u32>,
+) -> (Address, Address, Address) {
+    let token_id = env.register(MockToken, ());
+    MockTokenClient::new(env, &token_id).initialize(
+        &Address::generate(env),
+        &token_decimals,
+        &String::from_str(env, "Test Token"),
+        &String::from_str(env, "TEST"),
+    );
+    let admin = Address::generate(env);
+    let contract_id = env.register(
+        PrivacyPoolsContract,
+        (
+            init_vk(env),
+            token_id.clone(),
+            admin.clone(),
+            init_scope(env),
+            denomination,
+            PoolOptions {
+                token_decimals: checked_decimals,
+                ..pool_options()
+            },
+        ),
+    );
+    (token_id, contract_id, admin)
+}
+
+#[test]
+fn test_denomination_is_in_token_base_units() {
+    let env = Env::default();
+    // One whole unit of a 6-decimal token
+    let denomination = 1_000_000;
+    let (token_id, contract_id, admin) = register_with_denomination(&env, 6, denomination, Some(6));
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+    assert_eq!(client.get_denomination(), denomination);
+    assert_eq!(client.get_config().denomination, denomination);
+
+    let alice = Address::generate(&env);
+    env.mock_all_auths();
+    token_client.mint(&alice, &(3 * denomination));
+
+    // Deposits move exactly the configured base-unit amount
+    client.deposit(&alice, &BytesN::from_array(&env, &[7u8; 32]));
+    assert_eq!(token_client.balance(&alice), 2 * denomination);
+    assert_eq!(token_client.balance(&contract_id), denomination);
+    assert_eq!(client.get_pool_balance(), denomination);
+
+    // Coins are valued in the same units
+    let nonce = BytesN::from_array(&env, &[1u8; 32]);
+    let precommitment = BytesN::from_array(&env, &[2u8; 32]);
+    let result = client.try_deposit_structured(&alice, &FIXED_AMOUNT, &nonce, &precommitment);
+    assert_eq!(result, Err(Ok(Error::InvalidDenomination)));
+    client.deposit_structured(&alice, &denomination, &nonce, &precommitment);
+    assert_eq!(token_client.balance(&alice), denomination);
+    assert_eq!(client.get_pool_balance(), 2 * denomination);
+
+    // A proof for the 7-decimal default amount is not a coin of this pool
+    let association_root = fr_to_bytes32(
+        &env,
+        &PublicSignals::from_bytes(&env, &init_pub_signals(&env))
+            .unwrap()
+            .pub_signals
+            .get(3)
+            .unwrap(),
+    );
+    client.set_association_root(&admin, &association_root);
+    let result = client.withdraw(
+        &Address::generate(&env),
+        &init_proof(&env),
+        &init_pub_signals(&env),
+    );
+    assert_eq!(
+        result,
+        vec![
+            &env,
+            String::from_str(&env, Error::InvalidDenomination.message())
+        ]
+    );
+    assert_eq!(token_client.balance(&contract_id), 2 * denomination);
+}
+
+#[test]
+#[should_panic(expected = "Token decimals do not match")]
+fn test_constructor_rejects_mismatched_token_decimals() {
+    let env = Env::default();
+    register_with_denomination(&env, 6, FIXED_AMOUNT, Some(7));
+}
+
+#[test]
+#[should_panic(expected = "Denomination must be positive")]
+fn test_constructor_rejects_non_positive_denomination() {
+    let env = Env::default();
+    register_with_denomination(&env, 7, 0, None);
+}
+
+#[test]
+fn test_created_pool_has_its_own_token_and_denomination() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+    // The default pool moves one whole unit of a 6-decimal token
+    let default_denomination = 1_000_000;
+    let (token_id, contract_id, admin) =
+        register_with_denomination(&env, 6, default_denomination, Some(6));
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+    env.mock_all_auths();
+
+    // Pool 1 moves the fixture proof's 100 units of a 7-decimal token
+    let other_token_id = env.register(MockToken, ());
+    let other_token = MockTokenClient::new(&env, &other_token_id);
+    other_token.initialize(
+        &Address::generate(&env),
+        &7u32,
+        &String::from_str(&env, "Other Token"),
+        &String::from_str(&env, "OTHER"),
+    );
+    let scope = BytesN::from_array(&env, &[3u8; 32]);
+    let result =
+        client.try_create_pool(&admin, &1, &other_token_id, &scope, &FIXED_AMOUNT, &Some(6));
+    assert_eq!(result, Err(Ok(Error::TokenDecimalsMismatch)));
+    let result = client.try_create_pool(&admin, &1, &other_token_id, &scope, &0, &None);
+    assert_eq!(result, Err(Ok(Error::InvalidDenomination)));
+    assert!(!client.has_pool(&1));
+    client.create_pool(&admin, &1, &other_token_id, &scope, &FIXED_AMOUNT, &Some(7));
+    assert_eq!(client.get_pool_denomination(&1), FIXED_AMOUNT);
+    assert_eq!(client.get_pool_config(&1).denomination, FIXED_AMOUNT);
+    assert_eq!(client.get_denomination(), default_denomination);
+    assert_eq!(client.get_config().denomination, default_denomination);
+
+    // The created pool's state is kept in persistent entries, not in the instance
+    let token_key = DataKey::Pool(1, TOKEN_KEY);
+    let instance_key: Val = (TOKEN_KEY, 1u32).into_val(&env);
+    env.as_contract(&contract_id, || {
+        assert!(env.storage().persistent().has(&token_key));
+        assert!(!env.storage().instance().has(&instance_key));
+    });
+
+    // Each deposit moves its own pool's denomination of its own token
+    let alice = Address::generate(&env);
+    token_client.mint(&alice, &default_denomination);
+    other_token.mint(&alice, &FIXED_AMOUNT);
+    client.deposit(&alice, &BytesN::from_array(&env, &[7u8; 32]));
+    client.deposit_to_pool(&1, &alice, &fixture_commitment(&env));
+    assert_eq!(token_client.balance(&alice), 0);
+    assert_eq!(other_token.balance(&alice), 0);
+    assert_eq!(client.get_pool_balance(), default_denomination);
+    assert_eq!(client.get_pool_deposits(&1), FIXED_AMOUNT);
+    assert_eq!(
+        persistent_ttl(&env, &contract_id, &token_key),
+        INSTANCE_TTL_EXTEND_TO
+    );
+
+    // The fixture proof withdraws FIXED_AMOUNT, which only pool 1 accepts
+    let association_root = fixture_association_root(&env);
+    client.set_association_root(&admin, &association_root);
+    client.set_pool_association_root(&admin, &1, &association_root);
+    let bob = Address::generate(&env);
+    let proof = init_proof(&env);
+    let pub_signals = init_pub_signals(&env);
+    let result = client.withdraw(&bob, &proof, &pub_signals);
+    assert_eq!(
+        result,
+        vec![
+            &env,
+            String::from_str(&env, Error::InvalidDenomination.message())
+        ]
+    );
+    let result = client.withdraw_from_pool(&1, &bob, &proof, &pub_signals);
+    assert_eq!(result, vec![&env]);
+    assert_eq!(other_token.balance(&bob), FIXED_AMOUNT);
+    assert_eq!(token_client.balance(&bob), 0);
+    assert_eq!(client.get_pool_deposits(&1), 0);
+    assert_eq!(client.get_pool_balance(), default_denomination);
+
+    // Token balance mode is switched per pool as well
+    let result = client.try_enable_pool_token_balance_mode(&admin, &2);
+    assert_eq!(result, Err(Ok(Error::UnknownPool)));
+    client.enable_pool_token_balance_mode(&admin, &1);
+    assert!(client.uses_pool_token_balance(&1));
+    assert!(!client.uses_token_balance());
+    other_token.mint(&contract_id, &FIXED_AMOUNT);
+    assert_eq!(client.get_pool_deposits(&1), FIXED_AMOUNT);
+    assert_eq!(client.get_pool_balance(), default_denomination);
+}
+
+#[test]
+fn test_token_balance_mode_requires_an_unshared_token() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+    let (token_id, contract_id, admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+    env.mock_all_auths();
+
+    // Pool 1 holds the default pool's token, so neither may count the token balance
+    let scope = BytesN::from_array(&env, &[3u8; 32]);
+    client.create_pool(&admin, &1, &token_id, &scope, &FIXED_AMOUNT, &None);
+    let alice = Address::generate(&env);
+    token_client.mint(&alice, &FIXED_AMOUNT);
+    client.deposit(&alice, &BytesN::from_array(&env, &[7u8; 32]));
+    let result = client.try_enable_token_balance_mode(&admin);
+    assert_eq!(result, Err(Ok(Error::SharedToken)));
+    let result = client.try_enable_pool_token_balance_mode(&admin, &1);
+    assert_eq!(result, Err(Ok(Error::SharedToken)));
+
+    // The default pool's deposit stays out of pool 1's reach
+    assert_eq!(client.get_balance(), FIXED_AMOUNT);
+    assert_eq!(client.get_pool_balance(), FIXED_AMOUNT);
+    assert_eq!(client.get_pool_deposits(&1), 0);
+    client.set_pool_association_root(&admin, &1, &fixture_association_root(&env));
+    let result = client.withdraw_from_pool(
+        &1,
+        &Address::generate(&env),
+        &init_proof(&env),
+        &init_pub_signals(&env),
+    );
+    assert_eq!(
+        result,
+        vec![&env, String::from_str(&env, ERROR_INSUFFICIENT_BALANCE)]
+    );
+
+    // A pool alone on its token can switch, and then keeps that token to itself
+    let other_token_id = env.register(MockToken, ());
+    MockTokenClient::new(&env, &other_token_id).initialize(
+        &Address::generate(&env),
+        &7u32,
+        &String::from_str(&env, "Other Token"),
+        &String::from_str(&env, "OTHER"),
+    );
+    client.create_pool(&admin, &2, &other_token_id, &scope, &FIXED_AMOUNT, &None);
+    client.enable_pool_token_balance_mode(&admin, &2);
+    assert!(client.uses_pool_token_balance(&2));
+    let result = client.try_create_pool(&admin, &3, &other_token_id, &scope, &FIXED_AMOUNT, &None);
+    assert_eq!(result, Err(Ok(Error::SharedToken)));
+    assert!(!client.has_pool(&3));
+}
+
+#[test]
+fn test_root_history_evicts_oldest_root() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+    env.mock_all_auths();
+
+    let token_id = env.register(MockToken, ());
+    let token_client = MockTokenClient::new(&env, &token_id);
+    token_client.initialize(
+        &Address::generate(&env),
+        &7u32,
+        &String::from_str(&env, "Test Token"),
+        &String::from_str(&env, "TEST"),
+    );
+    let contract_id = env.register(
+        PrivacyPoolsContract,
+        (
+            init_vk(&env),
+            token_id.clone(),
+            Address::generate(&env),
+            init_scope(&env),
+            FIXED_AMOUNT,
+            PoolOptions {
+                root_history_size: 4u32,
+                ..pool_options()
+            },
+        ),
+    );
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    assert_eq!(client.get_root_history_size(), 4);
+
+    let alice = Address::generate(&env);
+    token_client.mint(&alice, &6000000000);
+
+    let empty_root = client.get_merkle_root();
+    assert!(client.is_known_root(&empty_root));
+
+    let mut roots = vec![&env, empty_root.clone()];
+    for i in 1..=6u8 {
+        client.deposit(&alice, &BytesN::from_array(&env, &[i; 32]));
+        roots.push_back(client.get_merkle_root());
+    }
+
+    // Only the 4 most recent roots remain
+    assert_eq!(client.get_root_history().len(), 4);
+    for (i, root) in roots.iter().enumerate() {
+        assert_eq!(client.is_known_root(&root), i >= 3);
+    }
+    assert_eq!(
+        client.get_root_history().last().unwrap(),
+        client.get_merkle_root()
+    );
+}
+
+#[test]
+fn test_get_roots_since_returns_new_roots() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+    let (token_id, contract_id, _admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+
+    let alice = Address::generate(&env);
+    env.mock_all_auths();
+    token_client.mint(&alice, &3000000000);
+
+    client.deposit(&alice, &BytesN::from_array(&env, &[1u8; 32]));
+    let synced_count = client.get_commitment_count();
+    assert_eq!(synced_count, 1);
+
+    let mut new_roots = vec![&env];
+    for i in 2..=3u8 {
+        client.deposit(&alice, &BytesN::from_array(&env, &[i; 32]));
+        new_roots.push_back(client.get_merkle_root());
+    }
+
+    // The deposit count tells the client exactly how many roots it is missing
+    let missing = client.get_commitment_count() - synced_count;
+    assert_eq!(client.get_roots_since(&missing), new_roots);
+    assert_eq!(client.get_roots_since(&0), vec![&env]);
+
+    // Asking for more than the history returns the whole history
+    assert_eq!(client.get_roots_since(&100), client.get_root_history());
+    assert_eq!(client.get_roots_since(&100).len(), 4);
+}
+
+#[test]
+fn test_get_roots_bundles_state_and_association_roots() {
+    let env = Env::default();
+    let (token_id, contract_id, admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    env.mock_all_auths();
+
+    // No association set yet: nothing to prove membership against
+    let (state_root, association_roots) = client.get_roots();
+    assert_eq!(state_root, client.get_merkle_root());
+    assert_eq!(association_roots, vec![&env]);
+
+    prepare_fixture_withdrawal(&env, &client, &token_id, &admin);
+    let (state_root, association_roots) = client.get_roots();
+    assert_eq!(state_root, client.get_merkle_root());
+    assert_eq!(association_roots, vec![&env, client.get_association_root()]);
+}
+
+#[test]
+fn test_root_history_size_default() {
+    let env = Env::default();
+    let (_token_id, contract_id, _admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+
+    assert_eq!(client.get_root_history_size(), DEFAULT_ROOT_HISTORY_SIZE);
+}
+
+#[test]
+#[should_panic]
+fn test_constructor_rejects_oversized_root_history() {
+    let env = Env::default();
+    let token_id = env.register(MockToken, ());
+    let admin = Address::generate(&env);
+    env.register(
+        PrivacyPoolsContract,
+        (
+            init_vk(&env),
+            token_id,
+            admin,
+            init_scope(&env),
+            FIXED_AMOUNT,
+            PoolOptions {
+                root_history_size: MAX_ROOT_HISTORY_SIZE + 1,
+                ..pool_options()
+            },
+        ),
+    );
+}
+
+#[test]
+fn test_deposit_structured_computes_commitment() {
+    let env = Env::default();
+    let (token_id, contract_id, _admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+
+    let alice = Address::generate(&env);
+    env.mock_all_auths();
+    token_client.mint(&alice, &1000000000);
+
+    let nonce = BytesN::from_array(&env, &[1u8; 32]);
+    let precommitment = BytesN::from_array(&env, &[2u8; 32]);
+    let leaf_index = client.deposit_structured(&alice, &FIXED_AMOUNT, &nonce, &precommitment);
+    assert_eq!(leaf_index, 0);
+
+    // Same hashes as coinutils' generate_label and generate_commitment
+    let label = soroban_poseidon::poseidon_hash::<3, Fr>(
+        &env,
+        &vec![
+            &env,
+            U256::from_be_bytes(&env, &init_scope(&env).into()),
+            U256::from_be_bytes(&env, &nonce.into()),
+        ],
+    );
+    let inputs = vec![
+        &env,
+        U256::from_u128(&env, FIXED_AMOUNT as u128),
+        label,
+        U256::from_be_bytes(&env, &precommitment.into()),
+    ];
+    let expected = Fr::from_u256(soroban_poseidon::poseidon_hash::<4, Fr>(&env, &inputs));
+    assert_eq!(
+        client.get_commitments().get(0).unwrap(),
+        fr_to_bytes32(&env, &expected)
+    );
+    assert_eq!(client.get_balance(), 1000000000);
+    assert_eq!(client.get_pool_balance(), 1000000000);
+}
+
+#[test]
+fn test_deposit_structured_rejects_invalid_inputs() {
+    let env = Env::default();
+    let (token_id, contract_id, _admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+
+    let alice = Address::generate(&env);
+    env.mock_all_auths();
+    token_client.mint(&alice, &1000000000);
+
+    let nonce = BytesN::from_array(&env, &[1u8; 32]);
+    let precommitment = BytesN::from_array(&env, &[2u8; 32]);
+    let oversized = BytesN::from_array(&env, &[0xffu8; 32]);
+
+    let result = client.try_deposit_structured(&alice, &1, &nonce, &precommitment);
+    assert_eq!(result, Err(Ok(Error::InvalidDenomination)));
+
+    let result = client.try_deposit_structured(&alice, &FIXED_AMOUNT, &oversized, &precommitment);
+    assert_eq!(result, Err(Ok(Error::InvalidFieldElement)));
+
+    let result = client.try_deposit_structured(&alice, &FIXED_AMOUNT, &nonce, &oversized);
+    assert_eq!(result, Err(Ok(Error::InvalidFieldElement)));
+
+    // Nothing moved
+    assert_eq!(token_client.balance(&alice), 1000000000);
+    assert_eq!(client.get_commitment_count(), 0);
+}
+
+#[test]
+fn test_deposit_structured_checked_rejects_mismatched_commitment() {
+    let env = Env::default();
+    let (token_id, contract_id, _admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+
+    let alice = Address::generate(&env);
+    env.mock_all_auths();
+    token_client.mint(&alice, &1000000000);
+
+    let nonce = BytesN::from_array(&env, &[1u8; 32]);
+    let precommitment = BytesN::from_array(&env, &[2u8; 32]);
+
+    // A commitment hashed from another precommitment is refused before any transfer
+    let wrong = BytesN::from_array(&env, &[3u8; 32]);
+    let result = client.try_deposit_structured_checked(
+        &alice,
+        &FIXED_AMOUNT,
+        &nonce,
+        &precommitment,
+        &wrong,
+    );
+    assert_eq!(result, Err(Ok(Error::CommitmentMismatch)));
+    assert_eq!(token_client.balance(&alice), 1000000000);
+    assert_eq!(client.get_commitment_count(), 0);
+
+    // The commitment deposit_structured would store is accepted
+    let expected = env.as_contract(&contract_id, || {
+        PrivacyPoolsContract::structured_commitment(
+            &env,
+            FIXED_AMOUNT,
+            nonce.clone(),
+            precommitment.clone(),
+        )
+        .unwrap()
+    });
+    let leaf_index =
+        client.deposit_structured_checked(&alice, &FIXED_AMOUNT, &nonce, &precommitment, &expected);
+    assert_eq!(leaf_index, 0);
+    assert_eq!(client.get_commitments().get(0).unwrap(), expected);
+    assert_eq!(token_client.balance(&alice), 0);
+}
+
+#[test]
+fn test_structured_deposit_instruction_cost() {
+    let env = Env::default();
+    let (token_id, contract_id, _admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    env.mock_all_auths();
+
+    let alice = Address::generate(&env);
+    MockTokenClient::new(&env, &token_id).mint(&alice, &2000000000);
+    client.deposit(&alice, &BytesN::from_array(&env, &[1u8; 32]));
+    let opaque_instructions = env.cost_estimate().resources().instructions;
+
+    let nonce = BytesN::from_array(&env, &[1u8; 32]);
+    let precommitment = BytesN::from_array(&env, &[2u8; 32]);
+    let commitment = env.as_contract(&contract_id, || {
+        PrivacyPoolsContract::structured_commitment(
+            &env,
+            FIXED_AMOUNT,
+            nonce.clone(),
+            precommitment.clone(),
+        )
+        .unwrap()
+    });
+    client.deposit_structured_checked(&alice, &FIXED_AMOUNT, &nonce, &precommitment, &commitment);
+    let checked_instructions = env.cost_estimate().resources().instructions;
+
+    // Hashing the commitment on-chain costs extra, but stays within the deposit ceiling
+    std::println!(
+        "deposit: {} instructions, deposit_structured_checked: {} instructions (max {})",
+        opaque_instructions,
+        checked_instructions,
+        MAX_DEPOSIT_INSTRUCTIONS
+    );
+    assert!(checked_instructions > opaque_instructions);
+    assert!(checked_instructions <= MAX_DEPOSIT_INSTRUCTIONS);
+}
+
+#[test]
+fn test_withdraw_rejects_wrong_public_signal_count() {
+    let env = Env::default();
+    let (token_id, contract_id, admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+
+    let association_root = fixture_association_root(&env);
+    env.mock_all_auths();
+    client.set_association_root(&admin, &association_root);
+    let alice = Address::generate(&env);
+    token_client.mint(&alice, &1000000000);
+    client.deposit(&alice, &BytesN::from_array(&env, &[1u8; 32]));
+
+    // Drop the association root: only three signals remain
+    let full_signals = PublicSignals::from_bytes(&env, &init_pub_signals(&env))
+        .unwrap()
+        .pub_signals;
+    let mut truncated = full_signals.clone();
+    truncated.pop_back();
+    // Append an extra signal: five signals
+    let mut extended = full_signals.clone();
+    extended.push_back(full_signals.get(0).unwrap());
+
+    let bob = Address::generate(&env);
+    for signals in [truncated, extended] {
+        let pub_signals = PublicSignals {
+            pub_signals: signals,
+        }
+        .to_bytes(&env);
+        let result = client.withdraw(&bob, &init_proof(&env), &pub_signals);
+        assert_eq!(
+            result,
+            vec![&env, String::from_str(&env, ERROR_PUBLIC_SIGNAL_COUNT)]
+        );
+    }
+
+    assert_eq!(client.get_nullifiers().len(), 0);
+    assert_eq!(token_client.balance(&bob), 0);
+}
+
+#[test]
+fn test_withdraw_rejects_malformed_proof_bytes() {
+    let env = Env::default();
+    let (token_id, contract_id, admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+
+    let association_root = fixture_association_root(&env);
+    env.mock_all_auths();
+    client.set_association_root(&admin, &association_root);
+    let alice = Address::generate(&env);
+    token_client.mint(&alice, &1000000000);
+    client.deposit(&alice, &BytesN::from_array(&env, &[1u8; 32]));
+
+    let bob = Address::generate(&env);
+    let pub_signals = init_pub_signals(&env);
+
+    // Short and empty proofs are rejected cleanly instead of trapping
+    for proof in [Bytes::from_array(&env, &[0xabu8; 48]), Bytes::new(&env)] {
+        let result = client.withdraw(&bob, &proof, &pub_signals);
+        assert_eq!(
+            result,
+            vec![&env, String::from_str(&env, ERROR_COIN_OWNERSHIP_PROOF)]
+        );
+    }
+
+    // Truncated signal bytes are rejected the same way as a wrong signal count
+    let truncated_signals = pub_signals.slice(0..pub_signals.len() - 1);
+    let result = client.withdraw(&bob, &init_proof(&env), &truncated_signals);
+    assert_eq!(
+        result,
+        vec![&env, String::from_str(&env, ERROR_PUBLIC_SIGNAL_COUNT)]
+    );
+
+    // So are well-sized blobs in another serialization version
+    let mut stale_proof = init_proof(&env);
+    stale_proof.set(0, zk::SERIALIZATION_VERSION + 1);
+    let result = client.withdraw(&bob, &stale_proof, &pub_signals);
+    assert_eq!(
+        result,
+        vec![&env, String::from_str(&env, ERROR_COIN_OWNERSHIP_PROOF)]
+    );
+    let mut stale_signals = pub_signals.clone();
+    stale_signals.set(0, zk::SERIALIZATION_VERSION + 1);
+    let result = client.withdraw(&bob, &init_proof(&env), &stale_signals);
+    assert_eq!(
+        result,
+        vec![&env, String::from_str(&env, ERROR_PUBLIC_SIGNAL_COUNT)]
+    );
+
+    assert_eq!(client.get_nullifiers().len(), 0);
+    assert_eq!(token_client.balance(&bob), 0);
+}
+
+#[test]
+fn test_withdraw_reentrancy_from_token_is_rejected() {
+    let env = Env::default();
+    env.mock_all_auths_allowing_non_root_auth();
+
+    let token_id = env.register(ReentrantToken, ());
+    let token_client = ReentrantTokenClient::new(&env, &token_id);
+    let admin = Address::generate(&env);
+    let contract_id = env.register(
+        PrivacyPoolsContract,
+        (
+            init_vk(&env),
+            token_id.clone(),
+            admin.clone(),
+            init_scope(&env),
+            FIXED_AMOUNT,
+            pool_options(),
+        ),
+    );
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+
+    prepare_fixture_withdrawal(&env, &client, &token_id, &admin);
+
+    // Only one deposit can match the proof's state root, so credit a second
+    // denomination directly to make a nested payout affordable
+    env.as_contract(&contract_id, || {
+        env.storage()
+            .instance()
+            .set(&BALANCE_KEY, &(2 * FIXED_AMOUNT));
+    });
+
+    let proof = init_proof(&env);
+    let pub_signals = init_pub_signals(&env);
+    token_client.arm(&contract_id, &proof, &pub_signals);
+
+    let bob = Address::generate(&env);
+    let result = client.withdraw(&bob, &proof, &pub_signals);
+    assert_eq!(result, vec![&env]);
+
+    // The host refuses to re-enter the pool before its own lock or the spent-nullifier
+    // check is even consulted (the lock is covered by `test_withdraw_rejected_while_locked`);
+    // only one payout was accounted for
+    assert!(token_client.reentry_refused());
+    assert_eq!(token_client.reentry_messages(), None);
+    assert_eq!(client.get_nullifiers().len(), 1);
+    assert_eq!(client.get_pool_balance(), FIXED_AMOUNT);
+
+    // Once the outer call completes, a replay is stopped by the spent nullifier
+    let result = client.withdraw(&bob, &proof, &pub_signals);
+    assert_eq!(
+        result,
+        vec![&env, String::from_str(&env, ERROR_NULLIFIER_USED)]
+    );
+}
+
+#[test]
+fn test_withdraw_rejected_while_locked() {
+    let env = Env::default();
+    let (_token_id, contract_id, _admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+
+    env.as_contract(&contract_id, || {
+        env.storage().instance().set(&LOCK_KEY, &true);
+    });
+
+    env.mock_all_auths();
+    let bob = Address::generate(&env);
+    let result = client.withdraw(&bob, &init_proof(&env), &init_pub_signals(&env));
+    assert_eq!(
+        result,
+        vec![&env, String::from_str(&env, ERROR_REENTRANT_CALL)]
+    );
+    assert_eq!(client.get_nullifiers().len(), 0);
+}
+
+#[test]
+fn test_fr_to_bytes32_matches_tree_encoding() {
+    let env = Env::default();
+    let value = Fr::from_u256(U256::from_u32(&env, 0x0102));
+
+    let bytes = fr_to_bytes32(&env, &value);
+    let mut expected = [0u8; 32];
+    expected[30] = 0x01;
+    expected[31] = 0x02;
+    assert_eq!(bytes, BytesN::from_array(&env, &expected));
+    assert_eq!(bytes, lean_imt::scalar_to_be_bytes(&value));
+
+    assert!(fr_matches_bytes32(&env, &value, &bytes));
+    assert!(!fr_matches_bytes32(
+        &env,
+        &value,
+        &BytesN::from_array(&env, &[0u8; 32])
+    ));
+}
+
+#[test]
+fn test_fr_to_bytes32_canonical_encoding() {
+    let env = Env::default();
+
+    // Zero is 32 zero bytes, not an empty or single-byte value
+    let zero = Fr::from_u256(U256::from_u32(&env, 0));
+    assert_eq!(
+        fr_to_bytes32(&env, &zero),
+        BytesN::from_array(&env, &[0u8; 32])
+    );
+
+    // r - 1 is the largest canonical value and encodes as itself
+    let mut max = FIELD_MODULUS;
+    max[31] -= 1;
+    let near_modulus = Fr::from_bytes(BytesN::from_array(&env, &max));
+    assert_eq!(
+        fr_to_bytes32(&env, &near_modulus),
+        BytesN::from_array(&env, &max)
+    );
+
+    // r + 5 is reduced, so it cannot alias 5 in storage
+    let mut above = FIELD_MODULUS;
+    above[31] += 5;
+    let aliased = Fr::from_bytes(BytesN::from_array(&env, &above));
+    let five = Fr::from_u256(U256::from_u32(&env, 5));
+    assert_eq!(fr_to_bytes32(&env, &aliased), fr_to_bytes32(&env, &five));
+}
+
+#[test]
+fn test_deposit_cost_does_not_grow_with_the_tree() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+    let (token_id, _, admin) = setup_test_environment(&env);
+
+    // 255 commitments: the next leaf has a full frontier of left siblings
+    let mut commitments = vec![&env];
+    for i in 1..=255u32 {
+        let mut bytes = [0u8; 32];
+        bytes[28..].copy_from_slice(&i.to_be_bytes());
+        commitments.push_back(BytesN::from_array(&env, &bytes));
+    }
+    let contract_id = register_migrated_pool(&env, &token_id, &admin, commitments, vec![&env]);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    env.mock_all_auths();
+    let alice = Address::generate(&env);
+    MockTokenClient::new(&env, &token_id).mint(&alice, &2000000000);
+
+    // The saved frontier spares each deposit from rehashing the existing leaves
+    for byte in [1u8, 2] {
+        client.deposit(&alice, &BytesN::from_array(&env, &[byte; 32]));
+        let instructions = env.cost_estimate().resources().instructions;
+        std::println!(
+            "deposit into a 256-leaf tree: {} instructions",
+            instructions
+        );
+        assert!(instructions <= MAX_DEPOSIT_INSTRUCTIONS);
+    }
+
+    let mut rebuilt = LeanIMT::new(&env, 20);
+    for leaf in client.get_commitments().iter() {
+        rebuilt.insert(leaf).unwrap();
+    }
+    assert_eq!(client.get_merkle_root(), rebuilt.get_root());
+}
+
+/// Fixture signals rearranged into `PARTIAL_SIGNAL_LAYOUT`, spending `amount` and
+/// committing the rest to `change_commitment`
+fn partial_pub_signals(env: &Env, amount: i128, change_commitment: &BytesN<32>) -> Bytes {
+    let fixture = PublicSignals::from_bytes(env, &init_pub_signals(env))
+        .unwrap()
+        .pub_signals;
+    let scalar = |value: i128| Fr::from_u256(U256::from_u128(env, value as u128));
+    let pub_signals = vec![
+        env,
+        fixture.get(PUBLIC_SIGNAL_LAYOUT.nullifier_hash).unwrap(),
+        scalar(amount),
+        fixture.get(PUBLIC_SIGNAL_LAYOUT.state_root).unwrap(),
+        fixture.get(PUBLIC_SIGNAL_LAYOUT.association_root).unwrap(),
+        Fr::from_bytes(change_commitment.clone()),
+        scalar(1000000000),
+    ];
+    PublicSignals { pub_signals }.to_bytes(env)
+}
+
+#[test]
+fn test_withdraw_partial_requires_partial_key() {
+    let env = Env::default();
+    let (token_id, contract_id, admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    env.mock_all_auths();
+    prepare_fixture_withdrawal(&env, &client, &token_id, &admin);
+
+    let bob = Address::generate(&env);
+    let change = BytesN::from_array(&env, &[2u8; 32]);
+    let result = client.withdraw_partial(
+        &bob,
+        &400000000,
+        &change,
+        &init_proof(&env),
+        &partial_pub_signals(&env, 400000000, &change),
+    );
+    assert_eq!(
+        result,
+        vec![
+            &env,
+            String::from_str(&env, "Partial withdrawals are not enabled")
+        ]
+    );
+
+    // Only the admin can enable them
+    assert_eq!(
+        client.try_set_partial_verification_key(&bob, &init_vk(&env)),
+        Err(Ok(Error::OnlyAdmin))
+    );
+}
+
+#[test]
+fn test_withdraw_partial_rejects_mismatched_arguments() {
+    let env = Env::default();
+    let (token_id, contract_id, admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+    env.mock_all_auths();
+    prepare_fixture_withdrawal(&env, &client, &token_id, &admin);
+    // Any well-formed key will do: no real partial proof is available here
+    client.set_partial_verification_key(&admin, &init_vk(&env));
+
+    let bob = Address::generate(&env);
+    let change = BytesN::from_array(&env, &[2u8; 32]);
+    let pub_signals = partial_pub_signals(&env, 400000000, &change);
+    let message = |text: &str| vec![&env, String::from_str(&env, text)];
+
+    // The amount must be positive, within the denomination and equal to the proof's
+    for amount in [0, -1, 1000000001, 300000000] {
+        let result =
+            client.withdraw_partial(&bob, &amount, &change, &init_proof(&env), &pub_signals);
+        assert_eq!(result, message("Withdraw amount does not match the proof"));
+    }
+
+    let other_change = BytesN::from_array(&env, &[3u8; 32]);
+    let result = client.withdraw_partial(
+        &bob,
+        &400000000,
+        &other_change,
+        &init_proof(&env),
+        &pub_signals,
+    );
+    assert_eq!(
+        result,
+        message("Change commitment does not match the proof")
+    );
+
+    // The withdraw layout is not accepted
+    let result = client.withdraw_partial(
+        &bob,
+        &1000000000,
+        &change,
+        &init_proof(&env),
+        &init_pub_signals(&env),
+    );
+    assert_eq!(result, message(ERROR_PUBLIC_SIGNAL_COUNT));
+
+    // Well-formed signals still need a proof under the partial key
+    let result =
+        client.withdraw_partial(&bob, &400000000, &change, &init_proof(&env), &pub_signals);
+    assert_eq!(result, message(ERROR_COIN_OWNERSHIP_PROOF));
+
+    assert_eq!(client.get_nullifiers().len(), 0);
+    assert_eq!(client.get_commitment_count(), 1);
+    assert_eq!(token_client.balance(&bob), 0);
+}
+
+#[test]
+fn test_withdraw_rejects_partial_value() {
+    let env = Env::default();
+    let (token_id, contract_id, admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    env.mock_all_auths();
+    prepare_fixture_withdrawal(&env, &client, &token_id, &admin);
+
+    // A change coin worth less than the denomination can't be withdrawn in full
+    let mut pub_signals = PublicSignals::from_bytes(&env, &init_pub_signals(&env))
+        .unwrap()
+        .pub_signals;
+    pub_signals.set(
+        PUBLIC_SIGNAL_LAYOUT.withdrawn_value,
+        Fr::from_u256(U256::from_u32(&env, 600000000)),
+    );
+    let bob = Address::generate(&env);
+    let result = client.withdraw(
+        &bob,
+        &init_proof(&env),
+        &PublicSignals { pub_signals }.to_bytes(&env),
+    );
+    assert_eq!(
+        result,
+        vec![
+            &env,
+            String::from_str(&env, "Value does not match the pool denomination")
+        ]
+    );
+    assert_eq!(client.get_nullifiers().len(), 0);
+}
+
+/// Fixture signals with a recipient binding appended, as `RECIPIENT_SIGNAL_LAYOUT`
+fn recipient_pub_signals(env: &Env, recipient: &BytesN<32>) -> Bytes {
+    let mut pub_signals = PublicSignals::from_bytes(env, &init_pub_signals(env))
+        .unwrap()
+        .pub_signals;
+    pub_signals.push_back(Fr::from_bytes(recipient.clone()));
+    PublicSignals { pub_signals }.to_bytes(env)
+}
+
+#[test]
+fn test_recipient_hash_is_a_distinct_field_element() {
+    let env = Env::default();
+    let (_, contract_id, _) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let alice_hash = client.recipient_hash(&alice);
+    assert_eq!(client.recipient_hash(&alice), alice_hash);
+    assert_ne!(client.recipient_hash(&bob), alice_hash);
+    assert!(alice_hash.to_array() < FIELD_MODULUS);
+}
+
+#[test]
+fn test_withdraw_to_recipient_rejects_other_recipients() {
+    let env = Env::default();
+    let (token_id, contract_id, admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+    env.mock_all_auths();
+    prepare_fixture_withdrawal(&env, &client, &token_id, &admin);
+
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let pub_signals = recipient_pub_signals(&env, &client.recipient_hash(&alice));
+    let message = |text: &str| vec![&env, String::from_str(&env, text)];
+
+    let result = client.withdraw_to_recipient(&alice, &init_proof(&env), &pub_signals);
+    assert_eq!(
+        result,
+        message("Recipient-bound withdrawals are not enabled")
+    );
+    assert_eq!(
+        client.try_set_recipient_verification_key(&bob, &init_vk(&env)),
+        Err(Ok(Error::OnlyAdmin))
+    );
+
+    // Any well-formed key will do: no real recipient-bound proof is available here
+    client.set_recipient_verification_key(&admin, &init_vk(&env));
+
+    // A proof for alice cannot be redirected to bob
+    let result = client.withdraw_to_recipient(&bob, &init_proof(&env), &pub_signals);
+    assert_eq!(result, message("Recipient does not match the proof"));
+
+    // The unbound layout is not accepted
+    let result = client.withdraw_to_recipient(&alice, &init_proof(&env), &init_pub_signals(&env));
+    assert_eq!(result, message(ERROR_PUBLIC_SIGNAL_COUNT));
+
+    // The recipient matches, but the proof must still verify under the recipient key
+    let result = client.withdraw_to_recipient(&alice, &init_proof(&env), &pub_signals);
+    assert_eq!(result, message(ERROR_COIN_OWNERSHIP_PROOF));
+
+    assert_eq!(client.get_nullifiers().len(), 0);
+    assert_eq!(token_client.balance(&alice), 0);
+    assert_eq!(token_client.balance(&bob), 0);
+}
+
+/// Fixture signals with an expiry ledger appended, as `EXPIRY_SIGNAL_LAYOUT`
+fn expiry_pub_signals(env: &Env, expiry_ledger: u32) -> Bytes {
+    let mut pub_signals = PublicSignals::from_bytes(env, &init_pub_signals(env))
+        .unwrap()
+        .pub_signals;
+    pub_signals.push_back(Fr::from_u256(U256::from_u32(env, expiry_ledger)));
+    PublicSignals { pub_signals }.to_bytes(env)
+}
+
+#[test]
+fn test_withdraw_with_expiry_rejects_expired_proofs() {
+    let env = Env::default();
+    let (token_id, contract_id, admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    let token_client = MockTokenClient::new(&env, &token_id);
+    env.mock_all_auths();
+    prepare_fixture_withdrawal(&env, &client, &token_id, &admin);
+    env.ledger().set_sequence_number(1000);
+
+    let bob = Address::generate(&env);
+    let message = |text: &str| vec![&env, String::from_str(&env, text)];
+
+    let result =
+        client.withdraw_with_expiry(&bob, &init_proof(&env), &expiry_pub_signals(&env, 1000));
+    assert_eq!(result, message("Expiring withdrawals are not enabled"));
+    assert_eq!(
+        client.try_set_expiry_verification_key(&bob, &init_vk(&env)),
+        Err(Ok(Error::OnlyAdmin))
+    );
+
+    // Any well-formed key will do: no real expiring proof is available here
+    client.set_expiry_verification_key(&admin, &init_vk(&env));
+
+    // One ledger past the expiry the proof is refused before it is verified
+    let result =
+        client.withdraw_with_expiry(&bob, &init_proof(&env), &expiry_pub_signals(&env, 999));
+    assert_eq!(result, message("Proof has expired"));
+
+    // The layout without an expiry is not accepted
+    let result = client.withdraw_with_expiry(&bob, &init_proof(&env), &init_pub_signals(&env));
+    assert_eq!(result, message(ERROR_PUBLIC_SIGNAL_COUNT));
+
+    // Up to and including the expiry ledger the proof reaches verification
+    for expiry_ledger in [1000, u32::MAX] {
+        let result = client.withdraw_with_expiry(
+            &bob,
+            &init_proof(&env),
+            &expiry_pub_signals(&env, expiry_ledger),
+        );
+        assert_eq!(result, message(ERROR_COIN_OWNERSHIP_PROOF));
+    }
+
+    assert_eq!(client.get_nullifiers().len(), 0);
+    assert_eq!(token_client.balance(&bob), 0);
+}
+
+#[test]
+fn test_expiring_proof_is_rejected_once_the_ledger_passes_it() {
+    let env = Env::default();
+    let (token_id, contract_id, admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    env.mock_all_auths();
+    prepare_fixture_withdrawal(&env, &client, &token_id, &admin);
+    client.set_expiry_verification_key(&admin, &init_vk(&env));
+
+    let bob = Address::generate(&env);
+    let pub_signals = expiry_pub_signals(&env, env.ledger().sequence() + 10);
+    let message = |text: &str| vec![&env, String::from_str(&env, text)];
+
+    // Until the expiry ledger the proof gets as far as verification
+    for ledgers in [0, 9, 1] {
+        advance_ledgers(&env, ledgers);
+        let result = client.withdraw_with_expiry(&bob, &init_proof(&env), &pub_signals);
+        assert_eq!(result, message(ERROR_COIN_OWNERSHIP_PROOF));
+    }
+
+    advance_ledgers(&env, 1);
+    let result = client.withdraw_with_expiry(&bob, &init_proof(&env), &pub_signals);
+    assert_eq!(result, message("Proof has expired"));
+    assert_eq!(client.get_nullifiers().len(), 0);
+}
+
+/// `CommitmentInserted` events published for `pool` by the last invocation of
+/// `contract_id`, as (leaf index, commitment) in publication order
+fn inserted_commitments(
+    env: &Env,
+    contract_id: &Address,
+    pool: u32,
+) -> std::vec::Vec<(u32, BytesN<32>)> {
+    let name = Symbol::new(env, "commitment_inserted");
+    let mut inserted = std::vec::Vec::new();
+    for event in env.events().all().filter_by_contract(contract_id).events() {
+        let xdr::ContractEventBody::V0(body) = &event.body;
+        let topics: std::vec::Vec<Val> = body
+            .topics
+            .iter()
+            .map(|topic| Val::try_from_val(env, topic).unwrap())
+            .collect();
+        if Symbol::try_from_val(env, &topics[0]) != Ok(name.clone())
+            || u32::try_from_val(env, &topics[1]) != Ok(pool)
+        {
+            continue;
+        }
+        let data: Map<Symbol, Val> =
+            Map::try_from_val(env, &Val::try_from_val(env, &body.data).unwrap()).unwrap();
+        let field = |key: &str| data.get(Symbol::new(env, key)).unwrap();
+        inserted.push((
+            u32::try_from_val(env, &field("leaf_index")).unwrap(),
+            BytesN::try_from_val(env, &field("commitment")).unwrap(),
+        ));
+    }
+    inserted
+}
+
+/// Rebuilds a pool's tree from its `CommitmentInserted` events alone
+///
+/// Fails if the events skip or repeat a leaf index, since the tree could then only be
+/// rebuilt with a gap or in the wrong order.
+fn rebuild_from_events(env: &Env, inserted: &[(u32, BytesN<32>)]) -> LeanIMT {
+    let mut tree = LeanIMT::new(env, TREE_DEPTH);
+    for (leaf_index, commitment) in inserted {
+        assert_eq!(*leaf_index, tree.get_leaf_count());
+        tree.insert(commitment.clone()).unwrap();
+    }
+    tree
+}
+
+#[test]
+fn test_commitment_events_rebuild_the_tree() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+    let (token_id, contract_id, admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    env.mock_all_auths();
+    client.create_pool(
+        &admin,
+        &1,
+        &token_id,
+        &BytesN::from_array(&env, &[3u8; 32]),
+        &FIXED_AMOUNT,
+        &None,
+    );
+
+    let alice = Address::generate(&env);
+    MockTokenClient::new(&env, &token_id).mint(&alice, &10000000000);
+
+    // Events only cover the last invocation, so collect them after every deposit
+    let mut default_pool_events = std::vec::Vec::new();
+    let mut other_pool_events = std::vec::Vec::new();
+    for byte in 1u8..=5 {
+        let commitment = BytesN::from_array(&env, &[byte; 32]);
+        let leaf_index = client.deposit(&alice, &commitment);
+        let inserted = inserted_commitments(&env, &contract_id, DEFAULT_POOL);
+        assert_eq!(inserted, [(leaf_index, commitment)]);
+        assert!(inserted_commitments(&env, &contract_id, 1).is_empty());
+        default_pool_events.extend(inserted);
+
+        // Interleave deposits into another pool; they must not leak into pool 0's stream
+        if byte % 2 == 1 {
+            client.deposit_to_pool(&1, &alice, &BytesN::from_array(&env, &[byte + 100; 32]));
+            assert!(inserted_commitments(&env, &contract_id, DEFAULT_POOL).is_empty());
+            other_pool_events.extend(inserted_commitments(&env, &contract_id, 1));
+        }
+    }
+
+    // A rejected deposit publishes nothing
+    let result = client.try_deposit(&alice, &BytesN::from_array(&env, &[1u8; 32]));
+    assert_eq!(result, Err(Ok(Error::DuplicateCommitment)));
+    assert!(inserted_commitments(&env, &contract_id, DEFAULT_POOL).is_empty());
+
+    let rebuilt = rebuild_from_events(&env, &default_pool_events);
+    assert_eq!(rebuilt.get_root(), client.get_merkle_root());
+    assert_eq!(rebuilt.get_leaves(), &client.get_commitments());
+
+    let rebuilt = rebuild_from_events(&env, &other_pool_events);
+    assert_eq!(rebuilt.get_leaf_count(), 3);
+    assert_eq!(rebuilt.get_root(), client.get_pool_merkle_root(&1));
+}
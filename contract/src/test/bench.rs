@@ -0,0 +1,73 @@
+//! Per-operation budget reports
+//!
+//! Each test runs one operation under `with_budget_report`, so
+//! `cargo test -p privacy-pools bench -- --nocapture` lists what every operation costs.
+//! The ceilings that fail the build live in `test_deposit_and_withdraw_instruction_budget`.
+
+use super::*;
+
+/// Resets the budget, runs `f` and prints the CPU instructions and memory bytes it
+/// consumed under `label`
+pub(super) fn with_budget_report<T>(env: &Env, label: &str, f: impl FnOnce() -> T) -> T {
+    env.cost_estimate().budget().reset_unlimited();
+    let result = f();
+    let budget = env.cost_estimate().budget();
+    std::println!(
+        "[budget] {}: cpu {} instructions, mem {} bytes",
+        label,
+        budget.cpu_instruction_cost(),
+        budget.memory_bytes_cost()
+    );
+    result
+}
+
+#[test]
+fn bench_deposit() {
+    let env = Env::default();
+    let (token_id, contract_id, _admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    env.mock_all_auths();
+
+    let alice = Address::generate(&env);
+    MockTokenClient::new(&env, &token_id).mint(&alice, &2000000000);
+    with_budget_report(&env, "deposit (empty tree)", || {
+        client.deposit(&alice, &BytesN::from_array(&env, &[1u8; 32]))
+    });
+    with_budget_report(&env, "deposit (one leaf)", || {
+        client.deposit(&alice, &BytesN::from_array(&env, &[2u8; 32]))
+    });
+    assert_eq!(client.get_commitment_count(), 2);
+}
+
+#[test]
+fn bench_withdraw() {
+    let env = Env::default();
+    let (token_id, contract_id, admin) = setup_test_environment(&env);
+    let client = PrivacyPoolsContractClient::new(&env, &contract_id);
+    env.mock_all_auths();
+
+    prepare_fixture_withdrawal(&env, &client, &token_id, &admin);
+    let bob = Address::generate(&env);
+    let result = with_budget_report(&env, "withdraw", || {
+        client.withdraw(&bob, &init_proof(&env), &init_pub_signals(&env))
+    });
+    assert_eq!(result, vec![&env]);
+}
+
+#[test]
+fn bench_tree_insert_and_generate_proof() {
+    let env = Env::default();
+    let mut tree = LeanIMT::new(&env, 20);
+    with_budget_report(&env, "LeanIMT::insert (depth 20, empty tree)", || {
+        tree.insert_u64(1).unwrap()
+    });
+    with_budget_report(&env, "LeanIMT::insert (depth 20, one leaf)", || {
+        tree.insert_u64(2).unwrap()
+    });
+
+    let (siblings, depth) = with_budget_report(&env, "LeanIMT::generate_proof (depth 20)", || {
+        tree.generate_proof(1).unwrap()
+    });
+    assert_eq!(depth, 20);
+    assert_eq!(siblings.len(), 20);
+}
@@ -34,8 +34,11 @@ if [ -z "$VK_HEX" ]; then
     exit 1
 fi
 
+# Encode the coin scope as the contract's 32-byte scope (right-aligned, zero-padded)
+SCOPE_HEX=$(python3 -c "print(b'demo_pool'.rjust(32, b'\0').hex())")
+
 echo "🚀 Deploying contract to $NETWORK..."
-DEPLOY_OUTPUT=$(stellar contract deploy --wasm target/wasm32v1-none/release/privacy_pools.optimized.wasm --source demo_user --network $NETWORK -- --vk_bytes $VK_HEX --token_address $TOKEN_ADDRESS --admin demo_user 2>&1)
+DEPLOY_OUTPUT=$(stellar contract deploy --wasm target/wasm32v1-none/release/privacy_pools.optimized.wasm --source demo_user --network $NETWORK -- --vk_bytes $VK_HEX --token_address $TOKEN_ADDRESS --admin demo_user --scope $SCOPE_HEX --denomination 1000000000 --root_history_size 0 2>&1)
 if [ $? -ne 0 ]; then
     echo "❌ Error: Contract deployment failed"
     echo "$DEPLOY_OUTPUT"
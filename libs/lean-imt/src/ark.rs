@@ -0,0 +1,134 @@
+//! Env-free tree building over arkworks' `Fr` (requires the `ark` feature)
+//!
+//! Off-chain tools that only need a root do not have to go through a soroban `Env` and
+//! its budget. This module runs the same Poseidon permutation as soroban-poseidon with
+//! plain `ark-bls12-381` arithmetic, so `build_root` gives exactly the root `LeanIMT`
+//! computes for the same leaves. Only `HashFunction::Poseidon` is covered.
+
+use std::vec::Vec;
+
+use ark_bls12_381::Fr;
+use ark_ff::{BigInteger, PrimeField, Zero};
+
+use crate::LeanIMTError;
+
+mod params;
+
+const WIDTH: usize = 3;
+const FULL_ROUNDS: usize = 8;
+const PARTIAL_ROUNDS: usize = 56;
+
+/// Decodes a scalar from 32 big-endian bytes, reducing modulo the field
+pub fn fr_from_be_bytes(bytes: &[u8; 32]) -> Fr {
+    Fr::from_be_bytes_mod_order(bytes)
+}
+
+/// Encodes a scalar as 32 big-endian bytes, the encoding of `scalar_to_be_bytes`
+pub fn fr_to_be_bytes(value: &Fr) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&value.into_bigint().to_bytes_be());
+    bytes
+}
+
+fn fr_from_hex(hex: &str) -> Fr {
+    let mut bytes = [0u8; 32];
+    for (index, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * index..2 * index + 2], 16).unwrap();
+    }
+    fr_from_be_bytes(&bytes)
+}
+
+/// `Poseidon255` without an `Env`: Poseidon(2) over the BLS12-381 scalar field
+#[derive(Clone)]
+pub struct ArkPoseidon255 {
+    mds: [[Fr; WIDTH]; WIDTH],
+    round_constants: Vec<[Fr; WIDTH]>,
+}
+
+impl ArkPoseidon255 {
+    pub fn new() -> Self {
+        Self {
+            mds: params::MDS.map(|row| row.map(fr_from_hex)),
+            round_constants: params::ROUND_CONSTANTS
+                .iter()
+                .map(|row| row.map(fr_from_hex))
+                .collect(),
+        }
+    }
+
+    /// Hashes two field elements: `Poseidon(left, right)`
+    pub fn hash_two(&self, left: &Fr, right: &Fr) -> Fr {
+        let mut state = [Fr::zero(), *left, *right];
+        let partial_end = FULL_ROUNDS / 2 + PARTIAL_ROUNDS;
+        for (round, constants) in self.round_constants.iter().enumerate() {
+            for (element, constant) in state.iter_mut().zip(constants) {
+                *element += constant;
+            }
+            if (FULL_ROUNDS / 2..partial_end).contains(&round) {
+                state[0] = sbox(state[0]);
+            } else {
+                state = state.map(sbox);
+            }
+            state = self.mix(&state);
+        }
+        state[0]
+    }
+
+    fn mix(&self, state: &[Fr; WIDTH]) -> [Fr; WIDTH] {
+        self.mds.map(|row| {
+            row.iter()
+                .zip(state)
+                .fold(Fr::zero(), |sum, (entry, element)| sum + *entry * element)
+        })
+    }
+}
+
+impl Default for ArkPoseidon255 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// x^5
+fn sbox(x: Fr) -> Fr {
+    let square = x * x;
+    square * square * x
+}
+
+/// Same as `crate::zero_hashes`: the all-zero subtree root at every level `0..=depth`
+pub fn zero_hashes(hasher: &ArkPoseidon255, depth: u32) -> Vec<Fr> {
+    let mut hashes = Vec::with_capacity(depth as usize + 1);
+    let mut current = Fr::zero();
+    hashes.push(current);
+    for _ in 0..depth {
+        current = hasher.hash_two(&current, &current);
+        hashes.push(current);
+    }
+    hashes
+}
+
+/// Computes the root `LeanIMT` would have after inserting `leaves` in order
+///
+/// Missing leaves are zero, as in `LeanIMT`. Returns `LeanIMTError::TreeFull` if there
+/// are more than `2^depth` leaves.
+pub fn build_root(depth: u32, leaves: &[Fr]) -> Result<Fr, LeanIMTError> {
+    let capacity = 1u64.checked_shl(depth).unwrap_or(u64::MAX);
+    if leaves.len() as u64 > capacity {
+        return Err(LeanIMTError::TreeFull);
+    }
+
+    let hasher = ArkPoseidon255::new();
+    let zeros = zero_hashes(&hasher, depth);
+    if leaves.is_empty() {
+        return Ok(zeros[depth as usize]);
+    }
+
+    let mut level_nodes = leaves.to_vec();
+    for zero in zeros.iter().take(depth as usize) {
+        level_nodes = level_nodes
+            .chunks(2)
+            .map(|pair| hasher.hash_two(&pair[0], pair.get(1).unwrap_or(zero)))
+            .collect();
+    }
+    Ok(level_nodes[0])
+}
@@ -0,0 +1,49 @@
+//! Coin hashes of `commitment.circom`, shared by coinutils and the contract
+//!
+//! ```text
+//! label         = Poseidon(scope, nonce)
+//! precommitment = Poseidon(nullifier, secret)
+//! commitment    = Poseidon(value, label, precommitment)
+//! nullifierHash = Poseidon(nullifier)
+//! ```
+//!
+//! All of them are circomlib's Poseidon over the BLS12-381 scalar field with state width
+//! `inputs + 1`. None of the inputs is domain-tagged: the circuit fixes this formula,
+//! and a tag would have to be added to `commitment.circom` (and a new trusted setup
+//! run) in the same change. Host tools and the contract both call these functions, so
+//! neither can drift from the other.
+
+use soroban_poseidon::poseidon_hash;
+use soroban_sdk::{crypto::bls12_381::Fr as BlsScalar, Env, Vec};
+
+/// `Poseidon(scope, nonce)`, the label that ties a coin to its pool
+pub fn label(env: &Env, scope: &BlsScalar, nonce: &BlsScalar) -> BlsScalar {
+    let inputs = Vec::from_array(env, [scope.to_u256(), nonce.to_u256()]);
+    BlsScalar::from_u256(poseidon_hash::<3, BlsScalar>(env, &inputs))
+}
+
+/// `Poseidon(nullifier, secret)`, the part of a commitment only the owner can open
+pub fn precommitment(env: &Env, nullifier: &BlsScalar, secret: &BlsScalar) -> BlsScalar {
+    let inputs = Vec::from_array(env, [nullifier.to_u256(), secret.to_u256()]);
+    BlsScalar::from_u256(poseidon_hash::<3, BlsScalar>(env, &inputs))
+}
+
+/// `Poseidon(value, label, precommitment)`, the leaf stored in the state tree
+pub fn commitment(
+    env: &Env,
+    value: &BlsScalar,
+    label: &BlsScalar,
+    precommitment: &BlsScalar,
+) -> BlsScalar {
+    let inputs = Vec::from_array(
+        env,
+        [value.to_u256(), label.to_u256(), precommitment.to_u256()],
+    );
+    BlsScalar::from_u256(poseidon_hash::<4, BlsScalar>(env, &inputs))
+}
+
+/// `Poseidon(nullifier)`, the value a withdrawal reveals to mark the coin spent
+pub fn nullifier_hash(env: &Env, nullifier: &BlsScalar) -> BlsScalar {
+    let inputs = Vec::from_array(env, [nullifier.to_u256()]);
+    BlsScalar::from_u256(poseidon_hash::<2, BlsScalar>(env, &inputs))
+}
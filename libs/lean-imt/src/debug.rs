@@ -0,0 +1,62 @@
+//! Host-side pretty printer for `LeanIMT` (requires the `std` feature)
+//!
+//! Renders the occupied part of the tree, root first, one node per line and indented
+//! by its distance from the root. Only subtrees that hold at least one leaf are
+//! expanded, so a depth-20 tree with a handful of deposits stays readable.
+
+use std::eprint;
+use std::fmt::Write;
+use std::string::String;
+
+use crate::json::bytes_to_decimal;
+use crate::LeanIMT;
+
+/// Decimal digits kept before a node value is cut short
+const MAX_DIGITS: usize = 12;
+
+fn truncated_decimal(decimal: String) -> String {
+    if decimal.len() <= MAX_DIGITS {
+        decimal
+    } else {
+        let mut short = String::from(&decimal[..MAX_DIGITS]);
+        short.push_str("...");
+        short
+    }
+}
+
+impl LeanIMT {
+    /// Prints every occupied node to stderr as an indented tree
+    ///
+    /// Each line reads `L<level>[<index>] <value>` with the value in decimal, truncated
+    /// to its leading digits. Nodes a light tree does not know are shown as `?`.
+    pub fn print_tree(&self) {
+        eprint!("{}", self.render_tree());
+    }
+
+    /// Text written by `print_tree`
+    pub(crate) fn render_tree(&self) -> String {
+        let mut out = String::new();
+        self.render_node(&mut out, self.depth, 0);
+        out
+    }
+
+    fn render_node(&self, out: &mut String, level: u32, index: u32) {
+        let indent = 2 * (self.depth - level) as usize;
+        let value = match self.get_node(level, index) {
+            Some(node) => truncated_decimal(bytes_to_decimal(&node)),
+            None => String::from("?"),
+        };
+        let _ = writeln!(out, "{:indent$}L{}[{}] {}", "", level, index, value);
+
+        if level == 0 {
+            return;
+        }
+        // A child is worth expanding only if its first leaf has been inserted
+        let child_span = 1u64 << (level - 1);
+        for child in [2 * index, 2 * index + 1] {
+            if (child as u64) * child_span < self.leaf_count as u64 {
+                self.render_node(out, level - 1, child);
+            }
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Host-side JSON persistence for `LeanIMT` (requires the `std` feature)
+//!
+//! Format: `{"depth": 20, "leaves": ["123", ...], "root": "456"}` with every field
+//! element written as a decimal string, the same representation coinutils uses.
+
+use std::format;
+use std::string::{String, ToString};
+
+use num_bigint::BigUint;
+use serde_json::{json, Value};
+use soroban_sdk::{BytesN, Env, Vec};
+
+use crate::{LeanIMT, FIELD_MODULUS};
+
+pub(crate) fn bytes_to_decimal(bytes: &BytesN<32>) -> String {
+    BigUint::from_bytes_be(&bytes.to_array()).to_str_radix(10)
+}
+
+fn decimal_to_bytes(env: &Env, decimal: &str) -> Result<BytesN<32>, String> {
+    let value = BigUint::parse_bytes(decimal.as_bytes(), 10)
+        .ok_or_else(|| format!("Invalid decimal field element: {}", decimal))?;
+    let modulus = BigUint::from_bytes_be(&FIELD_MODULUS);
+    if value >= modulus {
+        return Err(format!("Field element exceeds modulus: {}", decimal));
+    }
+
+    let be = value.to_bytes_be();
+    let mut padded = [0u8; 32];
+    padded[32 - be.len()..].copy_from_slice(&be);
+    Ok(BytesN::from_array(env, &padded))
+}
+
+impl LeanIMT {
+    /// Serializes leaves, depth and root to JSON so a host tool can reload the tree
+    /// with `from_json` instead of re-parsing and re-inserting every commitment
+    pub fn to_json(&self) -> String {
+        let leaves: std::vec::Vec<String> = self
+            .leaves
+            .iter()
+            .map(|leaf| bytes_to_decimal(&leaf))
+            .collect();
+
+        json!({
+            "depth": self.depth,
+            "leaves": leaves,
+            "root": bytes_to_decimal(&self.root),
+        })
+        .to_string()
+    }
+
+    /// Restores a tree written by `to_json`. The stored root is trusted as-is,
+    /// exactly like `from_storage`; nothing is re-hashed on load.
+    pub fn from_json(env: &Env, json: &str) -> Result<Self, String> {
+        let value: Value =
+            serde_json::from_str(json).map_err(|e| format!("Invalid tree JSON: {}", e))?;
+
+        let depth = value
+            .get("depth")
+            .and_then(Value::as_u64)
+            .and_then(|depth| u32::try_from(depth).ok())
+            .ok_or_else(|| "Missing or invalid \"depth\"".to_string())?;
+        let root = value
+            .get("root")
+            .and_then(Value::as_str)
+            .ok_or_else(|| "Missing or invalid \"root\"".to_string())?;
+        let leaf_values = value
+            .get("leaves")
+            .and_then(Value::as_array)
+            .ok_or_else(|| "Missing or invalid \"leaves\"".to_string())?;
+
+        let capacity = 1u64.checked_shl(depth).unwrap_or(u64::MAX);
+        if leaf_values.len() as u64 > capacity {
+            return Err(format!(
+                "{} leaves do not fit in a tree of depth {}",
+                leaf_values.len(),
+                depth
+            ));
+        }
+
+        let mut leaves = Vec::new(env);
+        for (index, leaf) in leaf_values.iter().enumerate() {
+            let decimal = leaf
+                .as_str()
+                .ok_or_else(|| format!("Leaf {} is not a decimal string", index))?;
+            leaves.push_back(decimal_to_bytes(env, decimal)?);
+        }
+
+        let root = decimal_to_bytes(env, root)?;
+        Ok(LeanIMT::from_storage(env, leaves, depth, root))
+    }
+}
@@ -1,6 +1,9 @@
 #![no_std]
 
-use soroban_poseidon::{poseidon_hash, PoseidonSponge};
+#[cfg(feature = "std")]
+extern crate std;
+
+use soroban_poseidon::{poseidon_hash, Poseidon2Sponge, PoseidonSponge};
 
 use soroban_sdk::{
     crypto::bls12_381::Fr as BlsScalar, symbol_short, vec, BytesN, Env, Map, Symbol, Vec, U256,
@@ -10,31 +13,296 @@ use soroban_sdk::{
 pub const TREE_ROOT_KEY: Symbol = symbol_short!("root");
 pub const TREE_DEPTH_KEY: Symbol = symbol_short!("depth");
 pub const TREE_LEAVES_KEY: Symbol = symbol_short!("leaves");
+/// Frontier (`get_frontier`) saved next to the leaves, for `from_storage_with_frontier`
+pub const TREE_FRONTIER_KEY: Symbol = symbol_short!("frontier");
+/// Leaf count of a light tree, saved instead of its leaves (`to_light_storage`)
+pub const TREE_LEAF_COUNT_KEY: Symbol = symbol_short!("count");
+
+/// Domain tag for leaves in a domain-separated tree: `leaf node = Poseidon(LEAF_DOMAIN, leaf)`
+///
+/// Matches `LEAF_DOMAIN` in `circuits/merkleProofDomainSeparated.circom`.
+pub const LEAF_DOMAIN: u32 = 1;
+
+/// Domain tag for internal nodes in a domain-separated tree:
+/// `parent = Poseidon(NODE_DOMAIN, left, right)`
+///
+/// Matches `NODE_DOMAIN` in `circuits/merkleProofDomainSeparated.circom`.
+pub const NODE_DOMAIN: u32 = 2;
+
+/// BLS12-381 scalar field modulus (big-endian); leaves and roots are below it
+pub const FIELD_MODULUS: [u8; 32] = [
+    0x73, 0xed, 0xa7, 0x53, 0x29, 0x9d, 0x7d, 0x48, 0x33, 0x39, 0xd8, 0x08, 0x09, 0xa1, 0xd8, 0x05,
+    0x53, 0xbd, 0xa4, 0x02, 0xff, 0xfe, 0x5b, 0xfe, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x01,
+];
+
+/// Poseidon state width `t` of the parent hash in trees from `new` and `from_storage`
+///
+/// Width 3 is circomlib's `Poseidon(2)`, the hash of `merkleProof.circom`.
+pub const DEFAULT_POSEIDON_WIDTH: u32 = 3;
+
+/// Poseidon state widths `new_with_poseidon_width` accepts: every width soroban-poseidon
+/// supports over BLS12-381 whose rate fits the two children
+pub const SUPPORTED_POSEIDON_WIDTHS: [u32; 4] = [3, 4, 5, 6];
 
 /// Converts u64 to BlsScalar for test compatibility
 pub fn u64_to_bls_scalar(env: &Env, value: u64) -> BlsScalar {
-    BlsScalar::from_u256(U256::from_u32(env, value as u32))
+    BlsScalar::from_u256(U256::from_u128(env, value as u128))
 }
 
-/// Converts BlsScalar to BytesN<32> for Soroban storage
-pub fn bls_scalar_to_bytes(scalar: BlsScalar) -> BytesN<32> {
+/// Encodes a scalar as 32 big-endian bytes.
+///
+/// This is the canonical encoding for everything stored on-chain (leaves, roots,
+/// nullifiers) and for public signals, matching snarkjs' decimal-to-bytes output.
+pub fn scalar_to_be_bytes(scalar: &BlsScalar) -> BytesN<32> {
     scalar.to_bytes()
 }
 
-/// Converts BytesN<32> to BlsScalar for computation
+/// Decodes a scalar from 32 big-endian bytes. Inverse of `scalar_to_be_bytes`.
+pub fn scalar_from_be_bytes(bytes: &BytesN<32>) -> BlsScalar {
+    BlsScalar::from_bytes(bytes.clone())
+}
+
+/// Encodes a scalar as 32 little-endian bytes.
+///
+/// Only for interop with little-endian tooling (e.g. `.wtns` files or arkworks'
+/// `to_bytes_le`); never store this encoding on-chain.
+pub fn scalar_to_le_bytes(scalar: &BlsScalar) -> BytesN<32> {
+    let be = scalar_to_be_bytes(scalar);
+    let mut le = be.to_array();
+    le.reverse();
+    BytesN::from_array(be.env(), &le)
+}
+
+/// Decodes a scalar from 32 little-endian bytes. Inverse of `scalar_to_le_bytes`.
+pub fn scalar_from_le_bytes(bytes: &BytesN<32>) -> BlsScalar {
+    let mut be = bytes.to_array();
+    be.reverse();
+    scalar_from_be_bytes(&BytesN::from_array(bytes.env(), &be))
+}
+
+/// Converts BlsScalar to BytesN<32> for Soroban storage (big-endian)
+pub fn bls_scalar_to_bytes(scalar: BlsScalar) -> BytesN<32> {
+    scalar_to_be_bytes(&scalar)
+}
+
+/// Converts BytesN<32> to BlsScalar for computation (big-endian)
 pub fn bytes_to_bls_scalar(bytes_n: &BytesN<32>) -> BlsScalar {
-    BlsScalar::from_bytes(bytes_n.clone())
+    scalar_from_be_bytes(bytes_n)
+}
+
+/// Errors returned by LeanIMT operations
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LeanIMTError {
+    /// The tree already holds `2^depth` leaves
+    TreeFull,
+    /// The Poseidon width is not in `SUPPORTED_POSEIDON_WIDTHS`
+    UnsupportedWidth,
+    /// Poseidon constants are not `expected` bytes long for their width and rounds;
+    /// `expected` is `u64::MAX` when the rounds are too many to count
+    ConstantsLength { expected: u64, actual: u32 },
+    /// A Poseidon round constant or MDS entry is not below the field modulus
+    NonCanonicalConstant,
+}
+
+impl LeanIMTError {
+    /// Human-readable description of the error
+    pub fn message(&self) -> &'static str {
+        match self {
+            LeanIMTError::TreeFull => "Tree is at capacity: cannot insert more leaves",
+            LeanIMTError::UnsupportedWidth => "Unsupported Poseidon width",
+            LeanIMTError::ConstantsLength { .. } => "Poseidon constants have the wrong length",
+            LeanIMTError::NonCanonicalConstant => {
+                "Poseidon constant is not below the field modulus"
+            }
+        }
+    }
+}
+
+impl core::fmt::Display for LeanIMTError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            LeanIMTError::ConstantsLength { expected, actual } => write!(
+                f,
+                "{}: expected {} bytes, got {}",
+                self.message(),
+                expected,
+                actual
+            ),
+            _ => f.write_str(self.message()),
+        }
+    }
+}
+
+/// Hash function used to combine two child nodes into their parent.
+///
+/// `Poseidon` is the canonical choice: it matches circomlib's Poseidon as used by
+/// `circuits/merkleProof.circom`, so only roots built with it verify in the withdrawal
+/// circuit. `Poseidon2` yields different roots for the same leaves and exists for
+/// off-chain experiments only; trees restored via `from_storage` are always `Poseidon`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HashFunction {
+    #[default]
+    Poseidon,
+    Poseidon2,
+}
+
+impl HashFunction {
+    /// Hashes two child nodes into their parent with this function's `HashBackend`
+    pub fn hash_two(self, env: &Env, left: &BlsScalar, right: &BlsScalar) -> BlsScalar {
+        match self {
+            HashFunction::Poseidon => Poseidon255::new(env).hash_two(left, right),
+            HashFunction::Poseidon2 => Poseidon2Hasher::new(env).hash_two(left, right),
+        }
+    }
+}
+
+/// Sponge for the configured hash function, created once and reused across a loop
+enum PairSponge {
+    Poseidon(PoseidonSponge<3, BlsScalar>),
+    Poseidon2(Poseidon2Sponge<3, BlsScalar>),
+    /// `Poseidon(left, right)` with a state wider than 3, for `new_with_poseidon_width`
+    PoseidonT4(PoseidonSponge<4, BlsScalar>),
+    PoseidonT5(PoseidonSponge<5, BlsScalar>),
+    PoseidonT6(PoseidonSponge<6, BlsScalar>),
+    /// `Poseidon(NODE_DOMAIN, left, right)`, for domain-separated trees
+    DomainSeparated(PoseidonSponge<4, BlsScalar>),
+}
+
+impl PairSponge {
+    fn new(env: &Env, hash_function: HashFunction) -> Self {
+        match hash_function {
+            HashFunction::Poseidon => PairSponge::Poseidon(PoseidonSponge::new(env)),
+            HashFunction::Poseidon2 => PairSponge::Poseidon2(Poseidon2Sponge::new(env)),
+        }
+    }
+
+    /// Poseidon sponge of state width `width`, one of `SUPPORTED_POSEIDON_WIDTHS`
+    fn new_poseidon(env: &Env, width: u32) -> Self {
+        match width {
+            4 => PairSponge::PoseidonT4(PoseidonSponge::new(env)),
+            5 => PairSponge::PoseidonT5(PoseidonSponge::new(env)),
+            6 => PairSponge::PoseidonT6(PoseidonSponge::new(env)),
+            _ => PairSponge::Poseidon(PoseidonSponge::new(env)),
+        }
+    }
+
+    fn new_domain_separated(env: &Env) -> Self {
+        PairSponge::DomainSeparated(PoseidonSponge::new(env))
+    }
+
+    fn hash_pair(&mut self, env: &Env, left: BlsScalar, right: BlsScalar) -> BlsScalar {
+        let left_u256 = BlsScalar::to_u256(&left);
+        let right_u256 = BlsScalar::to_u256(&right);
+        let result_u256 = match self {
+            PairSponge::Poseidon(sponge) => {
+                sponge.compute_hash(&Vec::from_array(env, [left_u256, right_u256]))
+            }
+            PairSponge::Poseidon2(sponge) => {
+                sponge.compute_hash(&Vec::from_array(env, [left_u256, right_u256]))
+            }
+            PairSponge::PoseidonT4(sponge) => {
+                sponge.compute_hash(&zero_padded(env, [left_u256, right_u256], 3))
+            }
+            PairSponge::PoseidonT5(sponge) => {
+                sponge.compute_hash(&zero_padded(env, [left_u256, right_u256], 4))
+            }
+            PairSponge::PoseidonT6(sponge) => {
+                sponge.compute_hash(&zero_padded(env, [left_u256, right_u256], 5))
+            }
+            PairSponge::DomainSeparated(sponge) => sponge.compute_hash(&Vec::from_array(
+                env,
+                [U256::from_u32(env, NODE_DOMAIN), left_u256, right_u256],
+            )),
+        };
+        BlsScalar::from_u256(result_u256)
+    }
+}
+
+/// `inputs` followed by zeros up to the sponge rate `rate`
+///
+/// soroban-poseidon fills the unused rate with zeros itself in older releases and
+/// requires a full rate in newer ones; explicit zeros hash the same under both.
+fn zero_padded<const N: usize>(env: &Env, inputs: [U256; N], rate: u32) -> Vec<U256> {
+    let mut padded = Vec::from_array(env, inputs);
+    while padded.len() < rate {
+        padded.push_back(U256::from_u32(env, 0));
+    }
+    padded
+}
+
+/// Smallest depth whose tree holds `n` leaves, i.e. the least `depth` with `2^depth >= n`
+///
+/// 0 and 1 leaves fit in a depth-0 tree; `u32::MAX` leaves need depth 32.
+pub fn depth_for_capacity(n: u32) -> u32 {
+    if n <= 1 {
+        0
+    } else {
+        u32::BITS - (n - 1).leading_zeros()
+    }
+}
+
+/// Returns the root of an all-zero subtree at every level `0..=depth`, hashed with Poseidon
+///
+/// Entry 0 is the zero leaf and entry `depth` is the root of an empty tree of that depth.
+/// These values depend only on the level, so one table serves every empty subtree.
+pub fn zero_hashes(env: &Env, depth: u32) -> Vec<BlsScalar> {
+    zero_hashes_with_hash_function(env, depth, HashFunction::Poseidon)
+}
+
+/// Same as `zero_hashes` for a specific `HashFunction`
+pub fn zero_hashes_with_hash_function(
+    env: &Env,
+    depth: u32,
+    hash_function: HashFunction,
+) -> Vec<BlsScalar> {
+    zero_hashes_with_sponge(
+        env,
+        depth,
+        BlsScalar::from_u256(U256::from_u32(env, 0)),
+        PairSponge::new(env, hash_function),
+    )
+}
+
+fn zero_hashes_with_sponge(
+    env: &Env,
+    depth: u32,
+    zero_leaf: BlsScalar,
+    mut sponge: PairSponge,
+) -> Vec<BlsScalar> {
+    let mut current = zero_leaf;
+    let mut hashes = vec![env, current.clone()];
+
+    // hash(level_n, level_n) = level_n+1
+    for _ in 0..depth {
+        current = sponge.hash_pair(env, current.clone(), current);
+        hashes.push_back(current.clone());
+    }
+
+    hashes
 }
 
 /// Lean Incremental Merkle Tree implementation with hybrid approach:
 /// - Internal computation uses BlsScalar for perfect Circom compatibility
 /// - Storage and API uses BytesN<32> for Soroban compatibility
+#[derive(Clone)]
 pub struct LeanIMT {
     env: Env,
+    // Inserted leaves; always empty in a light tree
     leaves: Vec<BytesN<32>>,
+    // Number of inserted leaves, which a light tree keeps without the leaves themselves
+    leaf_count: u32,
+    // Light trees keep only the nodes needed to insert (see `new_with_light_mode`)
+    light: bool,
     depth: u32,
     capacity: u32, // Pre-computed capacity (2^depth), cached for efficiency
     root: BytesN<32>,
+    hash_function: HashFunction,
+    // State width of the Poseidon parent hash (`HashFunction::Poseidon` only)
+    poseidon_width: u32,
+    // Leaves are tagged with LEAF_DOMAIN and nodes with NODE_DOMAIN (Poseidon only)
+    domain_separated: bool,
+    // Value of every leaf position that was never inserted (zero unless configured)
+    zero_leaf: BlsScalar,
     // Hybrid cache system:
     // 1. subtree_cache: Dynamic programming cache for empty tree levels
     //    Key: level -> Value: hash of subtrees at that level (all identical for empty trees)
@@ -44,17 +312,71 @@ pub struct LeanIMT {
     sparse_cache: Map<(u32, u32), BlsScalar>,
 }
 
+/// Lazy merkle proof returned by `LeanIMT::proof_iter`
+///
+/// Yields `(sibling, is_right)` pairs from the leaf level up to the root.
+pub struct ProofIter<'a> {
+    tree: &'a LeanIMT,
+    index: u32,
+    level: u32,
+}
+
+impl Iterator for ProofIter<'_> {
+    type Item = (BlsScalar, bool);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.level >= self.tree.depth {
+            return None;
+        }
+
+        let sibling = self.tree.proof_sibling(self.level, self.index);
+        let is_right = !self.index.is_multiple_of(2);
+        self.index /= 2;
+        self.level += 1;
+        Some((sibling, is_right))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.tree.depth - self.level) as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for ProofIter<'_> {}
+
+/// Merkle proof for several leaves at once, returned by `LeanIMT::generate_multiproof`
+#[derive(Clone, Debug, PartialEq)]
+pub struct MultiProof {
+    /// Indices of the proven leaves, ascending and without repeats
+    pub indices: Vec<u32>,
+    /// Siblings that can't be computed from the proven leaves, level by level from the
+    /// leaves up, and left to right within a level
+    pub nodes: Vec<BlsScalar>,
+}
+
 impl LeanIMT {
     /// Creates a new LeanIMT with a fixed depth. Missing leaves are assumed zero.
     pub fn new(env: &Env, depth: u32) -> Self {
+        Self::new_with_hash_function(env, depth, HashFunction::Poseidon)
+    }
+
+    /// Creates a new LeanIMT with a fixed depth, hashing nodes with the given function.
+    /// Use `HashFunction::Poseidon` for anything that has to verify against the circuit.
+    pub fn new_with_hash_function(env: &Env, depth: u32, hash_function: HashFunction) -> Self {
         let capacity = 1u32.checked_shl(depth).unwrap_or(u32::MAX);
         let env_clone = env.clone();
         let mut tree = Self {
             env: env_clone.clone(),
             leaves: vec![&env_clone],
+            leaf_count: 0,
+            light: false,
             depth,
             capacity,
             root: BytesN::from_array(&env_clone, &[0u8; 32]),
+            hash_function,
+            poseidon_width: DEFAULT_POSEIDON_WIDTH,
+            domain_separated: false,
+            zero_leaf: BlsScalar::from_u256(U256::from_u32(&env_clone, 0)),
             subtree_cache: Map::new(&env_clone),
             sparse_cache: Map::new(&env_clone),
         };
@@ -62,26 +384,223 @@ impl LeanIMT {
         tree
     }
 
+    /// Creates a new Poseidon LeanIMT whose parent hash uses state width `width`
+    ///
+    /// The parent is still `Poseidon(left, right)`, with the rest of the wider state's
+    /// rate zero; a wider state only changes the permutation, and with it every root. Width 3 gives the same tree
+    /// as `new`; other widths don't match `merkleProof.circom` and are for experiments
+    /// with circuits built on them. Like `Poseidon2` trees, they can't be restored with
+    /// `from_storage`. Returns `UnsupportedWidth` for a width outside
+    /// `SUPPORTED_POSEIDON_WIDTHS`.
+    pub fn new_with_poseidon_width(
+        env: &Env,
+        depth: u32,
+        width: u32,
+    ) -> Result<Self, LeanIMTError> {
+        if !SUPPORTED_POSEIDON_WIDTHS.contains(&width) {
+            return Err(LeanIMTError::UnsupportedWidth);
+        }
+
+        let mut tree = Self::new(env, depth);
+        if width != DEFAULT_POSEIDON_WIDTH {
+            tree.poseidon_width = width;
+            tree.recompute_tree();
+        }
+        Ok(tree)
+    }
+
+    /// Creates a new LeanIMT with a fixed depth, optionally in light mode
+    ///
+    /// A light tree stores no leaves, only their count and the frontier (the filled
+    /// subtree roots along the right edge), which is all `insert` needs. That keeps
+    /// storage constant instead of growing by 32 bytes per leaf, and loading it with
+    /// `from_light_storage` reads `depth` nodes instead of every leaf. In exchange the
+    /// tree can't prove or look up old leaves: `generate_proof`, `get_leaf`, `contains`
+    /// and `get_sibling` find nothing, and `get_node` only returns nodes this instance
+    /// has computed. Clients then rebuild proofs from the inserted leaves themselves,
+    /// e.g. from deposit events. Persist it with `to_light_storage`, not `to_storage`.
+    /// `false` gives the same tree as `new`.
+    pub fn new_with_light_mode(env: &Env, depth: u32, light: bool) -> Self {
+        let mut tree = Self::new(env, depth);
+        tree.light = light;
+        tree
+    }
+
+    /// Whether this is a light tree that keeps no leaves (see `new_with_light_mode`)
+    pub fn is_light(&self) -> bool {
+        self.light
+    }
+
+    /// Creates a new LeanIMT with a fixed depth, optionally with domain separation.
+    ///
+    /// With `domain_separated` set, each leaf enters the tree as `Poseidon(LEAF_DOMAIN, leaf)`
+    /// and each parent is `Poseidon(NODE_DOMAIN, left, right)`, so a leaf value can never
+    /// be mistaken for an internal node. Missing leaves are still zero. This changes every
+    /// root: such trees only verify against `merkleProofDomainSeparated.circom`, not the
+    /// withdrawal circuit. `false` gives the same tree as `new`.
+    pub fn new_with_domain_separation(env: &Env, depth: u32, domain_separated: bool) -> Self {
+        let mut tree = Self::new(env, depth);
+        if domain_separated {
+            tree.domain_separated = true;
+            tree.recompute_tree();
+        }
+        tree
+    }
+
+    /// Creates a new LeanIMT with a fixed depth whose missing leaves are `zero_leaf`
+    ///
+    /// For circuits that pad with a nonzero "empty leaf" constant (e.g. a hash of
+    /// `"empty"`) so a legitimately zero leaf can't be confused with an empty slot. The
+    /// constant is used for every empty position: empty subtree hashes, proof siblings
+    /// and the root of a depth-0 tree. A zero `zero_leaf` gives the same tree as `new`,
+    /// which is what `merkleProof.circom` and the withdrawal circuit expect.
+    pub fn new_with_zero_leaf(env: &Env, depth: u32, zero_leaf: BlsScalar) -> Self {
+        let mut tree = Self::new(env, depth);
+        tree.zero_leaf = zero_leaf;
+        tree.recompute_tree();
+        tree
+    }
+
+    /// Gets the value used for leaf positions that were never inserted
+    pub fn get_zero_leaf(&self) -> BlsScalar {
+        self.zero_leaf.clone()
+    }
+
+    /// Builds a tree from all of its leaves at once
+    ///
+    /// Hashes the tree level by level instead of inserting leaf by leaf, so every
+    /// non-empty node is computed exactly once. The result is the same tree (root and
+    /// caches) that inserting `leaves` in order would give.
+    pub fn new_from_leaves(
+        env: &Env,
+        depth: u32,
+        leaves: Vec<BytesN<32>>,
+    ) -> Result<Self, LeanIMTError> {
+        let mut tree = Self::new(env, depth);
+        if leaves.len() > tree.capacity {
+            return Err(LeanIMTError::TreeFull);
+        }
+        if !leaves.is_empty() {
+            tree.build_from_leaves(leaves);
+        }
+        Ok(tree)
+    }
+
+    /// Fills an empty tree with `leaves` level by level, keeping its hash function and
+    /// empty leaf. The caller has already checked that `leaves` fits.
+    fn build_from_leaves(&mut self, leaves: Vec<BytesN<32>>) {
+        let env = self.env.clone();
+        let mut level_nodes: Vec<BlsScalar> = vec![&env];
+        for leaf in leaves.iter() {
+            level_nodes.push_back(self.leaf_node(bytes_to_bls_scalar(&leaf)));
+        }
+        self.leaf_count = leaves.len();
+        self.leaves = leaves;
+
+        let mut sponge = self.new_sponge();
+        for level in 0..self.depth {
+            let zero = self.get_cached_subtree_level(level).unwrap();
+            let mut parents = vec![&env];
+            for index in (0..level_nodes.len()).step_by(2) {
+                let left = level_nodes.get(index).unwrap();
+                let right = level_nodes.get(index + 1).unwrap_or(zero.clone());
+                let parent = self.hash_pair_with_sponge(&mut sponge, left, right);
+                self.cache_sparse_node(level + 1, index / 2, parent.clone());
+                parents.push_back(parent);
+            }
+            level_nodes = parents;
+        }
+
+        self.root = bls_scalar_to_bytes(level_nodes.get(0).unwrap());
+    }
+
     /// Inserts a new leaf into the tree (appends; missing leaves remain zero)
     /// Uses incremental path recomputation for efficiency (Clever shortcut 2)
-    /// Returns Err if the tree is at capacity (2^depth leaves)
-    pub fn insert(&mut self, leaf: BytesN<32>) -> Result<(), &'static str> {
-        let current_count = self.leaves.len() as u32;
+    /// Returns `LeanIMTError::TreeFull` if the tree is at capacity (2^depth leaves)
+    pub fn insert(&mut self, leaf: BytesN<32>) -> Result<(), LeanIMTError> {
+        if self.leaf_count >= self.capacity {
+            return Err(LeanIMTError::TreeFull);
+        }
+
+        let leaf_scalar = bytes_to_bls_scalar(&leaf);
+        self.push_leaf(leaf);
+        self.incremental_update(leaf_scalar);
+        Ok(())
+    }
+
+    /// Appends `leaf` to the leaves (just counts it in a light tree)
+    fn push_leaf(&mut self, leaf: BytesN<32>) {
+        if !self.light {
+            self.leaves.push_back(leaf);
+        }
+        self.leaf_count += 1;
+    }
+
+    /// Inserts a leaf and returns the new root with the leaf's index
+    ///
+    /// Saves a `get_root()` and `get_leaf_count() - 1` after `insert`, as a deposit
+    /// needs both.
+    pub fn insert_returning(
+        &mut self,
+        leaf: BytesN<32>,
+    ) -> Result<(BytesN<32>, u32), LeanIMTError> {
+        self.insert(leaf)?;
+        Ok((self.get_root(), self.leaf_count - 1))
+    }
 
-        if current_count >= self.capacity {
-            return Err("Tree is at capacity: cannot insert more leaves");
+    /// Inserts a leaf that is already a scalar
+    ///
+    /// Same as `insert(bls_scalar_to_bytes(leaf))`, but the path is hashed from `leaf`
+    /// directly instead of decoding the stored bytes again.
+    pub fn insert_scalar(&mut self, leaf: BlsScalar) -> Result<(), LeanIMTError> {
+        if self.leaf_count >= self.capacity {
+            return Err(LeanIMTError::TreeFull);
         }
 
-        self.leaves.push_back(leaf);
-        self.incremental_update();
+        self.push_leaf(bls_scalar_to_bytes(leaf.clone()));
+        self.incremental_update(leaf);
         Ok(())
     }
 
+    /// Removes all leaves and resets the tree to the empty root for its current depth,
+    /// keeping the depth, hash function and empty leaf so the instance can be reused
+    pub fn clear(&mut self) {
+        self.leaves = vec![&self.env];
+        self.leaf_count = 0;
+        self.subtree_cache = Map::new(&self.env);
+        self.sparse_cache = Map::new(&self.env);
+        self.recompute_tree();
+    }
+
     /// Inserts a u64 leaf (converts to BlsScalar internally)
-    pub fn insert_u64(&mut self, leaf_value: u64) -> Result<(), &'static str> {
-        let leaf_scalar = u64_to_bls_scalar(&self.env, leaf_value);
-        let leaf_bytes = bls_scalar_to_bytes(leaf_scalar);
-        self.insert(leaf_bytes)
+    pub fn insert_u64(&mut self, leaf_value: u64) -> Result<(), LeanIMTError> {
+        self.insert_scalar(u64_to_bls_scalar(&self.env, leaf_value))
+    }
+
+    /// Inserts `values` in order as u64 leaves, all or nothing: returns
+    /// `LeanIMTError::TreeFull` without inserting any of them if they don't all fit.
+    /// An empty tree is built level by level as in `new_from_leaves`; otherwise the
+    /// values are inserted one at a time.
+    pub fn insert_many_u64(&mut self, values: &[u64]) -> Result<(), LeanIMTError> {
+        if values.len() as u64 > (self.capacity - self.leaf_count) as u64 {
+            return Err(LeanIMTError::TreeFull);
+        }
+        if values.is_empty() {
+            return Ok(());
+        }
+
+        if self.leaf_count == 0 && !self.light {
+            let mut leaves = vec![&self.env];
+            for &value in values {
+                leaves.push_back(bls_scalar_to_bytes(u64_to_bls_scalar(&self.env, value)));
+            }
+            self.build_from_leaves(leaves);
+        } else {
+            for &value in values {
+                self.insert_u64(value)?;
+            }
+        }
+        Ok(())
     }
 
     /// Gets the current root of the tree
@@ -94,6 +613,22 @@ impl LeanIMT {
         bytes_to_bls_scalar(&self.root)
     }
 
+    /// Gets the hash function used for internal nodes
+    pub fn get_hash_function(&self) -> HashFunction {
+        self.hash_function
+    }
+
+    /// Gets the Poseidon state width of the parent hash (`DEFAULT_POSEIDON_WIDTH` unless
+    /// built with `new_with_poseidon_width`)
+    pub fn get_poseidon_width(&self) -> u32 {
+        self.poseidon_width
+    }
+
+    /// Whether leaves and nodes are hashed with `LEAF_DOMAIN` / `NODE_DOMAIN` tags
+    pub fn is_domain_separated(&self) -> bool {
+        self.domain_separated
+    }
+
     /// Gets the current depth of the tree
     pub fn get_depth(&self) -> u32 {
         self.depth
@@ -101,12 +636,18 @@ impl LeanIMT {
 
     /// Gets the number of leaves that have been explicitly inserted
     pub fn get_leaf_count(&self) -> u32 {
-        self.leaves.len() as u32
+        self.leaf_count
+    }
+
+    /// Maximum number of leaves the tree can hold: 2^depth, saturating at `u32::MAX`
+    /// for depths of 32 and above
+    pub fn capacity(&self) -> u32 {
+        self.capacity
     }
 
     /// Gets the maximum capacity of the tree (2^depth)
     pub fn get_capacity(&self) -> u32 {
-        self.capacity
+        self.capacity()
     }
 
     /// Checks if the tree is at capacity
@@ -115,54 +656,306 @@ impl LeanIMT {
     }
 
     /// Generates a merkle proof for a given leaf index
+    ///
+    /// The proof always has exactly `depth` siblings, ordered from the leaf level up.
+    /// A depth-0 tree holds a single leaf that is its own root, so its proof is empty.
+    /// Siblings that were never inserted are zero, matching the padding used for the root.
     pub fn generate_proof(&self, leaf_index: u32) -> Option<(Vec<BlsScalar>, u32)> {
         if leaf_index >= self.leaves.len() as u32 {
             return None;
         }
 
         let mut siblings = vec![&self.env];
+        for (sibling, _) in self.proof_iter(leaf_index)? {
+            siblings.push_back(sibling);
+        }
+
+        Some((siblings, self.depth))
+    }
+
+    /// Generates the proof `leaf_index` had when the tree held only its first
+    /// `as_of_leaf_count` leaves
+    ///
+    /// Later leaves are treated as never inserted, so the siblings hash up to the root
+    /// the tree had at that count, for reproducing a historical withdrawal proof.
+    /// Returns `None` if `leaf_index >= as_of_leaf_count` or the tree has fewer than
+    /// `as_of_leaf_count` leaves.
+    pub fn generate_proof_at(
+        &self,
+        leaf_index: u32,
+        as_of_leaf_count: u32,
+    ) -> Option<(Vec<BlsScalar>, u32)> {
+        if leaf_index >= as_of_leaf_count || as_of_leaf_count > self.leaves.len() {
+            return None;
+        }
+
+        let mut siblings = vec![&self.env];
+        let mut index = leaf_index;
+        for level in 0..self.depth {
+            siblings.push_back(self.node_as_of(level, index ^ 1, as_of_leaf_count));
+            index /= 2;
+        }
+
+        Some((siblings, self.depth))
+    }
+
+    /// Value of the node at `(level, node_index)` when only the first `leaf_count`
+    /// leaves were inserted
+    fn node_as_of(&self, level: u32, node_index: u32, leaf_count: u32) -> BlsScalar {
+        let first_leaf = (node_index as u64) << level;
+        let end_leaf = (node_index as u64 + 1) << level;
+        if first_leaf >= leaf_count as u64 {
+            // No leaf of this subtree existed yet
+            self.get_cached_subtree_level(level).unwrap()
+        } else if end_leaf <= leaf_count as u64 {
+            // Every leaf of this subtree existed and hasn't changed since
+            self.compute_node_at_level_scalar(node_index, level)
+        } else {
+            let left = self.node_as_of(level - 1, node_index * 2, leaf_count);
+            let right = self.node_as_of(level - 1, node_index * 2 + 1, leaf_count);
+            self.hash_pair(left, right)
+        }
+    }
+
+    /// Generates a merkle proof padded to `max_depth` siblings, for circuits that take
+    /// the tree depth as an input (`MerkleProofWithDepth(maxDepth)` in `merkleProof.circom`)
+    ///
+    /// Padding convention: the first `depth` siblings are those of `generate_proof`, and
+    /// the remaining `max_depth - depth` are zero. The returned depth is the actual depth;
+    /// the circuit hashes every level but takes the node at that level as the root, so the
+    /// padding never changes the result. Returns `None` if the leaf doesn't exist or
+    /// `max_depth` is smaller than the tree's depth.
+    pub fn generate_padded_proof(
+        &self,
+        leaf_index: u32,
+        max_depth: u32,
+    ) -> Option<(Vec<BlsScalar>, u32)> {
+        if max_depth < self.depth {
+            return None;
+        }
+
+        let (mut siblings, depth) = self.generate_proof(leaf_index)?;
+        let zero = BlsScalar::from_u256(U256::from_u32(&self.env, 0));
+        for _ in depth..max_depth {
+            siblings.push_back(zero.clone());
+        }
+
+        Some((siblings, depth))
+    }
+
+    /// Returns a lazy iterator over the proof for `leaf_index`
+    ///
+    /// Yields the same siblings as `generate_proof`, from the leaf level up, each paired
+    /// with `true` when the path node is a right child (its sibling sits on the left).
+    /// Siblings are computed one level at a time as the iterator advances.
+    pub fn proof_iter(&self, leaf_index: u32) -> Option<ProofIter<'_>> {
+        if leaf_index >= self.leaves.len() as u32 {
+            return None;
+        }
+
+        Some(ProofIter {
+            tree: self,
+            index: leaf_index,
+            level: 0,
+        })
+    }
+
+    /// Gets the merkle path for `leaf_index` in storage form
+    ///
+    /// Returns the siblings from `proof_iter` as bytes together with their directions
+    /// (`true` where the path node is a right child), or `None` if the leaf doesn't exist.
+    pub fn get_path(&self, leaf_index: u32) -> Option<(Vec<BytesN<32>>, Vec<bool>)> {
+        let mut siblings = vec![&self.env];
+        let mut directions = vec![&self.env];
+        for (sibling, is_right) in self.proof_iter(leaf_index)? {
+            siblings.push_back(bls_scalar_to_bytes(sibling));
+            directions.push_back(is_right);
+        }
+
+        Some((siblings, directions))
+    }
 
-        // Handle the simple 2-leaf case correctly
-        if self.depth == 1 && self.leaves.len() == 2 {
-            if leaf_index == 0 {
-                let sibling_bytes = self.leaves.get(1).unwrap();
-                siblings.push_back(bytes_to_bls_scalar(&sibling_bytes));
+    /// Computes the sibling of the node at `(level, index)` for a merkle proof
+    fn proof_sibling(&self, level: u32, index: u32) -> BlsScalar {
+        let sibling_index = index ^ 1;
+
+        if level == 0 {
+            // At leaf level, use actual leaves or the empty leaf if missing
+            if sibling_index < self.leaves.len() as u32 {
+                let sibling_bytes = self.leaves.get(sibling_index).unwrap();
+                self.leaf_node(bytes_to_bls_scalar(&sibling_bytes))
             } else {
-                let sibling_bytes = self.leaves.get(0).unwrap();
-                siblings.push_back(bytes_to_bls_scalar(&sibling_bytes));
+                self.zero_leaf.clone()
             }
         } else {
-            // General approach
-            let mut current_index = leaf_index;
-            let mut current_depth = 0;
+            // At internal levels, compute the actual node value
+            self.compute_node_at_level_scalar(sibling_index, level)
+        }
+    }
+
+    /// Generates one proof for all of `indices`
+    ///
+    /// Where the paths of two proven leaves meet, the nodes below the meeting point are
+    /// computed by the verifier from the leaves, so only the siblings off every path are
+    /// included. Adjacent leaves share all their siblings above the leaf level, and
+    /// proving `k` leaves never takes more than the `k * depth` siblings of separate
+    /// proofs. Repeated indices are proven once. Returns `None` if `indices` is empty or
+    /// names a leaf that doesn't exist.
+    pub fn generate_multiproof(&self, indices: &[u32]) -> Option<MultiProof> {
+        let mut sorted: Vec<u32> = vec![&self.env];
+        for &index in indices {
+            if index >= self.leaves.len() {
+                return None;
+            }
+            let position = sorted.iter().position(|other| other >= index);
+            match position {
+                Some(position) if sorted.get(position as u32) == Some(index) => {}
+                Some(position) => sorted.insert(position as u32, index),
+                None => sorted.push_back(index),
+            }
+        }
+        if sorted.is_empty() {
+            return None;
+        }
 
-            while current_depth < self.depth {
-                let sibling_index = if current_index % 2 == 0 {
-                    current_index + 1
+        let mut nodes = vec![&self.env];
+        let mut level_indices = sorted.clone();
+        for level in 0..self.depth {
+            let mut parents = vec![&self.env];
+            let mut i = 0;
+            while i < level_indices.len() {
+                let index = level_indices.get(i).unwrap();
+                if index.is_multiple_of(2) && level_indices.get(i + 1) == Some(index + 1) {
+                    // Both children are on proven paths
+                    i += 2;
                 } else {
-                    current_index - 1
-                };
+                    nodes.push_back(self.proof_sibling(level, index));
+                    i += 1;
+                }
+                parents.push_back(index / 2);
+            }
+            level_indices = parents;
+        }
 
-                let sibling_scalar = if current_depth == 0 {
-                    // At leaf level, use actual leaves or zero if missing
-                    if sibling_index < self.leaves.len() as u32 {
-                        let sibling_bytes = self.leaves.get(sibling_index).unwrap();
-                        bytes_to_bls_scalar(&sibling_bytes)
-                    } else {
-                        BlsScalar::from_u256(U256::from_u32(&self.env, 0))
+        Some(MultiProof {
+            indices: sorted,
+            nodes,
+        })
+    }
+
+    /// Verifies a proof from `generate_multiproof` against the current root
+    ///
+    /// `leaves` are the proven leaves in the order of `proof.indices`. Hashes them up level
+    /// by level, taking a sibling from `proof.nodes` wherever it isn't one of the computed
+    /// nodes, and checks that every node was used and the result is the root.
+    pub fn verify_multiproof(&self, leaves: &Vec<BytesN<32>>, proof: &MultiProof) -> bool {
+        if leaves.is_empty() || leaves.len() != proof.indices.len() {
+            return false;
+        }
+
+        let mut level_nodes: Vec<(u32, BlsScalar)> = vec![&self.env];
+        for (i, leaf) in leaves.iter().enumerate() {
+            let index = proof.indices.get(i as u32).unwrap();
+            let ascending = match level_nodes.last() {
+                Some((previous, _)) => index > previous,
+                None => true,
+            };
+            if !ascending || (self.depth < 32 && index >= self.capacity) {
+                return false;
+            }
+            level_nodes.push_back((index, self.leaf_node(bytes_to_bls_scalar(&leaf))));
+        }
+
+        let mut next_node = 0;
+        for _ in 0..self.depth {
+            let mut parents = vec![&self.env];
+            let mut i = 0;
+            while i < level_nodes.len() {
+                let (index, node) = level_nodes.get(i).unwrap();
+                let sibling = match level_nodes.get(i + 1) {
+                    Some((right_index, right))
+                        if index.is_multiple_of(2) && right_index == index + 1 =>
+                    {
+                        i += 1;
+                        right
                     }
+                    _ => {
+                        let Some(sibling) = proof.nodes.get(next_node) else {
+                            return false;
+                        };
+                        next_node += 1;
+                        sibling
+                    }
+                };
+                let parent = if index.is_multiple_of(2) {
+                    self.hash_pair(node, sibling)
                 } else {
-                    // At internal levels, compute the actual node value
-                    self.compute_node_at_level_scalar(sibling_index, current_depth)
+                    self.hash_pair(sibling, node)
                 };
-
-                siblings.push_back(sibling_scalar);
-                current_index = current_index / 2;
-                current_depth += 1;
+                parents.push_back((index / 2, parent));
+                i += 1;
             }
+            level_nodes = parents;
         }
 
-        Some((siblings, self.depth))
+        next_node == proof.nodes.len()
+            && bls_scalar_to_bytes(level_nodes.get(0).unwrap().1) == self.root
+    }
+
+    /// Verifies a proof from `generate_proof` against the current root
+    ///
+    /// Hashes the leaf up through the siblings, taking the left or right position at each
+    /// level from the bits of `leaf_index`, the same way `merkleProof.circom` does.
+    pub fn verify_proof(
+        &self,
+        leaf: &BytesN<32>,
+        leaf_index: u32,
+        siblings: &Vec<BlsScalar>,
+    ) -> bool {
+        if siblings.len() != self.depth {
+            return false;
+        }
+        if self.depth < 32 && leaf_index >= self.capacity {
+            return false;
+        }
+
+        let mut current_scalar = self.leaf_node(bytes_to_bls_scalar(leaf));
+        let mut current_index = leaf_index;
+
+        for sibling in siblings.iter() {
+            current_scalar = if current_index.is_multiple_of(2) {
+                self.hash_pair(current_scalar, sibling)
+            } else {
+                self.hash_pair(sibling, current_scalar)
+            };
+            current_index /= 2;
+        }
+
+        bls_scalar_to_bytes(current_scalar) == self.root
+    }
+
+    /// Verifies a proof from `generate_padded_proof` against the current root
+    ///
+    /// `actual_depth` must be the tree's depth and every sibling past it must be zero
+    /// padding; the first `actual_depth` siblings are then checked with `verify_proof`.
+    pub fn verify_padded_proof(
+        &self,
+        leaf: &BytesN<32>,
+        leaf_index: u32,
+        siblings: &Vec<BlsScalar>,
+        actual_depth: u32,
+    ) -> bool {
+        if actual_depth != self.depth || siblings.len() < actual_depth {
+            return false;
+        }
+
+        let zero = BlsScalar::from_u256(U256::from_u32(&self.env, 0));
+        let padding = siblings.slice(actual_depth..);
+        if padding.iter().any(|sibling| sibling != zero) {
+            return false;
+        }
+
+        self.verify_proof(leaf, leaf_index, &siblings.slice(0..actual_depth))
     }
 
     /// Computes the value of an internal node at a specific level
@@ -187,9 +980,9 @@ impl LeanIMT {
         if target_level == 0 {
             if node_index < self.leaves.len() as u32 {
                 let leaf_bytes = self.leaves.get(node_index).unwrap();
-                bytes_to_bls_scalar(&leaf_bytes)
+                self.leaf_node(bytes_to_bls_scalar(&leaf_bytes))
             } else {
-                BlsScalar::from_u256(U256::from_u32(&self.env, 0))
+                self.zero_leaf.clone()
             }
         } else {
             // For levels > 0, compute by hashing the two children from the level below
@@ -213,26 +1006,28 @@ impl LeanIMT {
     ///
     /// Now with full memoization - we only recompute the specific path from the new leaf to root,
     /// and update the cache as we go.
-    fn incremental_update(&mut self) {
-        let leaf_index = (self.leaves.len() - 1) as u32;
+    ///
+    /// `leaf_scalar` is the value of the leaf just appended to `leaves`.
+    fn incremental_update(&mut self, leaf_scalar: BlsScalar) {
+        let leaf_index = self.leaf_count - 1;
+        let leaf_scalar = self.leaf_node(leaf_scalar);
 
         // Update the leaf in the sparse cache
-        let leaf_bytes = self.leaves.get(leaf_index).unwrap();
-        let leaf_scalar = bytes_to_bls_scalar(&leaf_bytes);
-        self.cache_sparse_node(0, leaf_index, leaf_scalar);
+        self.cache_sparse_node(0, leaf_index, leaf_scalar.clone());
 
         // Recompute the path to root and update cache
-        self.root = self.recompute_path_to_root_with_cache_update(leaf_index);
+        self.root = self.recompute_path_to_root_with_cache_update(leaf_index, leaf_scalar);
     }
 
     /// Recomputes only the path from a specific leaf to the root with cache updates
     /// This is the optimized version that updates the cache as it goes
-    fn recompute_path_to_root_with_cache_update(&mut self, leaf_index: u32) -> BytesN<32> {
-        let leaf_bytes = self.leaves.get(leaf_index).unwrap();
-        let leaf_scalar = bytes_to_bls_scalar(&leaf_bytes);
-
+    fn recompute_path_to_root_with_cache_update(
+        &mut self,
+        leaf_index: u32,
+        leaf_scalar: BlsScalar,
+    ) -> BytesN<32> {
         // Create sponge once for efficient repeated hashing
-        let mut sponge = PoseidonSponge::<3, BlsScalar>::new(&self.env);
+        let mut sponge = self.new_sponge();
 
         // Start from the leaf and work our way up to the root
         let mut current_index = leaf_index;
@@ -248,17 +1043,21 @@ impl LeanIMT {
 
             // Get the sibling value (either from cache or compute if missing)
             let sibling_scalar = if current_level == 0 {
-                // At leaf level, use actual leaves or zero if missing
-                if sibling_index < self.leaves.len() as u32 {
+                // At leaf level, use actual leaves or the empty leaf if missing
+                if self.light && sibling_index < self.leaf_count {
+                    self.light_sibling(current_level, sibling_index)
+                } else if sibling_index < self.leaves.len() as u32 {
                     let sibling_bytes = self.leaves.get(sibling_index).unwrap();
-                    bytes_to_bls_scalar(&sibling_bytes)
+                    self.leaf_node(bytes_to_bls_scalar(&sibling_bytes))
                 } else {
-                    BlsScalar::from_u256(U256::from_u32(&self.env, 0))
+                    self.zero_leaf.clone()
                 }
             } else {
                 // At internal levels, use hybrid cache system
                 if let Some(cached_value) = self.get_cached_node(current_level, sibling_index) {
                     cached_value
+                } else if self.light {
+                    self.light_sibling(current_level, sibling_index)
                 } else {
                     self.compute_node_at_level_scalar(sibling_index, current_level)
                 }
@@ -286,6 +1085,14 @@ impl LeanIMT {
         bls_scalar_to_bytes(current_scalar)
     }
 
+    /// A left sibling on a light tree's insertion path, which only the frontier (or an
+    /// earlier insert on this instance) can provide
+    fn light_sibling(&self, level: u32, index: u32) -> BlsScalar {
+        self.sparse_cache
+            .get((level, index))
+            .unwrap_or_else(|| panic!("Light tree is missing frontier node at level {}", level))
+    }
+
     /// Gets a cached subtree hash for a level if it exists
     fn get_cached_subtree_level(&self, level: u32) -> Option<BlsScalar> {
         self.subtree_cache.get(level)
@@ -298,15 +1105,21 @@ impl LeanIMT {
 
     /// Gets a cached node value using hybrid cache system:
     /// 1. First check sparse_cache for specific node updates
-    /// 2. If not found, fall back to subtree_cache for level-based cache
+    /// 2. If not found and the node covers no inserted leaves, fall back to subtree_cache
     fn get_cached_node(&self, level: u32, node_index: u32) -> Option<BlsScalar> {
         // First check sparse cache for specific node updates
         if let Some(cached_value) = self.sparse_cache.get((level, node_index)) {
             return Some(cached_value);
         }
 
-        // Fall back to subtree cache for level-based cache (empty tree optimization)
-        self.get_cached_subtree_level(level)
+        // Fall back to subtree cache for level-based cache (empty tree optimization).
+        // Only valid for nodes whose leaves are all zero padding.
+        let first_leaf = (node_index as u64) << level;
+        if first_leaf >= self.leaf_count as u64 {
+            self.get_cached_subtree_level(level)
+        } else {
+            None
+        }
     }
 
     /// Caches a specific node in the sparse cache (for incremental updates)
@@ -314,100 +1127,133 @@ impl LeanIMT {
         self.sparse_cache.set((level, node_index), hash);
     }
 
-    /// Rebuilds the cache from the current leaves
+    /// Rebuilds the cache from the current leaves, keeping the stored root
     /// This is used when deserializing from storage
     fn rebuild_cache_from_leaves(&mut self) {
-        if self.leaves.is_empty() {
-            // For empty trees, use the optimized empty tree construction
-            self.recompute_tree();
-            return;
-        }
-
-        // For trees with leaves, clear both caches and let them rebuild on-demand
-        // The hybrid cache system will handle the rest
+        // Clear both caches and let non-empty nodes rebuild on-demand.
+        // Empty subtree hashes only depend on the depth, so they are restored eagerly.
         self.subtree_cache = Map::new(&self.env);
         self.sparse_cache = Map::new(&self.env);
+        self.compute_empty_subtree_hashes();
     }
 
     /// Recomputes the entire tree after insertion using fixed depth and zero padding
     /// Optimized for empty trees: O(depth) instead of O(2^depth) using dynamic programming
     fn recompute_tree(&mut self) {
         if self.depth == 0 {
-            // Special case: depth 0 tree with no leaves
-            self.root = BytesN::from_array(&self.env, &[0u8; 32]);
+            // Special case: depth 0 tree with no leaves, whose root is the empty leaf
+            self.root = bls_scalar_to_bytes(self.zero_leaf.clone());
             return;
         }
 
-        // Create sponge once for efficient repeated hashing
-        let mut sponge = PoseidonSponge::<3, BlsScalar>::new(&self.env);
-
-        // For empty trees, all subtrees at the same level are identical
-        // We only need to compute one hash per level: hash(level_n, level_n) = level_n+1
-        let zero_scalar = BlsScalar::from_u256(U256::from_u32(&self.env, 0));
-        let mut current_level_hash = zero_scalar.clone();
-
-        // Compute hashes level by level, reusing the same hash for all nodes at each level
-        for level in 0..=self.depth {
-            if level == 0 {
-                // Level 0: all leaves are zero
-                current_level_hash = zero_scalar.clone();
-            } else {
-                // Level 1+: hash the previous level with itself (reuse sponge for efficiency)
-                current_level_hash = self.hash_pair_with_sponge(
-                    &mut sponge,
-                    current_level_hash.clone(),
-                    current_level_hash,
-                );
-            }
+        // Build the empty subtree hashes; the top one is the root
+        let empty_root = self.compute_empty_subtree_hashes();
+        self.root = bls_scalar_to_bytes(empty_root);
+    }
 
-            // Cache this hash for the level (all nodes at this level are identical)
-            self.cache_subtree_level(level, current_level_hash.clone());
+    /// Fills subtree_cache with the hash of an all-zero subtree at every level
+    /// and returns the hash at the top level (the root of an empty tree)
+    fn compute_empty_subtree_hashes(&mut self) -> BlsScalar {
+        // For empty trees, all subtrees at the same level are identical,
+        // so the precomputed zero hashes cover every empty node
+        let hashes = zero_hashes_with_sponge(
+            &self.env,
+            self.depth,
+            self.zero_leaf.clone(),
+            self.new_sponge(),
+        );
+        for (level, hash) in hashes.iter().enumerate() {
+            self.cache_subtree_level(level as u32, hash);
         }
 
-        // Set the root
-        self.root = bls_scalar_to_bytes(current_level_hash);
+        hashes.last().unwrap()
+    }
+
+    /// Value of a leaf at level 0: the leaf itself, or `Poseidon(LEAF_DOMAIN, leaf)` in a
+    /// domain-separated tree
+    fn leaf_node(&self, leaf: BlsScalar) -> BlsScalar {
+        if self.domain_separated {
+            let domain = BlsScalar::from_u256(U256::from_u32(&self.env, LEAF_DOMAIN));
+            Poseidon255::new(&self.env).hash_two(&domain, &leaf)
+        } else {
+            leaf
+        }
     }
 
-    /// Hashes two BlsScalar values using Poseidon hash function
+    /// Hashes two BlsScalar values using the configured hash function
     fn hash_pair(&self, left: BlsScalar, right: BlsScalar) -> BlsScalar {
-        let left_u256 = BlsScalar::to_u256(&left);
-        let right_u256 = BlsScalar::to_u256(&right);
-        let inputs = Vec::from_array(&self.env, [left_u256, right_u256]);
-        // Use poseidon_hash (not poseidon2_hash) to match circom circuit
-        let result_u256 = poseidon_hash::<3, BlsScalar>(&self.env, &inputs);
-        BlsScalar::from_u256(result_u256)
+        if self.domain_separated {
+            let inputs = Vec::from_array(
+                &self.env,
+                [
+                    U256::from_u32(&self.env, NODE_DOMAIN),
+                    left.to_u256(),
+                    right.to_u256(),
+                ],
+            );
+            return BlsScalar::from_u256(poseidon_hash::<4, BlsScalar>(&self.env, &inputs));
+        }
+
+        if self.poseidon_width != DEFAULT_POSEIDON_WIDTH {
+            return self.new_sponge().hash_pair(&self.env, left, right);
+        }
+
+        // Poseidon (not Poseidon2) by default to match the circom circuit
+        self.hash_function.hash_two(&self.env, &left, &right)
+    }
+
+    /// Creates a sponge for the configured hash function
+    fn new_sponge(&self) -> PairSponge {
+        if self.domain_separated {
+            PairSponge::new_domain_separated(&self.env)
+        } else if self.poseidon_width != DEFAULT_POSEIDON_WIDTH {
+            PairSponge::new_poseidon(&self.env, self.poseidon_width)
+        } else {
+            PairSponge::new(&self.env, self.hash_function)
+        }
     }
 
     /// Hashes two BlsScalar values using a pre-initialized sponge for efficiency
     /// Use this in loops where many hashes are computed
     fn hash_pair_with_sponge(
         &self,
-        sponge: &mut PoseidonSponge<3, BlsScalar>,
+        sponge: &mut PairSponge,
         left: BlsScalar,
         right: BlsScalar,
     ) -> BlsScalar {
-        let left_u256 = BlsScalar::to_u256(&left);
-        let right_u256 = BlsScalar::to_u256(&right);
-        let inputs = Vec::from_array(&self.env, [left_u256, right_u256]);
-        let result_u256 = sponge.compute_hash(&inputs);
-        BlsScalar::from_u256(result_u256)
+        sponge.hash_pair(&self.env, left, right)
     }
 
     /// Serializes the tree state for storage
+    ///
+    /// A light tree has no leaves to save; use `to_light_storage` for it.
     pub fn to_storage(&self) -> (Vec<BytesN<32>>, u32, BytesN<32>) {
         (self.leaves.clone(), self.depth, self.root.clone())
     }
 
     /// Deserializes the tree state from storage
+    ///
+    /// The restored tree uses Poseidon, no domain separation and a zero empty leaf.
+    ///
+    /// Only the empty subtree hashes are restored, so the first insert recomputes the
+    /// complete subtrees to the left of the new leaf, about one hash per stored leaf.
+    /// Callers that insert after every load should save `get_frontier` under
+    /// `TREE_FRONTIER_KEY` and use `from_storage_with_frontier` instead.
     pub fn from_storage(env: &Env, leaves: Vec<BytesN<32>>, depth: u32, root: BytesN<32>) -> Self {
         let capacity = 1u32.checked_shl(depth).unwrap_or(u32::MAX);
         let env_clone = env.clone();
         let mut tree = Self {
             env: env_clone.clone(),
+            leaf_count: leaves.len(),
             leaves,
+            light: false,
             depth,
             capacity,
             root,
+            hash_function: HashFunction::Poseidon,
+            poseidon_width: DEFAULT_POSEIDON_WIDTH,
+            domain_separated: false,
+            zero_leaf: BlsScalar::from_u256(U256::from_u32(env, 0)),
             subtree_cache: Map::new(&env_clone),
             sparse_cache: Map::new(&env_clone),
         };
@@ -417,22 +1263,225 @@ impl LeanIMT {
         tree
     }
 
+    /// Returns the frontier: the filled subtree roots along the current right edge.
+    ///
+    /// The result has one entry per level `0..depth`. With `n` leaves inserted, the next
+    /// leaf goes to index `n`; at level `l` its ancestor has index `n >> l`. When that index
+    /// is odd, the ancestor's left sibling `(l, (n >> l) - 1)` is a complete subtree and is
+    /// returned as `Some`. When it is even, the sibling lies to the right and is still zero
+    /// padding, whose hash is known from the depth alone, so the entry is `None`.
+    /// Equivalently, entry `l` is `Some` exactly when bit `l` of the leaf count is set.
+    ///
+    /// Persisting this alongside the leaves lets `from_storage_with_frontier` insert the next
+    /// leaf with `depth` hashes instead of recomputing the left side of the tree.
+    pub fn get_frontier(&self) -> Vec<Option<BytesN<32>>> {
+        let leaf_count = self.leaf_count;
+        let mut frontier = vec![&self.env];
+
+        for level in 0..self.depth {
+            let index_at_level = leaf_count.checked_shr(level).unwrap_or(0);
+            if index_at_level % 2 == 1 {
+                frontier.push_back(self.get_node(level, index_at_level - 1));
+            } else {
+                frontier.push_back(None);
+            }
+        }
+
+        frontier
+    }
+
+    /// Deserializes the tree state from storage together with a frontier previously
+    /// returned by `get_frontier`, so subsequent inserts only touch the insertion path.
+    ///
+    /// Entries that do not fit the frontier shape for the stored leaf count are ignored,
+    /// and the affected nodes are recomputed from the leaves on demand.
+    pub fn from_storage_with_frontier(
+        env: &Env,
+        leaves: Vec<BytesN<32>>,
+        depth: u32,
+        root: BytesN<32>,
+        frontier: Vec<Option<BytesN<32>>>,
+    ) -> Self {
+        let mut tree = Self::from_storage(env, leaves, depth, root);
+        tree.cache_frontier(frontier);
+        tree
+    }
+
+    /// Serializes a light tree for storage: its leaf count, depth, root and frontier
+    ///
+    /// Save these under `TREE_LEAF_COUNT_KEY`, `TREE_DEPTH_KEY`, `TREE_ROOT_KEY` and
+    /// `TREE_FRONTIER_KEY`, and load them with `from_light_storage`. Works for any tree,
+    /// but a full tree loaded this way loses its leaves.
+    pub fn to_light_storage(&self) -> (u32, u32, BytesN<32>, Vec<Option<BytesN<32>>>) {
+        (
+            self.leaf_count,
+            self.depth,
+            self.root.clone(),
+            self.get_frontier(),
+        )
+    }
+
+    /// Deserializes a light tree saved with `to_light_storage`
+    ///
+    /// The restored tree uses Poseidon, no domain separation and a zero empty leaf, like
+    /// `from_storage`. `frontier` must be the one saved with this leaf count: `insert`
+    /// hashes the new leaf against it, and has no leaves to fall back on.
+    pub fn from_light_storage(
+        env: &Env,
+        leaf_count: u32,
+        depth: u32,
+        root: BytesN<32>,
+        frontier: Vec<Option<BytesN<32>>>,
+    ) -> Self {
+        let mut tree = Self::from_storage(env, vec![env], depth, root);
+        tree.light = true;
+        tree.leaf_count = leaf_count;
+        tree.cache_frontier(frontier);
+        tree
+    }
+
+    /// Advances a tree state by `new_leaves` knowing only its frontier
+    ///
+    /// `frontier` is `get_frontier()` of a tree holding `old_leaf_count` leaves, and its
+    /// length is the tree depth. Returns the root and frontier of that tree after
+    /// appending `new_leaves`, the same as `new_from_leaves` over the old leaves followed
+    /// by the new ones, without the old leaves or the old root. A client mirroring the
+    /// tree can apply each batch of deposits this way and compare the root with the one
+    /// published. Like `from_light_storage`, this assumes Poseidon with no domain
+    /// separation and a zero empty leaf.
+    ///
+    /// Returns `LeanIMTError::TreeFull` if the leaves don't fit, or if the old tree is
+    /// already full (its frontier then has no entries to rebuild the root from). Panics if
+    /// `frontier` lacks an entry its leaf count requires.
+    pub fn apply_appends(
+        frontier: &Vec<Option<BytesN<32>>>,
+        old_leaf_count: u32,
+        new_leaves: &Vec<BytesN<32>>,
+    ) -> Result<(BytesN<32>, Vec<Option<BytesN<32>>>), LeanIMTError> {
+        let env = frontier.env();
+        let placeholder_root = BytesN::from_array(env, &[0u8; 32]);
+        let mut tree = Self::from_light_storage(
+            env,
+            old_leaf_count,
+            frontier.len(),
+            placeholder_root,
+            frontier.clone(),
+        );
+        if old_leaf_count >= tree.capacity {
+            return Err(LeanIMTError::TreeFull);
+        }
+
+        if new_leaves.is_empty() {
+            tree.root = bls_scalar_to_bytes(tree.root_from_frontier());
+        }
+        for leaf in new_leaves.iter() {
+            tree.insert(leaf)?;
+        }
+        Ok((tree.get_root(), tree.get_frontier()))
+    }
+
+    /// The root of a light tree that is not full, from its cached frontier
+    ///
+    /// Walks up from the first empty leaf: wherever the path is a right child its left
+    /// sibling is a frontier node, and wherever it is a left child the right side is
+    /// still empty.
+    fn root_from_frontier(&self) -> BlsScalar {
+        let mut current = self.zero_leaf.clone();
+        for level in 0..self.depth {
+            let index = self.leaf_count >> level;
+            current = if index % 2 == 1 {
+                self.hash_pair(self.light_sibling(level, index - 1), current)
+            } else {
+                let empty = self.get_cached_subtree_level(level).unwrap();
+                self.hash_pair(current, empty)
+            };
+        }
+        current
+    }
+
+    /// Caches the entries of `frontier` that fit the frontier shape for the leaf count
+    fn cache_frontier(&mut self, frontier: Vec<Option<BytesN<32>>>) {
+        let leaf_count = self.leaf_count;
+        for (level, node) in frontier.iter().enumerate() {
+            let level = level as u32;
+            if level >= self.depth {
+                break;
+            }
+            let index_at_level = leaf_count.checked_shr(level).unwrap_or(0);
+            if let (Some(node), 1) = (node, index_at_level % 2) {
+                self.cache_sparse_node(level, index_at_level - 1, bytes_to_bls_scalar(&node));
+            }
+        }
+    }
+
     /// Gets all leaves in the tree
     pub fn get_leaves(&self) -> &Vec<BytesN<32>> {
         &self.leaves
     }
 
+    /// Iterates over the leaves in insertion order, yielding each by value
+    ///
+    /// Empty for a light tree, like `get_leaves`.
+    pub fn leaves_iter(&self) -> impl Iterator<Item = BytesN<32>> + '_ {
+        self.leaves.iter()
+    }
+
+    /// Checks whether `leaf` has been inserted (linear scan over the leaves)
+    pub fn contains(&self, leaf: &BytesN<32>) -> bool {
+        self.leaves.contains(leaf)
+    }
+
     /// Checks if the tree is empty
     pub fn is_empty(&self) -> bool {
-        self.leaves.is_empty()
+        self.leaf_count == 0
+    }
+
+    /// Whether `other` holds the same leaves at the same depth with the same root
+    ///
+    /// Cached nodes are ignored, so a tree rebuilt with `new_from_leaves` or loaded with
+    /// `from_storage` equals one grown by sequential `insert` calls.
+    pub fn structural_eq(&self, other: &LeanIMT) -> bool {
+        self.depth == other.depth
+            && self.root == other.root
+            && self.leaf_count == other.leaf_count
+            && self.leaves == other.leaves
+    }
+
+    /// Whether the tree's root is `root`, e.g. a root read back from a contract
+    pub fn root_eq(&self, root: &BytesN<32>) -> bool {
+        &self.root == root
+    }
+
+    /// Whether the stored root is the one the tree's leaves hash to
+    ///
+    /// Rebuilds the tree from its leaves with the same configuration, ignoring every
+    /// cached node, and compares the result with the root. Catches a root that doesn't
+    /// match its leaves, e.g. one passed to `from_storage` after a migration or a manual
+    /// storage edit. A light tree has no leaves, so its root is checked against its
+    /// frontier instead; a full light tree has no frontier to check and always passes.
+    pub fn verify_internal_consistency(&self) -> bool {
+        if self.light {
+            return self.leaf_count >= self.capacity
+                || self.root_from_frontier() == self.get_root_scalar();
+        }
+        if self.leaves.len() > self.capacity {
+            return false;
+        }
+
+        let mut fresh = self.clone();
+        fresh.clear();
+        if !self.leaves.is_empty() {
+            fresh.build_from_leaves(self.leaves.clone());
+        }
+        fresh.root == self.root
     }
 
     /// Gets a leaf at a specific index
+    ///
+    /// `None` for any index that was never inserted, including ones that do not fit in
+    /// a `u32`.
     pub fn get_leaf(&self, index: usize) -> Option<BytesN<32>> {
-        match self.leaves.get(index.try_into().unwrap()) {
-            Some(leaf) => Some(leaf.clone()),
-            None => None,
-        }
+        self.leaves.get(u32::try_from(index).ok()?)
     }
 
     /// Gets a leaf as BlsScalar at a specific index
@@ -442,10 +1491,27 @@ impl LeanIMT {
     }
 
     /// Gets the value of a node at a specific level and index
+    ///
+    /// Level 0 holds the leaves, tagged with `LEAF_DOMAIN` in a domain-separated tree.
+    /// `None` for levels above the root and for indices outside the `2^(depth - level)`
+    /// nodes of the level.
     pub fn get_node(&self, level: u32, index: u32) -> Option<BytesN<32>> {
+        if level <= self.depth && index as u64 >= self.level_width(level) {
+            return None;
+        }
+        if self.light {
+            return self.get_node_scalar(level, index).map(bls_scalar_to_bytes);
+        }
         if level == 0 {
             if index < self.leaves.len() as u32 {
-                Some(self.leaves.get(index).unwrap())
+                let leaf = self.leaves.get(index).unwrap();
+                if self.domain_separated {
+                    Some(bls_scalar_to_bytes(
+                        self.leaf_node(bytes_to_bls_scalar(&leaf)),
+                    ))
+                } else {
+                    Some(leaf)
+                }
             } else {
                 None
             }
@@ -456,19 +1522,67 @@ impl LeanIMT {
         }
     }
 
-    /// Gets the sibling of a node at a specific level and index
-    pub fn get_sibling(&self, level: u32, index: u32) -> Option<BytesN<32>> {
-        if level > self.depth {
+    /// Gets the value of a node at a specific level and index as BlsScalar
+    /// (for computation, without a bytes roundtrip)
+    ///
+    /// Same rules as `get_node`: `None` for leaves that were never inserted, for levels
+    /// above the root and for indices outside the level; internal nodes over missing
+    /// leaves use zero padding.
+    pub fn get_node_scalar(&self, level: u32, index: u32) -> Option<BlsScalar> {
+        if level <= self.depth && index as u64 >= self.level_width(level) {
             return None;
         }
+        if self.light {
+            // Only nodes computed by this instance, or loaded with the frontier, are known
+            if level > self.depth || (level == 0 && index >= self.leaf_count) {
+                return None;
+            }
+            return self.get_cached_node(level, index);
+        }
+        if level == 0 {
+            if index < self.leaves.len() {
+                Some(self.leaf_node(bytes_to_bls_scalar(&self.leaves.get(index).unwrap())))
+            } else {
+                None
+            }
+        } else if level > self.depth {
+            None
+        } else {
+            Some(self.compute_node_at_level_scalar(index, level))
+        }
+    }
+
+    /// Gets the root of the subtree whose top node is at `level`/`index`.
+    /// Alias of `get_node_scalar`, named for building proofs over a subtree.
+    pub fn get_subtree_root(&self, level: u32, index: u32) -> Option<BlsScalar> {
+        self.get_node_scalar(level, index)
+    }
 
-        if level == self.depth {
+    /// Gets the sibling of a node at a specific level and index
+    ///
+    /// Bytes form of `get_sibling_scalar`: siblings that were never inserted are zero
+    /// (or the empty subtree hash above level 0), exactly as in `generate_proof`.
+    pub fn get_sibling(&self, level: u32, index: u32) -> Option<BytesN<32>> {
+        self.get_sibling_scalar(level, index)
+            .map(bls_scalar_to_bytes)
+    }
+
+    /// Gets the sibling of a node at a specific level and index as BlsScalar
+    ///
+    /// Returns the value a proof uses at that position, including zero padding for
+    /// positions past the inserted leaves. `None` only when `level` is the root level or
+    /// above, or `index` is outside the `2^(depth - level)` nodes of that level.
+    pub fn get_sibling_scalar(&self, level: u32, index: u32) -> Option<BlsScalar> {
+        if self.light || level >= self.depth || index as u64 >= self.level_width(level) {
             return None;
         }
 
-        let sibling_index = if index % 2 == 0 { index + 1 } else { index - 1 };
+        Some(self.proof_sibling(level, index))
+    }
 
-        self.get_node(level, sibling_index)
+    /// Number of node positions at `level` (at most `depth`): `2^(depth - level)`
+    fn level_width(&self, level: u32) -> u64 {
+        1u64.checked_shl(self.depth - level).unwrap_or(u64::MAX)
     }
 
     /// Demonstrates the "Clever shortcut 2" optimization concept
@@ -503,5 +1617,22 @@ impl LeanIMT {
     }
 }
 
+pub mod commitment;
+
+mod nary;
+pub use nary::{Arity, NaryIMT};
+
+mod poseidon;
+pub use poseidon::{HashBackend, Poseidon255, Poseidon2Hasher, BYTES_PER_CHUNK};
+
+#[cfg(feature = "std")]
+mod json;
+
+#[cfg(feature = "std")]
+mod debug;
+
+#[cfg(feature = "ark")]
+pub mod ark;
+
 #[cfg(test)]
 mod tests;
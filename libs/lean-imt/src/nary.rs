@@ -0,0 +1,237 @@
+//! Incremental merkle tree with a configurable number of children per node
+//!
+//! `LeanIMT` is binary. `NaryIMT` hashes all `arity` children of a node with a single
+//! Poseidon call, `Poseidon(c_0, ..., c_{arity-1})`, so a quaternary tree reaches the
+//! same capacity with half the levels. With `Arity::Binary` it produces the same roots
+//! as a `LeanIMT` using `HashFunction::Poseidon`.
+//!
+//! Only the binary layout matches `merkleProof.circom`; a quaternary tree needs a
+//! circuit that checks `arity - 1` siblings per level.
+
+use soroban_poseidon::poseidon_hash;
+use soroban_sdk::{crypto::bls12_381::Fr as BlsScalar, vec, BytesN, Env, Map, Vec, U256};
+
+use crate::{bls_scalar_to_bytes, bytes_to_bls_scalar, LeanIMTError};
+
+/// Number of children per internal node
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Arity {
+    /// Two children per node, hashed with Poseidon(2); same layout as `LeanIMT`
+    #[default]
+    Binary,
+    /// Four children per node, hashed with Poseidon(4)
+    Quaternary,
+}
+
+impl Arity {
+    /// Number of children per node
+    pub fn children(&self) -> u32 {
+        match self {
+            Arity::Binary => 2,
+            Arity::Quaternary => 4,
+        }
+    }
+}
+
+/// Incremental merkle tree whose internal nodes have `arity` children
+///
+/// Missing leaves are zero, like in `LeanIMT`. Every node with at least one inserted
+/// leaf below it is cached when that leaf is inserted; all other nodes are read from
+/// the zero-subtree table, so an insert hashes exactly `depth` nodes.
+pub struct NaryIMT {
+    env: Env,
+    leaves: Vec<BytesN<32>>,
+    depth: u32,
+    arity: Arity,
+    capacity: u32,
+    root: BytesN<32>,
+    /// Root of an all-zero subtree at each level `0..=depth`
+    zero_hashes: Vec<BlsScalar>,
+    /// Internal nodes (level >= 1) that cover at least one inserted leaf
+    nodes: Map<(u32, u32), BlsScalar>,
+}
+
+impl NaryIMT {
+    /// Creates an empty tree with `arity^depth` leaf slots
+    pub fn new(env: &Env, depth: u32, arity: Arity) -> Self {
+        let capacity = arity.children().checked_pow(depth).unwrap_or(u32::MAX);
+        let mut tree = Self {
+            env: env.clone(),
+            leaves: vec![env],
+            depth,
+            arity,
+            capacity,
+            root: BytesN::from_array(env, &[0u8; 32]),
+            zero_hashes: vec![env],
+            nodes: Map::new(env),
+        };
+
+        let mut current = BlsScalar::from_u256(U256::from_u32(env, 0));
+        tree.zero_hashes.push_back(current.clone());
+        for _ in 0..depth {
+            let mut children = vec![env];
+            for _ in 0..arity.children() {
+                children.push_back(current.clone());
+            }
+            current = tree.hash_children(&children);
+            tree.zero_hashes.push_back(current.clone());
+        }
+        tree.root = bls_scalar_to_bytes(current);
+        tree
+    }
+
+    /// Inserts a new leaf, recomputing only the path to the root
+    /// Returns `LeanIMTError::TreeFull` once `capacity()` leaves are stored
+    pub fn insert(&mut self, leaf: BytesN<32>) -> Result<(), LeanIMTError> {
+        if self.leaves.len() >= self.capacity {
+            return Err(LeanIMTError::TreeFull);
+        }
+
+        let mut index = self.leaves.len();
+        self.leaves.push_back(leaf);
+
+        let mut current = self.get_node_scalar(0, index);
+        for level in 1..=self.depth {
+            index /= self.arity.children();
+            current = self.hash_children(&self.children_of(level, index));
+            self.nodes.set((level, index), current.clone());
+        }
+
+        self.root = bls_scalar_to_bytes(current);
+        Ok(())
+    }
+
+    /// Hashes the children of one node with a single Poseidon call
+    pub fn hash_children(&self, children: &Vec<BlsScalar>) -> BlsScalar {
+        let mut inputs = vec![&self.env];
+        for child in children.iter() {
+            inputs.push_back(child.to_u256());
+        }
+        let result = match self.arity {
+            Arity::Binary => poseidon_hash::<3, BlsScalar>(&self.env, &inputs),
+            Arity::Quaternary => poseidon_hash::<5, BlsScalar>(&self.env, &inputs),
+        };
+        BlsScalar::from_u256(result)
+    }
+
+    /// Generates a merkle proof for a given leaf index
+    ///
+    /// Returns, from the leaf level up, the `arity - 1` siblings at each level (in
+    /// left-to-right order, skipping the path node) and the path node's position among
+    /// its siblings. The positions are the base-`arity` digits of `leaf_index`.
+    pub fn generate_proof(&self, leaf_index: u32) -> Option<(Vec<Vec<BlsScalar>>, Vec<u32>)> {
+        if leaf_index >= self.leaves.len() {
+            return None;
+        }
+
+        let arity = self.arity.children();
+        let mut siblings = vec![&self.env];
+        let mut positions = vec![&self.env];
+        let mut index = leaf_index;
+
+        for level in 1..=self.depth {
+            let position = index % arity;
+            index /= arity;
+
+            let mut level_siblings = vec![&self.env];
+            for (child, value) in self.children_of(level, index).iter().enumerate() {
+                if child as u32 != position {
+                    level_siblings.push_back(value);
+                }
+            }
+            siblings.push_back(level_siblings);
+            positions.push_back(position);
+        }
+
+        Some((siblings, positions))
+    }
+
+    /// Verifies a proof from `generate_proof` against the current root
+    pub fn verify_proof(
+        &self,
+        leaf: &BytesN<32>,
+        leaf_index: u32,
+        siblings: &Vec<Vec<BlsScalar>>,
+    ) -> bool {
+        if siblings.len() != self.depth || leaf_index >= self.capacity {
+            return false;
+        }
+
+        let arity = self.arity.children();
+        let mut current = bytes_to_bls_scalar(leaf);
+        let mut index = leaf_index;
+
+        for level_siblings in siblings.iter() {
+            if level_siblings.len() != arity - 1 {
+                return false;
+            }
+
+            let position = index % arity;
+            let mut children = level_siblings.clone();
+            children.insert(position, current);
+            current = self.hash_children(&children);
+            index /= arity;
+        }
+
+        bls_scalar_to_bytes(current) == self.root
+    }
+
+    /// Gets the value of the node at `(level, index)`; level 0 holds the leaves
+    pub fn get_node_scalar(&self, level: u32, index: u32) -> BlsScalar {
+        if level == 0 {
+            return match self.leaves.get(index) {
+                Some(leaf) => bytes_to_bls_scalar(&leaf),
+                None => self.zero_hashes.get(0).unwrap(),
+            };
+        }
+
+        self.nodes
+            .get((level, index))
+            .unwrap_or_else(|| self.zero_hashes.get(level).unwrap())
+    }
+
+    /// Collects the children of the node at `(level, index)`
+    fn children_of(&self, level: u32, index: u32) -> Vec<BlsScalar> {
+        let arity = self.arity.children();
+        let mut children = vec![&self.env];
+        for child in 0..arity {
+            children.push_back(self.get_node_scalar(level - 1, index * arity + child));
+        }
+        children
+    }
+
+    /// Gets the current root
+    pub fn get_root(&self) -> BytesN<32> {
+        self.root.clone()
+    }
+
+    /// Gets the current root as BlsScalar (for computation)
+    pub fn get_root_scalar(&self) -> BlsScalar {
+        bytes_to_bls_scalar(&self.root)
+    }
+
+    /// Gets the number of levels between the leaves and the root
+    pub fn get_depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Gets the number of children per node
+    pub fn get_arity(&self) -> Arity {
+        self.arity
+    }
+
+    /// Gets the number of leaves that have been explicitly inserted
+    pub fn get_leaf_count(&self) -> u32 {
+        self.leaves.len()
+    }
+
+    /// Maximum number of leaves: arity^depth, saturating at `u32::MAX`
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Gets all leaves
+    pub fn get_leaves(&self) -> &Vec<BytesN<32>> {
+        &self.leaves
+    }
+}
@@ -0,0 +1,262 @@
+//! Two-to-one Poseidon hash as used for `LeanIMT` nodes
+//!
+//! This is circomlib's `Poseidon(2)` over the BLS12-381 scalar field (the `Poseidon255`
+//! template in `circuits/poseidon255.circom`), computed with soroban-poseidon. `LeanIMT`
+//! hashes every `HashFunction::Poseidon` node through it, so callers that hash nodes
+//! themselves get exactly the tree's values.
+//!
+//! Both backends here call the host's Poseidon permutation through soroban-poseidon.
+//! Another implementation (a different crate, or a future native host hash) can be
+//! plugged in through `HashBackend`, but its roots only match the tree's, and the
+//! circuits', if it uses circomlib's round constants and MDS matrix for BLS12-381.
+
+use soroban_poseidon::{poseidon2_hash, poseidon_hash, Field, PoseidonConfig, PoseidonSponge};
+use soroban_sdk::{crypto::bls12_381::Fr as BlsScalar, vec, Bytes, BytesN, Env, Vec, U256};
+
+use crate::{
+    bls_scalar_to_bytes, bytes_to_bls_scalar, LeanIMTError, DEFAULT_POSEIDON_WIDTH,
+    SUPPORTED_POSEIDON_WIDTHS,
+};
+
+/// Bytes per field element in `Poseidon255::hash_bytes`; 31 bytes are always below the
+/// field modulus, so no chunk is reduced
+pub const BYTES_PER_CHUNK: usize = 31;
+
+/// Exponent of circomlib's S-box, `x^5`
+const SBOX_DEGREE: u32 = 5;
+
+/// Two-to-one hash for combining tree nodes
+pub trait HashBackend {
+    /// Hashes two field elements into their parent node
+    fn hash_two(&self, left: &BlsScalar, right: &BlsScalar) -> BlsScalar;
+}
+
+/// Poseidon with two inputs (state width 3) over the BLS12-381 scalar field
+///
+/// `from_constants` swaps in other round constants and MDS matrix, at any width.
+#[derive(Clone)]
+pub struct Poseidon255 {
+    env: Env,
+    // Parameters from `from_constants`; circomlib's width-3 ones when `None`
+    constants: Option<PoseidonConstants>,
+}
+
+/// Round constants and MDS matrix given to `Poseidon255::from_constants`
+#[derive(Clone)]
+struct PoseidonConstants {
+    t: u32,
+    rounds_f: u32,
+    rounds_p: u32,
+    rc: Vec<Vec<U256>>,
+    mds: Vec<Vec<U256>>,
+}
+
+impl Poseidon255 {
+    pub fn new(env: &Env) -> Self {
+        Self {
+            env: env.clone(),
+            constants: None,
+        }
+    }
+
+    /// Creates a Poseidon hash with state width `t` and the given parameters
+    ///
+    /// `constants` holds 32-byte big-endian field elements: the `(rounds_f + rounds_p)`
+    /// rows of `t` round constants, then the `t` rows of the MDS matrix, the layout of
+    /// `default_constants`. Lets a deployment match a circuit built with non-default
+    /// Poseidon parameters without recompiling; inputs go in state positions 1 and 2,
+    /// and the hash is position 0 after the permutation, as in circomlib.
+    ///
+    /// Returns `LeanIMTError::UnsupportedWidth` if `t` is not in
+    /// `SUPPORTED_POSEIDON_WIDTHS`, `LeanIMTError::ConstantsLength` if `constants` doesn't
+    /// hold exactly that many elements, and `LeanIMTError::NonCanonicalConstant` if one
+    /// isn't below the modulus.
+    pub fn from_constants(
+        env: &Env,
+        t: u32,
+        rounds_f: u32,
+        rounds_p: u32,
+        constants: &Bytes,
+    ) -> Result<Self, LeanIMTError> {
+        if !SUPPORTED_POSEIDON_WIDTHS.contains(&t) {
+            return Err(LeanIMTError::UnsupportedWidth);
+        }
+        let rounds = (rounds_f as u64).checked_add(rounds_p as u64);
+        let expected = rounds
+            .and_then(|rounds| rounds.checked_add(t as u64))
+            .and_then(|rows| rows.checked_mul(t as u64))
+            .and_then(|count| count.checked_mul(32))
+            .unwrap_or(u64::MAX);
+        let length_error = LeanIMTError::ConstantsLength {
+            expected,
+            actual: constants.len(),
+        };
+        if constants.len() as u64 != expected {
+            return Err(length_error);
+        }
+
+        let modulus = <BlsScalar as Field>::modulus(env);
+        let mut elements = (0..constants.len() / 32).map(|i| {
+            let element = U256::from_be_bytes(env, &constants.slice(i * 32..(i + 1) * 32));
+            if element < modulus {
+                Ok(element)
+            } else {
+                Err(LeanIMTError::NonCanonicalConstant)
+            }
+        });
+        let mut rows = |count: u64| -> Result<Vec<Vec<U256>>, LeanIMTError> {
+            let mut rows = vec![env];
+            for _ in 0..count {
+                let mut row = vec![env];
+                for _ in 0..t {
+                    row.push_back(elements.next().ok_or(length_error)??);
+                }
+                rows.push_back(row);
+            }
+            Ok(rows)
+        };
+        let rc = rows(rounds_f as u64 + rounds_p as u64)?;
+        let mds = rows(t as u64)?;
+
+        Ok(Self {
+            env: env.clone(),
+            constants: Some(PoseidonConstants {
+                t,
+                rounds_f,
+                rounds_p,
+                rc,
+                mds,
+            }),
+        })
+    }
+
+    /// circomlib's parameters for state width `t`, as `(rounds_f, rounds_p, constants)`
+    /// in the layout `from_constants` takes
+    ///
+    /// `None` for widths outside `SUPPORTED_POSEIDON_WIDTHS`.
+    pub fn default_constants(env: &Env, t: u32) -> Option<(u32, u32, Bytes)> {
+        match t {
+            3 => Some(encode_constants::<3>(env)),
+            4 => Some(encode_constants::<4>(env)),
+            5 => Some(encode_constants::<5>(env)),
+            6 => Some(encode_constants::<6>(env)),
+            _ => None,
+        }
+    }
+
+    /// Hashes two field elements: `Poseidon(left, right)`
+    pub fn hash_two(&self, left: &BlsScalar, right: &BlsScalar) -> BlsScalar {
+        let Some(constants) = &self.constants else {
+            let inputs = Vec::from_array(&self.env, [left.to_u256(), right.to_u256()]);
+            return BlsScalar::from_u256(poseidon_hash::<3, BlsScalar>(&self.env, &inputs));
+        };
+
+        let zero = U256::from_u32(&self.env, 0);
+        let mut state = vec![&self.env, zero.clone(), left.to_u256(), right.to_u256()];
+        for _ in DEFAULT_POSEIDON_WIDTH..constants.t {
+            state.push_back(zero.clone());
+        }
+        let state = self.env.crypto_hazmat().poseidon_permutation(
+            &state,
+            <BlsScalar as Field>::symbol(),
+            constants.t,
+            SBOX_DEGREE,
+            constants.rounds_f,
+            constants.rounds_p,
+            &constants.mds,
+            &constants.rc,
+        );
+        BlsScalar::from_u256(state.get(0).unwrap())
+    }
+
+    /// Hashes two field elements given as bytes
+    ///
+    /// Inputs and output are 32-byte big-endian, the encoding of `bls_scalar_to_bytes`
+    /// used for leaves and roots in storage. Inputs are not reduced, so they should be
+    /// below the field modulus.
+    pub fn hash_two_bytes(&self, left: &BytesN<32>, right: &BytesN<32>) -> BytesN<32> {
+        bls_scalar_to_bytes(self.hash_two(&bytes_to_bls_scalar(left), &bytes_to_bls_scalar(right)))
+    }
+
+    /// Hashes a byte string of any length into one field element
+    ///
+    /// `data` is split into chunks of `BYTES_PER_CHUNK` bytes, each read as a big-endian
+    /// integer (the last chunk may be shorter); empty input is a single empty chunk. The
+    /// chunks are absorbed left to right into a state that starts at the byte length:
+    ///
+    /// ```text
+    /// state = len(data)
+    /// state = Poseidon(state, chunk_i)   for each chunk
+    /// ```
+    ///
+    /// The length prefix keeps inputs that differ only in leading zero bytes apart. A
+    /// circuit computes the same value with one `Poseidon(2)` per chunk.
+    pub fn hash_bytes(&self, data: &[u8]) -> BlsScalar {
+        let mut state = BlsScalar::from_u256(U256::from_u128(&self.env, data.len() as u128));
+        if data.is_empty() {
+            return self.hash_two(&state, &self.chunk(&[]));
+        }
+        for chunk in data.chunks(BYTES_PER_CHUNK) {
+            state = self.hash_two(&state, &self.chunk(chunk));
+        }
+        state
+    }
+
+    /// A chunk of at most `BYTES_PER_CHUNK` bytes as a big-endian field element
+    fn chunk(&self, chunk: &[u8]) -> BlsScalar {
+        let mut padded = [0u8; 32];
+        padded[32 - chunk.len()..].copy_from_slice(chunk);
+        BlsScalar::from_u256(U256::from_be_bytes(
+            &self.env,
+            &Bytes::from_array(&self.env, &padded),
+        ))
+    }
+}
+
+/// Round constants followed by the MDS matrix of soroban-poseidon's width-`T` sponge
+fn encode_constants<const T: u32>(env: &Env) -> (u32, u32, Bytes)
+where
+    PoseidonSponge<T, BlsScalar>: PoseidonConfig<T, BlsScalar>,
+{
+    let mut constants = Bytes::new(env);
+    let rc = <PoseidonSponge<T, BlsScalar> as PoseidonConfig<T, BlsScalar>>::get_rc(env);
+    let mds = <PoseidonSponge<T, BlsScalar> as PoseidonConfig<T, BlsScalar>>::get_mds(env);
+    for row in rc.iter().chain(mds.iter()) {
+        for element in row.iter() {
+            constants.append(&element.to_be_bytes());
+        }
+    }
+    (
+        <PoseidonSponge<T, BlsScalar> as PoseidonConfig<T, BlsScalar>>::ROUNDS_F,
+        <PoseidonSponge<T, BlsScalar> as PoseidonConfig<T, BlsScalar>>::ROUNDS_P,
+        constants,
+    )
+}
+
+impl HashBackend for Poseidon255 {
+    fn hash_two(&self, left: &BlsScalar, right: &BlsScalar) -> BlsScalar {
+        Poseidon255::hash_two(self, left, right)
+    }
+}
+
+/// Poseidon2 with two inputs (state width 3) over the BLS12-381 scalar field
+///
+/// The hash behind `HashFunction::Poseidon2`. Its constants differ from `Poseidon255`'s,
+/// so it yields different nodes for the same inputs and no circuit here accepts them.
+#[derive(Clone)]
+pub struct Poseidon2Hasher {
+    env: Env,
+}
+
+impl Poseidon2Hasher {
+    pub fn new(env: &Env) -> Self {
+        Self { env: env.clone() }
+    }
+}
+
+impl HashBackend for Poseidon2Hasher {
+    fn hash_two(&self, left: &BlsScalar, right: &BlsScalar) -> BlsScalar {
+        let inputs = Vec::from_array(&self.env, [left.to_u256(), right.to_u256()]);
+        BlsScalar::from_u256(poseidon2_hash::<3, BlsScalar>(&self.env, &inputs))
+    }
+}
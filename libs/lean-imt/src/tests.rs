@@ -1,4 +1,6 @@
 use crate::*;
+use soroban_poseidon::poseidon_hash;
+use soroban_sdk::{bytesn, Bytes};
 
 #[test]
 fn test_new_tree() {
@@ -735,10 +737,15 @@ fn test_insert_beyond_capacity_returns_error() {
     // This should return an error
     let result = tree.insert_u64(3);
     assert!(result.is_err());
+    assert_eq!(result.unwrap_err(), LeanIMTError::TreeFull);
     assert_eq!(
-        result.unwrap_err(),
+        result.unwrap_err().message(),
         "Tree is at capacity: cannot insert more leaves"
     );
+
+    // The rejected leaf must not have touched the tree
+    assert_eq!(tree.get_leaf_count(), 2);
+    assert_eq!(tree.capacity(), 2);
 }
 
 #[test]
@@ -760,3 +767,1713 @@ fn test_capacity_for_various_depths() {
         );
     }
 }
+
+#[test]
+fn test_default_hash_function_is_canonical_poseidon() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let mut default_tree = LeanIMT::new(&env, 3);
+    let mut poseidon_tree = LeanIMT::new_with_hash_function(&env, 3, HashFunction::Poseidon);
+    assert_eq!(default_tree.get_hash_function(), HashFunction::Poseidon);
+
+    for i in 1..=5 {
+        default_tree.insert_u64(i).unwrap();
+        poseidon_tree.insert_u64(i).unwrap();
+    }
+    assert_eq!(default_tree.get_root(), poseidon_tree.get_root());
+
+    // A depth-1 root must be exactly circom's Poseidon(left, right) with t=3
+    let mut small_tree = LeanIMT::new(&env, 1);
+    small_tree.insert_u64(1).unwrap();
+    small_tree.insert_u64(2).unwrap();
+    let inputs = Vec::from_array(&env, [U256::from_u32(&env, 1), U256::from_u32(&env, 2)]);
+    let expected = BlsScalar::from_u256(poseidon_hash::<3, BlsScalar>(&env, &inputs));
+    assert_eq!(small_tree.get_root_scalar(), expected);
+}
+
+#[test]
+fn test_poseidon2_backend_is_not_interchangeable() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let mut poseidon_tree = LeanIMT::new_with_hash_function(&env, 3, HashFunction::Poseidon);
+    let mut poseidon2_tree = LeanIMT::new_with_hash_function(&env, 3, HashFunction::Poseidon2);
+
+    // Empty trees already differ since the zero-subtree hashes differ
+    assert_ne!(poseidon_tree.get_root(), poseidon2_tree.get_root());
+
+    for i in 1..=5 {
+        poseidon_tree.insert_u64(i).unwrap();
+        poseidon2_tree.insert_u64(i).unwrap();
+    }
+    assert_ne!(
+        poseidon_tree.get_root(),
+        poseidon2_tree.get_root(),
+        "Poseidon and Poseidon2 roots must not be mixed up"
+    );
+
+    // Incremental and on-demand paths must agree for the non-default backend too
+    let root_node = poseidon2_tree.get_node(3, 0).unwrap();
+    assert_eq!(root_node, poseidon2_tree.get_root());
+
+    // Restored trees always use the canonical backend
+    let (leaves, depth, root) = poseidon2_tree.to_storage();
+    let restored = LeanIMT::from_storage(&env, leaves, depth, root);
+    assert_eq!(restored.get_hash_function(), HashFunction::Poseidon);
+}
+
+#[test]
+fn test_scalar_byte_order_roundtrip() {
+    let env = Env::default();
+
+    let mut be = [0u8; 32];
+    for (i, byte) in be.iter_mut().enumerate().skip(1) {
+        *byte = i as u8;
+    }
+    let scalar = scalar_from_be_bytes(&BytesN::from_array(&env, &be));
+
+    // Each ordering round-trips through its own pair of helpers
+    assert_eq!(scalar_from_be_bytes(&scalar_to_be_bytes(&scalar)), scalar);
+    assert_eq!(scalar_from_le_bytes(&scalar_to_le_bytes(&scalar)), scalar);
+
+    // The two encodings are exact byte reversals of each other
+    let mut reversed = be;
+    reversed.reverse();
+    assert_eq!(scalar_to_be_bytes(&scalar).to_array(), be);
+    assert_eq!(scalar_to_le_bytes(&scalar).to_array(), reversed);
+
+    // Mixing orderings must not silently produce the same scalar
+    let mixed = scalar_from_le_bytes(&scalar_to_be_bytes(&scalar));
+    assert_ne!(mixed, scalar);
+
+    // Small values sit in the last byte for BE and the first byte for LE
+    let one = u64_to_bls_scalar(&env, 1);
+    assert_eq!(scalar_to_be_bytes(&one).to_array()[31], 1);
+    assert_eq!(scalar_to_le_bytes(&one).to_array()[0], 1);
+    assert_eq!(bls_scalar_to_bytes(one.clone()), scalar_to_be_bytes(&one));
+}
+
+#[test]
+fn test_get_frontier_shape_follows_leaf_count_bits() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let mut tree = LeanIMT::new(&env, 4);
+    assert!(tree.get_frontier().iter().all(|node| node.is_none()));
+
+    for i in 1..=6u64 {
+        tree.insert_u64(i).unwrap();
+    }
+
+    // 6 = 0b0110: levels 1 and 2 hold complete left subtrees
+    let frontier = tree.get_frontier();
+    assert_eq!(frontier.len(), 4);
+    assert_eq!(frontier.get(0).unwrap(), None);
+    assert_eq!(frontier.get(1).unwrap(), tree.get_node(1, 2));
+    assert_eq!(frontier.get(2).unwrap(), tree.get_node(2, 0));
+    assert_eq!(frontier.get(3).unwrap(), None);
+}
+
+#[test]
+fn test_from_storage_with_frontier_continues_inserts() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let mut reference = LeanIMT::new(&env, 5);
+    for i in 1..=11u64 {
+        reference.insert_u64(i).unwrap();
+    }
+
+    let (leaves, depth, root) = reference.to_storage();
+    let frontier = reference.get_frontier();
+    let mut restored = LeanIMT::from_storage_with_frontier(&env, leaves, depth, root, frontier);
+
+    for i in 12..=20u64 {
+        reference.insert_u64(i).unwrap();
+        restored.insert_u64(i).unwrap();
+        assert_eq!(restored.get_root(), reference.get_root());
+        assert_eq!(restored.get_frontier(), reference.get_frontier());
+    }
+
+    // Proofs on the restored tree still cover leaves left of the frontier
+    let (siblings, _) = restored.generate_proof(3).unwrap();
+    let (expected, _) = reference.generate_proof(3).unwrap();
+    assert_eq!(siblings, expected);
+}
+
+#[test]
+fn test_from_storage_insert_matches_fresh_tree() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let mut reference = LeanIMT::new(&env, 6);
+    for i in 1..=5u64 {
+        reference.insert_u64(i).unwrap();
+    }
+
+    let (leaves, depth, root) = reference.to_storage();
+    let mut restored = LeanIMT::from_storage(&env, leaves, depth, root);
+
+    reference.insert_u64(6).unwrap();
+    restored.insert_u64(6).unwrap();
+    assert_eq!(restored.get_root(), reference.get_root());
+}
+
+#[test]
+fn test_from_storage_proofs_match_original_tree() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    // 37 of 64 leaves: the reloaded tree has full, partial and empty subtrees to rebuild
+    let mut original = LeanIMT::new(&env, 6);
+    for i in 1..=37u64 {
+        original.insert_u64(i * 11).unwrap();
+    }
+
+    let (leaves, depth, root) = original.to_storage();
+    let restored = LeanIMT::from_storage(&env, leaves, depth, root);
+
+    for leaf_index in 0..original.get_leaf_count() {
+        let proof = restored.generate_proof(leaf_index);
+        assert_eq!(proof, original.generate_proof(leaf_index));
+        let (siblings, _) = proof.unwrap();
+        let leaf = restored.get_leaf(leaf_index as usize).unwrap();
+        assert!(restored.verify_proof(&leaf, leaf_index, &siblings));
+    }
+    assert_eq!(restored.generate_proof(37), None);
+}
+
+#[test]
+fn test_single_leaf_proofs_verify_at_small_depths() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    for depth in 0..=2u32 {
+        let mut tree = LeanIMT::new(&env, depth);
+        tree.insert_u64(7).unwrap();
+
+        let (siblings, proof_depth) = tree.generate_proof(0).unwrap();
+        assert_eq!(proof_depth, depth);
+        assert_eq!(siblings.len(), depth);
+
+        // Missing siblings are zero padding at the leaf level
+        if depth > 0 {
+            assert_eq!(siblings.get(0).unwrap(), u64_to_bls_scalar(&env, 0));
+        }
+
+        let leaf = tree.get_leaf(0).unwrap();
+        assert!(tree.verify_proof(&leaf, 0, &siblings), "depth {}", depth);
+    }
+
+    // A depth-0 tree's root is its only leaf
+    let mut tree = LeanIMT::new(&env, 0);
+    tree.insert_u64(7).unwrap();
+    assert_eq!(tree.get_root(), tree.get_leaf(0).unwrap());
+    assert!(tree.generate_proof(1).is_none());
+}
+
+#[test]
+fn test_verify_proof_rejects_tampered_inputs() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let mut tree = LeanIMT::new(&env, 3);
+    for i in 1..=5u64 {
+        tree.insert_u64(i).unwrap();
+    }
+
+    for index in 0..5u32 {
+        let (siblings, _) = tree.generate_proof(index).unwrap();
+        let leaf = tree.get_leaf(index as usize).unwrap();
+        assert!(tree.verify_proof(&leaf, index, &siblings));
+    }
+
+    let (siblings, _) = tree.generate_proof(2).unwrap();
+    let leaf = tree.get_leaf(2).unwrap();
+    let other_leaf = tree.get_leaf(3).unwrap();
+    assert!(!tree.verify_proof(&other_leaf, 2, &siblings));
+    assert!(!tree.verify_proof(&leaf, 3, &siblings));
+    assert!(!tree.verify_proof(&leaf, 2 + 8, &siblings));
+
+    let mut short = siblings.clone();
+    short.pop_back();
+    assert!(!tree.verify_proof(&leaf, 2, &short));
+}
+
+#[test]
+fn test_clear_resets_to_fresh_tree() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let mut tree = LeanIMT::new(&env, 4);
+    let empty_root = tree.get_root();
+    for i in 1..=9u64 {
+        tree.insert_u64(i).unwrap();
+    }
+
+    tree.clear();
+    assert!(tree.is_empty());
+    assert_eq!(tree.get_depth(), 4);
+    assert_eq!(tree.get_root(), empty_root);
+
+    let mut fresh = LeanIMT::new(&env, 4);
+    for i in [3u64, 1, 4] {
+        tree.insert_u64(i).unwrap();
+        fresh.insert_u64(i).unwrap();
+    }
+    assert_eq!(tree.get_root(), fresh.get_root());
+    assert_eq!(tree.generate_proof(2), fresh.generate_proof(2));
+
+    // The hash function survives a reset
+    let mut poseidon2_tree = LeanIMT::new_with_hash_function(&env, 2, HashFunction::Poseidon2);
+    let poseidon2_empty_root = poseidon2_tree.get_root();
+    poseidon2_tree.insert_u64(1).unwrap();
+    poseidon2_tree.clear();
+    assert_eq!(poseidon2_tree.get_hash_function(), HashFunction::Poseidon2);
+    assert_eq!(poseidon2_tree.get_root(), poseidon2_empty_root);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_json_roundtrip_restores_tree() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let mut tree = LeanIMT::new(&env, 3);
+    for i in [5u64, 10, 15] {
+        tree.insert_u64(i).unwrap();
+    }
+
+    let json = tree.to_json();
+    assert!(json.contains("\"leaves\":[\"5\",\"10\",\"15\"]"));
+
+    let mut restored = LeanIMT::from_json(&env, &json).unwrap();
+    assert_eq!(restored.to_storage(), tree.to_storage());
+    assert_eq!(restored.generate_proof(1), tree.generate_proof(1));
+
+    tree.insert_u64(20).unwrap();
+    restored.insert_u64(20).unwrap();
+    assert_eq!(restored.get_root(), tree.get_root());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_from_json_rejects_invalid_input() {
+    let env = Env::default();
+
+    assert!(LeanIMT::from_json(&env, "not json").is_err());
+    assert!(LeanIMT::from_json(&env, r#"{"depth": 1, "leaves": ["1"]}"#).is_err());
+    assert!(LeanIMT::from_json(
+        &env,
+        r#"{"depth": 1, "leaves": ["1", "2", "3"], "root": "0"}"#
+    )
+    .is_err());
+    assert!(LeanIMT::from_json(&env, r#"{"depth": 1, "leaves": ["0x01"], "root": "0"}"#).is_err());
+
+    // The field modulus itself is not a valid element
+    let modulus = "52435875175126190479447740508185965837690552500527637822603658699938581184513";
+    let json = std::format!(r#"{{"depth": 1, "leaves": ["{}"], "root": "0"}}"#, modulus);
+    assert!(LeanIMT::from_json(&env, &json).is_err());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_render_tree_shows_occupied_nodes() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let mut tree = LeanIMT::new(&env, 2);
+    for i in [5u64, 10, 15] {
+        tree.insert_u64(i).unwrap();
+    }
+
+    let rendered = tree.render_tree();
+    let lines: std::vec::Vec<&str> = rendered.lines().collect();
+    // Root, both level-1 nodes and the three inserted leaves
+    assert_eq!(lines.len(), 6);
+    assert!(lines[0].starts_with("L2[0] "));
+    assert!(lines[0].ends_with("..."));
+    assert!(lines[1].starts_with("  L1[0] "));
+    assert_eq!(lines[2], "    L0[0] 5");
+    assert_eq!(lines[3], "    L0[1] 10");
+    assert!(lines[4].starts_with("  L1[1] "));
+    assert_eq!(lines[5], "    L0[2] 15");
+
+    let empty = LeanIMT::new(&env, 20);
+    assert_eq!(empty.render_tree().lines().count(), 1);
+}
+
+#[test]
+fn test_get_node_scalar_matches_get_node() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let mut tree = LeanIMT::new(&env, 3);
+    for i in 1..=5u64 {
+        tree.insert_u64(i).unwrap();
+    }
+
+    for level in 0..=3u32 {
+        for index in 0..(8u32 >> level) {
+            let bytes = tree.get_node(level, index);
+            let scalar = tree.get_node_scalar(level, index);
+            assert_eq!(scalar.clone().map(bls_scalar_to_bytes), bytes);
+            assert_eq!(tree.get_subtree_root(level, index), scalar);
+        }
+    }
+
+    // Top of the tree is the root; out-of-range queries mirror get_node
+    assert_eq!(tree.get_node_scalar(3, 0), Some(tree.get_root_scalar()));
+    assert_eq!(tree.get_node_scalar(0, 5), None);
+    assert_eq!(tree.get_node_scalar(4, 0), None);
+
+    // A level-1 subtree root hashes its two leaves
+    let expected = tree.hash_pair(u64_to_bls_scalar(&env, 3), u64_to_bls_scalar(&env, 4));
+    assert_eq!(tree.get_subtree_root(1, 1), Some(expected));
+}
+
+#[test]
+fn test_proof_siblings_match_get_node() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    // A filled tree, and one whose right side is still empty. `get_node` has no node
+    // past the last leaf, where proofs use the zero subtree root instead.
+    let zeros = zero_hashes(&env, 3);
+    for leaf_count in [8u64, 5] {
+        let mut tree = LeanIMT::new(&env, 3);
+        for i in 1..=leaf_count {
+            tree.insert_u64(i).unwrap();
+        }
+
+        for leaf_index in 0..leaf_count as u32 {
+            let (siblings, _) = tree.generate_proof(leaf_index).unwrap();
+            assert_eq!(siblings.len(), 3);
+            for level in 0..3u32 {
+                let sibling_index = (leaf_index >> level) ^ 1;
+                let expected = match tree.get_node_scalar(level, sibling_index) {
+                    Some(node) => node,
+                    None => {
+                        assert!(leaf_count < 8);
+                        zeros.get(level).unwrap()
+                    }
+                };
+                assert_eq!(
+                    siblings.get(level).unwrap(),
+                    expected,
+                    "leaf {} level {}",
+                    leaf_index,
+                    level
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_generate_proof_at_matches_historical_roots() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    // Record the root after every insertion
+    let mut tree = LeanIMT::new(&env, 3);
+    let mut roots = vec![&env];
+    for leaf in 1u64..=6 {
+        tree.insert_u64(leaf).unwrap();
+        roots.push_back(tree.get_root_scalar());
+    }
+
+    for count in 1..=6u32 {
+        for leaf_index in 0..count {
+            let (siblings, depth) = tree.generate_proof_at(leaf_index, count).unwrap();
+            assert_eq!(depth, 3);
+
+            let mut node = u64_to_bls_scalar(&env, leaf_index as u64 + 1);
+            let mut index = leaf_index;
+            for sibling in siblings.iter() {
+                node = if index.is_multiple_of(2) {
+                    tree.hash_pair(node, sibling)
+                } else {
+                    tree.hash_pair(sibling, node)
+                };
+                index /= 2;
+            }
+            assert_eq!(
+                node,
+                roots.get(count - 1).unwrap(),
+                "leaf {} at count {}",
+                leaf_index,
+                count
+            );
+        }
+        assert!(tree.generate_proof_at(count, count).is_none());
+    }
+
+    // At the current count it is the ordinary proof
+    assert_eq!(tree.generate_proof_at(2, 6), tree.generate_proof(2));
+    assert!(tree.generate_proof_at(0, 7).is_none());
+}
+
+#[test]
+fn test_proof_iter_matches_generate_proof() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let mut tree = LeanIMT::new(&env, 3);
+    for leaf in 1u64..=5 {
+        tree.insert_u64(leaf).unwrap();
+    }
+
+    for leaf_index in 0..tree.get_leaf_count() {
+        let (siblings, _) = tree.generate_proof(leaf_index).unwrap();
+        let iter = tree.proof_iter(leaf_index).unwrap();
+        assert_eq!(iter.len(), 3);
+
+        for (level, (sibling, is_right)) in iter.enumerate() {
+            assert_eq!(sibling, siblings.get(level as u32).unwrap());
+            assert_eq!(is_right, (leaf_index >> level) & 1 == 1);
+        }
+    }
+
+    assert!(tree.proof_iter(5).is_none());
+}
+
+#[test]
+fn test_zero_hashes_match_recursive_computation() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let depth = 4;
+    let zeros = zero_hashes(&env, depth);
+    assert_eq!(zeros.len(), depth + 1);
+
+    // Hash a full level of zero leaves pairwise all the way up
+    let tree = LeanIMT::new(&env, depth);
+    let mut level_nodes: Vec<BlsScalar> = Vec::new(&env);
+    for _ in 0..1u32 << depth {
+        level_nodes.push_back(u64_to_bls_scalar(&env, 0));
+    }
+    for level in 0..=depth {
+        for node in level_nodes.iter() {
+            assert_eq!(node, zeros.get(level).unwrap());
+        }
+        let mut parents = Vec::new(&env);
+        for i in 0..level_nodes.len() / 2 {
+            parents.push_back(tree.hash_pair(
+                level_nodes.get(2 * i).unwrap(),
+                level_nodes.get(2 * i + 1).unwrap(),
+            ));
+        }
+        level_nodes = parents;
+    }
+
+    // The top entry is the empty root, for either hash function
+    assert_eq!(zeros.get(depth).unwrap(), tree.get_root_scalar());
+    let tree2 = LeanIMT::new_with_hash_function(&env, depth, HashFunction::Poseidon2);
+    let zeros2 = zero_hashes_with_hash_function(&env, depth, HashFunction::Poseidon2);
+    assert_eq!(zeros2.get(depth).unwrap(), tree2.get_root_scalar());
+}
+
+#[test]
+fn test_get_path_matches_proof_iter() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let mut tree = LeanIMT::new(&env, 2);
+    for leaf in 1u64..=3 {
+        tree.insert_u64(leaf).unwrap();
+    }
+
+    let (siblings, directions) = tree.get_path(2).unwrap();
+    assert_eq!(siblings.len(), 2);
+    assert_eq!(directions, vec![&env, false, true]);
+    for (level, (sibling, _)) in tree.proof_iter(2).unwrap().enumerate() {
+        assert_eq!(
+            siblings.get(level as u32).unwrap(),
+            bls_scalar_to_bytes(sibling)
+        );
+    }
+
+    assert!(tree.get_path(3).is_none());
+}
+
+#[test]
+fn test_nary_binary_matches_lean_imt() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let mut lean = LeanIMT::new(&env, 3);
+    let mut nary = NaryIMT::new(&env, 3, Arity::default());
+    assert_eq!(nary.get_root(), lean.get_root());
+
+    for leaf in 1u64..=5 {
+        lean.insert_u64(leaf).unwrap();
+        nary.insert(bls_scalar_to_bytes(u64_to_bls_scalar(&env, leaf)))
+            .unwrap();
+        assert_eq!(nary.get_root(), lean.get_root());
+    }
+
+    let (siblings, positions) = nary.generate_proof(4).unwrap();
+    let (lean_siblings, _) = lean.generate_proof(4).unwrap();
+    for level in 0..3 {
+        assert_eq!(
+            siblings.get(level).unwrap(),
+            vec![&env, lean_siblings.get(level).unwrap()]
+        );
+    }
+    assert_eq!(positions, vec![&env, 0, 0, 1]);
+}
+
+#[test]
+fn test_nary_quaternary_proofs() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    // Same capacity as a binary tree of depth 4 with half the levels
+    let mut tree = NaryIMT::new(&env, 2, Arity::Quaternary);
+    assert_eq!(tree.capacity(), 16);
+
+    for leaf in 1u64..=6 {
+        tree.insert(bls_scalar_to_bytes(u64_to_bls_scalar(&env, leaf)))
+            .unwrap();
+    }
+
+    for leaf_index in 0..6 {
+        let leaf = tree.get_leaves().get(leaf_index).unwrap();
+        let (siblings, positions) = tree.generate_proof(leaf_index).unwrap();
+        assert_eq!(siblings.len(), 2);
+        assert_eq!(siblings.get(0).unwrap().len(), 3);
+        // Base-4 digits of the index
+        assert_eq!(positions, vec![&env, leaf_index % 4, leaf_index / 4]);
+        assert!(tree.verify_proof(&leaf, leaf_index, &siblings));
+        assert!(!tree.verify_proof(&leaf, (leaf_index + 1) % 16, &siblings));
+    }
+
+    // The root hashes four children per node
+    let mut level_one = vec![&env];
+    for node in 0..4 {
+        level_one.push_back(tree.get_node_scalar(1, node));
+    }
+    assert_eq!(tree.hash_children(&level_one), tree.get_root_scalar());
+
+    assert!(tree.generate_proof(6).is_none());
+    for leaf in 7u64..=16 {
+        tree.insert(bls_scalar_to_bytes(u64_to_bls_scalar(&env, leaf)))
+            .unwrap();
+    }
+    assert_eq!(
+        tree.insert(bls_scalar_to_bytes(u64_to_bls_scalar(&env, 17))),
+        Err(LeanIMTError::TreeFull)
+    );
+}
+
+#[test]
+fn test_poseidon255_hash_two_bytes_matches_tree() {
+    let env = Env::default();
+    let poseidon = Poseidon255::new(&env);
+
+    let left = bls_scalar_to_bytes(u64_to_bls_scalar(&env, 1));
+    let right = bls_scalar_to_bytes(u64_to_bls_scalar(&env, 2));
+
+    let mut tree = LeanIMT::new(&env, 1);
+    tree.insert(left.clone()).unwrap();
+    tree.insert(right.clone()).unwrap();
+
+    assert_eq!(poseidon.hash_two_bytes(&left, &right), tree.get_root());
+    assert_eq!(
+        bytes_to_bls_scalar(&poseidon.hash_two_bytes(&left, &right)),
+        poseidon.hash_two(&u64_to_bls_scalar(&env, 1), &u64_to_bls_scalar(&env, 2))
+    );
+
+    // circomlib Poseidon(2)(1, 2), big-endian
+    assert_eq!(
+        poseidon.hash_two_bytes(&left, &right).to_array()[..4],
+        [0x3f, 0xb8, 0x31, 0x0b]
+    );
+}
+
+#[test]
+fn test_insert_scalar_matches_insert() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let mut by_bytes = LeanIMT::new(&env, 4);
+    let mut by_scalar = LeanIMT::new(&env, 4);
+    for value in [3u64, 0, 17, 42, 5] {
+        let scalar = u64_to_bls_scalar(&env, value);
+        by_bytes
+            .insert(bls_scalar_to_bytes(scalar.clone()))
+            .unwrap();
+        by_scalar.insert_scalar(scalar).unwrap();
+        assert_eq!(by_scalar.get_root(), by_bytes.get_root());
+    }
+    assert_eq!(by_scalar.get_leaves(), by_bytes.get_leaves());
+
+    let mut full = LeanIMT::new(&env, 1);
+    full.insert_scalar(u64_to_bls_scalar(&env, 1)).unwrap();
+    full.insert_scalar(u64_to_bls_scalar(&env, 2)).unwrap();
+    assert_eq!(
+        full.insert_scalar(u64_to_bls_scalar(&env, 3)),
+        Err(LeanIMTError::TreeFull)
+    );
+}
+
+/// xorshift64 so the random sequences are reproducible without extra dependencies
+struct TestRng(u64);
+
+impl TestRng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Random leaf below the field modulus (top two bits cleared)
+    fn leaf(&mut self, env: &Env) -> BytesN<32> {
+        let mut bytes = [0u8; 32];
+        for chunk in bytes.chunks_mut(8) {
+            chunk.copy_from_slice(&self.next_u64().to_be_bytes());
+        }
+        bytes[0] &= 0x3f;
+        BytesN::from_array(env, &bytes)
+    }
+}
+
+#[test]
+fn test_incremental_matches_full_build_for_random_sequences() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+    let mut rng = TestRng(0x5eed_1234_abcd_0001);
+
+    for _ in 0..12 {
+        let size = (rng.next_u64() % 40) as u32;
+        let mut leaves = vec![&env];
+        for _ in 0..size {
+            leaves.push_back(rng.leaf(&env));
+        }
+
+        let mut incremental = LeanIMT::new(&env, 8);
+        for leaf in leaves.iter() {
+            incremental.insert(leaf).unwrap();
+        }
+        let mut full = LeanIMT::new_from_leaves(&env, 8, leaves.clone()).unwrap();
+        assert_eq!(full.get_root(), incremental.get_root(), "size {}", size);
+
+        // Both trees keep accepting leaves and stay in sync
+        let extra = rng.leaf(&env);
+        incremental.insert(extra.clone()).unwrap();
+        full.insert(extra).unwrap();
+        assert_eq!(full.get_root(), incremental.get_root(), "size {}", size + 1);
+
+        // Proofs from the full build verify against the incremental root
+        let index = (rng.next_u64() % (size as u64 + 1)) as u32;
+        let (siblings, _) = full.generate_proof(index).unwrap();
+        let leaf = full.get_leaf(index as usize).unwrap();
+        assert!(incremental.verify_proof(&leaf, index, &siblings));
+    }
+}
+
+#[test]
+fn test_new_from_leaves_edge_cases() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let empty = LeanIMT::new_from_leaves(&env, 4, vec![&env]).unwrap();
+    assert_eq!(empty.get_root(), LeanIMT::new(&env, 4).get_root());
+
+    let leaf = bls_scalar_to_bytes(u64_to_bls_scalar(&env, 9));
+    let single = LeanIMT::new_from_leaves(&env, 0, vec![&env, leaf.clone()]).unwrap();
+    assert_eq!(single.get_root(), leaf);
+
+    let too_many = vec![&env, leaf.clone(), leaf.clone(), leaf];
+    assert!(LeanIMT::new_from_leaves(&env, 1, too_many).is_err());
+}
+
+#[test]
+fn test_insert_many_u64_matches_sequential_inserts() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+    // Includes values above u32::MAX, which the conversion used to truncate
+    let values = [1u64, 2, u32::MAX as u64 + 1, u64::MAX, 5];
+
+    let builders: [fn(&Env) -> LeanIMT; 3] = [
+        |env| LeanIMT::new(env, 3),
+        |env| LeanIMT::new_with_domain_separation(env, 3, true),
+        |env| LeanIMT::new_with_zero_leaf(env, 3, u64_to_bls_scalar(env, 7)),
+    ];
+    for build in builders {
+        // Into an empty tree (level-by-level build) and after an existing leaf
+        for prefix in [&[][..], &[9u64][..]] {
+            let mut sequential = build(&env);
+            let mut batched = build(&env);
+            for &value in prefix.iter().chain(values.iter()) {
+                sequential.insert_u64(value).unwrap();
+            }
+            batched.insert_many_u64(prefix).unwrap();
+            batched.insert_many_u64(&values).unwrap();
+
+            assert!(batched.structural_eq(&sequential));
+            for index in 0..sequential.get_leaf_count() {
+                assert_eq!(
+                    batched.generate_proof(index),
+                    sequential.generate_proof(index)
+                );
+            }
+        }
+    }
+
+    assert_ne!(
+        u64_to_bls_scalar(&env, u32::MAX as u64 + 1),
+        u64_to_bls_scalar(&env, 0)
+    );
+
+    // All or nothing: a batch that does not fit leaves the tree untouched
+    let mut tree = LeanIMT::new(&env, 2);
+    tree.insert_many_u64(&[1, 2]).unwrap();
+    let root = tree.get_root();
+    assert_eq!(
+        tree.insert_many_u64(&[3, 4, 5]),
+        Err(LeanIMTError::TreeFull)
+    );
+    assert_eq!(tree.get_leaf_count(), 2);
+    assert_eq!(tree.get_root(), root);
+}
+
+#[cfg(feature = "ark")]
+#[test]
+fn test_ark_poseidon_matches_soroban() {
+    let env = Env::default();
+    let hasher = ark::ArkPoseidon255::new();
+    let left = ark::fr_from_be_bytes(&bls_scalar_to_bytes(u64_to_bls_scalar(&env, 1)).to_array());
+    let right = ark::fr_from_be_bytes(&bls_scalar_to_bytes(u64_to_bls_scalar(&env, 2)).to_array());
+
+    let expected =
+        Poseidon255::new(&env).hash_two(&u64_to_bls_scalar(&env, 1), &u64_to_bls_scalar(&env, 2));
+    assert_eq!(
+        ark::fr_to_be_bytes(&hasher.hash_two(&left, &right)),
+        bls_scalar_to_bytes(expected).to_array()
+    );
+}
+
+#[cfg(feature = "ark")]
+#[test]
+fn test_ark_build_root_matches_lean_imt() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+    let mut rng = TestRng(0xa4c0_5eed_0000_0021);
+
+    for (depth, size) in [(0u32, 0u32), (0, 1), (1, 1), (3, 5), (4, 16), (6, 23)] {
+        let mut tree = LeanIMT::new(&env, depth);
+        let mut ark_leaves = std::vec::Vec::new();
+        for _ in 0..size {
+            let leaf = rng.leaf(&env);
+            ark_leaves.push(ark::fr_from_be_bytes(&leaf.to_array()));
+            tree.insert(leaf).unwrap();
+        }
+
+        let root = ark::build_root(depth, &ark_leaves).unwrap();
+        assert_eq!(
+            ark::fr_to_be_bytes(&root),
+            tree.get_root().to_array(),
+            "depth {} size {}",
+            depth,
+            size
+        );
+    }
+
+    let too_many = [ark::fr_from_be_bytes(&[0u8; 32]); 3];
+    assert_eq!(ark::build_root(1, &too_many), Err(LeanIMTError::TreeFull));
+}
+
+#[test]
+fn test_domain_separated_root_is_locked() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let mut tree = LeanIMT::new_with_domain_separation(&env, 2, true);
+    for leaf in [1u64, 2, 3] {
+        tree.insert_u64(leaf).unwrap();
+    }
+
+    // Recompute by hand: leaf nodes are Poseidon(1, leaf), parents Poseidon(2, left, right)
+    let scalar = |value: u64| u64_to_bls_scalar(&env, value);
+    let leaf_node = |value: u64| {
+        let inputs = vec![
+            &env,
+            U256::from_u32(&env, LEAF_DOMAIN),
+            scalar(value).to_u256(),
+        ];
+        poseidon_hash::<3, BlsScalar>(&env, &inputs)
+    };
+    let node = |left: U256, right: U256| {
+        let inputs = vec![&env, U256::from_u32(&env, NODE_DOMAIN), left, right];
+        poseidon_hash::<4, BlsScalar>(&env, &inputs)
+    };
+    let expected = node(
+        node(leaf_node(1), leaf_node(2)),
+        node(leaf_node(3), U256::from_u32(&env, 0)),
+    );
+    assert_eq!(tree.get_root_scalar().to_u256(), expected);
+
+    // Locked value, so any change to the tags or the hashing shows up here
+    assert_eq!(
+        tree.get_root(),
+        bytesn!(
+            &env,
+            0x3e383cc3f0f7ac232c4454d180242df7ad40fa3bc52fc6ce95fedd75bd250d4b
+        )
+    );
+
+    let mut plain = LeanIMT::new(&env, 2);
+    for leaf in [1u64, 2, 3] {
+        plain.insert_u64(leaf).unwrap();
+    }
+    assert_ne!(tree.get_root(), plain.get_root());
+
+    let (siblings, _) = tree.generate_proof(2).unwrap();
+    let leaf = bls_scalar_to_bytes(scalar(3));
+    assert!(tree.verify_proof(&leaf, 2, &siblings));
+    assert!(!plain.verify_proof(&leaf, 2, &siblings));
+}
+
+#[test]
+fn test_domain_separation_flag_off_matches_new() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let mut flagged = LeanIMT::new_with_domain_separation(&env, 3, false);
+    let mut plain = LeanIMT::new(&env, 3);
+    assert!(!flagged.is_domain_separated());
+    for leaf in [4u64, 8, 15] {
+        flagged.insert_u64(leaf).unwrap();
+        plain.insert_u64(leaf).unwrap();
+    }
+    assert_eq!(flagged.get_root(), plain.get_root());
+}
+
+#[test]
+fn test_get_sibling_returns_zero_padding() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let mut tree = LeanIMT::new(&env, 3);
+    for leaf in [3u64, 6, 9] {
+        tree.insert_u64(leaf).unwrap();
+    }
+
+    // Leaf 2's sibling was never inserted: it is the zero leaf, not missing
+    let zero = u64_to_bls_scalar(&env, 0);
+    assert_eq!(tree.get_sibling_scalar(0, 2), Some(zero.clone()));
+    assert_eq!(tree.get_sibling(0, 2), Some(bls_scalar_to_bytes(zero)));
+
+    // A proof assembled from get_sibling calls verifies against the root
+    let leaf_index = 2u32;
+    let mut siblings = vec![&env];
+    for level in 0..tree.get_depth() {
+        let sibling = tree.get_sibling(level, leaf_index >> level).unwrap();
+        siblings.push_back(bytes_to_bls_scalar(&sibling));
+    }
+    assert_eq!(siblings, tree.generate_proof(leaf_index).unwrap().0);
+    let leaf = tree.get_leaf(leaf_index as usize).unwrap();
+    assert!(tree.verify_proof(&leaf, leaf_index, &siblings));
+
+    assert_eq!(tree.get_sibling(3, 0), None);
+    assert_eq!(tree.get_sibling(0, 8), None);
+    assert_eq!(tree.get_sibling_scalar(2, 2), None);
+}
+
+#[test]
+fn test_insert_returning_matches_root_and_index() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let mut tree = LeanIMT::new(&env, 1);
+    for (expected_index, value) in [7u64, 8].into_iter().enumerate() {
+        let leaf = bls_scalar_to_bytes(u64_to_bls_scalar(&env, value));
+        let (root, index) = tree.insert_returning(leaf.clone()).unwrap();
+        assert_eq!(root, tree.get_root());
+        assert_eq!(index, expected_index as u32);
+        assert_eq!(tree.get_leaf(index as usize), Some(leaf));
+    }
+
+    let leaf = bls_scalar_to_bytes(u64_to_bls_scalar(&env, 9));
+    assert_eq!(tree.insert_returning(leaf), Err(LeanIMTError::TreeFull));
+}
+
+#[test]
+fn test_getters_reject_huge_indices() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let mut tree = LeanIMT::new(&env, 3);
+    for leaf in [3u64, 6, 9] {
+        tree.insert_u64(leaf).unwrap();
+    }
+
+    assert_eq!(tree.get_leaf(usize::MAX), None);
+    assert_eq!(tree.get_leaf(u32::MAX as usize + 1), None);
+    assert_eq!(tree.get_leaf_scalar(usize::MAX), None);
+    for level in 0..=tree.get_depth() + 1 {
+        assert_eq!(tree.get_node(level, u32::MAX), None);
+        assert_eq!(tree.get_node_scalar(level, u32::MAX), None);
+        assert_eq!(tree.get_subtree_root(level, u32::MAX), None);
+        assert_eq!(tree.get_sibling(level, u32::MAX), None);
+    }
+
+    // Just past the edge of each level
+    assert_eq!(tree.get_node(1, 4), None);
+    assert_eq!(tree.get_node(3, 1), None);
+    let empty_pair = bls_scalar_to_bytes(zero_hashes(&env, 1).get(1).unwrap());
+    assert_eq!(tree.get_node(1, 3), Some(empty_pair));
+    assert_eq!(tree.get_node(3, 0), Some(tree.get_root()));
+}
+
+#[test]
+fn test_padded_proof_verifies_like_unpadded() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let mut tree = LeanIMT::new(&env, 3);
+    for leaf in [11u64, 22, 33, 44, 55] {
+        tree.insert_u64(leaf).unwrap();
+    }
+
+    for leaf_index in 0..5u32 {
+        let leaf = tree.get_leaf(leaf_index as usize).unwrap();
+        let (siblings, depth) = tree.generate_proof(leaf_index).unwrap();
+        let (padded, actual_depth) = tree.generate_padded_proof(leaf_index, 8).unwrap();
+
+        assert_eq!(actual_depth, depth);
+        assert_eq!(padded.len(), 8);
+        assert_eq!(padded.slice(0..depth), siblings);
+        assert!(tree.verify_proof(&leaf, leaf_index, &siblings));
+        assert!(tree.verify_padded_proof(&leaf, leaf_index, &padded, actual_depth));
+
+        // A wrong leaf fails both ways
+        let other = bls_scalar_to_bytes(u64_to_bls_scalar(&env, 99));
+        assert!(!tree.verify_proof(&other, leaf_index, &siblings));
+        assert!(!tree.verify_padded_proof(&other, leaf_index, &padded, actual_depth));
+    }
+
+    // No padding is the plain proof
+    assert_eq!(tree.generate_padded_proof(1, 3), tree.generate_proof(1));
+    assert_eq!(tree.generate_padded_proof(1, 2), None);
+    assert_eq!(tree.generate_padded_proof(5, 8), None);
+
+    // Padding must be zero and the depth must be the tree's
+    let leaf = tree.get_leaf(1).unwrap();
+    let (mut padded, actual_depth) = tree.generate_padded_proof(1, 5).unwrap();
+    assert!(!tree.verify_padded_proof(&leaf, 1, &padded, actual_depth + 1));
+    padded.set(4, u64_to_bls_scalar(&env, 1));
+    assert!(!tree.verify_padded_proof(&leaf, 1, &padded, actual_depth));
+}
+
+#[test]
+fn test_structural_eq_ignores_how_the_tree_was_built() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+    let leaves = vec![
+        &env,
+        bls_scalar_to_bytes(u64_to_bls_scalar(&env, 1)),
+        bls_scalar_to_bytes(u64_to_bls_scalar(&env, 2)),
+        bls_scalar_to_bytes(u64_to_bls_scalar(&env, 3)),
+    ];
+
+    let mut sequential = LeanIMT::new(&env, 4);
+    for leaf in leaves.iter() {
+        sequential.insert(leaf).unwrap();
+    }
+    let bulk = LeanIMT::new_from_leaves(&env, 4, leaves.clone()).unwrap();
+    let (stored_leaves, depth, root) = bulk.to_storage();
+    let restored = LeanIMT::from_storage(&env, stored_leaves, depth, root);
+
+    assert!(sequential.structural_eq(&bulk));
+    assert!(bulk.structural_eq(&restored));
+    assert!(restored.root_eq(&sequential.get_root()));
+
+    // Same leaves at another depth, or one leaf more, are different trees
+    let deeper = LeanIMT::new_from_leaves(&env, 5, leaves.clone()).unwrap();
+    assert!(!sequential.structural_eq(&deeper));
+    assert!(!deeper.root_eq(&sequential.get_root()));
+    sequential
+        .insert(bls_scalar_to_bytes(u64_to_bls_scalar(&env, 4)))
+        .unwrap();
+    assert!(!sequential.structural_eq(&bulk));
+}
+
+/// Root of a depth-1 tree over `left` and `right`, through any backend
+fn two_leaf_root(backend: &impl HashBackend, left: &BlsScalar, right: &BlsScalar) -> BlsScalar {
+    backend.hash_two(left, right)
+}
+
+#[test]
+fn test_hash_backends_match_their_hash_function() {
+    let env = Env::default();
+    let left = u64_to_bls_scalar(&env, 1);
+    let right = u64_to_bls_scalar(&env, 2);
+
+    for (hash_function, root) in [
+        (
+            HashFunction::Poseidon,
+            two_leaf_root(&Poseidon255::new(&env), &left, &right),
+        ),
+        (
+            HashFunction::Poseidon2,
+            two_leaf_root(&Poseidon2Hasher::new(&env), &left, &right),
+        ),
+    ] {
+        let mut tree = LeanIMT::new_with_hash_function(&env, 1, hash_function);
+        tree.insert_scalar(left.clone()).unwrap();
+        tree.insert_scalar(right.clone()).unwrap();
+        assert_eq!(tree.get_root_scalar(), root, "{:?}", hash_function);
+        assert_eq!(hash_function.hash_two(&env, &left, &right), root);
+    }
+
+    // The backends use different constants, so they are not interchangeable
+    assert_ne!(
+        two_leaf_root(&Poseidon255::new(&env), &left, &right),
+        two_leaf_root(&Poseidon2Hasher::new(&env), &left, &right)
+    );
+}
+
+#[test]
+fn test_zero_leaf_pads_every_empty_position() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+    let poseidon = Poseidon255::new(&env);
+    let empty = u64_to_bls_scalar(&env, 0xe3);
+
+    // Empty roots hash the empty leaf up, level by level
+    let mut expected = empty.clone();
+    for depth in 0..4 {
+        let tree = LeanIMT::new_with_zero_leaf(&env, depth, empty.clone());
+        assert_eq!(tree.get_root_scalar(), expected, "depth {}", depth);
+        assert_ne!(tree.get_root(), LeanIMT::new(&env, depth).get_root());
+        expected = poseidon.hash_two(&expected, &expected);
+    }
+
+    // A zero empty leaf is the default tree
+    let zero = u64_to_bls_scalar(&env, 0);
+    let tree = LeanIMT::new_with_zero_leaf(&env, 3, zero.clone());
+    assert_eq!(tree.get_root(), LeanIMT::new(&env, 3).get_root());
+    assert_eq!(LeanIMT::new(&env, 3).get_zero_leaf(), zero);
+
+    // With leaves, the missing ones are the empty leaf: root = H(H(a, b), H(c, empty))
+    let mut tree = LeanIMT::new_with_zero_leaf(&env, 2, empty.clone());
+    let leaves = [1u64, 2, 3].map(|value| u64_to_bls_scalar(&env, value));
+    for leaf in leaves.iter() {
+        tree.insert_scalar(leaf.clone()).unwrap();
+    }
+    let expected = poseidon.hash_two(
+        &poseidon.hash_two(&leaves[0], &leaves[1]),
+        &poseidon.hash_two(&leaves[2], &empty),
+    );
+    assert_eq!(tree.get_root_scalar(), expected);
+
+    // Proofs carry the same padding and verify against that root
+    let (siblings, _) = tree.generate_proof(2).unwrap();
+    assert_eq!(siblings.get(0).unwrap(), empty);
+    let leaf = tree.get_leaf(2).unwrap();
+    assert!(tree.verify_proof(&leaf, 2, &siblings));
+    assert_eq!(tree.get_sibling_scalar(0, 2), Some(empty));
+}
+
+/// Every level of a tree of `depth` over `leaves`, bottom up, hashed without any cache
+fn levels_by_brute_force(env: &Env, depth: u32, leaves: &Vec<BlsScalar>) -> Vec<Vec<BlsScalar>> {
+    let poseidon = Poseidon255::new(env);
+    let zero = u64_to_bls_scalar(env, 0);
+    let mut level = vec![env];
+    for index in 0..1u32 << depth {
+        level.push_back(leaves.get(index).unwrap_or(zero.clone()));
+    }
+    let mut levels = vec![env, level.clone()];
+    for _ in 0..depth {
+        let mut parents = vec![env];
+        for index in (0..level.len()).step_by(2) {
+            parents.push_back(
+                poseidon.hash_two(&level.get(index).unwrap(), &level.get(index + 1).unwrap()),
+            );
+        }
+        level = parents;
+        levels.push_back(level.clone());
+    }
+    levels
+}
+
+#[test]
+fn test_every_node_matches_brute_force_levels() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    // The caches are keyed by level and (level, index), so nodes can't share a slot; what
+    // can go wrong is serving the empty-subtree hash for a node that covers a leaf
+    for depth in 0..=5u32 {
+        let capacity = 1u32 << depth;
+        let mut previous = None;
+        for count in [0, 1, capacity / 2 + 1, capacity - 1, capacity] {
+            if count > capacity || previous == Some(count) {
+                continue;
+            }
+            previous = Some(count);
+
+            let mut leaves = vec![&env];
+            let mut tree = LeanIMT::new(&env, depth);
+            for i in 0..count {
+                let leaf = u64_to_bls_scalar(&env, 1000 + i as u64);
+                tree.insert_scalar(leaf.clone()).unwrap();
+                leaves.push_back(leaf);
+            }
+            let levels = levels_by_brute_force(&env, depth, &leaves);
+
+            // Inserts fill the sparse cache; a tree loaded from storage starts without it
+            // and relies on the empty-subtree fallback
+            let (stored_leaves, _, root) = tree.to_storage();
+            let restored = LeanIMT::from_storage(&env, stored_leaves, depth, root);
+            for tree in [&tree, &restored] {
+                for (level, nodes) in levels.iter().enumerate() {
+                    let level = level as u32;
+                    for (index, expected) in nodes.iter().enumerate() {
+                        let index = index as u32;
+                        if level == 0 && index >= count {
+                            // Missing leaves are not nodes, but are zero siblings
+                            assert_eq!(tree.get_node_scalar(level, index), None);
+                        } else {
+                            assert_eq!(
+                                tree.get_node_scalar(level, index),
+                                Some(expected.clone()),
+                                "depth {} leaves {} node ({}, {})",
+                                depth,
+                                count,
+                                level,
+                                index
+                            );
+                        }
+                        if level < depth {
+                            assert_eq!(tree.get_sibling_scalar(level, index ^ 1), Some(expected));
+                        }
+                    }
+                }
+            }
+            let top = levels.get(depth).unwrap().get(0).unwrap();
+            assert_eq!(tree.get_root_scalar(), top);
+        }
+    }
+}
+
+#[test]
+fn test_poseidon_width_selects_the_parent_hash() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    // The default width 3 builds exactly the tree `new` does
+    let mut default_tree = LeanIMT::new(&env, 2);
+    let mut width_3 = LeanIMT::new_with_poseidon_width(&env, 2, 3).unwrap();
+    assert_eq!(width_3.get_poseidon_width(), DEFAULT_POSEIDON_WIDTH);
+    for leaf in 1u64..=3 {
+        default_tree.insert_u64(leaf).unwrap();
+        width_3.insert_u64(leaf).unwrap();
+        assert!(width_3.structural_eq(&default_tree));
+    }
+
+    // Wider states still hash exactly the two children
+    let zero = U256::from_u32(&env, 0);
+    let node = |width: u32, left: U256, right: U256| -> U256 {
+        let mut inputs = Vec::from_array(&env, [left, right]);
+        while inputs.len() < width - 1 {
+            inputs.push_back(zero.clone());
+        }
+        match width {
+            4 => poseidon_hash::<4, BlsScalar>(&env, &inputs),
+            5 => poseidon_hash::<5, BlsScalar>(&env, &inputs),
+            _ => poseidon_hash::<6, BlsScalar>(&env, &inputs),
+        }
+    };
+    for width in [4u32, 5, 6] {
+        let mut tree = LeanIMT::new_with_poseidon_width(&env, 2, width).unwrap();
+        assert_eq!(tree.get_poseidon_width(), width);
+        let empty_node = node(width, zero.clone(), zero.clone());
+        let empty_root = node(width, empty_node.clone(), empty_node.clone());
+        assert_eq!(tree.get_root_scalar(), BlsScalar::from_u256(empty_root));
+
+        for leaf in 1u64..=3 {
+            tree.insert_u64(leaf).unwrap();
+        }
+        let left = node(width, U256::from_u32(&env, 1), U256::from_u32(&env, 2));
+        let right = node(width, U256::from_u32(&env, 3), zero.clone());
+        let expected = BlsScalar::from_u256(node(width, left, right));
+        assert_eq!(tree.get_root_scalar(), expected);
+        assert_ne!(tree.get_root(), default_tree.get_root());
+
+        let (siblings, _) = tree.generate_proof(2).unwrap();
+        assert!(tree.verify_proof(
+            &bls_scalar_to_bytes(u64_to_bls_scalar(&env, 3)),
+            2,
+            &siblings
+        ));
+    }
+
+    for width in [0u32, 2, 7] {
+        assert_eq!(
+            LeanIMT::new_with_poseidon_width(&env, 2, width).err(),
+            Some(LeanIMTError::UnsupportedWidth)
+        );
+    }
+}
+
+#[test]
+fn test_light_tree_inserts_through_the_frontier() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    // Reload the light tree from its storage form before every insert, as a contract
+    // would, and compare it with a full tree grown alongside
+    let mut full = LeanIMT::new(&env, 3);
+    let light = LeanIMT::new_with_light_mode(&env, 3, true);
+    assert!(light.is_light());
+    let mut stored = light.to_light_storage();
+    for leaf in 1u64..=8 {
+        let (leaf_count, depth, root, frontier) = stored;
+        let mut light = LeanIMT::from_light_storage(&env, leaf_count, depth, root, frontier);
+        assert!(light.is_light());
+
+        full.insert_u64(leaf).unwrap();
+        let (new_root, index) = light
+            .insert_returning(bls_scalar_to_bytes(u64_to_bls_scalar(&env, leaf)))
+            .unwrap();
+        assert_eq!(new_root, full.get_root(), "leaf {}", leaf);
+        assert_eq!(index as u64, leaf - 1);
+        assert_eq!(light.get_leaf_count(), full.get_leaf_count());
+        assert_eq!(light.get_frontier(), full.get_frontier());
+
+        // No leaves are kept, so nothing can be proven or looked up
+        assert!(light.get_leaves().is_empty());
+        assert!(light.generate_proof(0).is_none());
+        assert!(light.get_leaf(0).is_none());
+        assert!(!light.contains(&bls_scalar_to_bytes(u64_to_bls_scalar(&env, leaf))));
+        assert!(light.get_sibling(0, 0).is_none());
+
+        stored = light.to_light_storage();
+    }
+
+    let (leaf_count, depth, root, frontier) = stored;
+    let mut light = LeanIMT::from_light_storage(&env, leaf_count, depth, root, frontier);
+    assert!(light.is_full());
+    assert_eq!(light.insert_u64(9), Err(LeanIMTError::TreeFull));
+
+    // A light tree used without reloading matches too, including bulk inserts
+    let mut light = LeanIMT::new_with_light_mode(&env, 3, true);
+    light.insert_many_u64(&[1, 2, 3, 4, 5]).unwrap();
+    let mut full = LeanIMT::new(&env, 3);
+    full.insert_many_u64(&[1, 2, 3, 4, 5]).unwrap();
+    assert_eq!(light.get_root(), full.get_root());
+    assert!(!light.structural_eq(&full));
+
+    // `false` is an ordinary tree
+    let mut tree = LeanIMT::new_with_light_mode(&env, 3, false);
+    tree.insert_many_u64(&[1, 2, 3, 4, 5]).unwrap();
+    assert!(!tree.is_light());
+    assert!(tree.structural_eq(&full));
+}
+
+#[test]
+fn test_apply_appends_matches_new_from_leaves() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let depth = 4;
+    let leaves: Vec<BytesN<32>> = Vec::from_iter(
+        &env,
+        (1u64..=13).map(|value| bls_scalar_to_bytes(u64_to_bls_scalar(&env, value))),
+    );
+    let prefix = |count: u32| leaves.slice(0..count);
+
+    // Split the leaves into old and new at every point, including no new leaves
+    for old_count in 0..=leaves.len() {
+        let old = LeanIMT::new_from_leaves(&env, depth, prefix(old_count)).unwrap();
+        let new_leaves = leaves.slice(old_count..);
+
+        let (root, frontier) =
+            LeanIMT::apply_appends(&old.get_frontier(), old_count, &new_leaves).unwrap();
+        let expected = LeanIMT::new_from_leaves(&env, depth, leaves.clone()).unwrap();
+        assert_eq!(root, expected.get_root(), "old count {}", old_count);
+        assert_eq!(frontier, expected.get_frontier(), "old count {}", old_count);
+
+        let (root, _) =
+            LeanIMT::apply_appends(&old.get_frontier(), old_count, &vec![&env]).unwrap();
+        assert_eq!(root, old.get_root(), "old count {}", old_count);
+    }
+
+    // Batches chain: each call only needs the previous frontier
+    let mut frontier = LeanIMT::new(&env, depth).get_frontier();
+    let mut count = 0;
+    for batch_end in [2, 3, 7, 13] {
+        let (root, next) =
+            LeanIMT::apply_appends(&frontier, count, &leaves.slice(count..batch_end)).unwrap();
+        let expected = LeanIMT::new_from_leaves(&env, depth, prefix(batch_end)).unwrap();
+        assert_eq!(root, expected.get_root());
+        frontier = next;
+        count = batch_end;
+    }
+
+    // Appends past the capacity, or onto a full tree, are rejected
+    let too_many = Vec::from_iter(
+        &env,
+        (0u64..4).map(|value| bls_scalar_to_bytes(u64_to_bls_scalar(&env, value))),
+    );
+    assert_eq!(
+        LeanIMT::apply_appends(&frontier, count, &too_many),
+        Err(LeanIMTError::TreeFull)
+    );
+    let full = LeanIMT::new_from_leaves(&env, 2, leaves.slice(0..4)).unwrap();
+    assert_eq!(
+        LeanIMT::apply_appends(&full.get_frontier(), 4, &vec![&env]),
+        Err(LeanIMTError::TreeFull)
+    );
+}
+
+#[test]
+fn test_multiproof_agrees_with_single_proofs() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let mut tree = LeanIMT::new(&env, 4);
+    tree.insert_many_u64(&[11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21])
+        .unwrap();
+    let leaf = |index: u32| tree.get_leaf(index as usize).unwrap();
+
+    let index_sets: [&[u32]; 6] = [
+        &[0],
+        &[2, 3],
+        &[10, 1, 4],
+        &[0, 1, 2, 3],
+        &[3, 3, 8],
+        &[9, 10],
+    ];
+    for indices in index_sets {
+        let proof = tree.generate_multiproof(indices).unwrap();
+
+        // Every leaf also verifies on its own
+        let mut single_nodes = 0;
+        for &index in indices {
+            let (siblings, _) = tree.generate_proof(index).unwrap();
+            assert!(tree.verify_proof(&leaf(index), index, &siblings));
+            single_nodes += siblings.len();
+        }
+        assert!(proof.nodes.len() <= single_nodes);
+
+        let leaves = Vec::from_iter(&env, proof.indices.iter().map(leaf));
+        assert!(tree.verify_multiproof(&leaves, &proof), "{:?}", indices);
+
+        // A wrong leaf fails both ways
+        let wrong = bls_scalar_to_bytes(u64_to_bls_scalar(&env, 99));
+        let first = proof.indices.get(0).unwrap();
+        let (siblings, _) = tree.generate_proof(first).unwrap();
+        assert!(!tree.verify_proof(&wrong, first, &siblings));
+        let mut tampered = leaves.clone();
+        tampered.set(0, wrong);
+        assert!(!tree.verify_multiproof(&tampered, &proof));
+    }
+
+    // Adjacent leaves share every sibling above the leaf level
+    let proof = tree.generate_multiproof(&[2, 3]).unwrap();
+    assert_eq!(proof.indices, vec![&env, 2, 3]);
+    assert_eq!(proof.nodes.len(), 3);
+    let proof = tree.generate_multiproof(&[0, 1, 2, 3]).unwrap();
+    assert_eq!(proof.nodes.len(), 2);
+
+    // Missing, extra or misordered parts are rejected
+    let proof = tree.generate_multiproof(&[1, 4]).unwrap();
+    let leaves = vec![&env, leaf(1), leaf(4)];
+    let mut short = proof.clone();
+    short.nodes.pop_back();
+    assert!(!tree.verify_multiproof(&leaves, &short));
+    let mut long = proof.clone();
+    long.nodes.push_back(u64_to_bls_scalar(&env, 0));
+    assert!(!tree.verify_multiproof(&leaves, &long));
+    let swapped = MultiProof {
+        indices: vec![&env, 4, 1],
+        nodes: proof.nodes.clone(),
+    };
+    assert!(!tree.verify_multiproof(&vec![&env, leaf(4), leaf(1)], &swapped));
+    assert!(!tree.verify_multiproof(&vec![&env, leaf(1)], &proof));
+
+    assert!(tree.generate_multiproof(&[]).is_none());
+    assert!(tree.generate_multiproof(&[0, 11]).is_none());
+}
+
+#[test]
+fn test_commitment_hashes_match_circuit_vectors() {
+    let env = Env::default();
+    let scalar = |value: u32| BlsScalar::from_u256(U256::from_u32(&env, value));
+    let poseidon = |inputs: &[BlsScalar]| {
+        let mut values = Vec::new(&env);
+        for input in inputs {
+            values.push_back(input.to_u256());
+        }
+        BlsScalar::from_u256(match inputs.len() {
+            1 => poseidon_hash::<2, BlsScalar>(&env, &values),
+            2 => poseidon_hash::<3, BlsScalar>(&env, &values),
+            _ => poseidon_hash::<4, BlsScalar>(&env, &values),
+        })
+    };
+
+    // "test_pool" right-aligned as the scope and nonce 0x0001..01, the vector coinutils'
+    // generate_label is pinned to
+    let scope = BlsScalar::from_u256(U256::from_u128(&env, 2147132627123717500780));
+    let mut nonce = [1u8; 32];
+    nonce[0] = 0;
+    let label = commitment::label(
+        &env,
+        &scope,
+        &BlsScalar::from_bytes(BytesN::from_array(&env, &nonce)),
+    );
+    assert_eq!(
+        label.to_bytes(),
+        bytesn!(
+            &env,
+            0x12489e054a0af75ff41b52dde16199c977397aef583dbbb419a6313dfc7452b9
+        )
+    );
+
+    // Each formula is Poseidon over its inputs, in the circuit's order
+    let precommitment = commitment::precommitment(&env, &scalar(3), &scalar(4));
+    assert_eq!(precommitment, poseidon(&[scalar(3), scalar(4)]));
+    assert_ne!(
+        precommitment,
+        commitment::precommitment(&env, &scalar(4), &scalar(3))
+    );
+    assert_eq!(
+        commitment::commitment(&env, &scalar(1), &label, &precommitment),
+        poseidon(&[scalar(1), label.clone(), precommitment.clone()])
+    );
+    assert_eq!(
+        commitment::nullifier_hash(&env, &scalar(3)),
+        poseidon(&[scalar(3)])
+    );
+}
+
+#[test]
+fn test_depth_for_capacity() {
+    assert_eq!(depth_for_capacity(0), 0);
+    assert_eq!(depth_for_capacity(1), 0);
+    assert_eq!(depth_for_capacity(2), 1);
+    assert_eq!(depth_for_capacity(3), 2);
+    assert_eq!(depth_for_capacity(5), 3);
+    assert_eq!(depth_for_capacity(1024), 10);
+    assert_eq!(depth_for_capacity(1025), 11);
+    assert_eq!(depth_for_capacity(u32::MAX), 32);
+
+    // The depth always fits `n`, and one level less never does
+    let env = Env::default();
+    for n in 2..=9u32 {
+        let depth = depth_for_capacity(n);
+        assert!(LeanIMT::new(&env, depth).get_capacity() >= n);
+        assert!(LeanIMT::new(&env, depth - 1).get_capacity() < n);
+    }
+}
+
+#[test]
+fn test_poseidon255_hash_bytes() {
+    let env = Env::default();
+    let poseidon = Poseidon255::new(&env);
+    let scalar = |value: u128| BlsScalar::from_u256(U256::from_u128(&env, value));
+
+    // A short input is one chunk after the length
+    let name = b"test_pool";
+    assert_eq!(
+        poseidon.hash_bytes(name),
+        poseidon.hash_two(&scalar(9), &scalar(2147132627123717500780))
+    );
+    assert_eq!(
+        poseidon.hash_bytes(&[]),
+        poseidon.hash_two(&scalar(0), &scalar(0))
+    );
+
+    // 32 bytes spill into a second chunk
+    let data = [0xabu8; 32];
+    let mut first = [0u8; 32];
+    first[1..].copy_from_slice(&data[..BYTES_PER_CHUNK]);
+    let first = BlsScalar::from_bytes(BytesN::from_array(&env, &first));
+    assert_eq!(
+        poseidon.hash_bytes(&data),
+        poseidon.hash_two(&poseidon.hash_two(&scalar(32), &first), &scalar(0xab))
+    );
+
+    // Leading zero bytes and the empty input are told apart by the length
+    assert_ne!(poseidon.hash_bytes(&[0, 1]), poseidon.hash_bytes(&[1]));
+    assert_ne!(poseidon.hash_bytes(&[0]), poseidon.hash_bytes(&[]));
+
+    // Test vector: a 40-byte scope, which `scope_to_bytes` would truncate to 31 bytes
+    let scope = b"privacy-pools/mainnet/xlm-100/version-01";
+    assert_eq!(scope.len(), 40);
+    assert_eq!(
+        poseidon.hash_bytes(scope).to_bytes(),
+        bytesn!(
+            &env,
+            0x3bee9ca420331d280675ac6d58efd07411f15f72c631b7644662f1ac6e4cd610
+        )
+    );
+}
+
+#[test]
+fn test_verify_internal_consistency_detects_corrupted_root() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+
+    let mut tree = LeanIMT::new(&env, 5);
+    assert!(tree.verify_internal_consistency());
+    for i in 1..=9u64 {
+        tree.insert_u64(i).unwrap();
+    }
+    assert!(tree.verify_internal_consistency());
+
+    let (leaves, depth, root) = tree.to_storage();
+    assert!(
+        LeanIMT::from_storage(&env, leaves.clone(), depth, root.clone())
+            .verify_internal_consistency()
+    );
+
+    // A root from a different set of leaves, as after a bad migration
+    let mut corrupted = root.to_array();
+    corrupted[31] ^= 1;
+    let loaded = LeanIMT::from_storage(
+        &env,
+        leaves.clone(),
+        depth,
+        BytesN::from_array(&env, &corrupted),
+    );
+    assert!(!loaded.verify_internal_consistency());
+
+    let empty = LeanIMT::from_storage(&env, vec![&env], depth, root.clone());
+    assert_eq!(empty.get_root(), root);
+    assert!(!empty.verify_internal_consistency());
+
+    let mut fewer = leaves.clone();
+    fewer.pop_back();
+    assert!(!LeanIMT::from_storage(&env, fewer, depth, root.clone()).verify_internal_consistency());
+
+    let (count, depth, root, frontier) = tree.to_light_storage();
+    assert!(
+        LeanIMT::from_light_storage(&env, count, depth, root, frontier.clone())
+            .verify_internal_consistency()
+    );
+    let light = LeanIMT::from_light_storage(
+        &env,
+        count,
+        depth,
+        BytesN::from_array(&env, &corrupted),
+        frontier,
+    );
+    assert!(!light.verify_internal_consistency());
+}
+
+#[test]
+fn test_poseidon255_from_default_constants_matches_builtin_widths() {
+    let env = Env::default();
+    env.cost_estimate().budget().reset_unlimited();
+    let left = u64_to_bls_scalar(&env, 1);
+    let right = u64_to_bls_scalar(&env, 2);
+
+    let (rounds_f, rounds_p, constants) = Poseidon255::default_constants(&env, 3).unwrap();
+    assert_eq!((rounds_f, rounds_p), (8, 56));
+    assert_eq!(constants.len(), (64 + 3) * 3 * 32);
+    let loaded = Poseidon255::from_constants(&env, 3, rounds_f, rounds_p, &constants).unwrap();
+    assert_eq!(
+        loaded.hash_two(&left, &right),
+        Poseidon255::new(&env).hash_two(&left, &right)
+    );
+    assert_eq!(
+        loaded.hash_bytes(b"privacy-pools"),
+        Poseidon255::new(&env).hash_bytes(b"privacy-pools")
+    );
+
+    // Wider states match the trees built with `new_with_poseidon_width`
+    for width in [4, 5, 6] {
+        let (rounds_f, rounds_p, constants) = Poseidon255::default_constants(&env, width).unwrap();
+        let loaded =
+            Poseidon255::from_constants(&env, width, rounds_f, rounds_p, &constants).unwrap();
+        let mut tree = LeanIMT::new_with_poseidon_width(&env, 1, width).unwrap();
+        tree.insert_scalar(left.clone()).unwrap();
+        tree.insert_scalar(right.clone()).unwrap();
+        assert_eq!(
+            loaded.hash_two(&left, &right),
+            tree.get_root_scalar(),
+            "t = {}",
+            width
+        );
+    }
+    assert!(Poseidon255::default_constants(&env, 7).is_none());
+}
+
+#[test]
+fn test_poseidon255_from_constants_rejects_malformed_constants() {
+    let env = Env::default();
+    let (rounds_f, rounds_p, constants) = Poseidon255::default_constants(&env, 3).unwrap();
+
+    // One round more than the constants cover
+    let result = Poseidon255::from_constants(&env, 3, rounds_f, rounds_p + 1, &constants);
+    assert_eq!(
+        result.err(),
+        Some(LeanIMTError::ConstantsLength {
+            expected: (65 + 3) * 3 * 32,
+            actual: constants.len(),
+        })
+    );
+    let truncated = constants.slice(..constants.len() - 1);
+    assert!(matches!(
+        Poseidon255::from_constants(&env, 3, rounds_f, rounds_p, &truncated).err(),
+        Some(LeanIMTError::ConstantsLength { .. })
+    ));
+    assert_eq!(
+        Poseidon255::from_constants(&env, 2, rounds_f, rounds_p, &constants).err(),
+        Some(LeanIMTError::UnsupportedWidth)
+    );
+
+    for t in [7, u32::MAX] {
+        assert_eq!(
+            Poseidon255::from_constants(&env, t, rounds_f, rounds_p, &constants).err(),
+            Some(LeanIMTError::UnsupportedWidth)
+        );
+    }
+
+    // Round counts whose constant count doesn't fit are a length error, not a panic
+    assert_eq!(
+        Poseidon255::from_constants(&env, 6, u32::MAX, u32::MAX, &constants).err(),
+        Some(LeanIMTError::ConstantsLength {
+            expected: (2 * u32::MAX as u64 + 6) * 6 * 32,
+            actual: constants.len(),
+        })
+    );
+
+    let mut above = Bytes::from_array(&env, &[0xff; 32]);
+    above.append(&constants.slice(32..));
+    assert_eq!(
+        Poseidon255::from_constants(&env, 3, rounds_f, rounds_p, &above).err(),
+        Some(LeanIMTError::NonCanonicalConstant)
+    );
+}
+
+#[test]
+fn test_leaves_iter_yields_leaves_in_order() {
+    let env = Env::default();
+    let mut tree = LeanIMT::new(&env, 3);
+    assert_eq!(tree.leaves_iter().count(), 0);
+    tree.insert_many_u64(&[5, 6, 7]).unwrap();
+
+    let scalars = Vec::from_iter(
+        &env,
+        tree.leaves_iter().map(|leaf| bytes_to_bls_scalar(&leaf)),
+    );
+    for (index, value) in [5u64, 6, 7].into_iter().enumerate() {
+        assert_eq!(
+            scalars.get(index as u32).unwrap(),
+            u64_to_bls_scalar(&env, value)
+        );
+    }
+    assert_eq!(
+        Vec::from_iter(&env, tree.leaves_iter()),
+        tree.get_leaves().clone()
+    );
+}
@@ -0,0 +1,195 @@
+//! snarkjs `verification_key.json`, `proof.json` and `public.json` parsing (host-only,
+//! behind the `std` feature)
+
+use ark_bls12_381::{Fq, Fq2};
+use ark_serialize::CanonicalSerialize;
+use core::fmt;
+use core::str::FromStr;
+use serde::{Deserialize, Serialize};
+use soroban_sdk::{
+    BytesN, Env, Vec,
+    crypto::bls12_381::{Fr, G1_SERIALIZED_SIZE, G1Affine, G2_SERIALIZED_SIZE, G2Affine},
+};
+use std::string::{String, ToString};
+
+use crate::{Proof, PublicSignals, VerificationKey};
+
+/// A snarkjs `verification_key.json` for a Groth16 circuit over BLS12-381
+///
+/// Points are affine coordinates as decimal strings, with a trailing projective `"1"`
+/// (or `["1", "0"]` for G2) that is ignored.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VerificationKeyJson {
+    pub vk_alpha_1: [String; 3],
+    pub vk_beta_2: [[String; 2]; 3],
+    pub vk_gamma_2: [[String; 2]; 3],
+    pub vk_delta_2: [[String; 2]; 3],
+    #[serde(rename = "IC")]
+    pub ic: std::vec::Vec<[String; 3]>,
+    #[serde(rename = "nPublic")]
+    pub n_public: u32,
+}
+
+/// A snarkjs `proof.json`, with the same point encoding as `VerificationKeyJson`
+///
+/// `protocol` and `curve` are not read.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProofJson {
+    pub pi_a: [String; 3],
+    pub pi_b: [[String; 2]; 3],
+    pub pi_c: [String; 3],
+}
+
+/// Why a snarkjs artifact could not be converted
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SnarkjsError {
+    /// The input is not the expected snarkjs artifact
+    Json(String),
+    /// `IC` does not hold `nPublic + 1` points
+    IcLengthMismatch { ic_len: usize, n_public: u32 },
+    /// A coordinate of the named point is not a base field element
+    InvalidFieldElement(String),
+    /// The named public signal is not a scalar field element
+    InvalidScalar(String),
+    /// The named point is off the curve or outside the prime-order subgroup
+    InvalidPoint(String),
+}
+
+impl fmt::Display for SnarkjsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(message) => write!(f, "{}", message),
+            Self::IcLengthMismatch { ic_len, n_public } => write!(
+                f,
+                "IC array has {} elements but nPublic={} requires {}",
+                ic_len,
+                n_public,
+                *n_public as usize + 1
+            ),
+            Self::InvalidFieldElement(name) => write!(f, "{} is not a base field element", name),
+            Self::InvalidPoint(name) => write!(f, "{} is not a valid curve point", name),
+            Self::InvalidScalar(name) => write!(f, "{} is not a scalar field element", name),
+        }
+    }
+}
+
+impl std::error::Error for SnarkjsError {}
+
+fn fq(name: &str, value: &str) -> Result<Fq, SnarkjsError> {
+    Fq::from_str(value).map_err(|_| SnarkjsError::InvalidFieldElement(name.to_string()))
+}
+
+fn g1_from_coords(env: &Env, name: &str, coords: &[String; 3]) -> Result<G1Affine, SnarkjsError> {
+    let point =
+        ark_bls12_381::G1Affine::new_unchecked(fq(name, &coords[0])?, fq(name, &coords[1])?);
+    if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+        return Err(SnarkjsError::InvalidPoint(name.to_string()));
+    }
+    let mut buf = [0u8; G1_SERIALIZED_SIZE];
+    point
+        .serialize_uncompressed(&mut buf[..])
+        .map_err(|_| SnarkjsError::InvalidPoint(name.to_string()))?;
+    Ok(G1Affine::from_array(env, &buf))
+}
+
+fn g2_from_coords(
+    env: &Env,
+    name: &str,
+    coords: &[[String; 2]; 3],
+) -> Result<G2Affine, SnarkjsError> {
+    let x = Fq2::new(fq(name, &coords[0][0])?, fq(name, &coords[0][1])?);
+    let y = Fq2::new(fq(name, &coords[1][0])?, fq(name, &coords[1][1])?);
+    let point = ark_bls12_381::G2Affine::new_unchecked(x, y);
+    if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+        return Err(SnarkjsError::InvalidPoint(name.to_string()));
+    }
+    let mut buf = [0u8; G2_SERIALIZED_SIZE];
+    point
+        .serialize_uncompressed(&mut buf[..])
+        .map_err(|_| SnarkjsError::InvalidPoint(name.to_string()))?;
+    Ok(G2Affine::from_array(env, &buf))
+}
+
+/// A public signal, as a decimal string below the scalar field modulus
+fn fr(env: &Env, name: &str, value: &str) -> Result<Fr, SnarkjsError> {
+    let invalid = || SnarkjsError::InvalidScalar(name.to_string());
+    let scalar = ark_bls12_381::Fr::from_str(value).map_err(|_| invalid())?;
+    // `from_str` reduces modulo r, so a value at or above it doesn't print back unchanged
+    if value != "0" && scalar.to_string() != value {
+        return Err(invalid());
+    }
+    let mut buf = [0u8; 32];
+    scalar
+        .serialize_uncompressed(&mut buf[..])
+        .map_err(|_| invalid())?;
+    buf.reverse();
+    Ok(Fr::from_bytes(BytesN::from_array(env, &buf)))
+}
+
+impl VerificationKey {
+    /// Parses a snarkjs `verification_key.json`
+    pub fn from_json(env: &Env, json: &str) -> Result<Self, SnarkjsError> {
+        let vk_json: VerificationKeyJson =
+            serde_json::from_str(json).map_err(|e| SnarkjsError::Json(e.to_string()))?;
+        Self::from_snarkjs(env, &vk_json)
+    }
+
+    /// Converts an already deserialized snarkjs verification key
+    ///
+    /// `IC` may have any length as long as it holds `nPublic + 1` points, and every
+    /// point must be on the curve and in the prime-order subgroup.
+    pub fn from_snarkjs(env: &Env, vk_json: &VerificationKeyJson) -> Result<Self, SnarkjsError> {
+        if vk_json.ic.len() != vk_json.n_public as usize + 1 {
+            return Err(SnarkjsError::IcLengthMismatch {
+                ic_len: vk_json.ic.len(),
+                n_public: vk_json.n_public,
+            });
+        }
+
+        let mut ic = Vec::new(env);
+        for (i, point) in vk_json.ic.iter().enumerate() {
+            ic.push_back(g1_from_coords(env, &std::format!("IC[{}]", i), point)?);
+        }
+
+        Ok(VerificationKey {
+            alpha: g1_from_coords(env, "vk_alpha_1", &vk_json.vk_alpha_1)?,
+            beta: g2_from_coords(env, "vk_beta_2", &vk_json.vk_beta_2)?,
+            gamma: g2_from_coords(env, "vk_gamma_2", &vk_json.vk_gamma_2)?,
+            delta: g2_from_coords(env, "vk_delta_2", &vk_json.vk_delta_2)?,
+            ic,
+        })
+    }
+}
+
+impl Proof {
+    /// Parses a snarkjs `proof.json`
+    pub fn from_json(env: &Env, json: &str) -> Result<Self, SnarkjsError> {
+        let proof_json: ProofJson =
+            serde_json::from_str(json).map_err(|e| SnarkjsError::Json(e.to_string()))?;
+        Self::from_snarkjs(env, &proof_json)
+    }
+
+    /// Converts an already deserialized snarkjs proof, checking every point
+    pub fn from_snarkjs(env: &Env, proof_json: &ProofJson) -> Result<Self, SnarkjsError> {
+        Ok(Proof {
+            a: g1_from_coords(env, "pi_a", &proof_json.pi_a)?,
+            b: g2_from_coords(env, "pi_b", &proof_json.pi_b)?,
+            c: g1_from_coords(env, "pi_c", &proof_json.pi_c)?,
+        })
+    }
+}
+
+impl PublicSignals {
+    /// Parses a snarkjs `public.json`: an array of decimal strings, in circuit order
+    ///
+    /// Values at or above the scalar field modulus are rejected rather than reduced.
+    pub fn from_json(env: &Env, json: &str) -> Result<Self, SnarkjsError> {
+        let signals: std::vec::Vec<String> =
+            serde_json::from_str(json).map_err(|e| SnarkjsError::Json(e.to_string()))?;
+        let mut pub_signals = Vec::new(env);
+        for (i, signal) in signals.iter().enumerate() {
+            pub_signals.push_back(fr(env, &std::format!("public[{}]", i), signal)?);
+        }
+        Ok(PublicSignals { pub_signals })
+    }
+}
@@ -1,7 +1,15 @@
 #![no_std]
 
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(feature = "std")]
+mod json;
+#[cfg(feature = "std")]
+pub use json::{ProofJson, SnarkjsError, VerificationKeyJson};
+
 use soroban_sdk::{
-    Bytes, Env, U256, Vec, contracterror,
+    Bytes, BytesN, Env, U256, Vec, contracterror,
     crypto::bls12_381::{Fr, G1_SERIALIZED_SIZE, G1Affine, G2_SERIALIZED_SIZE, G2Affine},
     vec,
 };
@@ -11,6 +19,25 @@ use soroban_sdk::{
 #[repr(u32)]
 pub enum Groth16Error {
     MalformedVerifyingKey = 0,
+    MalformedProof = 1,
+    MalformedPublicSignals = 2,
+    UnsupportedVersion = 3,
+    SignalCountMismatch = 4,
+}
+
+/// Leading byte of every serialized `Proof` and `PublicSignals`
+///
+/// Bump it whenever either encoding changes, so blobs in an older format are rejected
+/// with `Groth16Error::UnsupportedVersion` instead of being misparsed.
+pub const SERIALIZATION_VERSION: u8 = 1;
+
+/// Checks the version byte at the start of `bytes`
+fn check_version(bytes: &Bytes, malformed: Groth16Error) -> Result<(), Groth16Error> {
+    match bytes.first() {
+        None => Err(malformed),
+        Some(SERIALIZATION_VERSION) => Ok(()),
+        Some(_) => Err(Groth16Error::UnsupportedVersion),
+    }
 }
 
 #[derive(Clone)]
@@ -83,15 +110,27 @@ pub struct Proof {
 
 impl Proof {
     pub fn to_bytes(&self, env: &Env) -> Bytes {
-        let mut bytes = Bytes::new(env);
+        let mut bytes = Bytes::from_array(env, &[SERIALIZATION_VERSION]);
         bytes.append(&Bytes::from_slice(env, &self.a.to_bytes().to_array()));
         bytes.append(&Bytes::from_slice(env, &self.b.to_bytes().to_array()));
         bytes.append(&Bytes::from_slice(env, &self.c.to_bytes().to_array()));
         bytes
     }
 
-    pub fn from_bytes(env: &Env, bytes: &Bytes) -> Self {
-        let mut pos = 0;
+    /// Serialized size: the version byte, `a` and `c` in G1, `b` in G2
+    pub const SERIALIZED_SIZE: usize = 1 + 2 * G1_SERIALIZED_SIZE + G2_SERIALIZED_SIZE;
+
+    /// Parses a proof written by `to_bytes`
+    ///
+    /// Only the version byte and the length are checked here; points that are not on
+    /// the curve are still rejected by the host once they are used.
+    pub fn from_bytes(env: &Env, bytes: &Bytes) -> Result<Self, Groth16Error> {
+        check_version(bytes, Groth16Error::MalformedProof)?;
+        if bytes.len() as usize != Self::SERIALIZED_SIZE {
+            return Err(Groth16Error::MalformedProof);
+        }
+
+        let mut pos = 1;
         fn take<const N: usize>(bytes: &Bytes, pos: &mut usize) -> [u8; N] {
             let start = *pos as u32;
             let end = (*pos + N) as u32;
@@ -103,7 +142,7 @@ impl Proof {
         let a = G1Affine::from_array(env, &take::<G1_SERIALIZED_SIZE>(bytes, &mut pos));
         let b = G2Affine::from_array(env, &take::<G2_SERIALIZED_SIZE>(bytes, &mut pos));
         let c = G1Affine::from_array(env, &take::<G1_SERIALIZED_SIZE>(bytes, &mut pos));
-        Proof { a, b, c }
+        Ok(Proof { a, b, c })
     }
 }
 
@@ -114,7 +153,7 @@ pub struct PublicSignals {
 
 impl PublicSignals {
     pub fn to_bytes(&self, env: &Env) -> Bytes {
-        let mut bytes = Bytes::new(env);
+        let mut bytes = Bytes::from_array(env, &[SERIALIZATION_VERSION]);
         let len = self.pub_signals.len() as u32;
         let len_bytes = len.to_be_bytes();
         bytes.append(&Bytes::from_slice(env, &len_bytes));
@@ -126,8 +165,13 @@ impl PublicSignals {
         bytes
     }
 
-    pub fn from_bytes(env: &Env, bytes: &Bytes) -> Self {
-        let mut pos = 0;
+    /// Parses public signals written by `to_bytes`
+    ///
+    /// Fails unless the input is exactly the version byte and the 4-byte count,
+    /// followed by that many 32-byte field elements.
+    pub fn from_bytes(env: &Env, bytes: &Bytes) -> Result<Self, Groth16Error> {
+        check_version(bytes, Groth16Error::MalformedPublicSignals)?;
+        let mut pos = 1;
         fn take<const N: usize>(bytes: &Bytes, pos: &mut usize) -> [u8; N] {
             let start = *pos as u32;
             let end = (*pos + N) as u32;
@@ -136,9 +180,16 @@ impl PublicSignals {
             *pos += N;
             arr
         }
+        if bytes.len() < 5 {
+            return Err(Groth16Error::MalformedPublicSignals);
+        }
         // Read length (u32, big-endian)
         let len_bytes = take::<4>(bytes, &mut pos);
         let len = u32::from_be_bytes(len_bytes) as usize;
+        let expected_size = len.checked_mul(32).and_then(|size| size.checked_add(5));
+        if expected_size != Some(bytes.len() as usize) {
+            return Err(Groth16Error::MalformedPublicSignals);
+        }
         let mut pub_signals = Vec::new(env);
         for _ in 0..len {
             let arr = take::<32>(bytes, &mut pos);
@@ -146,26 +197,49 @@ impl PublicSignals {
             let fr = Fr::from_u256(u256);
             pub_signals.push_back(fr);
         }
-        PublicSignals { pub_signals }
+        Ok(PublicSignals { pub_signals })
+    }
+
+    /// Returns the `index`-th signal of `bytes` exactly as written, or `None` if
+    /// `bytes` holds no such signal
+    ///
+    /// Some SDK releases reduce an `Fr` modulo `r` when it is built, so `from_bytes`
+    /// cannot tell `x + r` from `x`; this is for callers that must.
+    pub fn raw_signal(env: &Env, bytes: &Bytes, index: u32) -> Option<BytesN<32>> {
+        Self::from_bytes(env, bytes).ok()?;
+        let start = 5 + index.checked_mul(32)?;
+        let end = start.checked_add(32)?;
+        if end > bytes.len() {
+            return None;
+        }
+        let mut arr = [0u8; 32];
+        bytes.slice(start..end).copy_into_slice(&mut arr);
+        Some(BytesN::from_array(env, &arr))
     }
 }
 
 pub struct Groth16Verifier;
 
 impl Groth16Verifier {
+    /// Checks a Groth16 proof against `vk` for the given public signals
+    ///
+    /// The key has one `ic` point per public signal plus one, so a key and signals
+    /// from different circuits are rejected with `Groth16Error::SignalCountMismatch`
+    /// before any curve arithmetic. Otherwise returns whether the pairing check holds.
     pub fn verify_proof(
         env: &Env,
         vk: VerificationKey,
         proof: Proof,
         pub_signals: &Vec<Fr>,
     ) -> Result<bool, Groth16Error> {
+        if pub_signals.len() + 1 != vk.ic.len() {
+            return Err(Groth16Error::SignalCountMismatch);
+        }
+
         let bls = env.crypto().bls12_381();
 
         // Prepare proof inputs:
         // Compute vk_x = ic[0] + sum(pub_signals[i] * ic[i+1])
-        if pub_signals.len() + 1 != vk.ic.len() {
-            return Err(Groth16Error::MalformedVerifyingKey);
-        }
         let mut vk_x = vk.ic.get(0).unwrap();
         for (s, v) in pub_signals.iter().zip(vk.ic.iter().skip(1)) {
             let prod = bls.g1_mul(&v, &s);
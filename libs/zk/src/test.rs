@@ -8,7 +8,9 @@ use soroban_sdk::{
     crypto::bls12_381::{Fr, G1_SERIALIZED_SIZE, G1Affine, G2_SERIALIZED_SIZE, G2Affine},
 };
 
-use crate::{Groth16Verifier, Proof, PublicSignals, VerificationKey};
+use crate::{
+    Groth16Error, Groth16Verifier, Proof, PublicSignals, SERIALIZATION_VERSION, VerificationKey,
+};
 
 fn g1_from_coords(env: &Env, x: &str, y: &str) -> G1Affine {
     let ark_g1 = ark_bls12_381::G1Affine::new(Fq::from_str(x).unwrap(), Fq::from_str(y).unwrap());
@@ -126,8 +128,18 @@ fn test_with_hardcoded_vk() {
 
     // Test Case 2: Verify the proof with an incorrect public output (22)
     let output = Vec::from_array(&env, [Fr::from_u256(U256::from_u32(&env, 22))]);
-    let res = Groth16Verifier::verify_proof(&env, vk, proof, &output).unwrap();
+    let res = Groth16Verifier::verify_proof(&env, vk.clone(), proof.clone(), &output).unwrap();
     assert_eq!(res, false);
+
+    // Test Case 3: The key has one public input, so no other signal count is accepted
+    let thirty_three = Fr::from_u256(U256::from_u32(&env, 33));
+    for output in [
+        Vec::new(&env),
+        Vec::from_array(&env, [thirty_three.clone(), thirty_three]),
+    ] {
+        let res = Groth16Verifier::verify_proof(&env, vk.clone(), proof.clone(), &output);
+        assert_eq!(res, Err(Groth16Error::SignalCountMismatch));
+    }
 }
 
 #[test]
@@ -359,7 +371,7 @@ fn test_proof_serde() {
     };
 
     let proof_bytes = proof.to_bytes(&env);
-    let deserialized_proof = Proof::from_bytes(&env, &proof_bytes);
+    let deserialized_proof = Proof::from_bytes(&env, &proof_bytes).unwrap();
 
     assert_eq!(proof.a, deserialized_proof.a);
     assert_eq!(proof.b, deserialized_proof.b);
@@ -375,10 +387,88 @@ fn test_public_signals_serde() {
     };
 
     let pub_signals_bytes = pub_signals.to_bytes(&env);
-    let deserialized_pub_signals = PublicSignals::from_bytes(&env, &pub_signals_bytes);
+    let deserialized_pub_signals = PublicSignals::from_bytes(&env, &pub_signals_bytes).unwrap();
 
     assert_eq!(
         pub_signals.pub_signals,
         deserialized_pub_signals.pub_signals
     );
 }
+
+#[test]
+fn test_proof_from_bytes_rejects_wrong_length() {
+    let env = Env::default();
+
+    let mut short = Bytes::from_array(&env, &[SERIALIZATION_VERSION]);
+    short.append(&Bytes::from_array(&env, &[7u8; 16]));
+    assert!(matches!(
+        Proof::from_bytes(&env, &short),
+        Err(Groth16Error::MalformedProof)
+    ));
+
+    let mut long = Bytes::from_array(&env, &[SERIALIZATION_VERSION]);
+    long.append(&Bytes::from_slice(&env, &[0u8; Proof::SERIALIZED_SIZE]));
+    long.push_back(0);
+    assert!(matches!(
+        Proof::from_bytes(&env, &long),
+        Err(Groth16Error::MalformedProof)
+    ));
+}
+
+#[test]
+fn test_public_signals_from_bytes_rejects_wrong_length() {
+    let env = Env::default();
+
+    // Too short to hold the count
+    let empty = Bytes::from_array(&env, &[SERIALIZATION_VERSION, 0]);
+    assert!(matches!(
+        PublicSignals::from_bytes(&env, &empty),
+        Err(Groth16Error::MalformedPublicSignals)
+    ));
+
+    // Count says two signals but only one follows
+    let mut truncated = Bytes::from_array(&env, &[SERIALIZATION_VERSION]);
+    truncated.append(&Bytes::from_array(&env, &2u32.to_be_bytes()));
+    truncated.append(&Bytes::from_array(&env, &[1u8; 32]));
+    assert!(matches!(
+        PublicSignals::from_bytes(&env, &truncated),
+        Err(Groth16Error::MalformedPublicSignals)
+    ));
+
+    // A huge count must not overflow the size check
+    let mut huge = Bytes::from_array(&env, &[SERIALIZATION_VERSION]);
+    huge.append(&Bytes::from_array(&env, &u32::MAX.to_be_bytes()));
+    assert!(matches!(
+        PublicSignals::from_bytes(&env, &huge),
+        Err(Groth16Error::MalformedPublicSignals)
+    ));
+}
+
+#[test]
+fn test_from_bytes_rejects_unknown_version() {
+    let env = Env::default();
+
+    let pub_signals = PublicSignals {
+        pub_signals: Vec::from_array(&env, [Fr::from_u256(U256::from_u32(&env, 33))]),
+    };
+    let mut signal_bytes = pub_signals.to_bytes(&env);
+    assert_eq!(signal_bytes.get(0), Some(SERIALIZATION_VERSION));
+    signal_bytes.set(0, SERIALIZATION_VERSION + 1);
+    assert!(matches!(
+        PublicSignals::from_bytes(&env, &signal_bytes),
+        Err(Groth16Error::UnsupportedVersion)
+    ));
+
+    // A proof in the unversioned encoding starts with a point byte, not the version
+    let mut proof_bytes = Bytes::from_array(&env, &[0x80u8]);
+    proof_bytes.append(&Bytes::from_slice(&env, &[0u8; Proof::SERIALIZED_SIZE - 1]));
+    assert!(matches!(
+        Proof::from_bytes(&env, &proof_bytes),
+        Err(Groth16Error::UnsupportedVersion)
+    ));
+
+    assert!(matches!(
+        Proof::from_bytes(&env, &Bytes::new(&env)),
+        Err(Groth16Error::MalformedProof)
+    ));
+}